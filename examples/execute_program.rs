@@ -1,4 +1,4 @@
-use tan::{api::eval_string, eval::env::Env};
+use tan::{api::eval_string, error::format_error_pretty, eval::env::Env};
 
 pub fn main() {
     let input_path = "tests/fixtures/fibonacci.tan";
@@ -9,9 +9,12 @@ pub fn main() {
 
     let value = eval_string(&input, &mut env);
 
-    if let Ok(value) = value {
-        println!("{value}");
-    } else {
-        eprintln!("{:?}", value.unwrap_err());
+    match value {
+        Ok(value) => println!("{value}"),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("{}\n", format_error_pretty(&input, &error));
+            }
+        }
     }
 }
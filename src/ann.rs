@@ -1,5 +1,5 @@
 use core::fmt;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::{
     expr::{format_value, Expr},
@@ -22,27 +22,30 @@ use crate::{
 
 // #Insight
 // The Annotated struct will be used a lot, it makes sense to use
-// Option to avoid unnecessary HashMap allocations.
+// Option to avoid unnecessary map allocations.
 
 // #Insight
 // Annotations are 'culled' in the parser, so we can use them for 'shebang'.
 
 // #TODO consider keeping annotations as Vec (to maintain order, and also, not many annotations, typically fast scanning)
 // #TODO keep range separate?
-// #TODO actually, we don't need insertion order but alphabetical order, a BTreeMap can work
+
+// #Insight
+// A BTreeMap is used, to keep the annotations in alphabetical order, the
+// iteration (e.g. serialization) order is deterministic.
 
 #[derive(Clone)]
-pub struct Ann<T>(pub T, pub Option<HashMap<String, Expr>>);
+pub struct Ann<T>(pub T, pub Option<BTreeMap<String, Expr>>);
 
 impl<T> Ann<T> {
     pub fn with_type(value: T, type_expr: Expr) -> Self {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         map.insert("type".to_owned(), type_expr);
         Self(value, Some(map))
     }
 
     pub fn with_range(value: T, range: Range) -> Self {
-        let mut map = HashMap::new();
+        let mut map = BTreeMap::new();
         map.insert("range".to_owned(), range_to_expr(&range));
         Self(value, Some(map))
     }
@@ -51,20 +54,22 @@ impl<T> Ann<T> {
 impl<T> Ann<T> {
     pub fn set_annotation(&mut self, name: impl Into<String>, expr: Expr) {
         self.1
-            .get_or_insert(HashMap::new())
+            .get_or_insert(BTreeMap::new())
             .insert(name.into(), expr);
     }
 
+    pub fn remove_annotation(&mut self, name: impl Into<String>) -> Option<Expr> {
+        self.1.as_mut()?.remove(&name.into())
+    }
+
     pub fn get_annotation(&self, name: impl Into<String>) -> Option<&Expr> {
-        let Some(ref ann ) = self.1 else {
-            return None;
-        };
+        let ann = self.1.as_ref()?;
 
         ann.get(&name.into())
     }
 
     pub fn contains_annotation(&self, name: impl Into<String>) -> bool {
-        let Some(ref ann ) = self.1 else {
+        let Some(ref ann) = self.1 else {
             return false;
         };
 
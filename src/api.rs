@@ -1,36 +1,52 @@
 // #TODO find a better name, e.g. `lang`, `sys`, `runtime`.
 
+pub mod foreign;
+pub mod hints;
+pub mod signature;
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
 use crate::{
     ann::Ann,
-    error::Error,
-    eval::{env::Env, eval},
-    expr::Expr,
+    error::{DiagnosedError, Error, PipelineError, Warning},
+    eval::{diagnostics::DiagnosticsState, env::Env, eval, limits::Limits, stats::EvalStats},
+    expr::{
+        data::{data_to_string, expr_to_data},
+        format_value, Expr,
+    },
     lexer::{token::Token, Lexer},
     macro_expand::macro_expand,
     optimize::optimize,
     parser::Parser,
     range::Ranged,
-    resolver::Resolver,
+    resolver::{Resolver, TypeEnv},
 };
 
+use self::signature::{signature_help_with, SignatureHelp};
+
+pub use self::foreign::{register_fn, register_form, register_host_fn, FnSignature};
+pub use self::hints::{inlay_hints, InlayHint, InlayHintKind};
+
 /// Lexes a Tan expression encoded as a text string.
-pub fn lex_string(input: impl AsRef<str>) -> Result<Vec<Ranged<Token>>, Vec<Ranged<Error>>> {
+pub fn lex_string(input: impl AsRef<str>) -> Result<Vec<Ranged<Token>>, PipelineError> {
     let input = input.as_ref();
     let mut lexer = Lexer::new(input);
-    lexer.lex()
+    lexer.lex().map_err(PipelineError::Lex)
 }
 
 // #TODO temp solution for compatibility.
 // #TODO remove this!
 /// Parses a Tan expression encoded as a text string, returns first expression.
-pub fn parse_string(input: impl AsRef<str>) -> Result<Ann<Expr>, Vec<Ranged<Error>>> {
+pub fn parse_string(input: impl AsRef<str>) -> Result<Ann<Expr>, PipelineError> {
     let input = input.as_ref();
 
-    let mut lexer = Lexer::new(input);
-    let tokens = lexer.lex()?;
+    let tokens = lex_string(input)?;
 
     let mut parser = Parser::new(tokens);
-    let mut expr = parser.parse()?;
+    let mut expr = parser.parse().map_err(PipelineError::Parse)?;
 
     // #TODO temp solution
     let expr = expr.swap_remove(0);
@@ -39,27 +55,104 @@ pub fn parse_string(input: impl AsRef<str>) -> Result<Ann<Expr>, Vec<Ranged<Erro
 }
 
 /// Parses a Tan expression encoded as a text string, returns all expressions parsed.
-pub fn parse_string_all(input: impl AsRef<str>) -> Result<Vec<Ann<Expr>>, Vec<Ranged<Error>>> {
+pub fn parse_string_all(input: impl AsRef<str>) -> Result<Vec<Ann<Expr>>, PipelineError> {
     let input = input.as_ref();
 
-    let mut lexer = Lexer::new(input);
-    let tokens = lexer.lex()?;
+    let tokens = lex_string(input)?;
 
     let mut parser = Parser::new(tokens);
-    let exprs = parser.parse()?;
+    let exprs = parser.parse().map_err(PipelineError::Parse)?;
 
     Ok(exprs)
 }
 
+// #TODO support multiple expressions per line, with the last one incomplete.
+
+/// The status of the input of an interactive session.
+#[derive(Debug, PartialEq)]
+pub enum ReplInput {
+    /// More lines are needed to complete the input.
+    Incomplete,
+    /// The source of one or more complete top-level expressions.
+    Complete(String),
+}
+
+/// An interactive (REPL) session. Accumulates the input lines until the
+/// top-level expressions are complete. Incomplete input is reported
+/// distinctly from syntax errors, so a REPL knows when to keep reading lines.
+#[derive(Debug, Default)]
+pub struct ReplSession {
+    buffer: String,
+}
+
+impl ReplSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns true if no incomplete input is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.trim().is_empty()
+    }
+
+    /// Discards the buffered input.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Appends a line of input. Returns the complete source, ready to be
+    /// evaluated, or `Incomplete` if more lines are needed. On syntax errors
+    /// the buffered input is discarded.
+    pub fn push_line(&mut self, line: impl AsRef<str>) -> Result<ReplInput, PipelineError> {
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        self.buffer.push_str(line.as_ref());
+
+        match parse_string_all(&self.buffer) {
+            Ok(..) => Ok(ReplInput::Complete(std::mem::take(&mut self.buffer))),
+            Err(error) if error.iter().all(|error| error.0.is_incomplete_input()) => {
+                Ok(ReplInput::Incomplete)
+            }
+            Err(error) => {
+                self.reset();
+                Err(error)
+            }
+        }
+    }
+}
+
 // #TODO what is a good name?
 /// Reads and resolves a Tan expression encoded as a text string.
 /// Updates the environment with definitions.
 pub fn resolve_string(
     input: impl AsRef<str>,
     env: &mut Env,
-) -> Result<Vec<Ann<Expr>>, Vec<Ranged<Error>>> {
+) -> Result<Vec<Ann<Expr>>, PipelineError> {
+    resolve_string_with_types(input, env, &mut TypeEnv::new())
+}
+
+/// Reads and resolves a Tan expression encoded as a text string, against the
+/// types of the previously resolved bindings. Updates the environment and the
+/// type environment with definitions.
+pub fn resolve_string_with_types(
+    input: impl AsRef<str>,
+    env: &mut Env,
+    types: &mut TypeEnv,
+) -> Result<Vec<Ann<Expr>>, PipelineError> {
     let exprs = parse_string_all(input)?;
 
+    resolve_exprs(exprs, env, types)
+}
+
+/// Resolves parsed Tan expressions, against the types of the previously
+/// resolved bindings. Updates the environment and the type environment with
+/// definitions.
+pub fn resolve_exprs(
+    exprs: Vec<Ann<Expr>>,
+    env: &mut Env,
+    types: &mut TypeEnv,
+) -> Result<Vec<Ann<Expr>>, PipelineError> {
     // // Nice debugging tool!
     // for ex in &exprs {
     //     for e in ex.iter() {
@@ -67,7 +160,28 @@ pub fn resolve_string(
     //     }
     // }
 
+    // #TODO should we push a new env?
+    let mut resolver = Resolver::with_types(std::mem::take(types));
+
+    let result = resolve_exprs_with(exprs, env, &mut resolver);
+
+    *types = resolver.into_types();
+
+    result
+}
+
+/// Resolves parsed Tan expressions with the given resolver, the resolver
+/// keeps the types and the warnings.
+fn resolve_exprs_with(
+    exprs: Vec<Ann<Expr>>,
+    env: &mut Env,
+    resolver: &mut Resolver,
+) -> Result<Vec<Ann<Expr>>, PipelineError> {
     let mut resolved_exprs = Vec::new();
+    let mut errors = Vec::new();
+
+    // #Insight
+    // All the expressions are processed, to report all the errors at once.
 
     for expr in exprs {
         // #Insight
@@ -75,11 +189,13 @@ pub fn resolve_string(
 
         // Expand macros.
 
-        let expr = macro_expand(expr, env);
-
-        // #TODO temp hack until macro_expand returns multiple errors.
-        let Ok(expr) = expr else {
-            return Err(vec![expr.unwrap_err()]);
+        let expr = match macro_expand(expr, env) {
+            Ok(expr) => expr,
+            Err(error) => {
+                // #TODO temp hack until macro_expand returns multiple errors.
+                errors.push(error);
+                continue;
+            }
         };
 
         let Some(expr) = expr else {
@@ -93,19 +209,62 @@ pub fn resolve_string(
 
         // Resolve pass (typechecking, definitions, etc)
 
-        // #TODO should we push a new env?
-        let mut resolver = Resolver::new();
-        let expr = resolver.resolve(expr, env)?;
+        match resolver.resolve(expr, env) {
+            Ok(expr) => resolved_exprs.push(expr),
+            Err(mut resolve_errors) => errors.append(&mut resolve_errors),
+        }
+    }
 
-        resolved_exprs.push(expr);
+    if errors.is_empty() {
+        Ok(resolved_exprs)
+    } else {
+        Err(PipelineError::Resolve(errors))
     }
+}
+
+/// Reads a data value encoded as Tan text, e.g. a configuration file. The
+/// text is not evaluated, only literal data expressions are accepted, see
+/// `expr_to_data`.
+pub fn read_value(input: impl AsRef<str>) -> Result<Ann<Expr>, PipelineError> {
+    let exprs = parse_string_all(input)?;
+
+    let [expr] = &exprs[..] else {
+        return Err(PipelineError::Parse(vec![Error::invalid_arguments(
+            "the data text should contain exactly one expression",
+        )
+        .into()]));
+    };
+
+    expr_to_data(expr).map_err(|error| PipelineError::Parse(vec![error]))
+}
+
+/// Writes a data value as Tan text, the text reads back to an equal value
+/// with `read_value`.
+pub fn write_value(expr: impl AsRef<Expr>) -> Result<String, Error> {
+    data_to_string(expr.as_ref())
+}
+
+/// Checks a Tan expression encoded as a text string, returns the warnings of
+/// the resolve (typecheck) pass, e.g. unreachable code. Updates the
+/// environment with definitions, like `resolve_string`.
+pub fn check_string(
+    input: impl AsRef<str>,
+    env: &mut Env,
+) -> Result<Vec<Ranged<Warning>>, PipelineError> {
+    let exprs = parse_string_all(input)?;
 
-    Ok(resolved_exprs)
+    let mut resolver = Resolver::new();
+
+    resolve_exprs_with(exprs, env, &mut resolver)?;
+
+    Ok(resolver.take_warnings())
 }
 
 // #TODO this implements in essence a do block. Maybe no value should be returned?
-/// Evaluates a Tan expression encoded as a text string.
-pub fn eval_string(input: impl AsRef<str>, env: &mut Env) -> Result<Ann<Expr>, Vec<Ranged<Error>>> {
+/// Evaluates a Tan expression encoded as a text string. The runtime
+/// configuration is read from the Context of the Env, `env.context`, the hosts
+/// that keep an Env across evaluations should use the `Runtime` instead.
+pub fn eval_string(input: impl AsRef<str>, env: &mut Env) -> Result<Ann<Expr>, PipelineError> {
     let exprs = resolve_string(input, env)?;
 
     let mut last_value = Expr::One.into();
@@ -114,7 +273,7 @@ pub fn eval_string(input: impl AsRef<str>, env: &mut Env) -> Result<Ann<Expr>, V
         let value = eval(&expr, env);
 
         let Ok(value) = value else {
-            return Err(vec![value.unwrap_err()]);
+            return Err(PipelineError::Eval(vec![value.unwrap_err()]));
         };
 
         last_value = value;
@@ -122,3 +281,185 @@ pub fn eval_string(input: impl AsRef<str>, env: &mut Env) -> Result<Ann<Expr>, V
 
     Ok(last_value)
 }
+
+/// Evaluates a Tan expression encoded as a text string, also returns the
+/// resource-usage statistics of the evaluation.
+pub fn eval_string_with_stats(
+    input: impl AsRef<str>,
+    env: &mut Env,
+) -> (Result<Ann<Expr>, PipelineError>, EvalStats) {
+    let start = Instant::now();
+
+    let previous_stats = env.context.stats.replace(EvalStats::default());
+
+    let result = eval_string(input, env);
+
+    let mut stats = std::mem::replace(&mut env.context.stats, previous_stats).unwrap_or_default();
+    stats.duration = start.elapsed();
+
+    (result, stats)
+}
+
+/// Evaluates a Tan expression encoded as a text string, on failure the error
+/// includes the diagnostics: the source excerpt of the failing expression,
+/// the call stack and the bindings of the nearest scope.
+pub fn eval_string_with_diagnostics(
+    input: impl AsRef<str>,
+    env: &mut Env,
+) -> Result<Ann<Expr>, DiagnosedError> {
+    let input = input.as_ref();
+
+    let previous_diagnostics = env.context.diagnostics.replace(DiagnosticsState::default());
+
+    let result = eval_string(input, env);
+
+    // A failure outside of any invocation, e.g. at the top level, is captured
+    // here.
+    if let Err(PipelineError::Eval(errors)) = &result {
+        if let Some(error) = errors.first() {
+            env.capture_failure(error);
+        }
+    }
+
+    let diagnostics =
+        std::mem::replace(&mut env.context.diagnostics, previous_diagnostics).unwrap_or_default();
+
+    result.map_err(|error| DiagnosedError {
+        diagnostics: error
+            .first()
+            .map(|first| diagnostics.to_diagnostics(input, first)),
+        error,
+    })
+}
+
+/// Evaluates a Tan expression encoded as a text string, the evaluation is
+/// aborted with `Error::TimedOut` if it takes longer than `timeout`.
+pub fn eval_with_timeout(
+    input: impl AsRef<str>,
+    env: &mut Env,
+    timeout: Duration,
+) -> Result<Ann<Expr>, PipelineError> {
+    let deadline = Instant::now() + timeout;
+
+    // Respect an earlier, outer deadline.
+    let previous_deadline = env.context.deadline;
+    env.context.deadline = Some(previous_deadline.map_or(deadline, |d| d.min(deadline)));
+
+    let result = eval_string(input, env);
+
+    env.context.deadline = previous_deadline;
+
+    result
+}
+
+/// Evaluates a Tan expression encoded as a text string, the evaluation is
+/// aborted with `Error::LimitExceeded` if it exceeds the fuel or the call
+/// depth limit, or with `Error::TimedOut` if it exceeds the timeout.
+pub fn eval_with_limits(
+    input: impl AsRef<str>,
+    env: &mut Env,
+    limits: &Limits,
+) -> Result<Ann<Expr>, PipelineError> {
+    let previous_limits = std::mem::replace(&mut env.context.limits, limits.clone());
+    let previous_fuel_used = std::mem::take(&mut env.context.fuel_used);
+
+    let result = match limits.timeout {
+        Some(timeout) => eval_with_timeout(input, env, timeout),
+        None => eval_string(input, env),
+    };
+
+    env.context.limits = previous_limits;
+    env.context.fuel_used = previous_fuel_used;
+
+    result
+}
+
+/// The outcome of a conformance test case.
+#[derive(Debug)]
+pub enum ConformanceOutcome {
+    Passed,
+    /// The value differs from the expected value.
+    Failed {
+        expected: String,
+        actual: String,
+    },
+    /// The evaluation failed.
+    Error(PipelineError),
+}
+
+/// The result of a conformance test case, a `.tan` file paired with a
+/// `.value.tan` file.
+#[derive(Debug)]
+pub struct ConformanceResult {
+    pub path: PathBuf,
+    pub outcome: ConformanceOutcome,
+}
+
+impl ConformanceResult {
+    pub fn passed(&self) -> bool {
+        matches!(self.outcome, ConformanceOutcome::Passed)
+    }
+}
+
+// #TODO support nested directories.
+// #TODO support error fixtures.
+
+/// Runs the conformance test cases in `dir`. Discovers the `.tan` files paired
+/// with `.value.tan` files, evaluates them and compares the formatted value
+/// with the expected value. A string value matches both the quoted and the
+/// plain text, see `format_value`. Each case is evaluated in a fresh prelude
+/// environment, the results are sorted by path.
+pub fn run_conformance(dir: impl AsRef<Path>) -> Result<Vec<ConformanceResult>, std::io::Error> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    paths.sort();
+
+    let mut results = Vec::new();
+
+    for path in paths {
+        let name = path.to_string_lossy();
+
+        if !name.ends_with(".tan") || name.ends_with(".value.tan") {
+            continue;
+        }
+
+        let value_path = path.with_extension("value.tan");
+
+        if !value_path.exists() {
+            continue;
+        }
+
+        let input = std::fs::read_to_string(&path)?;
+        let expected = std::fs::read_to_string(&value_path)?;
+
+        let mut env = Env::prelude();
+
+        let outcome = match eval_string(input, &mut env) {
+            Ok(value) => {
+                let actual = format!("{value}");
+                let plain = format_value(value);
+                let expected_value = expected.trim();
+
+                if actual.trim() == expected_value || plain.trim() == expected_value {
+                    ConformanceOutcome::Passed
+                } else {
+                    ConformanceOutcome::Failed { expected, actual }
+                }
+            }
+            Err(errors) => ConformanceOutcome::Error(errors),
+        };
+
+        results.push(ConformanceResult { path, outcome });
+    }
+
+    Ok(results)
+}
+
+/// Returns the signature help of the call form at the cursor `offset` (a char
+/// offset) of the source, against the prelude. See `Runtime::signature_help`
+/// to also consider the definitions of an interactive session.
+pub fn signature_help(source: impl AsRef<str>, offset: usize) -> Option<SignatureHelp> {
+    signature_help_with(source.as_ref(), offset, &Env::prelude(), &TypeEnv::new())
+}
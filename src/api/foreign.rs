@@ -0,0 +1,256 @@
+use std::rc::Rc;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{context::HostForm, env::Env},
+    expr::{
+        convert::{FromExpr, IntoExpr},
+        Expr,
+    },
+    ops::schema::{is_type, value_type},
+    range::Ranged,
+    util::is_reserved_symbol,
+};
+
+// #Insight
+// A typed foreign function is registered as a method (a type specialization),
+// e.g. `area$$Float$$Float`, the resolver selects the method statically from
+// the argument types, like the prelude operators. The function name itself is
+// bound to a dispatcher that selects the method dynamically, e.g. when the
+// function is passed as a value or the argument types are not resolved.
+//
+// The functions are closures, the host state is captured, e.g. in an
+// `Rc<RefCell<..>>`.
+
+// #Insight
+// A host function is a plain Rust function (or closure), the arguments and
+// the result are converted with `FromExpr` and `IntoExpr`, e.g.
+//
+// register_host_fn(&mut env, "clamp", |x: i64, lo: i64, hi: i64| x.clamp(lo, hi));
+//
+// The host function is registered as a typed function, the signature is
+// derived from the Rust types.
+
+// #TODO support variadic typed signatures, e.g. `(Many Int)`.
+// #TODO the dynamic dispatch scans the Env, cache the methods.
+
+/// The signature of a foreign function.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FnSignature {
+    /// Any number of arguments, of any type.
+    Variadic,
+    /// The number of the arguments, of any type.
+    Arity(usize),
+    /// The types of the parameters and the type of the result, e.g.
+    /// `Int`, `Float`, `Number`, `String`, `Bool`, `Array`, `Dict` or `Any`.
+    Typed(Vec<String>, String),
+}
+
+impl FnSignature {
+    /// A typed signature, e.g. `FnSignature::typed(&["Int", "Int"], "Int")`.
+    pub fn typed(params: &[&str], result: &str) -> Self {
+        FnSignature::Typed(
+            params.iter().map(|param| (*param).to_owned()).collect(),
+            result.to_owned(),
+        )
+    }
+}
+
+fn type_list<'a>(types: impl Iterator<Item = &'a str>) -> String {
+    format!("({})", types.collect::<Vec<_>>().join(" "))
+}
+
+/// Returns true if the arguments match the parameter types.
+fn args_match(args: &[Ann<Expr>], params: &[impl AsRef<str>]) -> bool {
+    args.len() == params.len()
+        && args
+            .iter()
+            .zip(params)
+            .all(|(arg, param)| is_type(arg.as_ref(), param.as_ref()))
+}
+
+/// Registers a foreign function in the Env. The typed functions are
+/// registered as methods, and the name is bound to a dispatcher of the
+/// methods, i.e. multiple typed functions can be registered with the same
+/// name. The arguments are checked against the signature.
+pub fn register_fn<F>(env: &mut Env, name: &str, signature: FnSignature, f: F)
+where
+    F: Fn(&[Ann<Expr>], &mut Env) -> Result<Ann<Expr>, Ranged<Error>> + 'static,
+{
+    match signature {
+        FnSignature::Variadic => {
+            env.insert(name, Expr::ForeignFunc(Rc::new(f)));
+        }
+        FnSignature::Arity(arity) => {
+            let fn_name = name.to_owned();
+            let func = move |args: &[Ann<Expr>], env: &mut Env| {
+                if args.len() != arity {
+                    return Err(Error::invalid_arguments(format!(
+                        "`{fn_name}` expects {arity} arguments, found {}",
+                        args.len()
+                    ))
+                    .into());
+                }
+                f(args, env)
+            };
+            env.insert(name, Expr::ForeignFunc(Rc::new(func)));
+        }
+        FnSignature::Typed(params, result) => {
+            let fn_name = name.to_owned();
+            let method = format!("{name}$${}", params.join("$$"));
+            let func = move |args: &[Ann<Expr>], env: &mut Env| {
+                if !args_match(args, &params) {
+                    return Err(Error::invalid_arguments(format!(
+                        "`{fn_name}` expects {}, found {}",
+                        type_list(params.iter().map(String::as_str)),
+                        type_list(args.iter().map(|arg| value_type(arg.as_ref())))
+                    ))
+                    .into());
+                }
+                f(args, env)
+            };
+            env.insert(
+                method,
+                Ann::with_type(Expr::ForeignFunc(Rc::new(func)), Expr::symbol(result)),
+            );
+
+            let fn_name = name.to_owned();
+            let dispatch = move |args: &[Ann<Expr>], env: &mut Env| dispatch(&fn_name, args, env);
+            env.insert(name, Expr::ForeignFunc(Rc::new(dispatch)));
+        }
+    }
+}
+
+/// Invokes the first method of the function that matches the argument types.
+fn dispatch(name: &str, args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let prefix_len = name.len() + 2;
+
+    for method in env.methods(name) {
+        let params: Vec<&str> = method[prefix_len..]
+            .split("$$")
+            .filter(|type_name| !type_name.is_empty())
+            .collect();
+
+        if !args_match(args, &params) {
+            continue;
+        }
+
+        if let Some(Ann(Expr::ForeignFunc(func), ..)) = env.get(&method) {
+            return func(args, env);
+        }
+    }
+
+    Err(Error::invalid_arguments(format!(
+        "no method of `{name}` matches the arguments {}",
+        type_list(args.iter().map(|arg| value_type(arg.as_ref())))
+    ))
+    .into())
+}
+
+/// The result of a host function, a value or a fallible value.
+pub trait HostResult {
+    /// The Tan type of the result, e.g. `Int`.
+    const TYPE_NAME: &'static str;
+
+    fn into_result(self) -> Result<Ann<Expr>, Ranged<Error>>;
+}
+
+impl<T: IntoExpr> HostResult for T {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn into_result(self) -> Result<Ann<Expr>, Ranged<Error>> {
+        Ok(self.into_expr())
+    }
+}
+
+impl<T: IntoExpr> HostResult for Result<T, Error> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn into_result(self) -> Result<Ann<Expr>, Ranged<Error>> {
+        self.map(IntoExpr::into_expr).map_err(Ranged::from)
+    }
+}
+
+/// A Rust function that can be registered as a foreign function, the `Args`
+/// are the types of the parameters, e.g. `(i64, i64)`.
+pub trait HostFn<Args> {
+    /// Returns the signature of the function, derived from the Rust types.
+    fn signature(&self) -> FnSignature;
+
+    fn call(&self, args: &[Ann<Expr>]) -> Result<Ann<Expr>, Ranged<Error>>;
+}
+
+macro_rules! impl_host_fn {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> HostFn<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R,
+            R: HostResult,
+            $($arg: FromExpr),*
+        {
+            fn signature(&self) -> FnSignature {
+                FnSignature::typed(&[$($arg::TYPE_NAME),*], R::TYPE_NAME)
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn call(&self, args: &[Ann<Expr>]) -> Result<Ann<Expr>, Ranged<Error>> {
+                let mut args = args.iter();
+                $(
+                    // The arity is checked by the typed signature.
+                    let $arg = $arg::from_expr(args.next().unwrap())?;
+                )*
+                self($($arg),*).into_result()
+            }
+        }
+    };
+}
+
+impl_host_fn!();
+impl_host_fn!(A);
+impl_host_fn!(A, B);
+impl_host_fn!(A, B, C);
+impl_host_fn!(A, B, C, D);
+impl_host_fn!(A, B, C, D, E);
+
+/// Registers a Rust function as a typed foreign function, the arguments and
+/// the result are converted automatically, e.g.
+/// `register_host_fn(&mut env, "add", |a: i64, b: i64| a + b)`.
+pub fn register_host_fn<Args, F>(env: &mut Env, name: &str, f: F)
+where
+    F: HostFn<Args> + 'static,
+{
+    let signature = f.signature();
+    register_fn(env, name, signature, move |args, _env| f.call(args));
+}
+
+// #Insight
+// A host form is a special form defined by the host, an fexpr: it receives the
+// unevaluated arguments and the Env, e.g. a query builder that captures the
+// condition of `(where (> age 18))` as data. The form can evaluate any
+// argument with `eval`, in the Env of the call-site.
+//
+// The host forms are dispatched by the head symbol, before the head is
+// evaluated, they take precedence over the bindings with the same name. The
+// arguments are not resolved, like quoted expressions.
+
+/// Registers a host special form in the Env, the form receives the
+/// unevaluated arguments.
+///
+/// # Panics
+///
+/// Panics if the name is a reserved symbol, the built-in special forms cannot
+/// be redefined.
+pub fn register_form<F>(env: &mut Env, name: &str, f: F)
+where
+    F: Fn(&[Ann<Expr>], &mut Env) -> Result<Ann<Expr>, Ranged<Error>> + 'static,
+{
+    assert!(
+        !is_reserved_symbol(name),
+        "the reserved symbol `{name}` cannot be redefined"
+    );
+
+    env.context
+        .host_forms
+        .insert(name.to_owned(), HostForm(Rc::new(f)));
+}
@@ -0,0 +1,162 @@
+use std::io;
+
+use crate::{
+    ann::Ann,
+    error::PipelineError,
+    eval::{audit::AuditReport, capabilities::Capabilities, env::Env, output::Output},
+    expr::Expr,
+    range::Range,
+    resolver::TypeEnv,
+};
+
+use super::{parse_string_all, resolve_exprs};
+
+// #Insight
+// The hints are derived from the resolve (typecheck) pass. The resolved
+// expressions lose some of the ranges of the source, e.g. a resolved list has
+// the range of its head, so the parsed and the resolved expressions are walked
+// in parallel: the ranges come from the parsed expressions, the types from the
+// resolved expressions.
+//
+// Only the known types are reported, the unresolved symbols are annotated
+// with the placeholder type `Symbol`.
+//
+// The resolver evaluates the values of the `let` bindings. The hints are
+// requested while editing, so the source is resolved without capabilities,
+// in audit mode and with the output discarded: the effects of the edited
+// program are never performed.
+
+// #TODO report the types of destructured bindings.
+// #TODO consider the parameter types in the return type of a function.
+
+/// The kind of an inlay hint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InlayHintKind {
+    /// The type of a `let` binding, e.g. `(let a 1)`.
+    Binding,
+    /// The return type of a function, e.g. `(Func (x) "hello")`.
+    Return,
+}
+
+/// An inferred type to render inline, after the range, e.g. after a binding
+/// name or the parameters of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InlayHint {
+    pub range: Range,
+    pub type_name: String,
+    pub kind: InlayHintKind,
+}
+
+/// Returns the type of a resolved expression, if known.
+fn known_type(expr: &Ann<Expr>) -> Option<String> {
+    match expr.get_type() {
+        Expr::One => None,
+        Expr::Symbol(type_name) if type_name == "Symbol" => None,
+        type_expr => Some(type_expr.to_string()),
+    }
+}
+
+/// Returns the head symbol of a list expression.
+fn head_symbol(terms: &[Ann<Expr>]) -> Option<&str> {
+    match terms.first() {
+        Some(Ann(Expr::Symbol(sym), ..)) => Some(sym),
+        _ => None,
+    }
+}
+
+fn collect_hints(parsed: &Ann<Expr>, resolved: &Ann<Expr>, hints: &mut Vec<InlayHint>) {
+    let Ann(Expr::List(terms), ..) = parsed else {
+        return;
+    };
+
+    // The optimizer rewrites `(Array ...)` lists to arrays.
+    if let Ann(Expr::Array(items), ..) = resolved {
+        if head_symbol(terms) == Some("Array") && terms.len() == items.len() + 1 {
+            for (parsed, resolved) in terms[1..].iter().zip(items) {
+                collect_hints(parsed, resolved, hints);
+            }
+        }
+        return;
+    }
+
+    let Ann(Expr::List(resolved_terms), ..) = resolved else {
+        return;
+    };
+
+    let head = head_symbol(terms);
+
+    // The structure differs, e.g. after a macro expansion.
+    if terms.len() != resolved_terms.len() || head != head_symbol(resolved_terms) {
+        return;
+    }
+
+    match head {
+        Some("let") => {
+            for (parsed, resolved) in terms[1..].chunks(2).zip(resolved_terms[1..].chunks(2)) {
+                if let ([pattern @ Ann(Expr::Symbol(..), ..), _], [_, value]) = (parsed, resolved) {
+                    if let Some(type_name) = known_type(value) {
+                        hints.push(InlayHint {
+                            range: pattern.get_range(),
+                            type_name,
+                            kind: InlayHintKind::Binding,
+                        });
+                    }
+                }
+            }
+        }
+        Some("Func") if terms.len() > 2 => {
+            // The type of the last expression of the body is the return type.
+            if let Some(type_name) = resolved_terms.last().and_then(known_type) {
+                hints.push(InlayHint {
+                    range: terms[1].get_range(),
+                    type_name,
+                    kind: InlayHintKind::Return,
+                });
+            }
+        }
+        _ => (),
+    }
+
+    for (parsed, resolved) in terms.iter().zip(resolved_terms).skip(1) {
+        collect_hints(parsed, resolved, hints);
+    }
+}
+
+/// Returns the inlay hints of the source, the inferred types of the `let`
+/// bindings and the return types of the functions. The source is resolved
+/// in the Env, the definitions are applied to the Env, like `resolve_string`.
+/// The expressions that fail to resolve are skipped. The effects of the
+/// source are not performed.
+pub fn inlay_hints(
+    source: impl AsRef<str>,
+    env: &mut Env,
+) -> Result<Vec<InlayHint>, PipelineError> {
+    let exprs = parse_string_all(source)?;
+
+    let context = &mut env.context;
+    let capabilities = std::mem::replace(&mut context.capabilities, Capabilities::none());
+    let audit = context.audit.replace(AuditReport::new());
+    let stdout = std::mem::replace(&mut context.stdout, Output::new(io::sink()));
+    let stderr = std::mem::replace(&mut context.stderr, Output::new(io::sink()));
+
+    let mut types = TypeEnv::new();
+    let mut hints = Vec::new();
+
+    for expr in exprs {
+        let Ok(resolved) = resolve_exprs(vec![expr.clone()], env, &mut types) else {
+            continue;
+        };
+
+        if let Some(resolved) = resolved.first() {
+            collect_hints(&expr, resolved, &mut hints);
+        }
+    }
+
+    let context = &mut env.context;
+    context.capabilities = capabilities;
+    context.audit = audit;
+    context.stdout = stdout;
+    context.stderr = stderr;
+
+    Ok(hints)
+}
@@ -0,0 +1,282 @@
+use crate::{
+    ann::Ann,
+    eval::env::Env,
+    expr::Expr,
+    lexer::token::Token,
+    parser::Parser,
+    range::Ranged,
+    resolver::{Resolver, TypeEnv},
+};
+
+use super::lex_string;
+
+// #Insight
+// The signature help is computed from the tokens, not the parsed expressions,
+// the source is typically incomplete while editing, e.g. `(+ 1 `.
+//
+// The overloads of a function are the methods registered in the Env, e.g.
+// `+$$Int$$Int`, and the clauses of a Tan function. The types of the arguments
+// are resolved against the type environment, the overload that matches the
+// argument types is the active signature.
+
+// #TODO resolve the types of nested call arguments.
+// #TODO consider the definitions of the source itself.
+
+/// A parameter of a signature, the name and the type are optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: Option<String>,
+    pub type_name: Option<String>,
+}
+
+impl Parameter {
+    /// Returns the label of the parameter, e.g. `#Int x`.
+    pub fn label(&self) -> String {
+        match (&self.name, &self.type_name) {
+            (Some(name), Some(type_name)) => format!("#{type_name} {name}"),
+            (Some(name), None) => name.clone(),
+            (None, Some(type_name)) => type_name.clone(),
+            (None, None) => "_".to_owned(),
+        }
+    }
+}
+
+/// A signature (overload) of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+    /// The label of the signature, e.g. `(+ Int Int)`.
+    pub label: String,
+    pub parameters: Vec<Parameter>,
+    /// True if the signature accepts more arguments than the parameters,
+    /// e.g. the parameters of a foreign function are unknown.
+    pub is_variadic: bool,
+}
+
+impl Signature {
+    fn new(name: &str, parameters: Vec<Parameter>, is_variadic: bool) -> Self {
+        let label = std::iter::once(name.to_owned())
+            .chain(parameters.iter().map(Parameter::label))
+            .chain(is_variadic.then(|| "...".to_owned()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Self {
+            label: format!("({label})"),
+            parameters,
+            is_variadic,
+        }
+    }
+
+    /// Returns true if the signature accepts arguments of the given types,
+    /// the unknown types match any parameter.
+    fn accepts(&self, arg_types: &[Option<String>]) -> bool {
+        (self.is_variadic || arg_types.len() <= self.parameters.len())
+            && arg_types
+                .iter()
+                .zip(&self.parameters)
+                .all(|(arg_type, param)| match (arg_type, &param.type_name) {
+                    (Some(arg_type), Some(param_type)) => arg_type == param_type,
+                    _ => true,
+                })
+    }
+}
+
+/// The signature help of a call form, see `signature_help`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureHelp {
+    /// The head symbol of the call form.
+    pub name: String,
+    /// The signatures (overloads) of the function.
+    pub signatures: Vec<Signature>,
+    /// The index of the signature that matches the argument types, if any.
+    pub active_signature: Option<usize>,
+    /// The index of the argument at the cursor.
+    pub active_parameter: usize,
+}
+
+/// A term of a form, the symbol (if the term is a symbol) and the type.
+struct Term {
+    symbol: Option<String>,
+    type_name: Option<String>,
+    end: usize,
+}
+
+/// An open form, a list (call) or an array/dict.
+struct Form {
+    is_call: bool,
+    terms: Vec<Term>,
+}
+
+/// Returns the type of an atom token, resolved against the type environment.
+fn token_type(token: &Ranged<Token>, types: &TypeEnv) -> Option<String> {
+    let mut exprs = Parser::new(vec![token.clone()]).parse().ok()?;
+
+    if exprs.len() != 1 {
+        return None;
+    }
+
+    // #Insight
+    // The atom is resolved in an empty Env, the symbols are resolved only
+    // against the type environment.
+    let expr = Resolver::with_types(types.clone()).resolve_expr(exprs.remove(0), &mut Env::new());
+
+    match expr.get_type() {
+        Expr::Symbol(type_name) if type_name != "Symbol" => Some(type_name.clone()),
+        _ => None,
+    }
+}
+
+/// Returns the parameters of a function clause.
+fn clause_parameters(params: &[Ann<Expr>]) -> Vec<Parameter> {
+    params
+        .iter()
+        .map(|param| Parameter {
+            name: match &param.0 {
+                Expr::Symbol(name) => Some(name.clone()),
+                _ => Some(param.0.to_string()),
+            },
+            // The resolver annotates the unbound symbols with the `Symbol`
+            // type, it's not the type of the parameter.
+            type_name: match param.get_annotation("type") {
+                Some(Expr::Symbol(type_name)) if type_name != "Symbol" => Some(type_name.clone()),
+                _ => None,
+            },
+        })
+        .collect()
+}
+
+/// Returns the signatures of the function bound to the name.
+fn signatures(name: &str, env: &Env) -> Vec<Signature> {
+    let prefix = format!("{name}$$");
+
+    let methods = env.methods(name);
+
+    let mut signatures: Vec<Signature> = methods
+        .iter()
+        .map(|method| {
+            let parameters = method[prefix.len()..]
+                .split("$$")
+                .filter(|type_name| !type_name.is_empty())
+                .map(|type_name| Parameter {
+                    name: None,
+                    type_name: Some(type_name.to_owned()),
+                })
+                .collect();
+            Signature::new(name, parameters, false)
+        })
+        .collect();
+
+    match env.get(name).map(|value| value.0) {
+        Some(Expr::Func(clauses, _)) => {
+            for clause in clauses.iter() {
+                signatures.push(Signature::new(
+                    name,
+                    clause_parameters(&clause.params),
+                    false,
+                ));
+            }
+        }
+        Some(Expr::ForeignFunc(..)) if signatures.is_empty() => {
+            // The parameters of a foreign function without methods are unknown.
+            signatures.push(Signature::new(name, Vec::new(), true));
+        }
+        _ => (),
+    }
+
+    signatures
+}
+
+/// Returns the signature help of the call form at the cursor `offset` (a
+/// char offset) of the source, resolved against the Env and the type
+/// environment. Returns None if the cursor is not in a call form of a known
+/// function.
+pub fn signature_help_with(
+    source: &str,
+    offset: usize,
+    env: &Env,
+    types: &TypeEnv,
+) -> Option<SignatureHelp> {
+    let tokens = lex_string(source).ok()?;
+
+    let mut forms: Vec<Form> = Vec::new();
+
+    for token in tokens.iter().take_while(|token| token.1.start < offset) {
+        match &token.0 {
+            Token::LeftParen => forms.push(Form {
+                is_call: true,
+                terms: Vec::new(),
+            }),
+            Token::LeftBracket | Token::LeftBrace => forms.push(Form {
+                is_call: false,
+                terms: Vec::new(),
+            }),
+            Token::RightParen | Token::RightBracket | Token::RightBrace => {
+                forms.pop();
+                if let Some(form) = forms.last_mut() {
+                    form.terms.push(Term {
+                        symbol: None,
+                        type_name: None,
+                        end: token.1.end,
+                    });
+                }
+            }
+            Token::Quote
+            | Token::QuasiQuote
+            | Token::Unquote
+            | Token::Annotation(..)
+            | Token::Comment(..) => (),
+            Token::Symbol(..)
+            | Token::Number(..)
+            | Token::String(..)
+            | Token::InterpolatedString(..) => {
+                if let Some(form) = forms.last_mut() {
+                    form.terms.push(Term {
+                        symbol: match &token.0 {
+                            Token::Symbol(symbol) => Some(symbol.clone()),
+                            _ => None,
+                        },
+                        type_name: token_type(token, types),
+                        end: token.1.end,
+                    });
+                }
+            }
+        }
+    }
+
+    // The innermost call form, an array or dict is an argument being edited.
+    let (i, form) = forms
+        .iter()
+        .enumerate()
+        .rev()
+        .find(|(_, form)| form.is_call)?;
+
+    let (head, args) = form.terms.split_first()?;
+    let name = head.symbol.clone()?;
+
+    let is_innermost = i == forms.len() - 1;
+
+    // The cursor at the end of an argument is still editing the argument.
+    let active_parameter = match args.last() {
+        Some(arg) if is_innermost && arg.end >= offset => args.len() - 1,
+        _ => args.len(),
+    };
+
+    let signatures = signatures(&name, env);
+
+    if signatures.is_empty() {
+        return None;
+    }
+
+    let arg_types: Vec<_> = args.iter().map(|arg| arg.type_name.clone()).collect();
+
+    let active_signature = signatures
+        .iter()
+        .position(|signature| signature.accepts(&arg_types));
+
+    Some(SignatureHelp {
+        name,
+        signatures,
+        active_signature,
+        active_parameter,
+    })
+}
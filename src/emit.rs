@@ -0,0 +1,56 @@
+pub mod js;
+
+use crate::{
+    ann::Ann,
+    api::parse_string_all,
+    error::{Error, PipelineError},
+    expr::Expr,
+    range::Ranged,
+};
+
+// #Insight
+// The emitters translate Tan programs to the source of another language
+// (source-to-source), e.g. to share validated configuration and logic between
+// the Rust runtime and web frontends:
+//
+// let js = emit_string("(let area (Func (w h) (* w h)))", &JsEmitter)?;
+//
+// The emitters operate on the parsed expressions, before the macro expansion
+// and the resolving, the programs are translated as written. A form without
+// an equivalent in the target language is an `Error::Unsupported`, with the
+// range of the form.
+//
+// The emitted code calls the functions of the program by name, a function of
+// the Tan prelude should be provided by the target runtime.
+
+// #TODO emit the source maps from the ranges.
+// #TODO support macro-expanded programs.
+
+/// A backend of the source-to-source translation, emits the source of a
+/// target language.
+pub trait Emitter {
+    /// Returns the name of the target language, e.g. `JavaScript`.
+    fn target(&self) -> &'static str;
+
+    /// Emits the target source of a program, i.e. of the top-level
+    /// expressions.
+    fn emit(&self, exprs: &[Ann<Expr>]) -> Result<String, Ranged<Error>>;
+
+    /// Returns the error of a form not supported by the emitter.
+    fn unsupported(&self, form: &str, expr: &Ann<Expr>) -> Ranged<Error> {
+        Ranged(
+            Error::Unsupported(form.to_owned(), self.target().to_owned()),
+            expr.get_range(),
+        )
+    }
+}
+
+/// Parses a Tan program encoded as a text string, emits the source of the
+/// target language of the emitter.
+pub fn emit_string(input: impl AsRef<str>, emitter: &dyn Emitter) -> Result<String, PipelineError> {
+    let exprs = parse_string_all(input)?;
+
+    emitter
+        .emit(&exprs)
+        .map_err(|error| PipelineError::Emit(vec![error]))
+}
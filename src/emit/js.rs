@@ -0,0 +1,484 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    expr::Expr,
+    range::Ranged,
+    util::{is_reserved_symbol, FORMAT_SYMBOL},
+};
+
+use super::Emitter;
+
+// #Insight
+// The JavaScript emitter covers the functional core of the language: the
+// literals, `let`, `if`, `do`, the functions and the calls. The `do` and
+// `let` forms in expression position are emitted as immediately invoked
+// arrow functions, e.g. `(() => { const x = 1; return x; })()`.
+//
+// The numbers are JavaScript numbers (i.e. the Ints are floats, `/` is not an
+// integer division), the BigInts are JavaScript BigInts. The equality `=` is
+// the strict equality `===`, the collections are compared by reference.
+
+// #TODO support `cond`, `for`, `match`, `set!` and the multi-clause functions.
+// #TODO the bindings are emitted as `const`, a redefinition fails in JavaScript.
+// #TODO the mangling of the symbols may collide, e.g. `a-b` and `a_b`.
+
+/// The reserved words of JavaScript, the symbols with these names are
+/// prefixed with `$`.
+const RESERVED_WORDS: [&str; 38] = [
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "null",
+    "return",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+/// Emits JavaScript (ES2020) source.
+#[derive(Debug, Default)]
+pub struct JsEmitter;
+
+impl Emitter for JsEmitter {
+    fn target(&self) -> &'static str {
+        "JavaScript"
+    }
+
+    fn emit(&self, exprs: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        let mut output = String::new();
+
+        for expr in exprs {
+            let stmt = match expr.as_ref() {
+                Expr::Comment(text) => format!("// {}", text.trim_start_matches([';', '-']).trim()),
+                _ => self.emit_stmt(expr)?,
+            };
+            output.push_str(&stmt);
+            output.push('\n');
+        }
+
+        Ok(output)
+    }
+}
+
+impl JsEmitter {
+    /// Emits a statement, i.e. a top-level expression or a non-final
+    /// expression of a block.
+    fn emit_stmt(&self, expr: &Ann<Expr>) -> Result<String, Ranged<Error>> {
+        match expr.as_ref() {
+            Expr::List(list) if is_form(list, "let") => self.emit_let(expr, &list[1..]),
+            _ => Ok(format!("{};", self.emit_expr(expr)?)),
+        }
+    }
+
+    /// Emits the declarations of a `let`, returns the declarations and the
+    /// identifier of the last bound value.
+    fn emit_let_decls(
+        &self,
+        expr: &Ann<Expr>,
+        args: &[Ann<Expr>],
+    ) -> Result<(String, String), Ranged<Error>> {
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed let"),
+                expr.get_range(),
+            ));
+        }
+
+        let mut decls = Vec::new();
+        let mut last_id = String::new();
+
+        for pair in args.chunks(2) {
+            let [pattern, value] = pair else {
+                unreachable!();
+            };
+
+            let Ann(Expr::Symbol(name), _) = pattern else {
+                return Err(self.unsupported("let destructuring", pattern));
+            };
+
+            last_id = ident(name);
+            decls.push(format!("const {last_id} = {};", self.emit_expr(value)?));
+        }
+
+        Ok((decls.join(" "), last_id))
+    }
+
+    fn emit_let(&self, expr: &Ann<Expr>, args: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        let (decls, _) = self.emit_let_decls(expr, args)?;
+        Ok(decls)
+    }
+
+    /// Emits a block of expressions, the block returns the value of the last
+    /// expression.
+    fn emit_block(&self, exprs: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        let Some((last, init)) = exprs.split_last() else {
+            return Ok("{ return null; }".to_owned());
+        };
+
+        let mut stmts = Vec::new();
+
+        for expr in init {
+            stmts.push(self.emit_stmt(expr)?);
+        }
+
+        match last.as_ref() {
+            Expr::List(list) if is_form(list, "let") => {
+                let (decls, last_id) = self.emit_let_decls(last, &list[1..])?;
+                stmts.push(decls);
+                stmts.push(format!("return {last_id};"));
+            }
+            _ => stmts.push(format!("return {};", self.emit_expr(last)?)),
+        }
+
+        Ok(format!("{{ {} }}", stmts.join(" ")))
+    }
+
+    fn emit_expr(&self, expr: &Ann<Expr>) -> Result<String, Ranged<Error>> {
+        let js = match expr.as_ref() {
+            Expr::One => "null".to_owned(),
+            Expr::Bool(b) => b.to_string(),
+            Expr::Int(n) => n.to_string(),
+            Expr::Float(n) => {
+                if n.is_nan() {
+                    "NaN".to_owned()
+                } else if n.is_infinite() {
+                    if *n > 0.0 { "Infinity" } else { "-Infinity" }.to_owned()
+                } else {
+                    n.to_string()
+                }
+            }
+            Expr::BigInt(n) => format!("{n}n"),
+            Expr::String(s) => js_string(s),
+            Expr::Char(c) => js_string(&c.to_string()),
+            Expr::KeySymbol(s) => js_string(s),
+            Expr::Symbol(sym) => ident(sym),
+            Expr::Array(items) => format!("[{}]", self.emit_args(items)?),
+            Expr::Dict(dict) => {
+                let mut entries = Vec::new();
+                for (key, value) in dict {
+                    entries.push(format!("{}: {}", js_string(key), self.emit_expr(value)?));
+                }
+                object(&entries)
+            }
+            Expr::List(list) => {
+                // The comments are elided.
+                let list: Vec<Ann<Expr>> = list
+                    .iter()
+                    .filter(|term| !matches!(term.as_ref(), Expr::Comment(..)))
+                    .cloned()
+                    .collect();
+                self.emit_list(expr, &list)?
+            }
+            Expr::Decimal(..) => return Err(self.unsupported("Decimal", expr)),
+            _ => return Err(self.unsupported(&expr.to_string(), expr)),
+        };
+
+        Ok(js)
+    }
+
+    fn emit_args(&self, args: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        let args: Result<Vec<String>, _> = args.iter().map(|arg| self.emit_expr(arg)).collect();
+        Ok(args?.join(", "))
+    }
+
+    fn emit_list(&self, expr: &Ann<Expr>, list: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        let Some((head, args)) = list.split_first() else {
+            return Ok("null".to_owned());
+        };
+
+        let Ann(Expr::Symbol(sym), _) = head else {
+            // The head is an expression, e.g. an inline function.
+            return Ok(format!(
+                "{}({})",
+                self.emit_callee(head)?,
+                self.emit_args(args)?
+            ));
+        };
+
+        let js = match sym.as_str() {
+            "do" => format!("(() => {})()", self.emit_block(args)?),
+            "let" => format!("(() => {})()", self.emit_block(std::slice::from_ref(expr))?),
+            "if" => match args {
+                [cond, then] => format!(
+                    "({} ? {} : null)",
+                    self.emit_expr(cond)?,
+                    self.emit_expr(then)?
+                ),
+                [cond, then, else_] => format!(
+                    "({} ? {} : {})",
+                    self.emit_expr(cond)?,
+                    self.emit_expr(then)?,
+                    self.emit_expr(else_)?
+                ),
+                _ => return Err(self.unsupported("multi-branch if", expr)),
+            },
+            "Func" => self.emit_func(expr, args)?,
+            "Array" | "List" => format!("[{}]", self.emit_args(args)?),
+            "Dict" => self.emit_dict(expr, args)?,
+            "not" => match args {
+                [arg] => format!("(!{})", self.emit_expr(arg)?),
+                _ => {
+                    return Err(Ranged(
+                        Error::invalid_arguments("`not` requires one argument"),
+                        expr.get_range(),
+                    ))
+                }
+            },
+            "-" if args.len() == 1 => {
+                let operand = self.emit_expr(&args[0])?;
+                // A space, to not emit the decrement operator, e.g. `(--2)`.
+                if operand.starts_with('-') {
+                    format!("(- {operand})")
+                } else {
+                    format!("(-{operand})")
+                }
+            }
+            // The identities of the logical operators.
+            "and" if args.is_empty() => "true".to_owned(),
+            "or" if args.is_empty() => "false".to_owned(),
+            // The interpolated strings call the `format` of the target runtime.
+            FORMAT_SYMBOL => format!("format({})", self.emit_args(args)?),
+            _ => {
+                if let Some(op) = infix_operator(sym) {
+                    return self.emit_infix(expr, op, args);
+                }
+
+                if is_reserved_symbol(sym) {
+                    return Err(self.unsupported(sym, expr));
+                }
+
+                format!("{}({})", ident(sym), self.emit_args(args)?)
+            }
+        };
+
+        Ok(js)
+    }
+
+    /// Emits a Dict constructor, e.g. `{:x 1}` -> `({"x": 1})`.
+    fn emit_dict(&self, expr: &Ann<Expr>, args: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        if !args.len().is_multiple_of(2) {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed Dict"),
+                expr.get_range(),
+            ));
+        }
+
+        let mut entries = Vec::new();
+
+        for pair in args.chunks(2) {
+            let [key, value] = pair else {
+                unreachable!();
+            };
+
+            let key = match key.as_ref() {
+                Expr::KeySymbol(s) | Expr::String(s) => js_string(s),
+                _ => return Err(self.unsupported("non-literal Dict key", key)),
+            };
+
+            entries.push(format!("{key}: {}", self.emit_expr(value)?));
+        }
+
+        Ok(object(&entries))
+    }
+
+    /// Emits the callee of a call, the inline functions are already
+    /// parenthesized.
+    fn emit_callee(&self, head: &Ann<Expr>) -> Result<String, Ranged<Error>> {
+        let callee = self.emit_expr(head)?;
+
+        match head.as_ref() {
+            Expr::List(list) if is_form(list, "Func") => Ok(callee),
+            _ => Ok(format!("({callee})")),
+        }
+    }
+
+    /// Emits an arrow function, e.g. `(Func (w h) (* w h))` -> `((w, h) => (w * h))`.
+    fn emit_func(&self, expr: &Ann<Expr>, args: &[Ann<Expr>]) -> Result<String, Ranged<Error>> {
+        if is_multi_clause(args) {
+            return Err(self.unsupported("multi-clause Func", expr));
+        }
+
+        let [params, body] = args else {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed func definition"),
+                expr.get_range(),
+            ));
+        };
+
+        // The empty parameter list `()` is parsed as One.
+        let params = match params.as_ref() {
+            Expr::List(params) => params.as_slice(),
+            Expr::One => &[],
+            _ => return Err(self.unsupported("Func parameter pattern", params)),
+        };
+
+        let mut ids = Vec::new();
+
+        for param in params {
+            let Ann(Expr::Symbol(name), _) = param else {
+                return Err(self.unsupported("Func parameter pattern", param));
+            };
+            ids.push(ident(name));
+        }
+
+        let body = match body.as_ref() {
+            Expr::List(list) if is_form(list, "do") => self.emit_block(&list[1..])?,
+            Expr::List(list) if is_form(list, "let") => {
+                self.emit_block(std::slice::from_ref(body))?
+            }
+            _ => self.emit_expr(body)?,
+        };
+
+        Ok(format!("(({}) => {body})", ids.join(", ")))
+    }
+
+    fn emit_infix(
+        &self,
+        expr: &Ann<Expr>,
+        op: &str,
+        args: &[Ann<Expr>],
+    ) -> Result<String, Ranged<Error>> {
+        let is_comparison = matches!(op, "===" | "!==" | "<" | ">" | "<=" | ">=");
+
+        if args.len() < 2 && (is_comparison || args.is_empty()) {
+            return Err(Ranged(
+                Error::invalid_arguments(format!("`{op}` requires at least two arguments")),
+                expr.get_range(),
+            ));
+        }
+
+        let operands: Result<Vec<String>, _> = args.iter().map(|arg| self.emit_expr(arg)).collect();
+        let operands = operands?;
+
+        if is_comparison && operands.len() > 2 {
+            // A chained comparison, e.g. `(< a b c)` -> `(a < b && b < c)`.
+            let pairs: Vec<String> = operands
+                .windows(2)
+                .map(|pair| format!("{} {op} {}", pair[0], pair[1]))
+                .collect();
+            return Ok(format!("({})", pairs.join(" && ")));
+        }
+
+        Ok(format!("({})", operands.join(&format!(" {op} "))))
+    }
+}
+
+/// Returns the JavaScript object literal of the entries, the parentheses
+/// disambiguate the object from a block.
+fn object(entries: &[String]) -> String {
+    format!("({{{}}})", entries.join(", "))
+}
+
+/// Returns true if the list is a special form, e.g. `(let ...)`.
+fn is_form(list: &[Ann<Expr>], name: &str) -> bool {
+    matches!(list.first(), Some(Ann(Expr::Symbol(sym), _)) if sym == name)
+}
+
+/// Returns true if the arguments of a `Func` are clauses, e.g.
+/// `(Func ((0) 1) ((n) (* n 2)))`.
+fn is_multi_clause(args: &[Ann<Expr>]) -> bool {
+    !args.is_empty()
+        && args.iter().all(|clause| {
+            matches!(
+                clause.as_ref(),
+                Expr::List(clause) if matches!(&clause[..], [Ann(Expr::List(..) | Expr::One, _), _])
+            )
+        })
+}
+
+/// Returns the JavaScript operator of a Tan function, if any.
+fn infix_operator(sym: &str) -> Option<&'static str> {
+    let op = match sym {
+        "+" => "+",
+        "-" => "-",
+        "*" => "*",
+        "/" => "/",
+        "%" => "%",
+        "=" => "===",
+        "!=" => "!==",
+        "<" => "<",
+        ">" => ">",
+        "<=" => "<=",
+        ">=" => ">=",
+        "and" => "&&",
+        "or" => "||",
+        _ => return None,
+    };
+
+    Some(op)
+}
+
+/// Returns the JavaScript identifier of a Tan symbol, e.g. `count-down` ->
+/// `count_down`, `even?` -> `even$p`.
+fn ident(sym: &str) -> String {
+    let mut id = String::new();
+
+    for c in sym.chars() {
+        match c {
+            c if c.is_alphanumeric() || c == '_' || c == '$' => id.push(c),
+            '-' => id.push('_'),
+            '?' => id.push_str("$p"),
+            '!' => id.push_str("$x"),
+            _ => id.push_str(&format!("${:x}", c as u32)),
+        }
+    }
+
+    if id.starts_with(|c: char| c.is_ascii_digit()) || RESERVED_WORDS.contains(&id.as_str()) {
+        id.insert(0, '$');
+    }
+
+    id
+}
+
+/// Returns the JavaScript string literal of the text.
+fn js_string(text: &str) -> String {
+    let mut literal = String::from('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            // The line separators are line terminators in JavaScript.
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                literal.push_str(&format!("\\u{:04x}", c as u32));
+            }
+            c => literal.push(c),
+        }
+    }
+
+    literal.push('"');
+    literal
+}
@@ -3,7 +3,16 @@ use std::{
     num::{ParseFloatError, ParseIntError},
 };
 
-use crate::{lexer::token::Token, range::Ranged};
+use num_bigint::ParseBigIntError;
+
+use crate::{
+    ann::Ann,
+    eval::{diagnostics::Diagnostics, flow::Flow, limits::Limit},
+    expr::{format_value, Expr},
+    lexer::token::Token,
+    range::{Position, Ranged},
+    style::{Color, Style},
+};
 
 // #TODO: Split comptime/runtime errors?
 
@@ -22,24 +31,51 @@ pub enum Error {
     UnexpectedEnd,
     MalformedInt(ParseIntError),
     MalformedFloat(ParseFloatError),
+    MalformedBigInt(ParseBigIntError),
+    MalformedDecimal(rust_decimal::Error),
     UnterminatedString,
     UnterminatedAnnotation,
+    MalformedStringEscape(String),
 
     // Syntactic (parse) errors
     InvalidQuote,
     UnexpectedToken(Token),
     UnterminatedList,
     MalformedAnnotation(String),
+    MalformedInterpolation(String),
+    MissingDictValue(String),
+    MalformedKeySymbol(String),
+    NumberOutOfRange(String, String),
 
     // Semantic errors
     UndefinedSymbol(String), // #TODO maybe pass the whole Symbol expression?
     UndefinedFunction(String, String), // #TODO maybe pass the whole Symbol expression?
     InvalidArguments(String),
     NotInvocable(String), // #TODO maybe the non-invocable Annotated<Expr> should be the param?
-    FailedUse,            // #TODO temp, better name needed, rethink!
+    FailedUse(String),
+    MacroExpansionLimit(String),
+    // The special form and the name of the disabled feature flag.
+    FeatureDisabled(String, String),
+    // The form and the target language of the emitter, see `Emitter`.
+    Unsupported(String, String),
 
     // Runtime errors
     Io(std::io::Error),
+    TimedOut,
+    Interrupted,
+    LimitExceeded(Limit),
+    // The op and the denied capability, see `Capabilities`.
+    CapabilityDenied(String, String),
+    DivisionByZero,
+    IntegerOverflow(String),
+    // A value thrown by `throw`, see the `try` special form.
+    Thrown(Ann<Expr>),
+
+    // Control-flow signals
+    Flow(Flow),
+    // A signal that escaped a function body, e.g. a `break` outside of a loop
+    // of the function.
+    EscapedFlow(Flow),
 }
 
 impl std::error::Error for Error {}
@@ -50,18 +86,55 @@ impl fmt::Display for Error {
             Error::UnexpectedEnd => "unexpected end of input".to_owned(),
             Error::MalformedInt(pie) => format!("malformed integer number: {pie}"),
             Error::MalformedFloat(pie) => format!("malformed float number: {pie}"),
+            Error::MalformedBigInt(pie) => format!("malformed BigInt number: {pie}"),
+            Error::MalformedDecimal(pie) => format!("malformed Decimal number: {pie}"),
             Error::UnterminatedString => "unterminated string".to_owned(),
             Error::UnterminatedAnnotation => "unterminated annotation".to_owned(),
+            Error::MalformedStringEscape(seq) => {
+                format!("malformed string escape sequence `\\{seq}`")
+            }
             Error::InvalidQuote => "invalid quote".to_owned(),
             Error::UnexpectedToken(token) => format!("unexpected `{token}`"),
             Error::UnterminatedList => "unterminated list".to_owned(),
             Error::MalformedAnnotation(ann) => format!("malformed annotation `{ann}`"),
+            Error::MalformedKeySymbol(sym) => format!("malformed KeySymbol `{sym}`"),
+            Error::NumberOutOfRange(n, type_name) => {
+                format!("the number `{n}` cannot be represented as `{type_name}`")
+            }
+            Error::MissingDictValue(key) => format!("missing value for Dict key `{key}`"),
+            Error::MalformedInterpolation(source) => {
+                format!("malformed string interpolation `${{{source}}}`")
+            }
             Error::UndefinedSymbol(sym) => format!("`{sym}` is undefined"),
             Error::UndefinedFunction(sym, signature) => {
                 format!("function `{sym}` with signature `{signature}` is undefined")
             }
             Error::Io(io_err) => format!("i/o error: {io_err}"),
-            Error::FailedUse => "failed use".to_owned(),
+            Error::TimedOut => "evaluation timed out".to_owned(),
+            Error::Interrupted => "evaluation interrupted".to_owned(),
+            Error::LimitExceeded(limit) => format!("{limit} is exceeded"),
+            Error::CapabilityDenied(op, capability) => {
+                format!("`{op}` requires the `{capability}` capability")
+            }
+            Error::DivisionByZero => "division by zero".to_owned(),
+            Error::IntegerOverflow(op) => format!("integer overflow in `{op}`"),
+            Error::Thrown(value) => format_value(value),
+            Error::Flow(Flow::Break(..)) | Error::EscapedFlow(Flow::Break(..)) => {
+                "`break` outside of a loop".to_owned()
+            }
+            Error::Flow(Flow::Continue) | Error::EscapedFlow(Flow::Continue) => {
+                "`continue` outside of a loop".to_owned()
+            }
+            Error::FailedUse(text) => text.to_owned(),
+            Error::MacroExpansionLimit(sym) => {
+                format!("the expansion of macro `{sym}` exceeds the maximum depth")
+            }
+            Error::FeatureDisabled(form, flag) => {
+                format!("feature `{form}` requires enabling `{flag}`")
+            }
+            Error::Unsupported(form, target) => {
+                format!("`{form}` is not supported by the {target} emitter")
+            }
             Error::InvalidArguments(text) => text.to_owned(),
             Error::NotInvocable(text) => text.to_owned(),
         };
@@ -83,6 +156,15 @@ impl From<std::io::Error> for Ranged<Error> {
 }
 
 impl Error {
+    /// Returns true if the error is caused by incomplete input, i.e. the
+    /// input could be completed with more text, e.g. an unterminated list.
+    pub fn is_incomplete_input(&self) -> bool {
+        matches!(
+            self,
+            Error::UnterminatedList | Error::UnterminatedString | Error::UnterminatedAnnotation
+        )
+    }
+
     pub fn invalid_arguments(text: impl Into<String>) -> Self {
         Self::InvalidArguments(text.into())
     }
@@ -90,6 +172,39 @@ impl Error {
     pub fn not_invocable(text: impl Into<String>) -> Self {
         Self::NotInvocable(text.into())
     }
+
+    /// Returns true if the error can be caught by a `try` expression. The
+    /// control-flow signals and the aborts of the evaluation are not
+    /// catchable.
+    pub fn is_catchable(&self) -> bool {
+        !matches!(self, Error::Flow(..)) && !self.is_abort()
+    }
+
+    /// Returns true if the error aborts the evaluation, i.e. a time-out, an
+    /// interrupt or an exceeded limit.
+    pub fn is_abort(&self) -> bool {
+        matches!(
+            self,
+            Error::TimedOut | Error::Interrupted | Error::LimitExceeded(..)
+        )
+    }
+}
+
+/// A diagnostic that does not prevent the evaluation, reported by the
+/// resolve (typecheck) pass.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The expression follows a diverging expression, e.g. `(exit 1)`, it is
+    /// never evaluated.
+    UnreachableCode,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::UnreachableCode => write!(f, "unreachable code"),
+        }
+    }
 }
 
 impl From<Error> for Ranged<Error> {
@@ -98,3 +213,213 @@ impl From<Error> for Ranged<Error> {
         Ranged(value, 0..0)
     }
 }
+
+// #Insight
+// The stages of the pipeline return multiple errors. The PipelineError keeps
+// the stage that failed, it dereferences to the errors for convenience.
+
+/// An error of the evaluation pipeline, the errors of the failed stage.
+#[derive(Debug)]
+pub enum PipelineError {
+    Lex(Vec<Ranged<Error>>),
+    Parse(Vec<Ranged<Error>>),
+    /// Macro expansion and resolving errors.
+    Resolve(Vec<Ranged<Error>>),
+    Eval(Vec<Ranged<Error>>),
+    /// Source-to-source emission errors, see `emit_string`.
+    Emit(Vec<Ranged<Error>>),
+}
+
+impl PipelineError {
+    /// Returns the errors of the failed stage.
+    pub fn errors(&self) -> &[Ranged<Error>] {
+        match self {
+            PipelineError::Lex(errors)
+            | PipelineError::Parse(errors)
+            | PipelineError::Resolve(errors)
+            | PipelineError::Eval(errors)
+            | PipelineError::Emit(errors) => errors,
+        }
+    }
+
+    /// Consumes the pipeline error, returns the errors of the failed stage.
+    pub fn into_errors(self) -> Vec<Ranged<Error>> {
+        match self {
+            PipelineError::Lex(errors)
+            | PipelineError::Parse(errors)
+            | PipelineError::Resolve(errors)
+            | PipelineError::Eval(errors)
+            | PipelineError::Emit(errors) => errors,
+        }
+    }
+
+    /// Returns the name of the failed stage.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            PipelineError::Lex(..) => "lex",
+            PipelineError::Parse(..) => "parse",
+            PipelineError::Resolve(..) => "resolve",
+            PipelineError::Eval(..) => "eval",
+            PipelineError::Emit(..) => "emit",
+        }
+    }
+}
+
+impl std::ops::Deref for PipelineError {
+    type Target = [Ranged<Error>];
+
+    fn deref(&self) -> &Self::Target {
+        self.errors()
+    }
+}
+
+impl IntoIterator for PipelineError {
+    type Item = Ranged<Error>;
+    type IntoIter = std::vec::IntoIter<Ranged<Error>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_errors().into_iter()
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors = self
+            .errors()
+            .iter()
+            .map(|error| error.to_string())
+            .collect::<Vec<String>>()
+            .join("; ");
+
+        write!(f, "{} failed: {errors}", self.stage())
+    }
+}
+
+impl From<PipelineError> for Vec<Ranged<Error>> {
+    fn from(value: PipelineError) -> Self {
+        value.into_errors()
+    }
+}
+
+impl From<Ranged<Error>> for PipelineError {
+    fn from(value: Ranged<Error>) -> Self {
+        // #Insight a single error is (typically) an evaluation error.
+        PipelineError::Eval(vec![value])
+    }
+}
+
+/// A pipeline error with the diagnostics of the (first) error, see
+/// `api::eval_string_with_diagnostics`.
+#[derive(Debug)]
+pub struct DiagnosedError {
+    pub error: PipelineError,
+    pub diagnostics: Option<Diagnostics>,
+}
+
+impl std::error::Error for DiagnosedError {}
+
+impl fmt::Display for DiagnosedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.diagnostics {
+            Some(diagnostics) => write!(f, "{diagnostics}"),
+            None => write!(f, "{}", self.error),
+        }
+    }
+}
+
+// #TODO support multi-line ranges, currently only the first line is rendered.
+
+/// Formats the error for humans, renders the offending line of the `input`
+/// source with a caret underline below the range of the error, e.g.
+///
+/// ```text
+/// error: `foo` is undefined
+///  --> 2:7
+///   |
+/// 2 | (do a foo)
+///   |       ^^^
+/// ```
+pub fn format_error_pretty(input: &str, error: &Ranged<Error>) -> String {
+    format_error_styled(input, error, false)
+}
+
+/// Formats the error like `format_error_pretty`, with colors if `colors` is
+/// true, see `style::stderr_colors_enabled`.
+pub fn format_error_styled(input: &str, error: &Ranged<Error>, colors: bool) -> String {
+    let Position { line, col } = error.start_position(input);
+
+    let line_text = input.lines().nth(line).unwrap_or_default();
+    let line_len = line_text.chars().count();
+
+    // The underline is clamped to the line, at least one caret is rendered.
+    let underline_len = error
+        .1
+        .end
+        .saturating_sub(error.1.start)
+        .min(line_len.saturating_sub(col))
+        .max(1);
+
+    let line_number = (line + 1).to_string();
+    let gutter = " ".repeat(line_number.len());
+
+    let error_style = Style::fg(Color::Red).bold();
+    let gutter_style = Style::fg(Color::Blue).bold();
+    let bar = gutter_style.paint("|", colors);
+
+    format!(
+        "{} {}\n{gutter}{} {}\n{gutter} {bar}\n{} {bar} {line_text}\n{gutter} {bar} {}{}",
+        error_style.paint("error:", colors),
+        Style::default().bold().paint(&error.0.to_string(), colors),
+        gutter_style.paint("-->", colors),
+        error.start_position(input),
+        gutter_style.paint(&line_number, colors),
+        " ".repeat(col),
+        error_style.paint(&"^".repeat(underline_len), colors),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::eval_string,
+        error::{format_error_pretty, format_error_styled, Error},
+        eval::env::Env,
+        range::Ranged,
+    };
+
+    #[test]
+    fn format_error_pretty_renders_the_offending_line() {
+        let input = "(let a 1)\n(do a foo)";
+        let mut env = Env::prelude();
+        let errors = eval_string(input, &mut env).unwrap_err();
+
+        let expected = "error: `foo` is undefined\n --> 2:7\n  |\n2 | (do a foo)\n  |       ^^^";
+
+        assert_eq!(format_error_pretty(input, &errors[0]), expected);
+    }
+
+    #[test]
+    fn format_error_pretty_clamps_the_underline() {
+        let input = "(+ 1\n 2";
+        let error = Ranged(Error::UnterminatedList, 0..8);
+
+        assert!(format_error_pretty(input, &error).ends_with("1 | (+ 1\n  | ^^^^"));
+    }
+
+    #[test]
+    fn format_error_styled_colors_the_diagnostic() {
+        let input = "(+ 1\n 2";
+        let error = Ranged(Error::UnterminatedList, 0..8);
+
+        let text = format_error_styled(input, &error, true);
+        assert!(text.starts_with("\x1b[1;31merror:\x1b[0m \x1b[1m"));
+        assert!(text.ends_with("\x1b[1;31m^^^^\x1b[0m"));
+
+        assert_eq!(
+            format_error_styled(input, &error, false),
+            format_error_pretty(input, &error)
+        );
+    }
+}
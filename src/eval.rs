@@ -1,18 +1,49 @@
+pub mod audit;
+pub mod cancellation;
+pub mod capabilities;
+pub mod capture;
+pub mod context;
+pub mod diagnostics;
 pub mod env;
+pub mod features;
+pub mod flow;
+pub mod limits;
+pub mod module;
+pub mod module_cache;
+pub mod output;
+pub mod pattern;
 pub mod prelude;
-
-use std::{collections::HashMap, fs};
+pub mod replay;
+pub mod stats;
+pub mod telemetry;
+
+use std::{
+    collections::BTreeMap,
+    ops::ControlFlow,
+    rc::Rc,
+    time::{Instant, SystemTime},
+};
 
 use crate::{
     ann::Ann,
-    api::resolve_string,
     error::Error,
-    expr::{format_value, Expr},
+    expr::{
+        dict_key, format_value,
+        seq::{IterSeq, Seq},
+        Expr, ExprFn, FuncClause,
+    },
     range::Ranged,
     util::is_reserved_symbol,
 };
 
-use self::env::Env;
+use self::{
+    context::HostForm,
+    env::Env,
+    flow::Flow,
+    module::use_module,
+    pattern::{is_literal_pattern, is_pattern, match_pattern, match_patterns, Bindings},
+    telemetry::OpEvent,
+};
 
 // #Insight
 // _Not_ a pure evaluator, performs side-effects.
@@ -34,9 +65,1336 @@ fn eval_args(args: &[Ann<Expr>], env: &mut Env) -> Result<Vec<Ann<Expr>>, Ranged
         .collect::<Result<Vec<_>, _>>()
 }
 
+fn check_binding_symbol(sym: &Ann<Expr>) -> Result<&str, Ranged<Error>> {
+    let Ann(Expr::Symbol(s), ..) = sym else {
+        return Err(Ranged(
+            Error::invalid_arguments(format!("`{sym}` is not a Symbol")),
+            sym.get_range(),
+        ));
+    };
+
+    if is_reserved_symbol(s) {
+        return Err(Ranged(
+            Error::invalid_arguments(format!("let cannot shadow the reserved symbol `{s}`")),
+            sym.get_range(),
+        ));
+    }
+
+    Ok(s)
+}
+
+// #Insight
+// There is no dedicated tuple type, a function returns multiple values as an
+// Array (or List), e.g. `(Func (x) (List x (* x x)))`.
+
+// #Insight
+// The patterns are checked before the optimize pass (in macro_expand) too, so
+// both the `(Array ...)`/`(Dict ...)` form and the optimized form are supported.
+
+// #TODO support nested destructuring patterns.
+
+/// A `let` binding pattern.
+enum LetPattern<'a> {
+    /// Binds the whole value.
+    Symbol(&'a Ann<Expr>),
+    /// Destructures an Array (or List) value, element-wise.
+    Seq(Vec<&'a Ann<Expr>>),
+    /// Destructures a Dict value, by key.
+    Dict(Vec<(String, &'a Ann<Expr>)>),
+}
+
+fn parse_dict_pattern<'a>(
+    terms: &'a [Ann<Expr>],
+    pattern: &Ann<Expr>,
+) -> Result<LetPattern<'a>, Ranged<Error>> {
+    if !terms.len().is_multiple_of(2) {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed Dict pattern, missing binding symbol"),
+            pattern.get_range(),
+        ));
+    }
+
+    let bindings = terms
+        .chunks(2)
+        .map(|pair| (format_value(&pair[0]), &pair[1]))
+        .collect();
+
+    Ok(LetPattern::Dict(bindings))
+}
+
+fn parse_let_pattern(pattern: &Ann<Expr>) -> Result<LetPattern<'_>, Ranged<Error>> {
+    match pattern.as_ref() {
+        Expr::List(terms) => match terms.split_first() {
+            Some((Ann(Expr::Symbol(s), ..), tail)) if s == "Array" => {
+                Ok(LetPattern::Seq(tail.iter().collect()))
+            }
+            Some((Ann(Expr::Symbol(s), ..), tail)) if s == "Dict" => {
+                parse_dict_pattern(tail, pattern)
+            }
+            _ => Ok(LetPattern::Seq(terms.iter().collect())),
+        },
+        Expr::Array(terms) => Ok(LetPattern::Seq(terms.iter().collect())),
+        Expr::Dict(dict) => Ok(LetPattern::Dict(
+            dict.iter().map(|(k, v)| (k.clone(), v)).collect(),
+        )),
+        _ => Ok(LetPattern::Symbol(pattern)),
+    }
+}
+
+/// Validates a `let` binding pattern. A pattern is either a Symbol, a List
+/// (or Array) of Symbols that destructures a multiple-value result, e.g.
+/// `(let [a b] (two_values))`, or a Dict of Symbols that destructures a Dict
+/// value, e.g. `(let {:name n} user)`. Returns the symbols bound by the pattern.
+pub fn check_pattern(pattern: &Ann<Expr>) -> Result<Vec<&str>, Ranged<Error>> {
+    match parse_let_pattern(pattern)? {
+        LetPattern::Symbol(sym) => Ok(vec![check_binding_symbol(sym)?]),
+        LetPattern::Seq(syms) => syms.into_iter().map(check_binding_symbol).collect(),
+        LetPattern::Dict(bindings) => bindings
+            .into_iter()
+            .map(|(_, sym)| check_binding_symbol(sym))
+            .collect(),
+    }
+}
+
+// #Insight
+// The mutability of a binding is encoded as a `mut` annotation on the bound
+// value, e.g. `(let #mut arr [1 2 3])`.
+
+/// Binds `value` to the binding symbol `sym`.
+fn bind(sym: &Ann<Expr>, name: &str, mut value: Ann<Expr>, env: &mut Env) {
+    if sym.contains_annotation("mut") {
+        value.set_annotation("mut", Expr::Bool(true));
+    } else {
+        value.remove_annotation("mut");
+    }
+
+    env.insert(name, value);
+}
+
+/// Binds `value` to the symbols of the `let` binding `pattern`, destructuring
+/// multiple values if needed.
+pub fn bind_pattern(
+    pattern: &Ann<Expr>,
+    value: Ann<Expr>,
+    env: &mut Env,
+) -> Result<(), Ranged<Error>> {
+    let names = check_pattern(pattern)?;
+
+    match parse_let_pattern(pattern)? {
+        LetPattern::Symbol(sym) => {
+            bind(sym, names[0], value, env);
+        }
+        LetPattern::Seq(syms) => {
+            let values: Vec<Ann<Expr>> = match value {
+                Ann(Expr::Array(values), ..) => values,
+                Ann(Expr::List(values), ..) => values,
+                _ => {
+                    return Err(Ranged(
+                        Error::invalid_arguments(format!("`{value}` is not a multiple-value")),
+                        pattern.get_range(),
+                    ));
+                }
+            };
+
+            if values.len() != names.len() {
+                return Err(Ranged(
+                    Error::invalid_arguments(format!(
+                        "expected {} values, found {}",
+                        names.len(),
+                        values.len()
+                    )),
+                    pattern.get_range(),
+                ));
+            }
+
+            for ((sym, name), value) in syms.into_iter().zip(names).zip(values) {
+                bind(sym, name, value, env);
+            }
+        }
+        LetPattern::Dict(bindings) => {
+            let Ann(Expr::Dict(mut dict), ..) = value else {
+                return Err(Ranged(
+                    Error::invalid_arguments(format!("`{value}` is not a Dict")),
+                    pattern.get_range(),
+                ));
+            };
+
+            for ((key, sym), name) in bindings.into_iter().zip(names) {
+                let Some(value) = dict.remove(&key) else {
+                    return Err(Ranged(
+                        Error::invalid_arguments(format!("missing Dict key `{key}`")),
+                        sym.get_range(),
+                    ));
+                };
+
+                bind(sym, name, value, env);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mutates, in-place, the collection bound to the `target` symbol. The binding
+/// should be mutable.
+fn mutate_binding(
+    target: &Ann<Expr>,
+    env: &mut Env,
+    f: impl FnOnce(&mut Expr) -> Result<(), Error>,
+) -> Result<(), Ranged<Error>> {
+    let Ann(Expr::Symbol(name), ..) = target else {
+        return Err(Ranged(
+            Error::invalid_arguments(format!("`{target}` is not a Symbol")),
+            target.get_range(),
+        ));
+    };
+
+    let result = env.update_with(name, |binding| {
+        if !binding.contains_annotation("mut") {
+            return Err(Error::invalid_arguments(format!(
+                "`{name}` is not mutable, bind with `(let #mut {name} ...)`"
+            )));
+        }
+
+        f(&mut binding.0)
+    });
+
+    let Some(result) = result else {
+        return Err(Ranged(
+            Error::UndefinedSymbol(name.clone()),
+            target.get_range(),
+        ));
+    };
+
+    result.map_err(|error| Ranged(error, target.get_range()))
+}
+
+/// Returns the parameter patterns of a function clause, `()` is an empty list
+/// of parameters.
+fn clause_params(params: &Ann<Expr>) -> Option<&[Ann<Expr>]> {
+    match params.as_ref() {
+        Expr::One => Some(&[]),
+        Expr::List(params) if params.iter().all(is_pattern) => Some(params),
+        _ => None,
+    }
+}
+
+/// Parses the clauses of a function definition. A function is either defined
+/// with parameters and a body, e.g. `(Func (n) (* n 2))`, or with multiple
+/// pattern clauses, e.g. `(Func ((0) 1) ((n) (* n (fact (- n 1)))))`.
+fn func_clauses(args: &[Ann<Expr>]) -> Result<Vec<FuncClause>, Error> {
+    let clauses: Option<Vec<FuncClause>> = args
+        .iter()
+        .map(|clause| {
+            let Ann(Expr::List(clause), ..) = clause else {
+                return None;
+            };
+
+            let [params, body] = &clause[..] else {
+                return None;
+            };
+
+            Some(FuncClause {
+                params: clause_params(params)?.to_vec(),
+                body: body.clone(),
+            })
+        })
+        .collect();
+
+    if let Some(clauses) = clauses {
+        if !clauses.is_empty() {
+            return Ok(clauses);
+        }
+    }
+
+    let [params, body] = args else {
+        return Err(Error::invalid_arguments("malformed func definition"));
+    };
+
+    let Some(params) = clause_params(params) else {
+        return Err(Error::invalid_arguments(
+            "malformed func parameters definition",
+        ));
+    };
+
+    Ok(vec![FuncClause {
+        params: params.to_vec(),
+        body: body.clone(),
+    }])
+}
+
+/// A clause of a `match` expression, e.g. `([a b] :when (> a b) a)`.
+struct MatchClause<'a> {
+    pattern: &'a Ann<Expr>,
+    guard: Option<&'a Ann<Expr>>,
+    body: &'a Ann<Expr>,
+}
+
+fn parse_match_clause(clause: &Ann<Expr>) -> Result<MatchClause<'_>, Ranged<Error>> {
+    let clause = match clause.as_ref() {
+        Expr::List(terms) => match &terms[..] {
+            [pattern, body] => MatchClause {
+                pattern,
+                guard: None,
+                body,
+            },
+            [pattern, Ann(Expr::KeySymbol(s), ..), guard, body] if s == "when" => MatchClause {
+                pattern,
+                guard: Some(guard),
+                body,
+            },
+            _ => {
+                return Err(Ranged(
+                    Error::invalid_arguments("malformed match clause"),
+                    clause.get_range(),
+                ));
+            }
+        },
+        _ => {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed match clause"),
+                clause.get_range(),
+            ));
+        }
+    };
+
+    if !is_pattern(clause.pattern) {
+        return Err(Ranged(
+            Error::invalid_arguments(format!("invalid match pattern `{}`", clause.pattern)),
+            clause.pattern.get_range(),
+        ));
+    }
+
+    Ok(clause)
+}
+
+/// Evaluates the body of a matched clause, if the guard accepts the match.
+fn eval_guarded(clause: &MatchClause, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+    if let Some(guard) = clause.guard {
+        let Ann(Expr::Bool(accepted), ..) = eval(guard, env)? else {
+            return Err(Ranged(
+                Error::invalid_arguments("the match guard is not a boolean value"),
+                guard.get_range(),
+            ));
+        };
+
+        if !accepted {
+            return Ok(None);
+        }
+    }
+
+    eval(clause.body, env).map(Some)
+}
+
+/// Evaluates the body of a matched clause, in a new scope with the pattern
+/// bindings. Returns None if the guard rejects the match.
+fn eval_match_clause(
+    clause: &MatchClause,
+    bindings: Bindings,
+    env: &mut Env,
+) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+    env.push_new_scope();
+
+    for (name, value) in bindings {
+        env.insert(name, value);
+    }
+
+    let result = eval_guarded(clause, env);
+
+    env.pop();
+
+    result
+}
+
+/// Evaluates the body of a loop, intercepts the `break` and `continue` signals.
+/// Returns `Break` with the break value if the loop should be exited.
+fn eval_loop_body(
+    body: &Ann<Expr>,
+    env: &mut Env,
+) -> Result<ControlFlow<Ann<Expr>, Ann<Expr>>, Ranged<Error>> {
+    // Every iteration is an evaluation step, even if the body is an atom,
+    // e.g. `(while true 1)`.
+    env.context
+        .check_step()
+        .map_err(|error| Ranged(error, body.get_range()))?;
+
+    // The signals exit the nested forms through `?`, e.g. a `do` before it
+    // pops its scope, the scopes of the loop are restored.
+    let scopes = env.capture();
+
+    match eval(body, env) {
+        Ok(value) => Ok(ControlFlow::Continue(value)),
+        Err(Ranged(Error::Flow(flow), ..)) => {
+            env.replace(scopes);
+
+            match flow {
+                Flow::Continue => Ok(ControlFlow::Continue(Expr::One.into())),
+                Flow::Break(value) => Ok(ControlFlow::Break(value)),
+            }
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Returns a lazy sequence over the items of a value, or None if the value is
+/// not a sequence. The entries of a Dict are iterated in key order, as
+/// `[key value]` pairs, the items of a String are Chars, the items of a Buffer
+/// are byte Ints.
+pub fn seq_iter(seq: Ann<Expr>) -> Option<Box<dyn Seq>> {
+    match seq {
+        Ann(Expr::Array(arr), ..) => Some(Box::new(IterSeq(arr.into_iter()))),
+        Ann(Expr::List(list), ..) => Some(Box::new(IterSeq(list.into_iter()))),
+        Ann(Expr::Dict(dict), ..) => {
+            Some(Box::new(IterSeq(dict.into_iter().map(|(key, value)| {
+                Expr::Array(vec![Expr::String(key).into(), value]).into()
+            }))))
+        }
+        Ann(Expr::String(s), ..) => {
+            let chars: Vec<char> = s.chars().collect();
+            Some(Box::new(IterSeq(
+                chars.into_iter().map(|c| Expr::Char(c).into()),
+            )))
+        }
+        Ann(Expr::Range(start, end, step), ..) => {
+            let mut i = start;
+            Some(Box::new(IterSeq(std::iter::from_fn(move || {
+                if (step > 0 && i < end) || (step < 0 && i > end) {
+                    let value = i;
+                    i = i.checked_add(step).unwrap_or(end);
+                    Some(Expr::Int(value).into())
+                } else {
+                    None
+                }
+            }))))
+        }
+        Ann(Expr::Buffer(bytes), ..) => {
+            let bytes = bytes.to_vec();
+            Some(Box::new(IterSeq(
+                bytes.into_iter().map(|byte| Expr::Int(byte.into()).into()),
+            )))
+        }
+        // The iterator is only borrowed while pulling the next item, the
+        // consumer may access the iterator.
+        Ann(Expr::Iterator(iter), ..) => Some(Box::new(iter)),
+        _ => None,
+    }
+}
+
+fn eval_items(
+    items: &mut dyn Seq,
+    var: &Ann<Expr>,
+    body: &Ann<Expr>,
+    env: &mut Env,
+) -> Result<(), Ranged<Error>> {
+    while let Some(x) = items.next(env)? {
+        bind_pattern(var, x, env)?;
+
+        if let ControlFlow::Break(_) = eval_loop_body(body, env)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Evaluates the `body` for each item of the sequence, the item is bound to
+/// the `var` pattern, e.g. `x` or `[key value]`.
+fn eval_for_each(
+    seq: &Ann<Expr>,
+    var: &Ann<Expr>,
+    body: &Ann<Expr>,
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let seq = eval(seq, env)?;
+    let range = seq.get_range();
+
+    let Some(mut items) = seq_iter(seq) else {
+        return Err(Ranged(
+            Error::invalid_arguments("`for_each` requires a `Seq` as the first argument"),
+            range,
+        ));
+    };
+
+    check_pattern(var)?;
+
+    env.push_new_scope();
+
+    let result = eval_items(items.as_mut(), var, body, env);
+
+    env.pop();
+
+    result?;
+
+    // #TODO intentionally don't return a value, reconsider this?
+    Ok(Expr::One.into())
+}
+
+/// Quasi-quotes the `template`, the unquoted `(unquot x)` (or `$x`) terms are
+/// replaced by their values, e.g. `(if $predicate () $body)`. Typically used
+/// to build the expansion of macros.
+fn quasi_quote(template: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Ann(Expr::List(terms), ann) = template else {
+        return Ok(template.clone());
+    };
+
+    if let Some(Ann(Expr::Symbol(sym), ..)) = terms.first() {
+        if sym == "unquot" {
+            let [_, value] = &terms[..] else {
+                return Err(Ranged(
+                    Error::invalid_arguments("missing unquote target"),
+                    template.get_range(),
+                ));
+            };
+
+            return eval(value, env);
+        }
+    }
+
+    let terms = terms
+        .iter()
+        .map(|term| quasi_quote(term, env))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Ann(Expr::List(terms), ann.clone()))
+}
+
+/// Invokes the function `func` with the given, already evaluated, arguments.
+/// Useful for applying function values passed to foreign functions.
+pub fn invoke(
+    func: &Ann<Expr>,
+    args: Vec<Ann<Expr>>,
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    match func.as_ref() {
+        Expr::Func(clauses, scopes) => {
+            if func.contains_annotation("curry") {
+                // #Insight
+                // The arity of a curried function is the arity of the first clause.
+                let arity = clauses.first().map_or(0, |clause| clause.params.len());
+
+                if args.len() < arity {
+                    // Partial application, returns a closure over the given arguments.
+                    let func = func.clone();
+                    let partial = move |rest: &[Ann<Expr>], env: &mut Env| {
+                        let mut all_args = args.clone();
+                        all_args.extend_from_slice(rest);
+                        invoke(&func, all_args, env)
+                    };
+                    return Ok(Expr::ForeignFunc(Rc::new(partial)).into());
+                }
+            }
+
+            // First-match dispatch, the first clause with matching parameter
+            // patterns is evaluated.
+            let Some((clause, bindings)) = clauses.iter().find_map(|clause| {
+                match_patterns(&clause.params, &args).map(|bindings| (clause, bindings))
+            }) else {
+                let error = if let [clause] = &clauses[..] {
+                    format!(
+                        "expected {} arguments, found {}",
+                        clause.params.len(),
+                        args.len()
+                    )
+                } else {
+                    "no function clause matches the arguments".to_owned()
+                };
+                return Err(Ranged(Error::invalid_arguments(error), func.get_range()));
+            };
+
+            // A call is an evaluation step, even if the body is an atom. The
+            // errors are attributed to the call-site, see `at_call_site`.
+            env.context.check_step()?;
+            env.context.enter_call()?;
+
+            // Lexical scoping, evaluate the body in the scopes captured at
+            // the definition site.
+
+            let caller_scopes = env.replace(scopes.to_vec());
+
+            env.push_new_scope();
+
+            // A function can always refer to itself as `self`, even when
+            // anonymous.
+            env.insert("self", func.clone());
+
+            for (name, value) in bindings {
+                env.insert(name, value);
+            }
+
+            let result = eval(&clause.body, env).map_err(|Ranged(error, range)| match error {
+                Error::Flow(flow) => Ranged(Error::EscapedFlow(flow), range),
+                error => Ranged(error, range),
+            });
+
+            if let Err(error) = &result {
+                env.capture_failure(error);
+            }
+
+            env.context.exit_call();
+            env.replace(caller_scopes);
+
+            result
+        }
+        Expr::ForeignFunc(foreign_function) => {
+            // #TODO use RefCell / interior mutability instead, to allow for changing the environment (with Mutation Effect)
+            if env.context.telemetry_handler.is_some() {
+                invoke_traced(func, foreign_function.as_ref(), args, env)
+            } else {
+                foreign_function(&args, env)
+            }
+        }
+        _ => Err(Ranged(
+            Error::NotInvocable(format!("expression `{func}`")),
+            func.get_range(),
+        )),
+    }
+}
+
+/// Evaluates an invocation of a host form, the arguments are passed
+/// unevaluated.
+fn eval_host_form(
+    form: &HostForm,
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Ann(Expr::List(list), ..) = expr else {
+        unreachable!("a host form is invoked by a List");
+    };
+
+    env.context.push_frame(&list[0], expr.get_range());
+
+    let result = (form.0)(tail, env).map_err(|error| at_call_site(error, expr));
+
+    if let Err(error) = &result {
+        env.capture_failure(error);
+    }
+
+    env.context.pop_frame();
+
+    result
+}
+
+/// Attributes an error to the call-site `expr`, if the error is missing the
+/// range, e.g. an error of a foreign function.
+fn at_call_site(error: Ranged<Error>, expr: &Ann<Expr>) -> Ranged<Error> {
+    let Ranged(error, range) = error;
+
+    if range.is_empty() {
+        Ranged(error, expr.get_range())
+    } else {
+        Ranged(error, range)
+    }
+}
+
+/// Invokes a foreign function, the invocation is reported to the telemetry
+/// handler. The function is named by the `name` annotation, see `eval_expr`.
+fn invoke_traced(
+    func: &Ann<Expr>,
+    foreign_function: &ExprFn,
+    args: Vec<Ann<Expr>>,
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let summaries = OpEvent::summarize_args(&args);
+    let start = SystemTime::now();
+    let timer = Instant::now();
+
+    let result = foreign_function(&args, env);
+
+    let head = match func.get_annotation("name") {
+        Some(name) => Ann::new(name.clone()),
+        None => func.clone(),
+    };
+    let range = func.get_range();
+    let event = OpEvent::new(&head, summaries, range, start, timer.elapsed(), &result);
+    env.context.report_op(&event);
+
+    result
+}
+
+// #Insight
+// The special forms are evaluated in separate functions, to keep the stack
+// frame of the (recursive) `eval` function small.
+
+/// Evaluates a `for` loop.
+fn eval_for(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (for x in seq body) iterates a sequence, like `for_each`.
+    if let [var, Ann(Expr::Symbol(keyword), ..), seq, body] = tail {
+        if keyword == "in" {
+            return eval_for_each(seq, var, body, env);
+        }
+    }
+
+    // #Insight
+    // `for` is a generalization of `if`.
+    // `for` is also related with `do`.
+    let [predicate, body] = tail else {
+        // #TODO proper error!
+        return Err(Ranged(
+            Error::invalid_arguments("missing for arguments"),
+            expr.get_range(),
+        ));
+    };
+
+    let mut value = Expr::One.into();
+
+    loop {
+        let predicate = eval(predicate, env)?;
+
+        let Ann(Expr::Bool(predicate), ..) = predicate else {
+            return Err(Ranged(
+                Error::invalid_arguments("the for predicate is not a boolean value"),
+                predicate.get_range(),
+            ));
+        };
+
+        if !predicate {
+            break;
+        }
+
+        match eval_loop_body(body, env)? {
+            ControlFlow::Continue(body_value) => value = body_value,
+            ControlFlow::Break(break_value) => return Ok(break_value),
+        }
+    }
+
+    Ok(value)
+}
+
+/// Evaluates a `while` loop.
+fn eval_while(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [predicate, body] = tail else {
+        return Err(Ranged(
+            Error::invalid_arguments("`while` requires `predicate`, `body` arguments"),
+            expr.get_range(),
+        ));
+    };
+
+    loop {
+        let predicate = eval(predicate, env)?;
+
+        let Ann(Expr::Bool(predicate), ..) = predicate else {
+            return Err(Ranged(
+                Error::invalid_arguments("the while predicate is not a boolean value"),
+                predicate.get_range(),
+            ));
+        };
+
+        if !predicate {
+            break;
+        }
+
+        if let ControlFlow::Break(value) = eval_loop_body(body, env)? {
+            return Ok(value);
+        }
+    }
+
+    // A while loop that is not exited with `break` evaluates to Unit.
+    Ok(Ann::with_type(Expr::One, Expr::symbol("Unit")))
+}
+
+/// Evaluates an `if` expression.
+fn eval_if(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // #TODO this is a temp hack!
+    let Some(predicate) = tail.first() else {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed if predicate"),
+            expr.get_range(),
+        ));
+    };
+
+    let Some(true_clause) = tail.get(1) else {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed if true clause"),
+            expr.get_range(),
+        ));
+    };
+
+    let false_clause = tail.get(2);
+
+    let predicate = eval(predicate, env)?;
+
+    let Ann(Expr::Bool(predicate), ..) = predicate else {
+        return Err(Ranged(
+            Error::InvalidArguments("the if predicate is not a boolean value".to_owned()),
+            predicate.get_range(),
+        ));
+    };
+
+    if predicate {
+        eval(true_clause, env)
+    } else if let Some(false_clause) = false_clause {
+        eval(false_clause, env)
+    } else {
+        // An `if` without a false-clause evaluates to Unit.
+        Ok(Ann::with_type(Expr::One, Expr::symbol("Unit")))
+    }
+}
+
+/// Evaluates a `cond` expression.
+fn eval_cond(tail: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (cond predicate clause ... else clause)
+    let mut args = tail.iter();
+
+    while let Some(predicate) = args.next() {
+        let Some(clause) = args.next() else {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed cond, missing clause"),
+                predicate.get_range(),
+            ));
+        };
+
+        if let Ann(Expr::Symbol(s), ..) = predicate {
+            if s == "else" {
+                return eval(clause, env);
+            }
+        }
+
+        let value = eval(predicate, env)?;
+
+        let Ann(Expr::Bool(value), ..) = value else {
+            return Err(Ranged(
+                Error::invalid_arguments("the cond predicate is not a boolean value"),
+                predicate.get_range(),
+            ));
+        };
+
+        if value {
+            return eval(clause, env);
+        }
+    }
+
+    // A cond without a matching clause evaluates to Unit, like `if`.
+    Ok(Ann::with_type(Expr::One, Expr::symbol("Unit")))
+}
+
+/// Wraps the result of a lookup in a Maybe.
+fn maybe(value: Option<&Ann<Expr>>) -> Ann<Expr> {
+    let value = match value {
+        Some(value) => Expr::some(value.clone()),
+        None => Expr::none(),
+    };
+
+    Ann::with_type(value, Expr::symbol("Maybe"))
+}
+
+// #Insight
+// `?` is a special form, the default is only evaluated if the value is `None`,
+// e.g. `(? (user :name) else "anonymous")`.
+
+/// Evaluates a `?` expression, unwraps a Maybe or evaluates the default.
+fn eval_unwrap_or_else(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (? value else default)
+    let [value, Ann(Expr::Symbol(keyword), ..), default] = tail else {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed `?`, expected `(? value else default)`"),
+            expr.get_range(),
+        ));
+    };
+
+    if keyword != "else" {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed `?`, expected `(? value else default)`"),
+            expr.get_range(),
+        ));
+    }
+
+    match eval(value, env)? {
+        Ann(Expr::Maybe(Some(value)), ..) => Ok(*value),
+        Ann(Expr::Maybe(None), ..) => eval(default, env),
+        other => Err(Ranged(
+            Error::invalid_arguments(format!("`{other}` is not a Maybe")),
+            value.get_range(),
+        )),
+    }
+}
+
+// #Insight
+// A thrown value is caught as-is, any other (catchable) error is caught as its
+// message, e.g. `(Error "division by zero")`.
+
+/// Converts a caught error into an Error value.
+fn error_value(error: Error) -> Ann<Expr> {
+    let value = match error {
+        Error::Thrown(value) => value,
+        error => Expr::String(error.to_string()).into(),
+    };
+
+    Ann::with_type(Expr::error(value), Expr::symbol("Error"))
+}
+
+/// Evaluates a `try` expression. If the body fails, the `catch` handler is
+/// evaluated with the error bound to the given name. The `finally` cleanup is
+/// evaluated, even on error, its value is ignored. The cleanup is skipped if
+/// the evaluation is aborted, see `Error::is_abort`.
+fn eval_try(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (try body (catch err handler) (finally cleanup)), at least one clause.
+    let malformed = || {
+        Ranged(
+            Error::invalid_arguments(
+                "malformed `try`, expected `(try body (catch err handler) (finally cleanup))`",
+            ),
+            expr.get_range(),
+        )
+    };
+
+    let Some((body, clauses)) = tail.split_first() else {
+        return Err(malformed());
+    };
+
+    let mut catch = None;
+    let mut finally = None;
+
+    for clause in clauses {
+        let Ann(Expr::List(clause), ..) = clause else {
+            return Err(malformed());
+        };
+
+        match &clause[..] {
+            [Ann(Expr::Symbol(keyword), ..), Ann(Expr::Symbol(name), ..), handler]
+                if keyword == "catch" && catch.is_none() && finally.is_none() =>
+            {
+                catch = Some((name, handler));
+            }
+            [Ann(Expr::Symbol(keyword), ..), cleanup]
+                if keyword == "finally" && finally.is_none() =>
+            {
+                finally = Some(cleanup);
+            }
+            _ => return Err(malformed()),
+        }
+    }
+
+    if catch.is_none() && finally.is_none() {
+        return Err(malformed());
+    }
+
+    // #Insight
+    // The failed evaluation may leave pushed scopes and calls behind, they are
+    // restored before the handler and the cleanup are evaluated.
+    let scopes = env.capture();
+    let call_depth = env.context.call_depth;
+
+    let result = match (eval(body, env), catch) {
+        (Err(Ranged(error, ..)), Some((name, handler))) if error.is_catchable() => {
+            env.replace(scopes.clone());
+            env.context.call_depth = call_depth;
+
+            if let Some(diagnostics) = &mut env.context.diagnostics {
+                diagnostics.discard();
+            }
+
+            env.push_new_scope();
+            env.insert(name, error_value(error));
+            let result = eval(handler, env);
+            env.pop();
+
+            result
+        }
+        (result, _) => result,
+    };
+
+    // #Insight
+    // An aborted evaluation (timed out, interrupted, out of fuel) would abort
+    // the cleanup at its first step, the cleanup is not evaluated and the
+    // abort is reported with the range of the aborted expression.
+    if let Err(Ranged(error, ..)) = &result {
+        if error.is_abort() {
+            return result;
+        }
+    }
+
+    if let Some(cleanup) = finally {
+        env.replace(scopes);
+        env.context.call_depth = call_depth;
+
+        // An error of the cleanup takes precedence.
+        eval(cleanup, env)?;
+    }
+
+    result
+}
+
+// #Insight
+// `and` and `or` are special forms, the operands are evaluated from left to
+// right, and the evaluation stops at the first operand that determines the
+// result, e.g. `(and false (exit 1))` does not exit.
+
+/// Evaluates an `and` or an `or` expression, the evaluation stops when an
+/// operand evaluates to the `short_circuit` value.
+fn eval_and_or(
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+    short_circuit: bool,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let name = if short_circuit { "or" } else { "and" };
+
+    for operand in tail {
+        let value = eval(operand, env)?;
+
+        let Ann(Expr::Bool(value), ..) = value else {
+            return Err(Ranged(
+                Error::invalid_arguments(format!("the `{name}` operand is not a boolean value")),
+                operand.get_range(),
+            ));
+        };
+
+        if value == short_circuit {
+            return Ok(Ann::with_type(Expr::Bool(value), Expr::symbol("Bool")));
+        }
+    }
+
+    // `(and)` is true, `(or)` is false.
+    Ok(Ann::with_type(
+        Expr::Bool(!short_circuit),
+        Expr::symbol("Bool"),
+    ))
+}
+
+/// Evaluates a `case` expression.
+fn eval_case(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (case value pattern clause ...)
+    let Some((value, clauses)) = tail.split_first() else {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed case, missing value"),
+            expr.get_range(),
+        ));
+    };
+
+    let value = eval(value, env)?;
+
+    let mut args = clauses.iter();
+
+    while let Some(pattern) = args.next() {
+        let Some(clause) = args.next() else {
+            return Err(Ranged(
+                Error::invalid_arguments("malformed case, missing clause"),
+                pattern.get_range(),
+            ));
+        };
+
+        // #TODO support destructuring patterns, see `match`.
+        if !is_literal_pattern(pattern) {
+            return Err(Ranged(
+                Error::invalid_arguments(format!(
+                    "invalid case pattern `{pattern}`, expecting a literal or `_`"
+                )),
+                pattern.get_range(),
+            ));
+        }
+
+        if match_pattern(pattern, &value, &mut Vec::new()) {
+            return eval(clause, env);
+        }
+    }
+
+    Ok(Ann::with_type(Expr::One, Expr::symbol("Unit")))
+}
+
+/// Evaluates a `match` expression.
+fn eval_match(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (match value (pattern body) (pattern :when guard body) ...)
+    let Some((value, clauses)) = tail.split_first() else {
+        return Err(Ranged(
+            Error::invalid_arguments("malformed match, missing value"),
+            expr.get_range(),
+        ));
+    };
+
+    let value = eval(value, env)?;
+
+    for clause in clauses {
+        let clause = parse_match_clause(clause)?;
+
+        let mut bindings = Bindings::new();
+
+        if !match_pattern(clause.pattern, &value, &mut bindings) {
+            continue;
+        }
+
+        if let Some(result) = eval_match_clause(&clause, bindings, env)? {
+            return Ok(result);
+        }
+    }
+
+    // #TODO consider reporting non-exhaustive matches.
+    Ok(Ann::with_type(Expr::One, Expr::symbol("Unit")))
+}
+
+/// Evaluates a `use` expression, imports a module.
+fn eval_use(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // (use math), binds the module, the exports are accessed as `math/sin`.
+    // (use math (sin cos)), binds the selected exports.
+    // (use math :as m), binds the module with an alias.
+
+    let malformed = || {
+        Ranged(
+            Error::invalid_arguments("malformed `use`, expected `(use module (names) :as alias)`"),
+            expr.get_range(),
+        )
+    };
+
+    let Some((module_path, options)) = tail.split_first() else {
+        return Err(malformed());
+    };
+
+    let (Ann(Expr::Symbol(module_name), ..) | Ann(Expr::String(module_name), ..)) = module_path
+    else {
+        return Err(malformed());
+    };
+
+    let mut names = None;
+    let mut alias = None;
+
+    let mut options = options.iter();
+
+    while let Some(option) = options.next() {
+        match option {
+            Ann(Expr::List(terms), ..) if names.is_none() => {
+                let mut selected = Vec::new();
+                for term in terms {
+                    let Ann(Expr::Symbol(name), ..) = term else {
+                        return Err(malformed());
+                    };
+                    selected.push(name);
+                }
+                names = Some(selected);
+            }
+            Ann(Expr::KeySymbol(key), ..) if key == "as" && alias.is_none() => {
+                let Some(Ann(Expr::Symbol(name), ..)) = options.next() else {
+                    return Err(malformed());
+                };
+                alias = Some(name);
+            }
+            _ => return Err(malformed()),
+        }
+    }
+
+    let module = use_module(module_name, env).map_err(|error| Ranged(error, expr.get_range()))?;
+
+    if let Some(names) = &names {
+        for name in names {
+            let Some(value) = module.exports.get(*name) else {
+                return Err(Ranged(
+                    Error::invalid_arguments(format!(
+                        "`{name}` is not exported by module `{}`",
+                        module.name
+                    )),
+                    expr.get_range(),
+                ));
+            };
+            env.insert(*name, value.clone());
+        }
+    }
+
+    let name = match alias {
+        Some(alias) => Some(alias.clone()),
+        None if names.is_none() => Some(module.name.clone()),
+        None => None,
+    };
+
+    let module = Ann::with_type(Expr::Module(module), Expr::symbol("Module"));
+
+    if let Some(name) = name {
+        env.insert(name, module.clone());
+    }
+
+    Ok(module)
+}
+
+/// Evaluates a `let` expression.
+fn eval_let(tail: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    // #TODO this is already parsed statically by resolver, no need to duplicate the tests here?
+    // #TODO also report some of these errors statically, maybe in a sema phase?
+    let mut args = tail.iter();
+
+    // The value of the let expression is the last bound value.
+    let mut result = Expr::One.into();
+
+    while let Some(pattern) = args.next() {
+        let Some(value) = args.next() else {
+            // #TODO error?
+            break;
+        };
+
+        let value = eval(value, env)?;
+
+        // #TODO notify about overrides? use `set`?
+        bind_pattern(pattern, value.clone(), env)?;
+
+        result = value;
+    }
+
+    Ok(result)
+}
+
+/// Evaluates a `push!` expression.
+fn eval_push(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [target, value] = tail else {
+        return Err(Ranged(
+            Error::invalid_arguments("`push!` requires `array`, `value` arguments"),
+            expr.get_range(),
+        ));
+    };
+
+    let value = eval(value, env)?;
+
+    mutate_binding(target, env, |collection| {
+        let Expr::Array(array) = collection else {
+            return Err(Error::invalid_arguments(format!(
+                "`{target}` is not an Array"
+            )));
+        };
+
+        array.push(value);
+
+        Ok(())
+    })?;
+
+    Ok(Expr::One.into())
+}
+
+/// Evaluates a `set!` expression.
+fn eval_set(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    // Rebinds an existing (mutable) variable, in the enclosing scope
+    // where it is defined.
+    if let [target, value] = tail {
+        let value = eval(value, env)?;
+
+        mutate_binding(target, env, |binding| {
+            *binding = value.0;
+            Ok(())
+        })?;
+
+        return Ok(Expr::One.into());
+    }
+
+    let [target, key, value] = tail else {
+        return Err(Ranged(
+            Error::invalid_arguments(
+                "`set!` requires `symbol`, `value` or `collection`, `key`, `value` arguments",
+            ),
+            expr.get_range(),
+        ));
+    };
+
+    let key = eval(key, env)?;
+    let value = eval(value, env)?;
+
+    mutate_binding(target, env, |collection| match collection {
+        Expr::Array(array) => {
+            let Ann(Expr::Int(index), ..) = key else {
+                return Err(Error::invalid_arguments(
+                    "invalid array index, expecting Int",
+                ));
+            };
+
+            let Some(elem) = usize::try_from(index).ok().and_then(|i| array.get_mut(i)) else {
+                return Err(Error::invalid_arguments(format!(
+                    "index `{index}` is out of bounds"
+                )));
+            };
+
+            *elem = value;
+
+            Ok(())
+        }
+        Expr::Dict(dict) => {
+            dict.insert(dict_key(&key)?, value);
+
+            Ok(())
+        }
+        _ => Err(Error::invalid_arguments(format!(
+            "`{target}` is not an Array or Dict"
+        ))),
+    })?;
+
+    Ok(Expr::One.into())
+}
+
+/// Evaluates a `Func` definition.
+fn eval_func(
+    expr: &Ann<Expr>,
+    tail: &[Ann<Expr>],
+    env: &mut Env,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let clauses = func_clauses(tail).map_err(|error| Ranged(error, expr.get_range()))?;
+
+    // The function captures the current scopes (closure).
+    let mut func = Ann::new(Expr::func(clauses, env.capture()));
+
+    // A `#curry` function supports partial application.
+    if expr.contains_annotation("curry") {
+        func.set_annotation("curry", Expr::Bool(true));
+    }
+
+    Ok(func)
+}
+
 /// Evaluates via expression rewriting. The expression `expr` evaluates to
 /// a fixed point. In essence this is a 'tree-walk' interpreter.
 pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    // The evaluated forms are recorded in the replay log, if enabled.
+    let Some(replay) = &mut env.context.replay else {
+        return eval_expr(expr, env);
+    };
+
+    if !matches!(expr.as_ref(), Expr::List(list) if !list.is_empty()) {
+        return eval_expr(expr, env);
+    }
+
+    let id = replay.begin();
+
+    let result = eval_expr(expr, env);
+
+    if let Some(replay) = &mut env.context.replay {
+        replay.record(id, expr, &result);
+    }
+
+    result
+}
+
+fn eval_expr(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     // let expr = expr.as_ref();
 
     match expr {
@@ -69,14 +1427,24 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
             };
 
             // #TODO hm, can we somehow work with references?
-            Ok(value.clone())
+            let mut value = value.clone();
+
+            // The traced foreign functions are named after the symbol, also
+            // when invoked as values, e.g. `(map File:delete paths)`.
+            if env.context.telemetry_handler.is_some()
+                && matches!(value.as_ref(), Expr::ForeignFunc(..))
+            {
+                value.set_annotation("name", Expr::Symbol(sym.clone()));
+                value.set_range(&expr.get_range());
+            }
+
+            Ok(value)
         }
         Ann(Expr::KeySymbol(..), ..) => {
             // #TODO handle 'PathSymbol'
 
-            // #TODO lint '::' etc.
-            // #TODO check that if there is a leading ':' there is only one ':', make this a lint warning!
-            // #TODO consider renaming KeywordSymbol to KeySymbol.
+            // #Insight
+            // KeySymbols are validated in the parser, e.g. `::a` is malformed.
 
             // A `Symbol` that starts with `:` is a so-called `KeywordSymbol`. Keyword
             // symbols evaluate to themselves, and are convenient to use as Map keys,
@@ -113,54 +1481,66 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                 return Ok(Expr::One.into());
             }
 
+            // #Insight
+            // Every evaluation step (of a non-trivial expression) passes
+            // through here, a good place to check the deadline and the
+            // cancellation.
+            env.context.record_step();
+            env.context
+                .check_step()
+                .map_err(|error| Ranged(error, expr.get_range()))?;
+
+            #[cfg(feature = "signal")]
+            crate::ops::signal::handle_signal(expr, env)?;
+
             // The unwrap here is safe.
             let head = list.first().unwrap();
             let tail = &list[1..];
 
             // #TODO could check special forms before the eval
 
+            // The host forms receive the unevaluated arguments.
+            if let Ann(Expr::Symbol(sym), ..) = head {
+                if let Some(form) = env.context.host_forms.get(sym).cloned() {
+                    return eval_host_form(&form, expr, tail, env);
+                }
+            }
+
             // Evaluate the head
-            let head = eval(head, env)?;
+            let mut head = eval(head, env)?;
+
+            // A traced foreign function that is not bound to a symbol is
+            // named after the head expression, e.g. `((get-op) x)`.
+            if env.context.telemetry_handler.is_some()
+                && matches!(head.as_ref(), Expr::ForeignFunc(..))
+                && !head.contains_annotation("name")
+            {
+                head.set_annotation("name", list[0].0.clone());
+                head.set_range(&expr.get_range());
+            }
 
             // #TODO move special forms to prelude, as Expr::Macro or Expr::Special
 
             match head.as_ref() {
-                Expr::Func(params, body) => {
+                Expr::Func(..) | Expr::ForeignFunc(..) => {
+                    // #TODO do NOT pre-evaluate args for ForeignFunc, allow to implement 'macros'.
+
                     // Evaluate the arguments before calling the function.
                     let args = eval_args(tail, env)?;
 
-                    // #TODO ultra-hack to kill shared ref to `env`.
-                    let params = params.clone();
-                    let body = body.clone();
+                    env.context.push_frame(&list[0], expr.get_range());
 
-                    // Dynamic scoping, #TODO convert to lexical.
+                    let result = invoke(&head, args, env);
+                    let result = result.map_err(|error| at_call_site(error, expr));
 
-                    env.push_new_scope();
-
-                    for (param, arg) in params.iter().zip(args) {
-                        let Ann(Expr::Symbol(param), ..) = param else {
-                                return Err(Ranged(Error::invalid_arguments("parameter is not a symbol"), param.get_range()));
-                            };
-
-                        env.insert(param, arg);
+                    if let Err(error) = &result {
+                        env.capture_failure(error);
                     }
 
-                    let result = eval(&body, env);
-
-                    env.pop();
+                    env.context.pop_frame();
 
                     result
                 }
-                Expr::ForeignFunc(foreign_function) => {
-                    // #TODO do NOT pre-evaluate args for ForeignFunc, allow to implement 'macros'.
-                    // Foreign Functions do NOT change the environment, hmm...
-                    // #TODO use RefCell / interior mutability instead, to allow for changing the environment (with Mutation Effect)
-
-                    // Evaluate the arguments before calling the function.
-                    let args = eval_args(tail, env)?;
-
-                    foreign_function(&args, env)
-                }
                 Expr::Array(arr) => {
                     // Evaluate the arguments before calling the function.
                     let args = eval_args(tail, env)?;
@@ -171,13 +1551,10 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                     let Ann(Expr::Int(index), ..) = index else {
                         return Err(Ranged(Error::InvalidArguments("invalid array index, expecting Int".to_string()), index.get_range()));
                     };
-                    let index = *index as usize;
-                    if let Some(value) = arr.get(index) {
-                        Ok(value.clone().into())
-                    } else {
-                        // #TODO introduce Maybe { Some, None }
-                        Ok(Expr::One.into())
-                    }
+                    let value = usize::try_from(*index)
+                        .ok()
+                        .and_then(|index| arr.get(index));
+                    Ok(maybe(value))
                 }
                 Expr::Dict(dict) => {
                     // Evaluate the arguments before calling the function.
@@ -185,13 +1562,9 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
 
                     // #TODO optimize this!
                     // #TODO error checking, one arg, stringable, etc.
-                    let key = format_value(&args[0]);
-                    if let Some(value) = dict.get(&key) {
-                        Ok(value.clone().into())
-                    } else {
-                        // #TODO introduce Maybe { Some, None }
-                        Ok(Expr::One.into())
-                    }
+                    let key =
+                        dict_key(&args[0]).map_err(|error| Ranged(error, expr.get_range()))?;
+                    Ok(maybe(dict.get(&key)))
                 }
                 // #TODO add handling of 'high-level', compound expressions here.
                 // #TODO Expr::If
@@ -199,6 +1572,16 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                 // #TODO Expr::Do
                 // #TODO Expr::..
                 Expr::Symbol(s) => {
+                    // The forms are checked statically, in macro_expand, but
+                    // an extra, dynamic check is needed for the constructed
+                    // expressions, e.g. `(eval '(match ...))`.
+                    if let Some(feature) = env.context.features.disabled_form(s) {
+                        return Err(Ranged(
+                            Error::FeatureDisabled(s.clone(), feature.to_string()),
+                            expr.get_range(),
+                        ));
+                    }
+
                     match s.as_str() {
                         // special term
                         // #TODO the low-level handling of special forms should use the above high-level cases.
@@ -233,9 +1616,10 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                             let expr = tail.first().unwrap();
 
                             if let Some(ann) = expr.1.clone() {
+                                let ann = ann.into_iter().map(|(k, v)| (k, Ann::new(v))).collect();
                                 Ok(Expr::Dict(ann).into())
                             } else {
-                                Ok(Expr::Dict(HashMap::new()).into())
+                                Ok(Expr::Dict(BTreeMap::new()).into())
                             }
                         }
                         "eval" => {
@@ -258,182 +1642,84 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                             // #TODO hm, that clone, maybe `Rc` can fix this?
                             Ok(value.0.clone().into())
                         }
-                        "for" => {
-                            // #Insight
-                            // `for` is a generalization of `if`.
-                            // `for` is also related with `do`.
-                            let [predicate, body] = tail else {
-                                // #TODO proper error!
-                                return Err(Ranged(Error::invalid_arguments("missing for arguments"), expr.get_range()));
+                        "qquot" => {
+                            let [template] = tail else {
+                                return Err(Ranged(
+                                    Error::invalid_arguments("missing quasi-quote template"),
+                                    expr.get_range(),
+                                ));
                             };
 
-                            let mut value = Expr::One.into();
-
-                            loop {
-                                let predicate = eval(predicate, env)?;
-
-                                let Ann(Expr::Bool(predicate), ..) = predicate else {
-                                    return Err(Ranged(Error::invalid_arguments("the for predicate is not a boolean value"), predicate.get_range()));
-                                };
-
-                                if !predicate {
-                                    break;
-                                }
-
-                                value = eval(body, env)?;
-                            }
-
-                            Ok(value)
+                            quasi_quote(template, env)
                         }
-                        "if" => {
-                            // #TODO this is a temp hack!
-                            let Some(predicate) = tail.get(0) else {
-                                return Err(Ranged(Error::invalid_arguments("malformed if predicate"), expr.get_range()));
-                            };
-
-                            let Some(true_clause) = tail.get(1) else {
-                                return Err(Ranged(Error::invalid_arguments("malformed if true clause"), expr.get_range()));
-                            };
-
-                            let false_clause = tail.get(2);
-
-                            let predicate = eval(predicate, env)?;
-
-                            let Ann(Expr::Bool(predicate), ..) = predicate else {
-                                return Err(Ranged(Error::InvalidArguments("the if predicate is not a boolean value".to_owned()), predicate.get_range()));
+                        "unquot" => Err(Ranged(
+                            Error::invalid_arguments(
+                                "unquote is only valid in a quasi-quote template",
+                            ),
+                            expr.get_range(),
+                        )),
+                        "for" => eval_for(expr, tail, env),
+                        "while" => eval_while(expr, tail, env),
+                        "break" => {
+                            let value = match tail {
+                                [] => Expr::One.into(),
+                                [value] => eval(value, env)?,
+                                _ => {
+                                    return Err(Ranged(
+                                        Error::invalid_arguments(
+                                            "`break` accepts at most one `value` argument",
+                                        ),
+                                        expr.get_range(),
+                                    ));
+                                }
                             };
 
-                            if predicate {
-                                eval(true_clause, env)
-                            } else if let Some(false_clause) = false_clause {
-                                eval(false_clause, env)
-                            } else {
-                                // #TODO what should we return if there is no false-clause? Zero/Never?
-                                Ok(Expr::One.into())
+                            Err(Ranged(Error::Flow(Flow::Break(value)), expr.get_range()))
+                        }
+                        "continue" => {
+                            if !tail.is_empty() {
+                                return Err(Ranged(
+                                    Error::invalid_arguments(
+                                        "`continue` does not accept arguments",
+                                    ),
+                                    expr.get_range(),
+                                ));
                             }
+
+                            Err(Ranged(Error::Flow(Flow::Continue), expr.get_range()))
                         }
+                        "if" => eval_if(expr, tail, env),
+                        "cond" => eval_cond(tail, env),
+                        "and" => eval_and_or(tail, env, false),
+                        "or" => eval_and_or(tail, env, true),
+                        "?" => eval_unwrap_or_else(expr, tail, env),
+                        "try" => eval_try(expr, tail, env),
+                        "case" => eval_case(expr, tail, env),
+                        "match" => eval_match(expr, tail, env),
                         "for_each" => {
                             // #TODO this is a temp hack!
                             let [seq, var, body] = tail else {
                                 return Err(Ranged(Error::invalid_arguments("malformed `for_each`"), expr.get_range()));
                             };
 
-                            let seq = eval(seq, env)?;
-
-                            let Ann(Expr::Array(arr), ..) = seq else {
-                                return Err(Ranged(Error::invalid_arguments("`for_each` requires a `Seq` as the first argument"), seq.get_range()));
-                            };
-
-                            let Ann(Expr::Symbol(sym), _) = var else {
-                                return Err(Ranged(Error::invalid_arguments("`for_each` requires a symbol as the second argument"), var.get_range()));
-                            };
-
-                            env.push_new_scope();
-
-                            for x in arr {
-                                // #TODO array should have Ann<Expr> use Ann<Expr> everywhere, avoid the clones!
-                                env.insert(sym, Ann::new(x.clone()));
-                                eval(body, env)?;
-                            }
-
-                            env.pop();
-
-                            // #TODO intentionally don't return a value, reconsider this?
-                            Ok(Expr::One.into())
-                        }
-                        "use" => {
-                            // Import a directory as a module.
-
-                            let Some(Ann(Expr::Symbol(module_name), _)) = tail.get(0) else {
-                                return Err(Ranged(Error::invalid_arguments("malformed use expression"), expr.get_range()));
-                            };
-
-                            // #TODO use `modl` instead of `module` or `mod`.
-                            // #TODO support nested modules
-                            // #TODO support 'absolute' modules
-                            // #TODO rewrite separators here.
-                            let module_path = module_name;
-
-                            let file_paths = fs::read_dir(module_path)?;
-
-                            let mut resolved_exprs: Vec<Ann<Expr>> = Vec::new();
-
-                            for file_path in file_paths {
-                                let path = file_path?.path();
-
-                                if !path.display().to_string().ends_with(".tan") {
-                                    continue;
-                                }
-
-                                // #TODO handle the range of the error.
-                                let input = std::fs::read_to_string(path)?;
-
-                                let result = resolve_string(input, env);
-
-                                let Ok(mut exprs) = result else {
-                                    let err = result.unwrap_err();
-                                    // #TODO better error handling here!
-                                    dbg!(&err);
-                                    // #TODO maybe continue parsing/resolving to find more errors?
-                                    // #TODO better error here!
-                                    return Err(Ranged(Error::FailedUse, expr.get_range()));
-                                };
-
-                                resolved_exprs.append(&mut exprs);
-                            }
-
-                            for expr in resolved_exprs {
-                                if let Err(err) = eval(&expr, env) {
-                                    // #TODO better error handling here!
-                                    dbg!(&err);
-                                    // #TODO better error here!
-                                    return Err(Ranged(Error::FailedUse, expr.get_range()));
-                                }
-                            }
-
-                            // #TODO what could we return here?
-                            Ok(Expr::One.into())
-                        }
-                        "let" => {
-                            // #TODO this is already parsed statically by resolver, no need to duplicate the tests here?
-                            // #TODO also report some of these errors statically, maybe in a sema phase?
-                            let mut args = tail.iter();
-
-                            loop {
-                                let Some(sym) = args.next() else {
-                                    break;
-                                };
-
-                                let Some(value) = args.next() else {
-                                    // #TODO error?
-                                    break;
-                                };
-
-                                let Ann(Expr::Symbol(s), ..) = sym else {
-                                    return Err(Ranged(Error::invalid_arguments(format!("`{sym}` is not a Symbol")), sym.get_range()));
-                                };
-
-                                if is_reserved_symbol(s) {
-                                    return Err(Ranged(
-                                        Error::invalid_arguments(format!(
-                                            "let cannot shadow the reserved symbol `{s}`"
-                                        )),
-                                        sym.get_range(),
-                                    ));
-                                }
-
-                                let value = eval(value, env)?;
-
-                                // #TODO notify about overrides? use `set`?
-                                env.insert(s, value);
-                            }
-
-                            // #TODO return last value!
-                            Ok(Expr::One.into())
+                            eval_for_each(seq, var, body, env)
                         }
+                        "use" => eval_use(expr, tail, env),
+                        "export" => Err(Ranged(
+                            Error::invalid_arguments(
+                                "`export` is only valid at the top level of a module",
+                            ),
+                            expr.get_range(),
+                        )),
+                        "let" => eval_let(tail, env),
+                        // #Insight
+                        // The mutating forms are special forms, as they operate
+                        // on the binding, not on the value.
+                        "push!" => eval_push(expr, tail, env),
+                        "set!" => eval_set(expr, tail, env),
                         "Char" => {
                             // #TODO report more than 1 arguments.
-                            let Some(Ann(Expr::String(c), _)) = tail.get(0) else {
+                            let Some(Ann(Expr::String(c), _)) = tail.first() else {
                                 return Err(Ranged(Error::invalid_arguments("malformed Char constructor"), expr.get_range()));
                             };
 
@@ -455,18 +1741,32 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                             let args = eval_args(tail, env)?;
                             Ok(Expr::List(args).into())
                         }
-                        "Func" => {
-                            let [args, body] = tail else {
-                                return Err(Ranged(Error::invalid_arguments("malformed func definition"), expr.get_range()));
-                            };
+                        "Never" => {
+                            if !tail.is_empty() {
+                                return Err(Ranged(
+                                    Error::invalid_arguments("`Never` does not accept arguments"),
+                                    expr.get_range(),
+                                ));
+                            }
 
-                            let Ann(Expr::List(params), ..) = args else {
-                                return Err(Ranged(Error::invalid_arguments("malformed func parameters definition"), args.get_range()));
-                            };
+                            Ok(Ann::with_type(Expr::Never, Expr::symbol("Never")))
+                        }
+                        "True" | "False" => {
+                            if !tail.is_empty() {
+                                return Err(Ranged(
+                                    Error::invalid_arguments(format!(
+                                        "`{s}` does not accept arguments"
+                                    )),
+                                    expr.get_range(),
+                                ));
+                            }
 
-                            // #TODO optimize!
-                            Ok(Expr::Func(params.clone(), Box::new(body.clone())).into())
+                            Ok(Ann::with_type(
+                                Expr::Bool(s == "True"),
+                                Expr::symbol("Bool"),
+                            ))
                         }
+                        "Func" => eval_func(expr, tail, env),
                         // #TODO macros should be handled at a separate, comptime, macroexpand pass.
                         // #TODO actually two passes, macro_def, macro_expand
                         "Macro" => {
@@ -478,24 +1778,35 @@ pub fn eval(expr: &Ann<Expr>, env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>>
                                 return Err(Ranged(Error::invalid_arguments("malformed macro parameters definition"), args.get_range()));
                             };
 
-                            // #TODO optimize!
-                            Ok(Expr::Macro(params.clone(), Box::new(body.clone())).into())
-                        }
-                        _ => {
-                            return Err(Ranged(
-                                Error::NotInvocable(format!("symbol `{head}`")),
-                                head.get_range(),
-                            ));
+                            Ok(Expr::macro_(params.clone(), body.clone()).into())
                         }
+                        _ => Err(Ranged(
+                            Error::NotInvocable(format!("symbol `{head}`")),
+                            head.get_range(),
+                        )),
                     }
                 }
-                _ => {
-                    return Err(Ranged(
-                        Error::NotInvocable(format!("expression `{head}`")),
-                        head.get_range(),
-                    ));
-                }
+                _ => Err(Ranged(
+                    Error::NotInvocable(format!("expression `{head}`")),
+                    head.get_range(),
+                )),
+            }
+        }
+        Ann(Expr::Array(items), ann) => {
+            // #Insight
+            // The elements are evaluated, the errors refer to the range of
+            // the specific element.
+            let items = eval_args(items, env)?;
+            Ok(Ann(Expr::Array(items), ann.clone()))
+        }
+        Ann(Expr::Dict(dict), ann) => {
+            let mut values = BTreeMap::new();
+
+            for (key, value) in dict {
+                values.insert(key.clone(), eval(value, env)?);
             }
+
+            Ok(Ann(Expr::Dict(values), ann.clone()))
         }
         _ => {
             // #TODO hm, maybe need to report an error here? or even select the desired behavior? -> NO ERROR
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::{ann::Ann, error::Error, expr::Expr, range::Ranged, util::truncate_chars};
+
+use super::env::Env;
+
+// #Insight
+// In audit (dry-run) mode the effectful ops (the file system mutations, e.g.
+// `io/write-string`, `io/delete-dir`, the `store/put` and `db/exec` writes,
+// `exit`) are not executed, the intended actions are recorded in a report
+// instead. The host inspects the report, and applies the approved actions,
+// e.g.
+//
+// env.context.audit = Some(AuditReport::new());
+// eval_string(script, &mut env)?;
+// let report = env.context.audit.take().unwrap();
+// if confirm(&report) {
+//     report.apply(&mut env)?;
+// }
+//
+// The arguments are validated before the action is recorded. The skipped
+// effects are not simulated, e.g. a file 'written' in audit mode does not
+// exist for a later read.
+
+// #TODO record the `io/open` handle writes as a single action.
+
+/// The maximum length (in chars) of an argument in the report.
+const MAX_ARG_LEN: usize = 60;
+
+/// An effectful action, recorded in audit mode.
+#[derive(Debug, Clone)]
+pub struct AuditAction {
+    /// The qualified name of the op, e.g. `io/write-string`.
+    pub op: String,
+    /// The (evaluated) arguments of the op.
+    pub args: Vec<Ann<Expr>>,
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}", self.op)?;
+        for arg in &self.args {
+            write!(f, " {}", truncate_chars(arg.to_string(), MAX_ARG_LEN))?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The report of the actions intended by a script, recorded in audit mode.
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    actions: Vec<AuditAction>,
+}
+
+impl AuditReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, op: &str, args: &[Ann<Expr>]) {
+        self.actions.push(AuditAction {
+            op: op.to_owned(),
+            args: args.to_vec(),
+        });
+    }
+
+    /// Returns the recorded actions, in the order of the evaluation.
+    pub fn actions(&self) -> &[AuditAction] {
+        &self.actions
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    /// Keeps only the actions approved by the predicate.
+    pub fn retain(&mut self, approve: impl FnMut(&AuditAction) -> bool) {
+        self.actions.retain(approve);
+    }
+
+    /// Executes the recorded actions, in order, stops at the first error. The
+    /// audit mode of the Env should be disabled, else the actions are recorded
+    /// again.
+    pub fn apply(&self, env: &mut Env) -> Result<(), Ranged<Error>> {
+        for action in &self.actions {
+            let Some(Ann(Expr::ForeignFunc(op), ..)) = env.get(&action.op) else {
+                return Err(Error::invalid_arguments(format!(
+                    "`{}` is not a foreign function",
+                    action.op
+                ))
+                .into());
+            };
+
+            op(&action.args, env)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let actions: Vec<String> = self.actions.iter().map(|a| a.to_string()).collect();
+        write!(f, "{}", actions.join("\n"))
+    }
+}
@@ -0,0 +1,50 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// #Insight
+// The cancellation token stops a running evaluation from another thread, e.g.
+// the stop button of a GUI, or a server that drops a request:
+//
+// let token = CancellationToken::new();
+// env.context.cancellation = Some(token.clone());
+// // on another thread
+// token.cancel();
+//
+// The token is checked at every evaluation step, and periodically while
+// blocked in `sleep`, `recv`, `await` or `watch`. A cancelled evaluation is
+// aborted with `Error::Interrupted`, like the time-outs it is not catchable.
+//
+// The token stays cancelled, it should be reset before the next evaluation.
+// The token is shared with the modules, the agents and the `pmap` workers.
+
+/// The interval between cancellation checks, while blocked.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A token to cancel a running evaluation, shared between threads.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests the cancellation of the evaluations that observe the token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Clears the cancellation, e.g. before evaluating the next input.
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
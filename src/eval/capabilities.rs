@@ -0,0 +1,87 @@
+use std::fmt;
+
+// #Insight
+// The capabilities restrict the effects of the ops, e.g. to evaluate
+// user-provided scripts in a sandbox:
+//
+// let mut env = Env::prelude_sandboxed();
+// env.context.capabilities.fs = true; // allow the file system ops only
+//
+// The ops check the capabilities when invoked, a denied op is an error, like
+// any other error it is catchable with `try`. The capabilities are inherited
+// by the modules and the isolates.
+//
+// - fs: the file system ops, e.g. `read-string`, `glob`, and loading modules
+//   with `use`.
+// - process: the ops that affect or interact with the process, e.g. `exit`,
+//   `on-signal`, `prompt`.
+// - net: the network ops.
+// - env: the environment variables, e.g. the module search paths of `TAN_PATH`.
+
+// #TODO support finer-grained capabilities, e.g. read-only fs, allowed paths.
+
+/// A capability of the ops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    Fs,
+    Process,
+    Net,
+    Env,
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Capability::Fs => "fs",
+            Capability::Process => "process",
+            Capability::Net => "net",
+            Capability::Env => "env",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// The capabilities allowed to the ops, all allowed by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub fs: bool,
+    pub process: bool,
+    pub net: bool,
+    pub env: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    pub fn all() -> Self {
+        Self {
+            fs: true,
+            process: true,
+            net: true,
+            env: true,
+        }
+    }
+
+    /// Returns the capabilities of a sandbox, nothing is allowed.
+    pub fn none() -> Self {
+        Self {
+            fs: false,
+            process: false,
+            net: false,
+            env: false,
+        }
+    }
+
+    pub fn allows(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::Fs => self.fs,
+            Capability::Process => self.process,
+            Capability::Net => self.net,
+            Capability::Env => self.env,
+        }
+    }
+}
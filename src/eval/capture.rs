@@ -0,0 +1,311 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    io,
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    expr::{portable::Portable, Expr},
+    range::Ranged,
+    util::is_reserved_symbol,
+};
+
+use super::{env::Env, limits::Limit};
+
+// #Insight
+// The expressions are not thread-safe, a function is evaluated in another
+// thread as a captured copy, see `Portable`. The values the function refers to
+// are captured too, the prelude functions are available in every Env.
+//
+// Only pure functions, i.e. without Mutation effects and without calls to the
+// effectful prelude functions (e.g. `writeln`, the file ops), are captured, the
+// captured function does not share state with the original and does not
+// bypass the output and the audit of the host.
+
+// #TODO avoid creating a prelude Env per capture.
+
+/// A thread-safe copy of an evaluation error, the kind of the error is
+/// preserved.
+#[derive(Debug, Clone)]
+pub enum PortableError {
+    Thrown(Portable),
+    TimedOut,
+    Interrupted,
+    LimitExceeded(Limit),
+    CapabilityDenied(String, String),
+    DivisionByZero,
+    IntegerOverflow(String),
+    UndefinedSymbol(String),
+    UndefinedFunction(String, String),
+    InvalidArguments(String),
+    NotInvocable(String),
+    FailedUse(String),
+    FeatureDisabled(String, String),
+    Io(io::ErrorKind, String),
+    /// An error without a portable kind, e.g. a parse error of `eval`, the
+    /// message is preserved.
+    Other(String),
+}
+
+impl PortableError {
+    pub fn from_error(Ranged(error, range): Ranged<Error>) -> Ranged<PortableError> {
+        let error = match error {
+            Error::Thrown(value) => match Portable::from_ann(&value) {
+                Some(value) if value.is_data() => PortableError::Thrown(value),
+                // A non-data value is thrown as its repr.
+                _ => match Portable::from_ann(&Expr::String(value.to_string()).into()) {
+                    Some(value) => PortableError::Thrown(value),
+                    None => PortableError::Other(value.to_string()),
+                },
+            },
+            Error::TimedOut => PortableError::TimedOut,
+            Error::Interrupted => PortableError::Interrupted,
+            Error::LimitExceeded(limit) => PortableError::LimitExceeded(limit),
+            Error::CapabilityDenied(op, capability) => {
+                PortableError::CapabilityDenied(op, capability)
+            }
+            Error::DivisionByZero => PortableError::DivisionByZero,
+            Error::IntegerOverflow(op) => PortableError::IntegerOverflow(op),
+            Error::UndefinedSymbol(sym) => PortableError::UndefinedSymbol(sym),
+            Error::UndefinedFunction(sym, signature) => {
+                PortableError::UndefinedFunction(sym, signature)
+            }
+            Error::InvalidArguments(text) => PortableError::InvalidArguments(text),
+            Error::NotInvocable(text) => PortableError::NotInvocable(text),
+            Error::FailedUse(text) => PortableError::FailedUse(text),
+            Error::FeatureDisabled(form, flag) => PortableError::FeatureDisabled(form, flag),
+            Error::Io(error) => PortableError::Io(error.kind(), error.to_string()),
+            error => PortableError::Other(error.to_string()),
+        };
+
+        Ranged(error, range)
+    }
+
+    pub fn into_error(Ranged(error, range): Ranged<PortableError>) -> Ranged<Error> {
+        let error = match error {
+            PortableError::Thrown(value) => Error::Thrown(value.to_ann(&[])),
+            PortableError::TimedOut => Error::TimedOut,
+            PortableError::Interrupted => Error::Interrupted,
+            PortableError::LimitExceeded(limit) => Error::LimitExceeded(limit),
+            PortableError::CapabilityDenied(op, capability) => {
+                Error::CapabilityDenied(op, capability)
+            }
+            PortableError::DivisionByZero => Error::DivisionByZero,
+            PortableError::IntegerOverflow(op) => Error::IntegerOverflow(op),
+            PortableError::UndefinedSymbol(sym) => Error::UndefinedSymbol(sym),
+            PortableError::UndefinedFunction(sym, signature) => {
+                Error::UndefinedFunction(sym, signature)
+            }
+            PortableError::InvalidArguments(text) | PortableError::Other(text) => {
+                Error::InvalidArguments(text)
+            }
+            PortableError::NotInvocable(text) => Error::NotInvocable(text),
+            PortableError::FailedUse(text) => Error::FailedUse(text),
+            PortableError::FeatureDisabled(form, flag) => Error::FeatureDisabled(form, flag),
+            PortableError::Io(kind, message) => Error::Io(io::Error::new(kind, message)),
+        };
+
+        Ranged(error, range)
+    }
+}
+
+/// Collects the symbols referenced in the expression, returns false if the
+/// expression has side-effects.
+fn collect_symbols<'a>(expr: &'a Ann<Expr>, symbols: &mut BTreeSet<&'a str>) -> bool {
+    if expr.contains_annotation("effect") {
+        return false;
+    }
+
+    match &expr.0 {
+        Expr::Symbol(sym) => {
+            symbols.insert(sym);
+            true
+        }
+        Expr::List(items) | Expr::Array(items) => {
+            items.iter().all(|item| collect_symbols(item, symbols))
+        }
+        Expr::Dict(dict) => dict.values().all(|value| collect_symbols(value, symbols)),
+        Expr::If(predicate, then, otherwise) => {
+            collect_symbols(predicate, symbols)
+                && collect_symbols(then, symbols)
+                && otherwise
+                    .as_ref()
+                    .is_none_or(|otherwise| collect_symbols(otherwise, symbols))
+        }
+        _ => true,
+    }
+}
+
+/// Captures a function and the values it refers to, for evaluation in another
+/// Env.
+struct Capture<'a> {
+    env: &'a mut Env,
+    prelude: Env,
+    bindings: BTreeMap<String, Portable>,
+    // The functions that are captured, or being captured.
+    funcs: HashSet<String>,
+}
+
+impl Capture<'_> {
+    fn insert(&mut self, name: &str, value: Portable) -> Option<()> {
+        // Different values with the same name cannot be captured.
+        match self.bindings.get(name) {
+            Some(existing) if *existing != value => None,
+            _ => {
+                self.bindings.insert(name.to_owned(), value);
+                Some(())
+            }
+        }
+    }
+
+    /// Captures the value of a symbol, returns None if the value cannot be
+    /// captured.
+    fn capture_binding(&mut self, name: &str, value: &Ann<Expr>) -> Option<()> {
+        match &value.0 {
+            Expr::ForeignFunc(..) => {
+                if value.contains_annotation("effect") {
+                    return None;
+                }
+                // The prelude functions are available in every Env.
+                matches!(self.prelude.get(name), Some(Ann(Expr::ForeignFunc(..), ..))).then_some(())
+            }
+            Expr::Func(..) => {
+                if self.funcs.insert(name.to_owned()) {
+                    let func = self.capture_func(value)?;
+                    self.insert(name, func)
+                } else {
+                    // A recursive reference, or an already captured function.
+                    match self.bindings.get(name) {
+                        Some(existing) if Some(existing) != Portable::from_ann(value).as_ref() => {
+                            None
+                        }
+                        _ => Some(()),
+                    }
+                }
+            }
+            _ => {
+                let value = Portable::from_ann(value).filter(Portable::is_data)?;
+                self.insert(name, value)
+            }
+        }
+    }
+
+    /// Captures a pure function, returns None if the function has side-effects
+    /// or refers to values that cannot be captured.
+    fn capture_func(&mut self, func: &Ann<Expr>) -> Option<Portable> {
+        let Expr::Func(clauses, scopes) = &func.0 else {
+            return None;
+        };
+
+        let mut symbols = BTreeSet::new();
+        let mut params = BTreeSet::new();
+
+        for clause in clauses.iter() {
+            if !collect_symbols(&clause.body, &mut symbols) {
+                return None;
+            }
+            for param in &clause.params {
+                collect_symbols(param, &mut params);
+            }
+        }
+
+        for sym in symbols.difference(&params) {
+            if *sym == "self" || is_reserved_symbol(sym) {
+                continue;
+            }
+
+            let caller_scopes = self.env.replace(scopes.to_vec());
+            let value = self.env.get(sym);
+            self.env.replace(caller_scopes);
+
+            // Not found, the symbol is bound in the function body, or undefined.
+            let Some(value) = value else {
+                continue;
+            };
+
+            self.capture_binding(sym, &value)?;
+        }
+
+        Portable::from_ann(func)
+    }
+}
+
+/// A captured copy of a pure function, that can be sent to another thread.
+#[derive(Debug, Clone)]
+pub struct CapturedFunc {
+    func: Portable,
+    bindings: BTreeMap<String, Portable>,
+}
+
+impl CapturedFunc {
+    /// Captures a pure function and the values it refers to. Returns None if
+    /// the function has side-effects or refers to values that cannot be
+    /// captured, e.g. host values.
+    pub fn capture(func: &Ann<Expr>, env: &mut Env) -> Option<Self> {
+        let mut capture = Capture {
+            env,
+            prelude: Env::prelude(),
+            bindings: BTreeMap::new(),
+            funcs: HashSet::new(),
+        };
+
+        let func = capture.capture_func(func)?;
+
+        Some(CapturedFunc {
+            func,
+            bindings: capture.bindings,
+        })
+    }
+
+    /// Returns the names of the captured values.
+    pub fn binding_names(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+
+    /// Recreates the function in the Env. The captured values are bound in a
+    /// new scope, captured by the function, visible to the (recursive)
+    /// functions.
+    pub fn instantiate(&self, env: &mut Env) -> Ann<Expr> {
+        env.push_new_scope();
+        let scopes = env.capture();
+
+        for (name, value) in &self.bindings {
+            env.insert(name, value.to_ann(&scopes));
+        }
+
+        env.pop();
+
+        self.func.to_ann(&scopes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{api::eval_string, eval::env::Env};
+
+    use super::CapturedFunc;
+
+    #[test]
+    fn capture_accepts_pure_functions_only() {
+        let mut env = Env::prelude();
+        eval_string("(let factor 3)", &mut env).unwrap();
+        eval_string("(let scale (Func (x) (* x factor)))", &mut env).unwrap();
+        eval_string("(let #mut total 0)", &mut env).unwrap();
+
+        let func = eval_string("(Func (x) (scale x))", &mut env).unwrap();
+        let captured = CapturedFunc::capture(&func, &mut env).expect("a pure function");
+        let names: Vec<_> = captured.binding_names().collect();
+        assert_eq!(names, ["factor", "scale"]);
+
+        let func = eval_string("(Func (x) (set! total x))", &mut env).unwrap();
+        assert!(CapturedFunc::capture(&func, &mut env).is_none());
+
+        let func = eval_string("(Func (x) (writeln x))", &mut env).unwrap();
+        assert!(CapturedFunc::capture(&func, &mut env).is_none());
+
+        env.insert("host", env.get("+").unwrap());
+        let func = eval_string("(Func (x) (host x 1))", &mut env).unwrap();
+        assert!(CapturedFunc::capture(&func, &mut env).is_none());
+    }
+}
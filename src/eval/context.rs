@@ -0,0 +1,392 @@
+use std::{collections::HashMap, fmt, io, path::PathBuf, rc::Rc, time::Instant};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    expr::{Expr, ExprFn},
+    range::Range,
+    runtime::Mailbox,
+};
+
+use super::{
+    audit::AuditReport,
+    cancellation::CancellationToken,
+    capabilities::{Capabilities, Capability},
+    diagnostics::DiagnosticsState,
+    env::Env,
+    features::Features,
+    limits::{Limit, Limits},
+    module::{default_module_paths, ModuleCache},
+    module_cache::{default_ast_cache, AstCache},
+    output::Output,
+    replay::ReplayLog,
+    stats::EvalStats,
+    telemetry::{OpEvent, TelemetryHandler},
+};
+
+// #Insight
+// The Env models the scoping (the bindings), the Context holds the runtime
+// configuration (limits, hooks, handlers) and the runtime state that is not
+// related to scoping (statistics, loaded modules, etc.).
+//
+// The Context is a field of the Env and the Env stays the entry point of the
+// `api` functions, `eval` and the ops, they reach the Context through it, e.g.
+// `env.context.require(..)`. Passing the Context next to the Env would change
+// the signature of every op and every registered host function, it is out of
+// scope. The hosts use the `Runtime` instead, it owns the root Env with its
+// Context and exposes the configuration (limits, capabilities, I/O
+// redirection, etc.) without the scoping.
+
+// #TODO consider sharing the Context between Envs, e.g. with Rc<RefCell<..>>.
+
+/// A host callback that receives the progress reports of a script, the
+/// percent (0-100) and a message, see `progress/report`.
+pub type ProgressFn = dyn FnMut(f64, &str);
+
+pub struct ProgressHandler(pub Box<ProgressFn>);
+
+impl fmt::Debug for ProgressHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<progress_handler>")
+    }
+}
+
+/// A host special form, receives the unevaluated arguments, see
+/// `api::register_form`.
+#[derive(Clone)]
+pub struct HostForm(pub Rc<ExprFn>);
+
+impl fmt::Debug for HostForm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<host_form>")
+    }
+}
+
+/// The runtime configuration and state of an evaluation.
+#[derive(Debug)]
+pub struct Context {
+    /// The enabled language features.
+    pub features: Features,
+    /// The capabilities allowed to the ops.
+    pub capabilities: Capabilities,
+    /// The special forms defined by the host, by name.
+    pub host_forms: HashMap<String, HostForm>,
+    /// The output stream of `write`/`writeln`, the process stdout by default.
+    pub stdout: Output,
+    /// The output stream of the diagnostic messages, e.g. the progress
+    /// reports and the prompts, the process stderr by default.
+    pub stderr: Output,
+    /// The evaluation is aborted after the deadline.
+    pub deadline: Option<Instant>,
+    /// The evaluation is aborted when the token is cancelled, e.g. from
+    /// another thread.
+    pub cancellation: Option<CancellationToken>,
+    /// The limits of the evaluation, the timeout is enforced with the
+    /// deadline, see `api::eval_with_limits`.
+    pub limits: Limits,
+    /// The evaluation steps counted against the fuel limit.
+    pub fuel_used: u64,
+    /// The resource-usage statistics, only collected if enabled.
+    pub stats: Option<EvalStats>,
+    /// The state of the diagnostics of a failure, only tracked if enabled.
+    pub diagnostics: Option<DiagnosticsState>,
+    /// The log of the evaluated forms, only recorded if enabled.
+    pub replay: Option<ReplayLog>,
+    /// The report of the intended effects, in audit (dry-run) mode the
+    /// effectful ops are not executed.
+    pub audit: Option<AuditReport>,
+    /// The number of the worker threads of the parallel ops, e.g. `pmap`,
+    /// the available parallelism by default.
+    pub workers: Option<usize>,
+    /// The current nesting of function invocations.
+    pub call_depth: usize,
+    /// The handler of the termination signals, if registered.
+    pub signal_handler: Option<Ann<Expr>>,
+    /// The current key-value store, opened with `store/open`.
+    pub store: Option<Ann<Expr>>,
+    /// The host handler of the progress reports, the reports are written to
+    /// the stderr stream if no handler is registered.
+    pub progress_handler: Option<ProgressHandler>,
+    /// The host handler of the foreign function invocations, if registered.
+    pub telemetry_handler: Option<TelemetryHandler>,
+    /// The paths of the modules being loaded, to detect circular
+    /// dependencies.
+    pub module_stack: Vec<PathBuf>,
+    /// The module search paths, initialized from `TAN_PATH`.
+    pub module_paths: Vec<PathBuf>,
+    /// The loaded modules.
+    pub modules: ModuleCache,
+    /// The cache of the resolved module files, see `module_cache`.
+    pub ast_cache: Option<Rc<dyn AstCache>>,
+    /// The message channels to the host, if evaluated in an isolate.
+    pub mailbox: Option<Mailbox>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self {
+            features: Features::default(),
+            capabilities: Capabilities::default(),
+            host_forms: HashMap::new(),
+            stdout: Output::stdout(),
+            stderr: Output::stderr(),
+            deadline: None,
+            cancellation: None,
+            limits: Limits::default(),
+            fuel_used: 0,
+            stats: None,
+            diagnostics: None,
+            replay: None,
+            audit: None,
+            workers: None,
+            call_depth: 0,
+            signal_handler: None,
+            store: None,
+            progress_handler: None,
+            telemetry_handler: None,
+            module_stack: Vec::new(),
+            module_paths: default_module_paths(),
+            modules: ModuleCache::default(),
+            ast_cache: default_ast_cache(),
+            mailbox: None,
+        }
+    }
+}
+
+impl Context {
+    /// Returns the Context of a module Env, loaded from this Context. The
+    /// language features, the capabilities, the host forms, the output
+    /// streams, the deadline, the cancellation, the limits, the workers, the
+    /// module search paths, the loaded modules and the module cache are
+    /// inherited.
+    pub fn for_module(&self, module_path: PathBuf) -> Self {
+        let mut module_stack = self.module_stack.clone();
+        module_stack.push(module_path);
+
+        Self {
+            features: self.features.clone(),
+            capabilities: self.capabilities,
+            host_forms: self.host_forms.clone(),
+            stdout: self.stdout.clone(),
+            stderr: self.stderr.clone(),
+            deadline: self.deadline,
+            cancellation: self.cancellation.clone(),
+            limits: self.limits.clone(),
+            workers: self.workers,
+            module_stack,
+            module_paths: self.module_paths.clone(),
+            modules: self.modules.clone(),
+            ast_cache: self.ast_cache.clone(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the policy of the worker threads spawned by the evaluation,
+    /// e.g. the agents and the `pmap` workers.
+    pub fn worker_policy(&self) -> WorkerPolicy {
+        WorkerPolicy {
+            features: self.features.clone(),
+            capabilities: self.capabilities,
+            module_paths: self.module_paths.clone(),
+            limits: self.limits.clone(),
+            deadline: self.deadline,
+            cancellation: self.cancellation.clone(),
+            is_audited: self.audit.is_some(),
+            is_stdout_redirected: self.stdout.is_redirected(),
+            is_stderr_redirected: self.stderr.is_redirected(),
+        }
+    }
+
+    /// Checks that the capability is allowed to the op, e.g.
+    /// `env.context.require(Capability::Fs, "read-string")?`.
+    pub fn require(&self, capability: Capability, op: &str) -> Result<(), Error> {
+        if self.capabilities.allows(capability) {
+            Ok(())
+        } else {
+            Err(Error::CapabilityDenied(
+                op.to_owned(),
+                capability.to_string(),
+            ))
+        }
+    }
+
+    /// Records an evaluation step, if statistics are enabled.
+    pub fn record_step(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            stats.steps += 1;
+        }
+    }
+
+    /// Checks the deadline, the cancellation and the fuel of an evaluation
+    /// step.
+    pub fn check_step(&mut self) -> Result<(), Error> {
+        self.check_deadline()?;
+        self.check_cancelled()?;
+        self.consume_fuel()
+    }
+
+    /// Consumes the fuel of an evaluation step, if the fuel is limited.
+    pub fn consume_fuel(&mut self) -> Result<(), Error> {
+        if let Some(fuel) = self.limits.fuel {
+            if self.fuel_used >= fuel {
+                return Err(Error::LimitExceeded(Limit::Fuel(fuel)));
+            }
+            self.fuel_used += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Enters a function invocation, fails if the call depth limit is
+    /// exceeded.
+    pub fn enter_call(&mut self) -> Result<(), Error> {
+        if let Some(max_call_depth) = self.limits.max_call_depth {
+            if self.call_depth >= max_call_depth {
+                return Err(Error::LimitExceeded(Limit::CallDepth(max_call_depth)));
+            }
+        }
+
+        self.call_depth += 1;
+
+        if let Some(stats) = &mut self.stats {
+            stats.max_call_depth = stats.max_call_depth.max(self.call_depth);
+        }
+
+        Ok(())
+    }
+
+    /// Exits a function invocation.
+    pub fn exit_call(&mut self) {
+        self.call_depth -= 1;
+    }
+
+    /// Enters an invocation of the `head` function expression, if the
+    /// diagnostics are enabled.
+    pub fn push_frame(&mut self, head: &Ann<Expr>, range: Range) {
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.push_frame(head, range);
+        }
+    }
+
+    /// Exits an invocation, if the diagnostics are enabled.
+    pub fn pop_frame(&mut self) {
+        if let Some(diagnostics) = &mut self.diagnostics {
+            diagnostics.pop_frame();
+        }
+    }
+
+    /// Records the effect of an op in audit mode. Returns true if the effect
+    /// should be skipped.
+    pub fn audit_effect(&mut self, op: &str, args: &[Ann<Expr>]) -> bool {
+        match &mut self.audit {
+            Some(report) => {
+                report.record(op, args);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Registers the host handler of the progress reports, e.g. to update a
+    /// progress bar.
+    pub fn set_progress_handler(&mut self, handler: impl FnMut(f64, &str) + 'static) {
+        self.progress_handler = Some(ProgressHandler(Box::new(handler)));
+    }
+
+    /// Registers the host handler of the foreign function invocations, e.g.
+    /// to audit or trace the effects of a script.
+    pub fn set_telemetry_handler(&mut self, handler: impl FnMut(&OpEvent) + 'static) {
+        self.telemetry_handler = Some(TelemetryHandler(Box::new(handler)));
+    }
+
+    /// Reports a foreign function invocation to the telemetry handler.
+    pub fn report_op(&mut self, event: &OpEvent) {
+        if let Some(handler) = &mut self.telemetry_handler {
+            (handler.0)(event);
+        }
+    }
+
+    /// Appends a module search path, searched after the existing paths.
+    pub fn add_module_path(&mut self, path: impl Into<PathBuf>) {
+        self.module_paths.push(path.into());
+    }
+
+    /// Checks if the evaluation deadline has passed.
+    pub fn check_deadline(&self) -> Result<(), Error> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Error::TimedOut),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks if the evaluation is cancelled.
+    pub fn check_cancelled(&self) -> Result<(), Error> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(Error::Interrupted),
+            _ => Ok(()),
+        }
+    }
+}
+
+// #Insight
+// A worker thread (e.g. an agent, a `pmap` worker) evaluates in its own Env,
+// the Context cannot be shared between threads. The worker Env inherits the
+// policy of the spawning Context, a sandboxed script cannot escape the
+// sandbox through a worker:
+//
+// - the language features, the capabilities and the module search paths.
+// - the limits, the deadline and the cancellation. The fuel is counted per
+//   worker.
+// - the audit mode, the effects of a worker are skipped. The actions of a
+//   worker are not reported.
+// - the output streams, the effectful functions are not captured, they are
+//   applied in the spawning thread, see `CapturedFunc`. The output of a worker
+//   to a redirected stream is discarded.
+
+// #TODO report the audited actions of the workers.
+// #TODO forward the output of the workers to the redirected streams.
+
+/// The policy of the worker threads spawned by an evaluation, inherited from
+/// the spawning Context. Unlike the Context, the policy is sent to the
+/// worker thread.
+#[derive(Debug, Clone)]
+pub struct WorkerPolicy {
+    features: Features,
+    capabilities: Capabilities,
+    module_paths: Vec<PathBuf>,
+    limits: Limits,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+    is_audited: bool,
+    is_stdout_redirected: bool,
+    is_stderr_redirected: bool,
+}
+
+impl WorkerPolicy {
+    /// Returns the Env of a worker thread, configured with the policy.
+    pub fn env(&self) -> Env {
+        let mut env = Env::prelude();
+
+        let context = &mut env.context;
+        context.features = self.features.clone();
+        context.capabilities = self.capabilities;
+        context.module_paths = self.module_paths.clone();
+        context.limits = self.limits.clone();
+        context.deadline = self.deadline;
+        context.cancellation = self.cancellation.clone();
+
+        if self.is_audited {
+            context.audit = Some(AuditReport::new());
+        }
+
+        if self.is_stdout_redirected {
+            context.stdout = Output::new(io::sink());
+        }
+
+        if self.is_stderr_redirected {
+            context.stderr = Output::new(io::sink());
+        }
+
+        env
+    }
+}
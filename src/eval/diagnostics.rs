@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::{
+    ann::Ann,
+    error::{format_error_pretty, Error},
+    expr::Expr,
+    range::{Position, Range, Ranged},
+    util::truncate_chars,
+};
+
+use super::env::Scope;
+
+// #Insight
+// The diagnostics are only collected if enabled, see
+// `api::eval_string_with_diagnostics`. The call frames are tracked during the
+// evaluation, the bindings are captured at the innermost failing invocation,
+// before its scope is dropped. A caught error discards the captured state.
+
+// #TODO include the arguments of the call frames.
+// #TODO support capturing the diagnostics of the lex/parse stages.
+
+/// The maximum number of bindings in the diagnostics.
+const MAX_BINDINGS: usize = 20;
+
+/// The maximum length (in chars) of the value repr of a binding.
+const MAX_VALUE_LEN: usize = 60;
+
+/// A frame of the call stack, an invocation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    /// The name of the invoked function, or the source of the function
+    /// expression, e.g. for anonymous functions.
+    pub name: String,
+    /// The position of the invocation in the source.
+    pub position: Position,
+}
+
+/// The diagnostic bundle of a failed evaluation, useful to log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    /// The error message with the source excerpt of the failing expression.
+    pub excerpt: String,
+    /// The call stack at the failure, innermost first.
+    pub call_stack: Vec<CallFrame>,
+    /// The bindings of the nearest scope of the failure, the names and the
+    /// (truncated) reprs of the values, sorted by name. The foreign functions
+    /// (e.g. the prelude) are omitted.
+    pub bindings: Vec<(String, String)>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.excerpt)?;
+
+        if !self.call_stack.is_empty() {
+            write!(f, "\ncall stack:")?;
+            for (i, frame) in self.call_stack.iter().enumerate() {
+                write!(f, "\n  {i}: {} at {}", frame.name, frame.position)?;
+            }
+        }
+
+        if !self.bindings.is_empty() {
+            write!(f, "\nbindings:")?;
+            for (name, value) in &self.bindings {
+                write!(f, "\n  {name} = {value}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The evaluation state captured at the failure.
+#[derive(Debug, Default)]
+struct Snapshot {
+    frames: Vec<(String, Range)>,
+    bindings: Vec<(String, String)>,
+}
+
+/// Tracks the evaluation state for the diagnostics.
+#[derive(Debug, Default)]
+pub struct DiagnosticsState {
+    frames: Vec<(String, Range)>,
+    captured: Option<Snapshot>,
+}
+
+impl DiagnosticsState {
+    /// Enters an invocation, the head is the (unevaluated) function
+    /// expression.
+    pub fn push_frame(&mut self, head: &Ann<Expr>, range: Range) {
+        let name = match head.as_ref() {
+            Expr::Symbol(sym) => sym.clone(),
+            _ => truncate_chars(head.to_string(), MAX_VALUE_LEN),
+        };
+
+        self.frames.push((name, range));
+    }
+
+    pub fn pop_frame(&mut self) {
+        self.frames.pop();
+    }
+
+    /// Captures the call stack and the bindings of the scope, unless already
+    /// captured by an inner invocation. The control-flow signals are ignored.
+    pub fn capture(&mut self, error: &Ranged<Error>, scope: &Scope) {
+        if self.captured.is_some() || matches!(error.0, Error::Flow(..)) {
+            return;
+        }
+
+        let mut bindings: Vec<(String, String)> = scope
+            .iter()
+            .filter(|(name, value)| {
+                *name != "self" && !matches!(value.as_ref(), Expr::ForeignFunc(..))
+            })
+            .map(|(name, value)| {
+                (
+                    name.clone(),
+                    truncate_chars(value.to_string(), MAX_VALUE_LEN),
+                )
+            })
+            .collect();
+
+        bindings.sort();
+        bindings.truncate(MAX_BINDINGS);
+
+        self.captured = Some(Snapshot {
+            frames: self.frames.clone(),
+            bindings,
+        });
+    }
+
+    /// Discards the captured state, e.g. when the error is caught.
+    pub fn discard(&mut self) {
+        self.captured = None;
+    }
+
+    /// Returns the diagnostics of the error, the positions are computed from
+    /// the `input` source.
+    pub fn to_diagnostics(&self, input: &str, error: &Ranged<Error>) -> Diagnostics {
+        let snapshot = self.captured.as_ref();
+
+        let call_stack = snapshot
+            .map(|snapshot| {
+                snapshot
+                    .frames
+                    .iter()
+                    .rev()
+                    .map(|(name, range)| CallFrame {
+                        name: name.clone(),
+                        position: Position::from(range.start, input),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        // The errors of the foreign functions may miss the range, the range of
+        // the innermost invocation is used instead (only the message of the
+        // error is rendered).
+        let excerpt = match snapshot.and_then(|snapshot| snapshot.frames.last()) {
+            Some((_, range)) if error.1.is_empty() => {
+                let error = Error::invalid_arguments(error.0.to_string());
+                format_error_pretty(input, &Ranged(error, range.clone()))
+            }
+            _ => format_error_pretty(input, error),
+        };
+
+        Diagnostics {
+            excerpt,
+            call_stack,
+            bindings: snapshot
+                .map(|snapshot| snapshot.bindings.clone())
+                .unwrap_or_default(),
+        }
+    }
+}
@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::{ann::Ann, expr::Expr};
+use crate::{ann::Ann, error::Error, expr::Expr, range::Ranged};
 
-use super::prelude::setup_prelude;
+use super::{
+    capabilities::Capabilities,
+    context::Context,
+    prelude::{setup_prelude, setup_prelude_with, PreludeOptions},
+};
 
 // #TODO separate global_scope.
 // #TODO global <> local scope.
@@ -12,6 +16,9 @@ use super::prelude::setup_prelude;
 // #TODO find another name than `Scope`?
 pub type Scope = HashMap<String, Ann<Expr>>;
 
+/// A scope that can be shared between the environment and closures.
+pub type ScopeRef = Rc<RefCell<Scope>>;
+
 // #TODO support global scope + lexical/static scope + dynamic scope.
 
 // #Insight
@@ -21,6 +28,15 @@ pub type Scope = HashMap<String, Ann<Expr>>;
 
 // ~~Scope is static, Environment is dynamic~~ <-- nah (static/dynamic scoping)
 
+// #Insight
+// The scopes are shared (reference-counted) to support lexical scoping. A closure
+// captures the stack of scopes at the definition site. As the scopes are shared,
+// bindings added after the definition (e.g. the binding of a recursive function)
+// are visible to the closure.
+
+// #TODO closures stored in a scope they capture create reference cycles (leaks),
+// only the scopes of a `Runtime` are cleared when it is dropped.
+
 /// An evaluation environment.
 ///
 /// An environment is a stack of scopes.
@@ -29,8 +45,9 @@ pub type Scope = HashMap<String, Ann<Expr>>;
 #[derive(Debug)]
 pub struct Env {
     pub global: Scope,
-    pub local: Vec<Scope>,
-    // #TODO maybe even keep the inner local scope as field?
+    pub local: Vec<ScopeRef>,
+    /// The runtime configuration and state of the evaluation.
+    pub context: Context,
 }
 
 impl Default for Env {
@@ -43,7 +60,8 @@ impl Env {
     pub fn new() -> Self {
         Self {
             global: Scope::default(),
-            local: vec![Scope::default()],
+            local: vec![ScopeRef::default()],
+            context: Context::default(),
         }
     }
 
@@ -52,8 +70,23 @@ impl Env {
         setup_prelude(Env::default())
     }
 
+    /// Returns an Env with the prelude, in a sandbox, the file system,
+    /// process, network and environment ops are denied, see `Capabilities`.
+    pub fn prelude_sandboxed() -> Self {
+        let mut env = Env::prelude();
+        env.context.capabilities = Capabilities::none();
+        // The search paths are read from the `TAN_PATH` environment variable.
+        env.context.module_paths.clear();
+        env
+    }
+
+    /// Returns an Env with the prelude, configured with the options.
+    pub fn prelude_with(options: &PreludeOptions) -> Self {
+        setup_prelude_with(Env::default(), options)
+    }
+
     pub fn push(&mut self, scope: Scope) {
-        self.local.push(scope);
+        self.local.push(Rc::new(RefCell::new(scope)));
     }
 
     // #TODO maybe remove this?
@@ -61,10 +94,30 @@ impl Env {
         self.push(Scope::default());
     }
 
-    pub fn pop(&mut self) -> Option<Scope> {
+    pub fn pop(&mut self) -> Option<ScopeRef> {
         self.local.pop()
     }
 
+    /// Captures the current stack of scopes, used to create closures.
+    pub fn capture(&self) -> Vec<ScopeRef> {
+        self.local.clone()
+    }
+
+    /// Replaces the current stack of scopes, returns the replaced stack.
+    pub fn replace(&mut self, scopes: Vec<ScopeRef>) -> Vec<ScopeRef> {
+        std::mem::replace(&mut self.local, scopes)
+    }
+
+    /// Captures the diagnostics of a failure in the current scope, if the
+    /// diagnostics are enabled.
+    pub fn capture_failure(&mut self, error: &Ranged<Error>) {
+        if let Some(diagnostics) = &mut self.context.diagnostics {
+            if let Some(scope) = self.local.last() {
+                diagnostics.capture(error, &scope.borrow());
+            }
+        }
+    }
+
     // #TODO better offer get/set interface?
 
     pub fn insert(
@@ -72,26 +125,114 @@ impl Env {
         name: impl Into<String>,
         value: impl Into<Ann<Expr>>,
     ) -> Option<Ann<Expr>> {
+        if let Some(stats) = &mut self.context.stats {
+            stats.allocations_estimate += 1;
+        }
+
         let last = self.local.len() - 1;
-        let scope = &mut self.local[last];
+        let mut scope = self.local[last].borrow_mut();
         scope.insert(name.into(), value.into())
     }
 
     // #TODO extract the stack walking?
 
-    pub fn get(&self, name: &str) -> Option<&Ann<Expr>> {
+    pub fn get(&self, name: &str) -> Option<Ann<Expr>> {
         let nesting = self.local.len();
 
         // #TODO optimize here!
+        // #TODO hm, can we somehow work with references?
 
         for i in (0..nesting).rev() {
-            let scope = &self.local[i];
+            let scope = self.local[i].borrow();
             if let Some(binding) = scope.get(name) {
+                return Some(binding.clone());
+            }
+        }
+
+        if let Some(binding) = self.global.get(name) {
+            return Some(binding.clone());
+        }
+
+        self.get_qualified(name)
+    }
+
+    /// Returns the names of the methods (the type specializations) of the
+    /// function bound to the name, e.g. `+$$Int$$Int`, sorted.
+    pub fn methods(&self, name: &str) -> Vec<String> {
+        let prefix = format!("{name}$$");
+
+        let mut methods: Vec<String> = self
+            .local
+            .iter()
+            .flat_map(|scope| {
+                scope
+                    .borrow()
+                    .keys()
+                    .filter(|key| key.starts_with(&prefix))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .chain(
+                self.global
+                    .keys()
+                    .filter(|key| key.starts_with(&prefix))
+                    .cloned(),
+            )
+            .collect();
+
+        methods.sort();
+        methods.dedup();
+
+        methods
+    }
+
+    /// Looks up a qualified name, e.g. `math/sin`, in the exports of a module.
+    /// The module is the innermost binding of a Module value, the other values
+    /// do not shadow the modules, e.g. `(let str "x") (str/len str)`. The
+    /// prelude modules are bound in the global scope.
+    fn get_qualified(&self, name: &str) -> Option<Ann<Expr>> {
+        let (module, name) = name.split_once('/')?;
+
+        let as_module = |binding: Option<&Ann<Expr>>| match binding {
+            Some(Ann(Expr::Module(module), ..)) => Some(module.clone()),
+            _ => None,
+        };
+
+        let module = self
+            .local
+            .iter()
+            .rev()
+            .find_map(|scope| as_module(scope.borrow().get(module)))
+            .or_else(|| as_module(self.global.get(module)))?;
+
+        module.exports.get(name).cloned()
+    }
+
+    /// Updates, in-place, an existing binding with the function `f`, walks the
+    /// environment. Returns None if the binding is not found.
+    pub fn update_with<R>(&mut self, name: &str, f: impl FnOnce(&mut Ann<Expr>) -> R) -> Option<R> {
+        let nesting = self.local.len();
+
+        for i in (0..nesting).rev() {
+            let mut scope = self.local[i].borrow_mut();
+            if let Some(binding) = scope.get_mut(name) {
+                return Some(f(binding));
+            }
+        }
+
+        None
+    }
+
+    /// Removes a binding from the innermost scope that contains it, walks the
+    /// environment.
+    pub fn remove(&mut self, name: &str) -> Option<Ann<Expr>> {
+        for scope in self.local.iter().rev() {
+            if let Some(binding) = scope.borrow_mut().remove(name) {
                 return Some(binding);
             }
         }
 
-        self.global.get(name)
+        None
     }
 
     /// Updates an existing binding, walks the environment.
@@ -102,7 +243,7 @@ impl Env {
         // #TODO what to return?
 
         for i in (0..nesting).rev() {
-            let scope = &mut self.local[i];
+            let mut scope = self.local[i].borrow_mut();
             if let Some(binding) = scope.get_mut(name) {
                 *binding = value.into();
                 break;
@@ -0,0 +1,133 @@
+use std::{collections::BTreeSet, fmt};
+
+// #Insight
+// The language features gate the special forms (and their syntax) that are
+// not yet stable, so an embedder can pin the language that the scripts are
+// allowed to use, e.g.
+//
+// let runtime = Runtime::new().with_language_version(LanguageVersion::V1);
+// let runtime = Runtime::new().with_features(Features::default().without(Feature::Macros));
+//
+// The features are checked statically, in the macro_expand pass, a script
+// that uses a disabled form fails before any evaluation. By default, all the
+// features are enabled.
+
+// #TODO support gating the prelude functions.
+// #TODO support enabling the features from the script, e.g. `(use-feature :match)`.
+
+/// An optional language feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// The `match` special form.
+    Match,
+    /// The `qquot`/`unquot` special forms, e.g. `` `(a $b) ``.
+    QuasiQuote,
+    /// The `Macro` definitions.
+    Macros,
+    /// The `use`/`export` special forms.
+    Modules,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [
+        Feature::Match,
+        Feature::QuasiQuote,
+        Feature::Macros,
+        Feature::Modules,
+    ];
+
+    /// Returns the name of the feature flag.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Match => "match",
+            Feature::QuasiQuote => "quasi-quote",
+            Feature::Macros => "macros",
+            Feature::Modules => "modules",
+        }
+    }
+
+    /// Returns the feature that gates the special form, if any.
+    pub fn of_form(sym: &str) -> Option<Feature> {
+        match sym {
+            "match" => Some(Feature::Match),
+            "qquot" | "unquot" => Some(Feature::QuasiQuote),
+            "Macro" => Some(Feature::Macros),
+            "use" | "export" => Some(Feature::Modules),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A version of the language, a stable set of features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LanguageVersion {
+    /// The core language, with modules.
+    V1,
+    /// Adds the `match` special form, the quasi-quotes and the macros.
+    V2,
+}
+
+impl LanguageVersion {
+    pub const LATEST: LanguageVersion = LanguageVersion::V2;
+
+    /// Returns the features of the version.
+    pub fn features(&self) -> Features {
+        match self {
+            LanguageVersion::V1 => Features::none().with(Feature::Modules),
+            LanguageVersion::V2 => Features::all(),
+        }
+    }
+}
+
+/// The set of the enabled language features.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Features {
+    enabled: BTreeSet<Feature>,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        LanguageVersion::LATEST.features()
+    }
+}
+
+impl Features {
+    /// Returns a set with all the features enabled.
+    pub fn all() -> Self {
+        Self {
+            enabled: Feature::ALL.into_iter().collect(),
+        }
+    }
+
+    /// Returns a set with no features enabled.
+    pub fn none() -> Self {
+        Self {
+            enabled: BTreeSet::new(),
+        }
+    }
+
+    pub fn with(mut self, feature: Feature) -> Self {
+        self.enabled.insert(feature);
+        self
+    }
+
+    pub fn without(mut self, feature: Feature) -> Self {
+        self.enabled.remove(&feature);
+        self
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+    }
+
+    /// Returns the disabled feature that gates the special form, if any.
+    pub fn disabled_form(&self, sym: &str) -> Option<Feature> {
+        Feature::of_form(sym).filter(|feature| !self.is_enabled(*feature))
+    }
+}
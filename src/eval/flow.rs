@@ -0,0 +1,19 @@
+use crate::{ann::Ann, expr::Expr};
+
+// #Insight
+// The control-flow signals are propagated through the evaluation results, like
+// errors, and are intercepted by the enclosing loop. A signal that escapes the
+// loops is reported as an error.
+//
+// The signals do not cross the function boundaries, a signal that escapes a
+// function body is converted to `Error::EscapedFlow`, i.e. a `break` in a
+// function does not exit a loop of the caller.
+
+/// A non-local control-flow signal.
+#[derive(Debug)]
+pub enum Flow {
+    /// Exits the enclosing loop, with a value.
+    Break(Ann<Expr>),
+    /// Skips to the next iteration of the enclosing loop.
+    Continue,
+}
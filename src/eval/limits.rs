@@ -0,0 +1,65 @@
+use std::{fmt, time::Duration};
+
+// #Insight
+// The limits protect the host from runaway (e.g. untrusted) scripts:
+//
+// - the call depth, the maximum nesting of function invocations, e.g. an
+//   unbounded recursion fails instead of overflowing the stack of the host.
+// - the fuel, the maximum number of evaluation steps (the evaluated lists, the
+//   loop iterations and the function calls), e.g. an infinite loop fails
+//   deterministically, independent of the host speed.
+// - the timeout, the wall-clock duration of the evaluation, enforced with the
+//   deadline of the Context, see `Error::TimedOut`.
+//
+// An exceeded limit aborts the evaluation with `Error::LimitExceeded`, like
+// the time-outs it is not catchable.
+
+// #TODO support a memory limit, e.g. based on the allocations estimate.
+
+/// A limit of the evaluation, with its configured maximum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    CallDepth(usize),
+    Fuel(u64),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::CallDepth(max) => write!(f, "the call depth limit ({max})"),
+            Limit::Fuel(max) => write!(f, "the fuel limit ({max} steps)"),
+        }
+    }
+}
+
+/// The configurable limits of an evaluation, unlimited by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    /// The maximum nesting of function invocations.
+    pub max_call_depth: Option<usize>,
+    /// The maximum number of evaluation steps.
+    pub fuel: Option<u64>,
+    /// The maximum wall-clock duration of the evaluation.
+    pub timeout: Option<Duration>,
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_call_depth(mut self, max_call_depth: usize) -> Self {
+        self.max_call_depth = Some(max_call_depth);
+        self
+    }
+
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    ann::Ann,
+    api::{parse_string_all, resolve_exprs},
+    error::{Error, PipelineError},
+    expr::Expr,
+    resolver::TypeEnv,
+};
+
+use super::{capabilities::Capability, env::Env, eval, module_cache::cache_key};
+
+// #Insight
+// A module is a directory of Tan files, or a single Tan file, evaluated in its
+// own Env. The files of a directory are evaluated in a deterministic order.
+// A module exports the names listed in its `(export ...)` declarations, or all
+// its definitions if there are no declarations.
+//
+// The exported functions keep the scopes of the module, they can refer to the
+// private definitions of the module.
+//
+// A module name is resolved to a path:
+//
+// - absolute names, e.g. `/opt/tan/math`, are used as-is.
+// - names starting with `./` or `../` are relative to the directory of the
+//   using module, or the current directory at the top level.
+// - any other name, e.g. `lib/math`, is searched in the directory of the using
+//   module, the current directory, and the search paths, in order. The
+//   search paths are initialized from the `TAN_PATH` environment variable.
+//
+// A module is loaded once, repeated `use` expressions share the loaded module.
+
+// #TODO support nested modules bound as `lib/math`, now bound as `math`.
+// #TODO report the errors of a module with the range in the module file.
+
+/// The environment variable with the module search paths, separated like
+/// `PATH`.
+pub const TAN_PATH: &str = "TAN_PATH";
+
+/// A loaded module.
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub path: PathBuf,
+    pub exports: BTreeMap<String, Ann<Expr>>,
+}
+
+/// The loaded modules by canonical path, shared by the Env of a program and
+/// the Envs of its modules.
+pub type ModuleCache = Rc<RefCell<HashMap<PathBuf, Rc<Module>>>>;
+
+/// Returns the module search paths of the `TAN_PATH` environment variable.
+pub fn default_module_paths() -> Vec<PathBuf> {
+    std::env::var_os(TAN_PATH)
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn failed_use(path: &Path, message: impl AsRef<str>) -> Error {
+    Error::FailedUse(format!(
+        "failed to use module `{}`: {}",
+        path.display(),
+        message.as_ref()
+    ))
+}
+
+/// Returns the names of an `(export ...)` declaration, or None if the
+/// expression is not a declaration.
+fn export_names(expr: &Ann<Expr>) -> Option<Result<Vec<String>, String>> {
+    let Ann(Expr::List(terms), ..) = expr else {
+        return None;
+    };
+
+    let Some((Ann(Expr::Symbol(head), ..), names)) = terms.split_first() else {
+        return None;
+    };
+
+    if head != "export" {
+        return None;
+    }
+
+    let names = names
+        .iter()
+        .map(|name| match name {
+            Ann(Expr::Symbol(name), ..) => Ok(name.clone()),
+            _ => Err(format!("`{name}` is not a valid export name")),
+        })
+        .collect();
+
+    Some(names)
+}
+
+/// Returns the Tan files of a module, the module path is a directory or a
+/// file, the `.tan` extension is optional.
+fn module_files(path: &Path) -> Result<(PathBuf, Vec<PathBuf>), Error> {
+    if path.is_dir() {
+        let mut file_paths = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // The files are loaded in a deterministic order,
+        // read_dir does not guarantee any order.
+        file_paths.sort();
+        file_paths.retain(|path| path.extension().is_some_and(|ext| ext == "tan"));
+
+        return Ok((path.to_owned(), file_paths));
+    }
+
+    let file_path = if path.extension().is_some_and(|ext| ext == "tan") {
+        path.to_owned()
+    } else {
+        path.with_extension("tan")
+    };
+
+    if file_path.is_file() {
+        Ok((file_path.clone(), vec![file_path]))
+    } else {
+        Err(failed_use(path, "not found"))
+    }
+}
+
+/// Normalizes a module name to a relative or absolute path, e.g.
+/// `lib//math/` to `lib/math`. Empty names are rejected.
+pub fn normalize_module_name(name: &str) -> Result<PathBuf, Error> {
+    let path: PathBuf = Path::new(name.trim())
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect();
+
+    if path.file_name().is_none() {
+        return Err(Error::FailedUse(format!("invalid module name `{name}`")));
+    }
+
+    Ok(path)
+}
+
+/// Returns the directory of the module being loaded, if any.
+fn current_module_dir(env: &Env) -> Option<PathBuf> {
+    let path = env.context.module_stack.last()?;
+
+    if path.is_dir() {
+        Some(path.clone())
+    } else {
+        path.parent().map(Path::to_owned)
+    }
+}
+
+/// Returns true if the path refers to a module, a directory or a Tan file.
+fn is_module_path(path: &Path) -> bool {
+    path.is_dir() || path.is_file() || path.with_extension("tan").is_file()
+}
+
+/// Resolves a module name to the path of the module, see the rules above.
+pub fn resolve_module_path(name: &str, env: &Env) -> Result<PathBuf, Error> {
+    let path = normalize_module_name(name)?;
+
+    if path.is_absolute() {
+        return Ok(path);
+    }
+
+    let module_dir = current_module_dir(env);
+
+    let is_explicitly_relative = name.trim().starts_with("./") || name.trim().starts_with("../");
+
+    if is_explicitly_relative {
+        return Ok(match module_dir {
+            Some(dir) => dir.join(path),
+            None => path,
+        });
+    }
+
+    let mut roots = Vec::new();
+    roots.extend(module_dir);
+    roots.push(PathBuf::new());
+    roots.extend(env.context.module_paths.iter().cloned());
+
+    roots
+        .into_iter()
+        .map(|root| root.join(&path))
+        .find(|path| is_module_path(path))
+        .ok_or_else(|| failed_use(&path, "not found in the module search paths"))
+}
+
+/// Resolves and loads a module, the loaded modules are cached, see
+/// `ModuleCache`.
+pub fn use_module(name: &str, env: &Env) -> Result<Rc<Module>, Error> {
+    env.context.require(Capability::Fs, "use")?;
+
+    let path = resolve_module_path(name, env)?;
+
+    let (module_path, _) = module_files(&path)?;
+    let module_path = fs::canonicalize(module_path)?;
+
+    if let Some(module) = env.context.modules.borrow().get(&module_path) {
+        return Ok(module.clone());
+    }
+
+    let module = Rc::new(load_module(&path, env)?);
+
+    env.context
+        .modules
+        .borrow_mut()
+        .insert(module_path, module.clone());
+
+    Ok(module)
+}
+
+/// Reports the first error of a module file.
+fn module_file_error(path: &Path, file_path: &Path, errors: &PipelineError) -> Error {
+    failed_use(path, format!("{}: {}", file_path.display(), errors[0]))
+}
+
+/// The resolved expressions of a module file.
+type ResolvedFile = (PathBuf, Vec<Ann<Expr>>);
+
+/// Returns the resolved expressions of the module files, from the module
+/// cache if all the files hit, see `module_cache`.
+fn resolve_module_files(
+    path: &Path,
+    file_paths: Vec<PathBuf>,
+    module_env: &mut Env,
+) -> Result<Vec<ResolvedFile>, Error> {
+    let mut inputs = Vec::new();
+    let mut previous_key = None;
+
+    for file_path in file_paths {
+        let input = fs::read_to_string(&file_path)?;
+        let key = cache_key(previous_key, &input, &module_env.context.features);
+        previous_key = Some(key);
+        inputs.push((file_path, input, key));
+    }
+
+    let cache = module_env.context.ast_cache.clone();
+
+    if let Some(cache) = &cache {
+        let cached: Option<Vec<_>> = inputs
+            .iter()
+            .map(|(file_path, _, key)| {
+                cache
+                    .read(file_path, *key)
+                    .map(|exprs| (file_path.clone(), exprs))
+            })
+            .collect();
+
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+    }
+
+    let mut types = TypeEnv::new();
+    let mut resolved = Vec::new();
+
+    for (file_path, input, _) in &inputs {
+        let exprs = parse_string_all(input)
+            .and_then(|exprs| resolve_exprs(exprs, module_env, &mut types))
+            .map_err(|errors| module_file_error(path, file_path, &errors))?;
+
+        resolved.push((file_path.clone(), exprs));
+    }
+
+    // The unwrap is safe, the module scope is pushed by the caller.
+    let defines_macros = module_env
+        .local
+        .last()
+        .unwrap()
+        .borrow()
+        .values()
+        .any(|value| matches!(value.as_ref(), Expr::Macro(..)));
+
+    if let Some(cache) = cache.filter(|_| !defines_macros) {
+        for ((file_path, exprs), (_, _, key)) in resolved.iter().zip(&inputs) {
+            cache.write(file_path, *key, exprs);
+        }
+    }
+
+    Ok(resolved)
+}
+
+/// Loads the module at the path, in a new Env. Circular dependencies between
+/// modules are reported as errors.
+pub fn load_module(path: &Path, env: &Env) -> Result<Module, Error> {
+    let (module_path, file_paths) = module_files(path)?;
+
+    let module_path = fs::canonicalize(module_path)?;
+
+    if let Some(i) = env
+        .context
+        .module_stack
+        .iter()
+        .position(|p| *p == module_path)
+    {
+        let cycle = env.context.module_stack[i..]
+            .iter()
+            .chain([&module_path])
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+        return Err(failed_use(path, format!("circular dependency {cycle}")));
+    }
+
+    let name = module_path
+        .file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut module_env = Env::prelude();
+    module_env.context = env.context.for_module(module_path.clone());
+
+    // The definitions of the module are kept in a separate scope, on top of
+    // the prelude.
+    module_env.push_new_scope();
+
+    let mut export_declarations: Option<Vec<String>> = None;
+
+    for (file_path, exprs) in resolve_module_files(path, file_paths, &mut module_env)? {
+        for expr in exprs {
+            if let Some(names) = export_names(&expr) {
+                let names = names.map_err(|error| failed_use(path, error))?;
+                export_declarations
+                    .get_or_insert_with(Vec::new)
+                    .extend(names);
+                continue;
+            }
+
+            eval(&expr, &mut module_env)
+                .map_err(|error| failed_use(path, format!("{}: {}", file_path.display(), error)))?;
+        }
+    }
+
+    // The unwrap is safe, the module scope is pushed above.
+    let scope = module_env.local.last().unwrap().borrow();
+
+    let exports = match export_declarations {
+        None => scope
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect(),
+        Some(names) => names
+            .into_iter()
+            .map(|name| match scope.get(&name) {
+                Some(value) => Ok((name, value.clone())),
+                None => Err(failed_use(
+                    path,
+                    format!("the exported `{name}` is not defined"),
+                )),
+            })
+            .collect::<Result<_, _>>()?,
+    };
+
+    Ok(Module {
+        name,
+        path: module_path,
+        exports,
+    })
+}
@@ -0,0 +1,164 @@
+use std::{cell::RefCell, collections::HashMap, fmt, path::Path, rc::Rc};
+
+#[cfg(feature = "cache")]
+use std::{fs, path::PathBuf};
+
+#[cfg(feature = "cache")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "cache")]
+use crate::expr::portable::Portable;
+use crate::{ann::Ann, expr::Expr};
+
+use super::features::Features;
+
+// #Insight
+// The resolved expressions of the module files (parsed, macro-expanded and
+// typechecked) are cached, keyed by the hash of the contents of the files. On
+// a hit, the module is evaluated without the lex/parse/expand/resolve passes,
+// large multi-module programs start quickly on repeat runs.
+//
+// The key of a file also covers the preceding files of the module (the
+// resolution of a file depends on the definitions of the previous files), the
+// language features and the version of the cache. A module is resolved as a
+// whole, it is only read from the cache if all its files hit.
+//
+// The modules that define macros are not cached, the macro definitions are
+// pruned from the expanded expressions.
+//
+// The cache is host-pluggable, see `AstCache`. With the `cache` feature, the
+// default cache is a `.tanc` sidecar file next to the module file, e.g.
+// `math.tanc` next to `math.tan`.
+//
+// The cache is an optimization, failures to read or write the cache are
+// ignored.
+
+// #TODO the key does not cover the used modules, their types may affect the resolution.
+// #TODO use a compact binary encoding.
+
+/// The version of the cache format, bumped on incompatible changes.
+const CACHE_VERSION: u32 = 2;
+
+/// A cache of the resolved expressions of the module files.
+pub trait AstCache: fmt::Debug {
+    /// Returns the cached expressions of the module file, if the key matches.
+    fn read(&self, file_path: &Path, key: u64) -> Option<Vec<Ann<Expr>>>;
+
+    /// Caches the resolved expressions of the module file.
+    fn write(&self, file_path: &Path, key: u64, exprs: &[Ann<Expr>]);
+}
+
+/// Returns the cache key of the contents of a module file, chained to the key
+/// of the previous file of the module, if any.
+pub fn cache_key(previous_key: Option<u64>, input: &str, features: &Features) -> u64 {
+    // #Insight
+    // FNV-1a is used instead of the std hasher, the keys should be stable
+    // across builds.
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    write(&CACHE_VERSION.to_le_bytes());
+    write(env!("CARGO_PKG_VERSION").as_bytes());
+    write(&previous_key.unwrap_or_default().to_le_bytes());
+    write(format!("{features:?}").as_bytes());
+    write(input.as_bytes());
+
+    hash
+}
+
+/// An in-memory cache, shared between the Envs of a host, e.g. to reuse the
+/// resolved modules across the evaluations of a server.
+#[derive(Debug, Default)]
+pub struct MemoryAstCache {
+    entries: RefCell<HashMap<u64, Vec<Ann<Expr>>>>,
+}
+
+impl MemoryAstCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+}
+
+impl AstCache for MemoryAstCache {
+    fn read(&self, _file_path: &Path, key: u64) -> Option<Vec<Ann<Expr>>> {
+        self.entries.borrow().get(&key).cloned()
+    }
+
+    fn write(&self, _file_path: &Path, key: u64, exprs: &[Ann<Expr>]) {
+        self.entries.borrow_mut().insert(key, exprs.to_vec());
+    }
+}
+
+#[cfg(feature = "cache")]
+#[derive(Serialize, Deserialize)]
+struct CachedModule {
+    version: u32,
+    key: u64,
+    exprs: Vec<Portable>,
+}
+
+/// Returns the path of the cache file of a module file.
+#[cfg(feature = "cache")]
+pub fn cache_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("tanc")
+}
+
+/// The `.tanc` sidecar files, next to the module files.
+#[cfg(feature = "cache")]
+#[derive(Debug, Default)]
+pub struct FileAstCache;
+
+#[cfg(feature = "cache")]
+impl AstCache for FileAstCache {
+    fn read(&self, file_path: &Path, key: u64) -> Option<Vec<Ann<Expr>>> {
+        let contents = fs::read_to_string(cache_path(file_path)).ok()?;
+        let cached: CachedModule = serde_json::from_str(&contents).ok()?;
+
+        if cached.version != CACHE_VERSION || cached.key != key {
+            return None;
+        }
+
+        Some(cached.exprs.iter().map(|expr| expr.to_ann(&[])).collect())
+    }
+
+    fn write(&self, file_path: &Path, key: u64, exprs: &[Ann<Expr>]) {
+        // The expressions with host values are not cached.
+        let Some(exprs) = exprs.iter().map(Portable::from_ann).collect() else {
+            return;
+        };
+
+        let cached = CachedModule {
+            version: CACHE_VERSION,
+            key,
+            exprs,
+        };
+
+        if let Ok(contents) = serde_json::to_string(&cached) {
+            let _ = fs::write(cache_path(file_path), contents);
+        }
+    }
+}
+
+/// Returns the default cache, the sidecar files if the `cache` feature is
+/// enabled.
+pub fn default_ast_cache() -> Option<Rc<dyn AstCache>> {
+    #[cfg(feature = "cache")]
+    return Some(Rc::new(FileAstCache));
+
+    #[cfg(not(feature = "cache"))]
+    None
+}
@@ -0,0 +1,110 @@
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, IsTerminal, Write},
+    rc::Rc,
+};
+
+// #Insight
+// The output ops (e.g. `write`, `writeln`) write to the output streams of the
+// Context, instead of the process stdout/stderr, so a host can capture or
+// redirect the output of a script, e.g.
+//
+// let buffer = SharedBuffer::default();
+// env.context.stdout = Output::new(buffer.clone());
+// eval_string(r#"(writeln "hello")"#, &mut env)?;
+// assert_eq!(buffer.contents(), "hello\n");
+//
+// The streams are shared, the modules of a program write to the streams of
+// the program.
+
+/// An output stream of the evaluation, e.g. the stdout.
+#[derive(Clone)]
+pub struct Output {
+    writer: Rc<RefCell<dyn Write>>,
+    is_redirected: bool,
+    is_terminal: bool,
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<output>")
+    }
+}
+
+impl Output {
+    /// Returns a stream redirected to the writer.
+    pub fn new(writer: impl Write + 'static) -> Self {
+        Self {
+            writer: Rc::new(RefCell::new(writer)),
+            is_redirected: true,
+            is_terminal: false,
+        }
+    }
+
+    /// Returns the stdout of the process.
+    pub fn stdout() -> Self {
+        Self {
+            is_redirected: false,
+            is_terminal: io::stdout().is_terminal(),
+            ..Self::new(io::stdout())
+        }
+    }
+
+    /// Returns the stderr of the process.
+    pub fn stderr() -> Self {
+        Self {
+            is_redirected: false,
+            is_terminal: io::stderr().is_terminal(),
+            ..Self::new(io::stderr())
+        }
+    }
+
+    /// Returns true if the stream is redirected by the host, i.e. it is not
+    /// a stream of the process.
+    pub fn is_redirected(&self) -> bool {
+        self.is_redirected
+    }
+
+    /// Returns true if the stream is a terminal, a redirected stream is never
+    /// a terminal.
+    pub fn is_terminal(&self) -> bool {
+        self.is_terminal
+    }
+
+    /// Writes the text to the stream.
+    pub fn write_str(&self, text: &str) -> io::Result<()> {
+        self.writer.borrow_mut().write_all(text.as_bytes())
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.borrow_mut().flush()
+    }
+}
+
+/// An in-memory stream, shared between its clones, e.g. to capture the output
+/// of a script.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Returns the written text, the invalid UTF-8 sequences are replaced.
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
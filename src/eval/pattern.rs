@@ -0,0 +1,124 @@
+use crate::{ann::Ann, expr::Expr};
+
+// #Insight
+// Patterns are plain expressions, a symbol binds the matched value, `_` matches
+// anything without binding, literals match equal values, an Array (or List)
+// pattern matches an Array (or List) of the same length element-wise, and a
+// Dict pattern matches a Dict that contains all the pattern keys. Patterns can
+// be nested.
+
+// #TODO support rest patterns, e.g. `[head ...tail]`.
+// #TODO compile patterns to a decision tree.
+// #TODO consider moving the literal equality to Expr.
+
+/// The bindings produced by a successful match.
+pub type Bindings = Vec<(String, Ann<Expr>)>;
+
+/// Returns true if the literal expressions `a` and `b` are equal.
+fn literal_eq(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::One, Expr::One) => true,
+        (Expr::Bool(a), Expr::Bool(b)) => a == b,
+        (Expr::Int(a), Expr::Int(b)) => a == b,
+        (Expr::Float(a), Expr::Float(b)) => a == b,
+        (Expr::BigInt(a), Expr::BigInt(b)) => a == b,
+        (Expr::Decimal(a), Expr::Decimal(b)) => a == b,
+        (Expr::Char(a), Expr::Char(b)) => a == b,
+        (Expr::String(a), Expr::String(b)) => a == b,
+        (Expr::KeySymbol(a), Expr::KeySymbol(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Matches the `value` against the `pattern`, pushes the bindings of the
+/// pattern symbols. Returns false if the value does not match.
+pub fn match_pattern(pattern: &Ann<Expr>, value: &Ann<Expr>, bindings: &mut Bindings) -> bool {
+    match pattern.as_ref() {
+        Expr::Symbol(sym) if sym == "_" => true,
+        Expr::Symbol(sym) => {
+            bindings.push((sym.clone(), value.clone()));
+            true
+        }
+        Expr::Array(patterns) => {
+            let Expr::Array(values) = value.as_ref() else {
+                return false;
+            };
+
+            match_elements(patterns, values, bindings)
+        }
+        Expr::List(patterns) => {
+            let Expr::List(values) = value.as_ref() else {
+                return false;
+            };
+
+            match_elements(patterns, values, bindings)
+        }
+        Expr::Dict(patterns) => {
+            let Expr::Dict(values) = value.as_ref() else {
+                return false;
+            };
+
+            patterns.iter().all(|(key, pattern)| {
+                values
+                    .get(key)
+                    .is_some_and(|value| match_pattern(pattern, value, bindings))
+            })
+        }
+        pattern => literal_eq(pattern, value.as_ref()),
+    }
+}
+
+fn match_elements(patterns: &[Ann<Expr>], values: &[Ann<Expr>], bindings: &mut Bindings) -> bool {
+    patterns.len() == values.len()
+        && patterns
+            .iter()
+            .zip(values)
+            .all(|(pattern, value)| match_pattern(pattern, value, bindings))
+}
+
+/// Matches the `values` against the `patterns`, element-wise. Returns the
+/// bindings if all values match.
+pub fn match_patterns(patterns: &[Ann<Expr>], values: &[Ann<Expr>]) -> Option<Bindings> {
+    if patterns.len() != values.len() {
+        return None;
+    }
+
+    let mut bindings = Bindings::new();
+
+    for (pattern, value) in patterns.iter().zip(values) {
+        if !match_pattern(pattern, value, &mut bindings) {
+            return None;
+        }
+    }
+
+    Some(bindings)
+}
+
+/// Returns true if the `expr` is a valid pattern.
+pub fn is_pattern(expr: &Ann<Expr>) -> bool {
+    match expr.as_ref() {
+        Expr::Array(patterns) | Expr::List(patterns) => patterns.iter().all(is_pattern),
+        Expr::Dict(patterns) => patterns.values().all(is_pattern),
+        Expr::Symbol(..)
+        | Expr::One
+        | Expr::Bool(..)
+        | Expr::Int(..)
+        | Expr::Float(..)
+        | Expr::BigInt(..)
+        | Expr::Decimal(..)
+        | Expr::Char(..)
+        | Expr::String(..)
+        | Expr::KeySymbol(..) => true,
+        _ => false,
+    }
+}
+
+/// Returns true if the `expr` is a literal pattern, i.e. a literal value or the
+/// wildcard `_`.
+pub fn is_literal_pattern(expr: &Ann<Expr>) -> bool {
+    match expr.as_ref() {
+        Expr::Symbol(sym) => sym == "_",
+        Expr::Array(..) | Expr::List(..) | Expr::Dict(..) => false,
+        _ => is_pattern(expr),
+    }
+}
@@ -1,32 +1,343 @@
-use std::rc::Rc;
+use std::{collections::BTreeMap, path::PathBuf, rc::Rc};
 
 use crate::{
     ann::Ann,
     expr::Expr,
     ops::{
-        arithmetic::{add_float, add_int, mul, sub},
-        eq::{eq, gt, lt},
-        io::{file_read_as_string, write, writeln},
+        agent::{agent, await_agent, deref, send_update},
+        arithmetic::{
+            add, add_big_int, add_decimal, add_float, add_int, div as div_op,
+            div_big_int as div_op_big_int, div_decimal as div_op_decimal,
+            div_float as div_op_float, div_int as div_op_int, mul, mul_big_int, mul_decimal,
+            mul_float, mul_int, rem, rem_big_int, rem_decimal, rem_float, rem_int, sub,
+            sub_big_int, sub_decimal, sub_float, sub_int,
+        },
+        array::append,
+        buffer::{
+            buf_hex, buf_i16_be, buf_i16_le, buf_i32_be, buf_i32_le, buf_i64_be, buf_i64_le,
+            buf_i8, buf_len, buf_slice, buf_u16_be, buf_u16_le, buf_u32_be, buf_u32_le, buf_u8,
+            buffer_new,
+        },
+        cli::cli_parse,
+        dict::{
+            assoc, assoc_in, contains_key, delete, dict_from_pairs, dissoc, get_in, keys, merge,
+            put, update, update_in, values,
+        },
+        diff::{diff, text_diff},
+        encoding::{html_escape, url_decode, url_encode},
+        eq::{eq, ge, gt, le, lt, ne},
+        error::{error_message, error_value, is_error, throw},
+        io::{
+            close, dir_create, dir_delete, dir_list, file_append, file_copy, file_delete,
+            file_exists, file_lines, file_open, file_read_as_string, file_read_bytes, file_write,
+            file_write_bytes, read_line, write, write_line, writeln,
+        },
+        isolate::{recv, send},
+        lang::{expr_read, expr_write, is_never, is_unit},
+        logic::not,
+        math::{
+            abs, abs_float, abs_int, ceil, div, div_float, div_int, floor, max, min, mod_float,
+            mod_int, modulo, pow, pow_float, pow_int, round, sqrt, to_i16, to_i32, to_i64, to_i8,
+            to_u16, to_u32, to_u8, E, PI,
+        },
+        maybe::{is_none, is_some, some, unwrap, unwrap_or},
+        parallel::pmap,
+        path::{path_extension, path_join, path_parent},
         process::exit,
+        progress::progress_report,
+        schema::validate,
+        seq::{all, any, count, filter, iter, map, next, range, reduce, take},
+        stats::{
+            stats_histogram, stats_mean, stats_median, stats_percentile, stats_std, stats_variance,
+        },
+        string::{
+            fmt, format, lowercase, str_contains, str_join, str_len, str_replace, str_slice,
+            str_split, trim, uppercase,
+        },
+        style::style,
+        table::table_render,
+        template::render,
+        time::{sleep, time_add, time_elapsed, time_format, time_instant, time_now, time_parse},
+        walk::{postwalk, walk},
     },
+    util::FORMAT_SYMBOL,
 };
 
-use super::env::Env;
+use super::{env::Env, module::Module};
 
 // #TODO use typeclasses (== traits) for overloading
 // #TODO make Env::top() -> in fact it's bottom (of the stack)
 // #TODO alternative Env::prelude()
 
+// #Insight
+// The prelude functions are organized in modules, e.g. `str/len`, `io/write`,
+// bound as Module values and accessed with qualified names, like the user
+// modules. For compatibility, the functions are also bound to the flat names,
+// e.g. `str-len`, `write`, unless disabled with `PreludeOptions`. The `core`
+// functions (operators, comparisons, Maybe and Dict functions) are always
+// bound to the flat names.
+//
+// The functions registered with a qualified name, e.g. `time/now`, are
+// exported by the module of the prefix, they have no flat names. The modules
+// are bound in the global scope, a variable does not shadow a module in a
+// qualified name, see `Env::get`.
+
+// #Insight
+// The effectful functions, e.g. the I/O ops and the ops that require a
+// capability, are annotated with an `effect`. An effectful function is not
+// captured for the evaluation in another thread, see `CapturedFunc`.
+
+/// The options of the prelude.
+#[derive(Debug, Clone)]
+pub struct PreludeOptions {
+    /// Bind the module functions to the flat names too, e.g. `str-len` for
+    /// `str/len`, enabled by default.
+    pub flat_aliases: bool,
+}
+
+impl Default for PreludeOptions {
+    fn default() -> Self {
+        Self { flat_aliases: true }
+    }
+}
+
+/// The prelude modules, the exported names and the flat names of the
+/// functions.
+const PRELUDE_MODULES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "core",
+        &[
+            ("+", "+"),
+            ("-", "-"),
+            ("*", "*"),
+            ("/", "/"),
+            ("%", "%"),
+            ("div", "div"),
+            ("mod", "mod"),
+            ("=", "="),
+            ("!=", "!="),
+            (">", ">"),
+            ("<", "<"),
+            (">=", ">="),
+            ("<=", "<="),
+            ("not", "not"),
+            ("Some", "Some"),
+            ("None", "None"),
+            ("is-some?", "is-some?"),
+            ("is-none?", "is-none?"),
+            ("unwrap", "unwrap"),
+            ("unwrap-or", "unwrap-or"),
+            ("throw", "throw"),
+            ("is-error?", "is-error?"),
+            ("unit?", "unit?"),
+            ("never?", "never?"),
+            ("format", "format"),
+            ("assoc", "assoc"),
+            ("dissoc", "dissoc"),
+            ("update", "update"),
+            ("get-in", "get-in"),
+            ("assoc-in", "assoc-in"),
+            ("update-in", "update-in"),
+            ("put", "put"),
+            ("delete", "delete"),
+            ("keys", "keys"),
+            ("values", "values"),
+            ("contains-key?", "contains-key?"),
+            ("merge", "merge"),
+            ("append", "append"),
+            ("dict-from-pairs", "dict-from-pairs"),
+        ],
+    ),
+    (
+        "str",
+        &[
+            ("fmt", "fmt"),
+            ("len", "str-len"),
+            ("slice", "str-slice"),
+            ("split", "str-split"),
+            ("join", "str-join"),
+            ("contains?", "str-contains?"),
+            ("replace", "str-replace"),
+            ("uppercase", "uppercase"),
+            ("lowercase", "lowercase"),
+            ("trim", "trim"),
+        ],
+    ),
+    (
+        "seq",
+        &[
+            ("range", "range"),
+            ("iter", "iter"),
+            ("next", "next"),
+            ("take", "take"),
+            ("map", "map"),
+            ("filter", "filter"),
+            ("reduce", "reduce"),
+            ("any?", "any?"),
+            ("all?", "all?"),
+            ("count", "count"),
+            ("walk", "walk"),
+            ("postwalk", "postwalk"),
+            ("pmap", "pmap"),
+        ],
+    ),
+    (
+        "io",
+        &[
+            ("write", "write"),
+            ("writeln", "writeln"),
+            ("read-line", "read-line"),
+            ("write-line", "write-line"),
+            ("close", "close"),
+            ("open", "File:open"),
+            ("read-string", "File:read_as_string"),
+            ("read-bytes", "File:read_bytes"),
+            ("write-string", "File:write"),
+            ("write-bytes", "File:write_bytes"),
+            ("append-string", "File:append"),
+            ("lines", "File:lines"),
+            ("exists?", "File:exists?"),
+            ("delete-file", "File:delete"),
+            ("copy-file", "File:copy"),
+            ("list-dir", "Dir:list"),
+            ("create-dir", "Dir:create"),
+            ("delete-dir", "Dir:delete"),
+        ],
+    ),
+    (
+        "proc",
+        &[
+            ("exit", "exit"),
+            ("sleep", "sleep"),
+            ("send", "send"),
+            ("recv", "recv"),
+        ],
+    ),
+    (
+        "math",
+        &[
+            ("pi", "pi"),
+            ("e", "e"),
+            ("pow", "pow"),
+            ("min", "min"),
+            ("max", "max"),
+            ("abs", "abs"),
+            ("floor", "floor"),
+            ("ceil", "ceil"),
+            ("round", "round"),
+            ("sqrt", "sqrt"),
+            ("to-i8", "to-i8"),
+            ("to-u8", "to-u8"),
+            ("to-i16", "to-i16"),
+            ("to-u16", "to-u16"),
+            ("to-i32", "to-i32"),
+            ("to-u32", "to-u32"),
+            ("to-i64", "to-i64"),
+        ],
+    ),
+];
+
+/// Annotates an effectful function, see `CapturedFunc`.
+fn effectful(func: impl Into<Ann<Expr>>) -> Ann<Expr> {
+    let mut func = func.into();
+    func.set_annotation("effect", Expr::symbol("Io"));
+    func
+}
+
+/// Binds the prelude modules in the global scope, the exports are the flat
+/// bindings of the functions, including the methods, e.g. `str/len` for
+/// `str-len`, and the bindings of the qualified names, e.g. `time/now`.
+fn setup_modules(env: &mut Env, options: &PreludeOptions) {
+    let methods: Vec<String> = env
+        .local
+        .iter()
+        .flat_map(|scope| {
+            scope
+                .borrow()
+                .keys()
+                .filter(|key| key.contains("$$"))
+                .cloned()
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+    let mut modules: BTreeMap<String, BTreeMap<String, Ann<Expr>>> = BTreeMap::new();
+
+    for (name, functions) in PRELUDE_MODULES {
+        let exports = modules.entry((*name).to_owned()).or_default();
+
+        for (export, flat) in functions.iter() {
+            let Some(value) = env.get(flat) else {
+                continue;
+            };
+            exports.insert((*export).to_owned(), value);
+
+            let prefix = format!("{flat}$$");
+
+            for method in methods.iter().filter(|method| method.starts_with(&prefix)) {
+                if let Some(value) = env.get(method) {
+                    exports.insert(format!("{export}{}", &method[flat.len()..]), value);
+                }
+                if !options.flat_aliases && *name != "core" {
+                    env.remove(method);
+                }
+            }
+
+            if !options.flat_aliases && *name != "core" {
+                env.remove(flat);
+            }
+        }
+    }
+
+    // The qualified names are moved to the exports of the modules, e.g.
+    // `time/now` to the `now` export of `time`.
+    for scope in &env.local {
+        let mut scope = scope.borrow_mut();
+
+        let qualified: Vec<String> = scope
+            .keys()
+            .filter(|key| matches!(key.split_once('/'), Some((module, _)) if !module.is_empty()))
+            .cloned()
+            .collect();
+
+        for key in qualified {
+            // The unwraps are safe, the keys are qualified names of the scope.
+            let (module, export) = key.split_once('/').unwrap();
+            let value = scope.remove(&key).unwrap();
+            modules
+                .entry(module.to_owned())
+                .or_default()
+                .insert(export.to_owned(), value);
+        }
+    }
+
+    for (name, exports) in modules {
+        let module = Module {
+            name: name.clone(),
+            path: PathBuf::new(),
+            exports,
+        };
+
+        env.global
+            .insert(name, Expr::Module(Rc::new(module)).into());
+    }
+}
+
 pub fn setup_prelude(env: Env) -> Env {
+    setup_prelude_with(env, &PreludeOptions::default())
+}
+
+pub fn setup_prelude_with(env: Env, options: &PreludeOptions) -> Env {
     let mut env = env;
 
     // num
 
     // #TODO forget the mangling, implement with a dispatcher function, multi-function.
-    env.insert(
-        "+",
-        Ann::with_type(Expr::ForeignFunc(Rc::new(add_int)), Expr::symbol("Int")),
-    );
+
+    // #Insight
+    // The unspecialized operators dispatch dynamically, they are used when the
+    // operators are passed as values or the argument types are not resolved.
+    env.insert("+", Expr::ForeignFunc(Rc::new(add)));
     env.insert(
         "+$$Int$$Int",
         Ann::with_type(Expr::ForeignFunc(Rc::new(add_int)), Expr::symbol("Int")),
@@ -37,33 +348,661 @@ pub fn setup_prelude(env: Env) -> Env {
         // #TODO even better: (Func (Many Float) Float)
         Ann::with_type(Expr::ForeignFunc(Rc::new(add_float)), Expr::symbol("Float")),
     );
+    env.insert(
+        "+$$BigInt$$BigInt",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(add_big_int)),
+            Expr::symbol("BigInt"),
+        ),
+    );
+    env.insert(
+        "+$$Decimal$$Decimal",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(add_decimal)),
+            Expr::symbol("Decimal"),
+        ),
+    );
     env.insert("-", Expr::ForeignFunc(Rc::new(sub)));
+    env.insert(
+        "-$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(sub_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "-$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(sub_float)), Expr::symbol("Float")),
+    );
+    env.insert(
+        "-$$BigInt$$BigInt",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(sub_big_int)),
+            Expr::symbol("BigInt"),
+        ),
+    );
+    env.insert(
+        "-$$Decimal$$Decimal",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(sub_decimal)),
+            Expr::symbol("Decimal"),
+        ),
+    );
     env.insert("*", Expr::ForeignFunc(Rc::new(mul)));
+    env.insert(
+        "*$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(mul_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "*$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(mul_float)), Expr::symbol("Float")),
+    );
+    env.insert(
+        "*$$BigInt$$BigInt",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(mul_big_int)),
+            Expr::symbol("BigInt"),
+        ),
+    );
+    env.insert(
+        "*$$Decimal$$Decimal",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(mul_decimal)),
+            Expr::symbol("Decimal"),
+        ),
+    );
+
+    env.insert("/", Expr::ForeignFunc(Rc::new(div_op)));
+    env.insert(
+        "/$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(div_op_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "/$$Float$$Float",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(div_op_float)),
+            Expr::symbol("Float"),
+        ),
+    );
+    env.insert(
+        "/$$BigInt$$BigInt",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(div_op_big_int)),
+            Expr::symbol("BigInt"),
+        ),
+    );
+    env.insert(
+        "/$$Decimal$$Decimal",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(div_op_decimal)),
+            Expr::symbol("Decimal"),
+        ),
+    );
+    env.insert("%", Expr::ForeignFunc(Rc::new(rem)));
+    env.insert(
+        "%$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(rem_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "%$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(rem_float)), Expr::symbol("Float")),
+    );
+    env.insert(
+        "%$$BigInt$$BigInt",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(rem_big_int)),
+            Expr::symbol("BigInt"),
+        ),
+    );
+    env.insert(
+        "%$$Decimal$$Decimal",
+        Ann::with_type(
+            Expr::ForeignFunc(Rc::new(rem_decimal)),
+            Expr::symbol("Decimal"),
+        ),
+    );
+
+    // math
+
+    env.insert("pi", Ann::with_type(Expr::Float(PI), Expr::symbol("Float")));
+    env.insert("e", Ann::with_type(Expr::Float(E), Expr::symbol("Float")));
+
+    env.insert("div", Expr::ForeignFunc(Rc::new(div)));
+    env.insert(
+        "div$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(div_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "div$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(div_float)), Expr::symbol("Float")),
+    );
+    env.insert("mod", Expr::ForeignFunc(Rc::new(modulo)));
+    env.insert(
+        "mod$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(mod_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "mod$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(mod_float)), Expr::symbol("Float")),
+    );
+    env.insert("pow", Expr::ForeignFunc(Rc::new(pow)));
+    env.insert(
+        "pow$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(pow_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "pow$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(pow_float)), Expr::symbol("Float")),
+    );
+    env.insert("min", Expr::ForeignFunc(Rc::new(min)));
+    env.insert(
+        "min$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(min)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "min$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(min)), Expr::symbol("Float")),
+    );
+    env.insert("max", Expr::ForeignFunc(Rc::new(max)));
+    env.insert(
+        "max$$Int$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(max)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "max$$Float$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(max)), Expr::symbol("Float")),
+    );
+    env.insert("abs", Expr::ForeignFunc(Rc::new(abs)));
+    env.insert(
+        "abs$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(abs_int)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "abs$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(abs_float)), Expr::symbol("Float")),
+    );
+    env.insert("floor", Expr::ForeignFunc(Rc::new(floor)));
+    env.insert(
+        "floor$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(floor)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "floor$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(floor)), Expr::symbol("Float")),
+    );
+    env.insert("ceil", Expr::ForeignFunc(Rc::new(ceil)));
+    env.insert(
+        "ceil$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(ceil)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "ceil$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(ceil)), Expr::symbol("Float")),
+    );
+    env.insert("round", Expr::ForeignFunc(Rc::new(round)));
+    env.insert(
+        "round$$Int",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(round)), Expr::symbol("Int")),
+    );
+    env.insert(
+        "round$$Float",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(round)), Expr::symbol("Float")),
+    );
+    env.insert(
+        "sqrt",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(sqrt)), Expr::symbol("Float")),
+    );
+
+    // The checked casts to the sized integers.
+    env.insert(
+        "to-i8",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_i8)), Expr::symbol("I8")),
+    );
+    env.insert(
+        "to-u8",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_u8)), Expr::symbol("U8")),
+    );
+    env.insert(
+        "to-i16",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_i16)), Expr::symbol("I16")),
+    );
+    env.insert(
+        "to-u16",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_u16)), Expr::symbol("U16")),
+    );
+    env.insert(
+        "to-i32",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_i32)), Expr::symbol("I32")),
+    );
+    env.insert(
+        "to-u32",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_u32)), Expr::symbol("U32")),
+    );
+    env.insert(
+        "to-i64",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(to_i64)), Expr::symbol("I64")),
+    );
+
+    // diff
+
+    env.insert("diff", Expr::ForeignFunc(Rc::new(diff)));
+    env.insert("text/diff", Expr::ForeignFunc(Rc::new(text_diff)));
+
+    // schema
+
+    env.insert("validate", Expr::ForeignFunc(Rc::new(validate)));
+
+    // stats
+
+    env.insert("stats/mean", Expr::ForeignFunc(Rc::new(stats_mean)));
+    env.insert("stats/median", Expr::ForeignFunc(Rc::new(stats_median)));
+    env.insert(
+        "stats/percentile",
+        Expr::ForeignFunc(Rc::new(stats_percentile)),
+    );
+    env.insert("stats/variance", Expr::ForeignFunc(Rc::new(stats_variance)));
+    env.insert("stats/std", Expr::ForeignFunc(Rc::new(stats_std)));
+    env.insert(
+        "stats/histogram",
+        Expr::ForeignFunc(Rc::new(stats_histogram)),
+    );
 
     // eq
 
     env.insert("=", Expr::ForeignFunc(Rc::new(eq)));
     env.insert(">", Expr::ForeignFunc(Rc::new(gt)));
     env.insert("<", Expr::ForeignFunc(Rc::new(lt)));
+    env.insert("!=", Expr::ForeignFunc(Rc::new(ne)));
+    env.insert(">=", Expr::ForeignFunc(Rc::new(ge)));
+    env.insert("<=", Expr::ForeignFunc(Rc::new(le)));
+
+    // logic
+
+    env.insert("not", Expr::ForeignFunc(Rc::new(not)));
+
+    // error
+
+    // `throw` diverges, it has the bottom type Never.
+    env.insert(
+        "throw",
+        Ann::with_type(Expr::ForeignFunc(Rc::new(throw)), Expr::symbol("Never")),
+    );
+    env.insert("error/message", Expr::ForeignFunc(Rc::new(error_message)));
+    env.insert("error/value", Expr::ForeignFunc(Rc::new(error_value)));
+    env.insert("is-error?", Expr::ForeignFunc(Rc::new(is_error)));
+
+    // maybe
+
+    env.insert("Some", Expr::ForeignFunc(Rc::new(some)));
+    env.insert("None", Ann::with_type(Expr::none(), Expr::symbol("Maybe")));
+    env.insert("is-some?", Expr::ForeignFunc(Rc::new(is_some)));
+    env.insert("is-none?", Expr::ForeignFunc(Rc::new(is_none)));
+    env.insert("unwrap", Expr::ForeignFunc(Rc::new(unwrap)));
+    env.insert("unwrap-or", Expr::ForeignFunc(Rc::new(unwrap_or)));
+
+    // dict
+
+    env.insert("assoc", Expr::ForeignFunc(Rc::new(assoc)));
+    env.insert("dissoc", Expr::ForeignFunc(Rc::new(dissoc)));
+    env.insert("update", Expr::ForeignFunc(Rc::new(update)));
+    env.insert("get-in", Expr::ForeignFunc(Rc::new(get_in)));
+    env.insert("assoc-in", Expr::ForeignFunc(Rc::new(assoc_in)));
+    env.insert("update-in", Expr::ForeignFunc(Rc::new(update_in)));
+    env.insert("put", Expr::ForeignFunc(Rc::new(put)));
+    env.insert("delete", Expr::ForeignFunc(Rc::new(delete)));
+    env.insert("keys", Expr::ForeignFunc(Rc::new(keys)));
+    env.insert("values", Expr::ForeignFunc(Rc::new(values)));
+    env.insert("contains-key?", Expr::ForeignFunc(Rc::new(contains_key)));
+    env.insert("merge", Expr::ForeignFunc(Rc::new(merge)));
+    env.insert(
+        "dict-from-pairs",
+        Expr::ForeignFunc(Rc::new(dict_from_pairs)),
+    );
+
+    // array
+
+    // #Insight
+    // `put` is registered with the dict ops, it also handles Arrays.
+    env.insert("append", Expr::ForeignFunc(Rc::new(append)));
+
+    // buffer
+
+    env.insert("Buffer", Expr::ForeignFunc(Rc::new(buffer_new)));
+    env.insert("buf/len", Expr::ForeignFunc(Rc::new(buf_len)));
+    env.insert("buf/slice", Expr::ForeignFunc(Rc::new(buf_slice)));
+    env.insert("buf/hex", Expr::ForeignFunc(Rc::new(buf_hex)));
+    env.insert("buf/u8", Expr::ForeignFunc(Rc::new(buf_u8)));
+    env.insert("buf/i8", Expr::ForeignFunc(Rc::new(buf_i8)));
+    env.insert("buf/u16-le", Expr::ForeignFunc(Rc::new(buf_u16_le)));
+    env.insert("buf/u16-be", Expr::ForeignFunc(Rc::new(buf_u16_be)));
+    env.insert("buf/i16-le", Expr::ForeignFunc(Rc::new(buf_i16_le)));
+    env.insert("buf/i16-be", Expr::ForeignFunc(Rc::new(buf_i16_be)));
+    env.insert("buf/u32-le", Expr::ForeignFunc(Rc::new(buf_u32_le)));
+    env.insert("buf/u32-be", Expr::ForeignFunc(Rc::new(buf_u32_be)));
+    env.insert("buf/i32-le", Expr::ForeignFunc(Rc::new(buf_i32_le)));
+    env.insert("buf/i32-be", Expr::ForeignFunc(Rc::new(buf_i32_be)));
+    env.insert("buf/i64-le", Expr::ForeignFunc(Rc::new(buf_i64_le)));
+    env.insert("buf/i64-be", Expr::ForeignFunc(Rc::new(buf_i64_be)));
+
+    // lang
+
+    env.insert("unit?", Expr::ForeignFunc(Rc::new(is_unit)));
+    env.insert("never?", Expr::ForeignFunc(Rc::new(is_never)));
+    env.insert("expr/read", Expr::ForeignFunc(Rc::new(expr_read)));
+    env.insert("expr/write", Expr::ForeignFunc(Rc::new(expr_write)));
+
+    // string
+
+    env.insert("format", Expr::ForeignFunc(Rc::new(format)));
+    // The interpolated strings call `format` with a reserved name.
+    env.insert(FORMAT_SYMBOL, Expr::ForeignFunc(Rc::new(format)));
+    env.insert("fmt", Expr::ForeignFunc(Rc::new(fmt)));
+    env.insert("str-len", Expr::ForeignFunc(Rc::new(str_len)));
+    env.insert("str-slice", Expr::ForeignFunc(Rc::new(str_slice)));
+    env.insert("str-split", Expr::ForeignFunc(Rc::new(str_split)));
+    env.insert("str-join", Expr::ForeignFunc(Rc::new(str_join)));
+    env.insert("str-contains?", Expr::ForeignFunc(Rc::new(str_contains)));
+    env.insert("str-replace", Expr::ForeignFunc(Rc::new(str_replace)));
+    env.insert("uppercase", Expr::ForeignFunc(Rc::new(uppercase)));
+    env.insert("lowercase", Expr::ForeignFunc(Rc::new(lowercase)));
+    env.insert("trim", Expr::ForeignFunc(Rc::new(trim)));
+    env.insert("render", Expr::ForeignFunc(Rc::new(render)));
+    env.insert("style", Expr::ForeignFunc(Rc::new(style)));
+    env.insert("table/render", Expr::ForeignFunc(Rc::new(table_render)));
+
+    // encoding
+
+    env.insert("html/escape", Expr::ForeignFunc(Rc::new(html_escape)));
+    env.insert("url/encode", Expr::ForeignFunc(Rc::new(url_encode)));
+    env.insert("url/decode", Expr::ForeignFunc(Rc::new(url_decode)));
+
+    // seq
+
+    env.insert("range", Expr::ForeignFunc(Rc::new(range)));
+    env.insert("iter", Expr::ForeignFunc(Rc::new(iter)));
+    env.insert("next", Expr::ForeignFunc(Rc::new(next)));
+    env.insert("take", Expr::ForeignFunc(Rc::new(take)));
+    env.insert("map", Expr::ForeignFunc(Rc::new(map)));
+    env.insert("filter", Expr::ForeignFunc(Rc::new(filter)));
+    env.insert("reduce", Expr::ForeignFunc(Rc::new(reduce)));
+    env.insert("any?", Expr::ForeignFunc(Rc::new(any)));
+    env.insert("all?", Expr::ForeignFunc(Rc::new(all)));
+    env.insert("count", Expr::ForeignFunc(Rc::new(count)));
+
+    // time
+
+    env.insert("time/now", Expr::ForeignFunc(Rc::new(time_now)));
+    env.insert("time/parse", Expr::ForeignFunc(Rc::new(time_parse)));
+    env.insert("time/format", Expr::ForeignFunc(Rc::new(time_format)));
+    env.insert("time/add", Expr::ForeignFunc(Rc::new(time_add)));
+    env.insert("time/instant", Expr::ForeignFunc(Rc::new(time_instant)));
+    env.insert("time/elapsed", Expr::ForeignFunc(Rc::new(time_elapsed)));
+    env.insert("sleep", Expr::ForeignFunc(Rc::new(sleep)));
+
+    // walk
+
+    env.insert("walk", Expr::ForeignFunc(Rc::new(walk)));
+    env.insert("postwalk", Expr::ForeignFunc(Rc::new(postwalk)));
+
+    // parallel
+
+    env.insert("pmap", Expr::ForeignFunc(Rc::new(pmap)));
+
+    // isolate
+
+    env.insert("send", effectful(Expr::ForeignFunc(Rc::new(send))));
+    env.insert("recv", effectful(Expr::ForeignFunc(Rc::new(recv))));
+
+    // agent
+
+    env.insert("agent", Expr::ForeignFunc(Rc::new(agent)));
+    env.insert(
+        "send-update",
+        effectful(Expr::ForeignFunc(Rc::new(send_update))),
+    );
+    env.insert("deref", Expr::ForeignFunc(Rc::new(deref)));
+    env.insert("await", Expr::ForeignFunc(Rc::new(await_agent)));
 
     // io
 
-    env.insert("write", Expr::ForeignFunc(Rc::new(write)));
-    env.insert("write$$String", Expr::ForeignFunc(Rc::new(write)));
-    env.insert("writeln", Expr::ForeignFunc(Rc::new(writeln)));
-    env.insert("writeln$$String", Expr::ForeignFunc(Rc::new(writeln)));
+    env.insert("write", effectful(Expr::ForeignFunc(Rc::new(write))));
+    env.insert(
+        "write$$String",
+        effectful(Expr::ForeignFunc(Rc::new(write))),
+    );
+    env.insert("writeln", effectful(Expr::ForeignFunc(Rc::new(writeln))));
+    env.insert(
+        "writeln$$String",
+        effectful(Expr::ForeignFunc(Rc::new(writeln))),
+    );
     env.insert(
         "File:read_as_string",
-        Expr::ForeignFunc(Rc::new(file_read_as_string)),
+        effectful(Expr::ForeignFunc(Rc::new(file_read_as_string))),
     );
     env.insert(
         "File:read_as_string$$String",
-        Expr::ForeignFunc(Rc::new(file_read_as_string)),
+        effectful(Expr::ForeignFunc(Rc::new(file_read_as_string))),
+    );
+    env.insert(
+        "File:read_bytes",
+        effectful(Expr::ForeignFunc(Rc::new(file_read_bytes))),
+    );
+    env.insert(
+        "File:read_bytes$$String",
+        effectful(Expr::ForeignFunc(Rc::new(file_read_bytes))),
+    );
+    env.insert(
+        "File:write_bytes",
+        effectful(Expr::ForeignFunc(Rc::new(file_write_bytes))),
     );
+    env.insert(
+        "File:lines",
+        effectful(Expr::ForeignFunc(Rc::new(file_lines))),
+    );
+    env.insert(
+        "File:lines$$String",
+        effectful(Expr::ForeignFunc(Rc::new(file_lines))),
+    );
+    env.insert(
+        "File:write",
+        effectful(Expr::ForeignFunc(Rc::new(file_write))),
+    );
+    env.insert(
+        "File:append",
+        effectful(Expr::ForeignFunc(Rc::new(file_append))),
+    );
+    env.insert(
+        "File:exists?",
+        effectful(Expr::ForeignFunc(Rc::new(file_exists))),
+    );
+    env.insert(
+        "File:delete",
+        effectful(Expr::ForeignFunc(Rc::new(file_delete))),
+    );
+    env.insert(
+        "File:copy",
+        effectful(Expr::ForeignFunc(Rc::new(file_copy))),
+    );
+    env.insert(
+        "File:open",
+        effectful(Expr::ForeignFunc(Rc::new(file_open))),
+    );
+    env.insert(
+        "read-line",
+        effectful(Expr::ForeignFunc(Rc::new(read_line))),
+    );
+    env.insert(
+        "write-line",
+        effectful(Expr::ForeignFunc(Rc::new(write_line))),
+    );
+    env.insert("close", effectful(Expr::ForeignFunc(Rc::new(close))));
+    env.insert("Dir:list", effectful(Expr::ForeignFunc(Rc::new(dir_list))));
+    env.insert(
+        "Dir:create",
+        effectful(Expr::ForeignFunc(Rc::new(dir_create))),
+    );
+    env.insert(
+        "Dir:delete",
+        effectful(Expr::ForeignFunc(Rc::new(dir_delete))),
+    );
+
+    // path
+
+    env.insert("path/join", Expr::ForeignFunc(Rc::new(path_join)));
+    env.insert("path/parent", Expr::ForeignFunc(Rc::new(path_parent)));
+    env.insert("path/extension", Expr::ForeignFunc(Rc::new(path_extension)));
+
+    #[cfg(feature = "glob")]
+    {
+        use crate::ops::glob::glob;
+
+        env.insert("glob", effectful(Expr::ForeignFunc(Rc::new(glob))));
+        env.insert("glob$$String", effectful(Expr::ForeignFunc(Rc::new(glob))));
+    }
+
+    #[cfg(feature = "prompt")]
+    {
+        use crate::ops::prompt::{confirm, prompt, prompt_secret};
+
+        env.insert("prompt", effectful(Expr::ForeignFunc(Rc::new(prompt))));
+        env.insert("confirm", effectful(Expr::ForeignFunc(Rc::new(confirm))));
+        env.insert(
+            "prompt/secret",
+            effectful(Expr::ForeignFunc(Rc::new(prompt_secret))),
+        );
+    }
+
+    #[cfg(feature = "signal")]
+    {
+        use crate::ops::signal::{is_signaled, on_signal};
+
+        env.insert(
+            "on-signal",
+            effectful(Expr::ForeignFunc(Rc::new(on_signal))),
+        );
+        env.insert(
+            "signaled?",
+            effectful(Expr::ForeignFunc(Rc::new(is_signaled))),
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    {
+        use crate::ops::sqlite::{db_exec, db_open, db_query};
+
+        env.insert("db/open", effectful(Expr::ForeignFunc(Rc::new(db_open))));
+        env.insert("db/query", effectful(Expr::ForeignFunc(Rc::new(db_query))));
+        env.insert("db/exec", effectful(Expr::ForeignFunc(Rc::new(db_exec))));
+    }
+
+    #[cfg(feature = "store")]
+    {
+        use crate::ops::store::{store_get, store_open, store_put};
+
+        env.insert(
+            "store/open",
+            effectful(Expr::ForeignFunc(Rc::new(store_open))),
+        );
+        env.insert(
+            "store/get",
+            effectful(Expr::ForeignFunc(Rc::new(store_get))),
+        );
+        env.insert(
+            "store/put",
+            effectful(Expr::ForeignFunc(Rc::new(store_put))),
+        );
+    }
+
+    #[cfg(feature = "tensor")]
+    {
+        use crate::ops::tensor::{
+            register_tensor_hooks, tensor_add, tensor_div, tensor_dot, tensor_from, tensor_mean,
+            tensor_mul, tensor_shape, tensor_std, tensor_sub, tensor_sum, tensor_to_array,
+            tensor_transpose,
+        };
+
+        register_tensor_hooks();
+
+        env.insert("tensor/from", Expr::ForeignFunc(Rc::new(tensor_from)));
+        env.insert(
+            "tensor/to-array",
+            Expr::ForeignFunc(Rc::new(tensor_to_array)),
+        );
+        env.insert("tensor/shape", Expr::ForeignFunc(Rc::new(tensor_shape)));
+        env.insert("tensor/add", Expr::ForeignFunc(Rc::new(tensor_add)));
+        env.insert("tensor/sub", Expr::ForeignFunc(Rc::new(tensor_sub)));
+        env.insert("tensor/mul", Expr::ForeignFunc(Rc::new(tensor_mul)));
+        env.insert("tensor/div", Expr::ForeignFunc(Rc::new(tensor_div)));
+        env.insert("tensor/dot", Expr::ForeignFunc(Rc::new(tensor_dot)));
+        env.insert(
+            "tensor/transpose",
+            Expr::ForeignFunc(Rc::new(tensor_transpose)),
+        );
+        env.insert("tensor/sum", Expr::ForeignFunc(Rc::new(tensor_sum)));
+        env.insert("tensor/mean", Expr::ForeignFunc(Rc::new(tensor_mean)));
+        env.insert("tensor/std", Expr::ForeignFunc(Rc::new(tensor_std)));
+    }
+
+    #[cfg(feature = "toml")]
+    {
+        use crate::ops::toml::toml_parse;
+
+        env.insert("toml/parse", Expr::ForeignFunc(Rc::new(toml_parse)));
+    }
+
+    #[cfg(feature = "watch")]
+    {
+        use crate::ops::watch::watch;
+
+        env.insert("watch", effectful(Expr::ForeignFunc(Rc::new(watch))));
+    }
+
+    #[cfg(feature = "yaml")]
+    {
+        use crate::ops::yaml::yaml_parse;
+
+        env.insert("yaml/parse", Expr::ForeignFunc(Rc::new(yaml_parse)));
+    }
+
+    #[cfg(feature = "regex")]
+    {
+        use crate::ops::regex::{
+            regex_find, regex_find_all, regex_is_match, regex_replace, regex_split,
+        };
+
+        env.insert("regex/match?", Expr::ForeignFunc(Rc::new(regex_is_match)));
+        env.insert("regex/find", Expr::ForeignFunc(Rc::new(regex_find)));
+        env.insert("regex/find-all", Expr::ForeignFunc(Rc::new(regex_find_all)));
+        env.insert("regex/replace", Expr::ForeignFunc(Rc::new(regex_replace)));
+        env.insert("regex/split", Expr::ForeignFunc(Rc::new(regex_split)));
+    }
 
     // process
-    env.insert("exit", Expr::ForeignFunc(Rc::new(exit)));
-    env.insert("exit$$", Expr::ForeignFunc(Rc::new(exit)));
+
+    // #Insight
+    // `exit` diverges, it has the bottom type Never.
+    env.insert(
+        "exit",
+        effectful(Ann::with_type(
+            Expr::ForeignFunc(Rc::new(exit)),
+            Expr::symbol("Never"),
+        )),
+    );
+    env.insert(
+        "exit$$",
+        effectful(Ann::with_type(
+            Expr::ForeignFunc(Rc::new(exit)),
+            Expr::symbol("Never"),
+        )),
+    );
+
+    env.insert(
+        "progress/report",
+        effectful(Expr::ForeignFunc(Rc::new(progress_report))),
+    );
+
+    // cli
+
+    env.insert("cli/parse", Expr::ForeignFunc(Rc::new(cli_parse)));
+
+    setup_modules(&mut env, options);
 
     env
 }
@@ -0,0 +1,149 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    ann::Ann, error::Error, expr::Expr, range::Range, range::Ranged, util::truncate_chars,
+};
+
+// #Insight
+// The replay log is a poor-man's time-travel debugger, it records the
+// evaluated forms (the non-empty Lists) with the summary of their result. A
+// form is recorded when its evaluation completes, i.e. the inner forms are
+// recorded before the outer forms. After a failure, the last steps lead to the
+// failing form, e.g.
+//
+// let mut env = Env::prelude();
+// env.context.replay = Some(ReplayLog::new(100));
+// if eval_string(input, &mut env).is_err() {
+//     eprintln!("{}", env.context.replay.as_ref().unwrap().dump(10));
+// }
+//
+// The log is opt-in, the recording has a cost per evaluation step.
+
+// #TODO support recording the symbol lookups.
+// #TODO support a structured (e.g. JSON Lines) file format.
+
+/// The maximum length (in chars) of the result summary of a step.
+const MAX_SUMMARY_LEN: usize = 80;
+
+/// A recorded evaluation step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayStep {
+    /// The sequence number of the form, in the order the evaluation started.
+    pub id: u64,
+    /// The range of the form in the source.
+    pub range: Range,
+    /// True if the form was evaluated successfully.
+    pub ok: bool,
+    /// The (truncated) repr of the value, or the error message.
+    pub summary: String,
+}
+
+impl fmt::Display for ReplayStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.ok { "=>" } else { "!!" };
+        write!(
+            f,
+            "#{} {}..{} {outcome} {}",
+            self.id, self.range.start, self.range.end, self.summary
+        )
+    }
+}
+
+/// Records the last evaluation steps in a ring buffer, optionally all the
+/// steps are also written to a file.
+#[derive(Debug)]
+pub struct ReplayLog {
+    capacity: usize,
+    steps: VecDeque<ReplayStep>,
+    next_id: u64,
+    file: Option<BufWriter<File>>,
+}
+
+impl ReplayLog {
+    /// Creates a replay log that keeps the last `capacity` steps.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            steps: VecDeque::with_capacity(capacity),
+            next_id: 0,
+            file: None,
+        }
+    }
+
+    /// Creates a replay log that keeps the last `capacity` steps and writes
+    /// all the steps to the file at `path`, one step per line.
+    pub fn with_file(capacity: usize, path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut log = Self::new(capacity);
+        log.file = Some(BufWriter::new(File::create(path)?));
+        Ok(log)
+    }
+
+    /// Starts the evaluation of a form, returns the id of the form.
+    pub fn begin(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    /// Records the completed evaluation of a form.
+    pub fn record(&mut self, id: u64, expr: &Ann<Expr>, result: &Result<Ann<Expr>, Ranged<Error>>) {
+        let (ok, summary) = match result {
+            Ok(value) => (true, value.to_string()),
+            Err(error) => (false, error.to_string()),
+        };
+
+        let step = ReplayStep {
+            id,
+            range: expr.get_range(),
+            ok,
+            summary: truncate_chars(summary, MAX_SUMMARY_LEN),
+        };
+
+        // #Insight
+        // The replay log should never fail the evaluation, the write errors
+        // are ignored.
+        if let Some(file) = &mut self.file {
+            let _ = writeln!(file, "{step}");
+        }
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.steps.len() == self.capacity {
+            self.steps.pop_front();
+        }
+        self.steps.push_back(step);
+    }
+
+    /// Returns the recorded steps, oldest first.
+    pub fn steps(&self) -> impl Iterator<Item = &ReplayStep> {
+        self.steps.iter()
+    }
+
+    /// Returns the last `n` recorded steps, oldest first, one step per line.
+    pub fn dump(&self, n: usize) -> String {
+        let skip = self.steps.len().saturating_sub(n);
+
+        self.steps
+            .iter()
+            .skip(skip)
+            .map(|step| step.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Flushes the steps written to the file, if any.
+    pub fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use std::time::Duration;
+
+// #TODO consider tracking the peak memory usage instead of an allocation estimate.
+
+/// Resource-usage statistics of an evaluation, useful to log per-script cost,
+/// enforce quotas and detect runaway scripts.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EvalStats {
+    /// The number of evaluation steps, i.e. evaluated lists (invocations,
+    /// special forms).
+    pub steps: u64,
+    /// The maximum nesting of function invocations.
+    pub max_call_depth: usize,
+    /// A rough estimate of the allocations, the number of bindings created.
+    pub allocations_estimate: u64,
+    /// The wall-clock duration of the evaluation.
+    pub duration: Duration,
+}
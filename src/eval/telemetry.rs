@@ -0,0 +1,107 @@
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    ann::Ann, error::Error, expr::Expr, range::Range, range::Ranged, util::truncate_chars,
+};
+
+// #Insight
+// The telemetry handler is invoked after every invocation of a foreign
+// function (e.g. `fs/read`, `http/get`), also when invoked as a value, e.g.
+// `(map fs/read paths)`, with the name of the function, the summaries of the
+// arguments, the duration and the outcome. The events are useful to audit
+// what a script does or to export traces, e.g.
+//
+// env.context.set_telemetry_handler(|event| span_exporter.export(event));
+//
+// The handler is opt-in, the Tan functions are not reported.
+
+// #TODO consider reporting the Tan function invocations, as parent spans.
+
+/// The maximum length (in chars) of the summary of an argument or a result.
+const MAX_SUMMARY_LEN: usize = 80;
+
+/// An invocation of a foreign function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpEvent {
+    /// The name of the function, or the source of the function expression.
+    pub name: String,
+    /// The (truncated) reprs of the arguments.
+    pub args: Vec<String>,
+    /// The range of the invocation in the source.
+    pub range: Range,
+    /// The wall-clock time of the start of the invocation.
+    pub start: SystemTime,
+    /// The duration of the invocation.
+    pub duration: Duration,
+    /// True if the invocation was successful.
+    pub ok: bool,
+    /// The (truncated) repr of the result, or the error message.
+    pub summary: String,
+}
+
+impl OpEvent {
+    /// Returns the summaries of the arguments, before the invocation consumes
+    /// them.
+    pub fn summarize_args(args: &[Ann<Expr>]) -> Vec<String> {
+        args.iter()
+            .map(|arg| truncate_chars(arg.to_string(), MAX_SUMMARY_LEN))
+            .collect()
+    }
+
+    pub fn new(
+        head: &Ann<Expr>,
+        args: Vec<String>,
+        range: Range,
+        start: SystemTime,
+        duration: Duration,
+        result: &Result<Ann<Expr>, Ranged<Error>>,
+    ) -> Self {
+        let name = match head.as_ref() {
+            Expr::Symbol(sym) => sym.clone(),
+            _ => truncate_chars(head.to_string(), MAX_SUMMARY_LEN),
+        };
+
+        let (ok, summary) = match result {
+            Ok(value) => (true, value.to_string()),
+            Err(error) => (false, error.to_string()),
+        };
+
+        Self {
+            name,
+            args,
+            range,
+            start,
+            duration,
+            ok,
+            summary: truncate_chars(summary, MAX_SUMMARY_LEN),
+        }
+    }
+}
+
+impl fmt::Display for OpEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.ok { "=>" } else { "!!" };
+        write!(
+            f,
+            "({} {}) {:.3}ms {outcome} {}",
+            self.name,
+            self.args.join(" "),
+            self.duration.as_secs_f64() * 1000.0,
+            self.summary
+        )
+    }
+}
+
+/// A host callback that receives the foreign function invocations.
+pub type TelemetryFn = dyn FnMut(&OpEvent);
+
+pub struct TelemetryHandler(pub Box<TelemetryFn>);
+
+impl fmt::Debug for TelemetryHandler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#<telemetry_handler>")
+    }
+}
@@ -1,9 +1,34 @@
+pub mod convert;
+pub mod data;
 pub mod expr_iter;
 pub mod expr_transform;
+pub mod foreign;
+pub mod number;
+pub mod portable;
+pub mod seq;
+#[cfg(feature = "serde")]
+pub mod serde_bridge;
+#[cfg(feature = "serde")]
+mod serde_impl;
 
-use std::{collections::HashMap, fmt, rc::Rc};
+use std::{cell::RefCell, collections::BTreeMap, fmt, rc::Rc};
 
-use crate::{ann::Ann, error::Error, eval::env::Env, range::Ranged};
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use self::{
+    foreign::ForeignValue,
+    seq::{IterSeq, Seq, SeqRef},
+};
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{
+        env::{Env, ScopeRef},
+        module::Module,
+    },
+    range::Ranged,
+};
 
 // #TODO separate variant for list and apply/call (can this be defined statically?)
 // #TODO List, MaybeList, Call
@@ -16,20 +41,35 @@ use crate::{ann::Ann, error::Error, eval::env::Env, range::Ranged};
 // The use of Vec in the Expr enum, keeps the nested expressions in the heap.
 
 // #Insight
-// No need for a Zero/Never/Nothing Expr variant?
+// Function clauses, macro bodies and captured scopes are shared through Rc,
+// values are cloned on every env lookup and invocation, a deep copy of the
+// function body would be prohibitively expensive, e.g. in loops.
+
+// #TODO share Array and Dict values too (copy-on-write with Rc::make_mut).
+
+// #Insight
+// One (Unit) is the value of expressions that produce no meaningful value,
+// e.g. an `if` without an else clause. Never (Zero) is the bottom type, the
+// type of diverging expressions, e.g. `exit`.
 
 // #TODO consider parsing to 'simple' Expr, only List and Symbols
 // #TODO optimize 'simple' Expr to 'execution' Expr
-// #TODO introduce ForeignValue?
 // #TODO ExprFn should get a single Expr? -> nah, it's foreign.
 
 // #TODO not all Expr variants really need Ann, maybe the annotation should be internal to Expr?
 
 // A function that accepts a list of Exprs and returns an Expr.
-pub type ExprFn = dyn Fn(&[Ann<Expr>], &Env) -> Result<Ann<Expr>, Ranged<Error>>;
+pub type ExprFn = dyn Fn(&[Ann<Expr>], &mut Env) -> Result<Ann<Expr>, Ranged<Error>>;
 
 // #TODO use normal structs instead of tuple-structs?
 
+/// A function clause, the parameter patterns and the body.
+#[derive(Clone)]
+pub struct FuncClause {
+    pub params: Vec<Ann<Expr>>,
+    pub body: Ann<Expr>,
+}
+
 #[derive(Clone)]
 /// A symbolic expression. This is the 'universal' data type in the language,
 /// all values are expressions (and expressions are values). Evaluation is expression
@@ -37,10 +77,15 @@ pub type ExprFn = dyn Fn(&[Ann<Expr>], &Env) -> Result<Ann<Expr>, Ranged<Error>>
 pub enum Expr {
     // --- Low-level ---
     One,             // Unit == List(Vec::new())
+    Never,           // Zero, the bottom type
     Comment(String), // #TODO consider renaming to Remark (REM)
     Bool(bool),      // #TODO remove?
     Int(i64),
     Float(f64),
+    // An arbitrary-precision integer, e.g. `123n`.
+    BigInt(BigInt),
+    // A fixed-point decimal number, without rounding errors, e.g. `1.5d`.
+    Decimal(Decimal),
     Symbol(String),
     KeySymbol(String),
     Char(char),
@@ -48,16 +93,33 @@ pub enum Expr {
     // #TODO better name for 'generic' List, how about `Cons` or `ConsList` or `Cell`?
     // #TODO add 'quoted' List -> Array!
     List(Vec<Ann<Expr>>),
-    // #TODO should Array contain Ann<Expr>?
-    Array(Vec<Expr>),
+    // #Insight the elements are annotated, e.g. to report errors with precise ranges.
+    Array(Vec<Ann<Expr>>),
     // #TODO different name?
     // #TODO support Expr as keys?
-    // #TODO should Dict contain Ann<Expr>?
-    Dict(HashMap<String, Expr>),
+    // #Insight A BTreeMap is used for deterministic iteration (sorted keys).
+    Dict(BTreeMap<String, Ann<Expr>>),
     // Range(Box<Ann<Expr>>, Box<Ann<Expr>>, Option<Box<Ann<Expr>>>),
-    Func(Vec<Ann<Expr>>, Box<Ann<Expr>>), // #TODO is there a need to use Rc instead of Box? YES! fast clones? INVESTIGATE!
-    Macro(Vec<Ann<Expr>>, Box<Ann<Expr>>),
+    // A function (closure) with one or more clauses, and the captured lexical
+    // scopes. The clauses are tried in order (first-match dispatch).
+    Func(Rc<[FuncClause]>, Rc<[ScopeRef]>),
+    Macro(Rc<[Ann<Expr>]>, Rc<Ann<Expr>>),
     ForeignFunc(Rc<ExprFn>), // #TODO for some reason, Box is not working here!
+    // A host value, opaque to Tan.
+    Foreign(ForeignValue),
+    // A lazy range of Ints, `start`, `end` (exclusive), `step`.
+    Range(i64, i64, i64),
+    // An immutable sequence of bytes, e.g. the contents of a binary file.
+    Buffer(Rc<[u8]>),
+    // An optional value, `(Some value)` or `None`, e.g. the result of a lookup.
+    Maybe(Option<Box<Ann<Expr>>>),
+    // A caught error, the payload is the thrown value or the error message.
+    Error(Box<Ann<Expr>>),
+    // A loaded module, see `use`.
+    Module(Rc<Module>),
+    // A lazy sequence, the state is shared between clones, i.e. the items are
+    // consumed once.
+    Iterator(SeqRef),
     // --- High-level ---
     // #TODO do should contain the expressions also, pre-parsed!
     Do,
@@ -73,6 +135,7 @@ impl fmt::Debug for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let text = match self {
             Expr::One => "()".to_owned(),
+            Expr::Never => "Never".to_owned(),
             Expr::Comment(s) => format!("Comment({s})"),
             Expr::Bool(b) => format!("Bool({b})"),
             Expr::Symbol(s) => format!("Symbol({s})"),
@@ -81,6 +144,8 @@ impl fmt::Debug for Expr {
             Expr::String(s) => format!("String(\"{s}\")"),
             Expr::Int(num) => format!("Int({num})"),
             Expr::Float(num) => format!("Float({num})"),
+            Expr::BigInt(num) => format!("BigInt({num})"),
+            Expr::Decimal(num) => format!("Decimal({num})"),
             Expr::Do => "do".to_owned(),
             Expr::List(terms) => {
                 format!(
@@ -92,11 +157,26 @@ impl fmt::Debug for Expr {
                         .join(", ")
                 )
             }
-            Expr::Array(v) => format!("Array({v:?})"),
-            Expr::Dict(d) => format!("Dict({d:?})"),
+            // The annotations of the elements are omitted, for brevity.
+            Expr::Array(v) => {
+                let v: Vec<&Expr> = v.iter().map(|x| &x.0).collect();
+                format!("Array({v:?})")
+            }
+            Expr::Dict(d) => {
+                let d: BTreeMap<&String, &Expr> = d.iter().map(|(k, v)| (k, &v.0)).collect();
+                format!("Dict({d:?})")
+            }
             Expr::Func(..) => "#<func>".to_owned(),
             Expr::Macro(..) => "#<macro>".to_owned(),
             Expr::ForeignFunc(..) => "#<foreign_func>".to_owned(),
+            Expr::Foreign(value) => format!("Foreign({value})"),
+            Expr::Range(start, end, step) => format!("Range({start}, {end}, {step})"),
+            Expr::Buffer(bytes) => format!("Buffer({})", hex(bytes)),
+            Expr::Maybe(Some(value)) => format!("Some({:?})", value.0),
+            Expr::Maybe(None) => "None".to_owned(),
+            Expr::Error(value) => format!("Error({:?})", value.0),
+            Expr::Module(module) => format!("Module({})", module.name),
+            Expr::Iterator(..) => "#<iterator>".to_owned(),
             Expr::Let => "let".to_owned(),
             // #TODO properly format do, let, if, etc.
             Expr::If(_, _, _) => "if".to_owned(),
@@ -112,10 +192,13 @@ impl fmt::Display for Expr {
         f.write_str(
             (match self {
                 Expr::One => "()".to_owned(),
+                Expr::Never => "Never".to_owned(),
                 Expr::Comment(s) => format!(r#"(rem "{s}")"#), // #TODO what would be a good representation?
                 Expr::Bool(b) => b.to_string(),
                 Expr::Int(n) => n.to_string(),
                 Expr::Float(n) => n.to_string(),
+                Expr::BigInt(n) => format!("{n}n"),
+                Expr::Decimal(n) => format!("{n}d"),
                 Expr::Symbol(s) => s.clone(),
                 Expr::KeySymbol(s) => format!(":{s}"),
                 Expr::Char(c) => format!(r#"(Char "{c}")"#), // #TODO no char literal?
@@ -154,6 +237,14 @@ impl fmt::Display for Expr {
                 Expr::Func(..) => "#<func>".to_owned(),
                 Expr::Macro(..) => "#<func>".to_owned(),
                 Expr::ForeignFunc(..) => "#<foreign_func>".to_owned(),
+                Expr::Foreign(value) => value.to_string(),
+                Expr::Range(start, end, step) => format!("(range {start} {end} {step})"),
+                Expr::Buffer(bytes) => format!(r#"(Buffer "{}")"#, hex(bytes)),
+                Expr::Maybe(Some(value)) => format!("(Some {value})"),
+                Expr::Maybe(None) => "None".to_owned(),
+                Expr::Error(value) => format!("(Error {value})"),
+                Expr::Module(module) => format!("#<module {}>", module.name),
+                Expr::Iterator(..) => "#<iterator>".to_owned(),
             })
             .as_str(),
         )
@@ -167,6 +258,45 @@ impl AsRef<Expr> for Expr {
 }
 
 impl Expr {
+    /// Creates a function (closure) from the clauses and the captured scopes.
+    pub fn func(clauses: Vec<FuncClause>, scopes: Vec<ScopeRef>) -> Self {
+        Expr::Func(clauses.into(), scopes.into())
+    }
+
+    /// Creates a macro from the parameters and the body.
+    pub fn macro_(params: Vec<Ann<Expr>>, body: Ann<Expr>) -> Self {
+        Expr::Macro(params.into(), Rc::new(body))
+    }
+
+    /// Creates a lazy sequence from a (host) iterator. The items are pulled
+    /// on demand, e.g. to stream large datasets into scripts.
+    pub fn iterator<T>(iter: impl Iterator<Item = T> + 'static) -> Self
+    where
+        T: Into<Ann<Expr>> + 'static,
+    {
+        Expr::seq(IterSeq(iter.map(Into::into)))
+    }
+
+    /// Creates a lazy sequence.
+    pub fn seq(seq: impl Seq + 'static) -> Self {
+        Expr::Iterator(Rc::new(RefCell::new(seq)))
+    }
+
+    /// Creates a present optional value, `(Some value)`.
+    pub fn some(value: impl Into<Ann<Expr>>) -> Self {
+        Expr::Maybe(Some(Box::new(value.into())))
+    }
+
+    /// Creates a missing optional value, `None`.
+    pub fn none() -> Self {
+        Expr::Maybe(None)
+    }
+
+    /// Creates an error value, `(Error value)`.
+    pub fn error(value: impl Into<Ann<Expr>>) -> Self {
+        Expr::Error(Box::new(value.into()))
+    }
+
     pub fn symbol(s: impl Into<String>) -> Self {
         Expr::Symbol(s.into())
     }
@@ -174,6 +304,31 @@ impl Expr {
     pub fn string(s: impl Into<String>) -> Self {
         Expr::String(s.into())
     }
+
+    /// Returns the namespace of a KeySymbol, e.g. `http` for `:http/status`.
+    /// Returns None for other variants or if the KeySymbol is not namespaced.
+    pub fn namespace(&self) -> Option<&str> {
+        let Expr::KeySymbol(s) = self else {
+            return None;
+        };
+
+        s.split_once('/').map(|(namespace, _)| namespace)
+    }
+
+    /// Returns the name of a KeySymbol, e.g. `status` for `:http/status`.
+    /// Returns None for other variants.
+    pub fn name(&self) -> Option<&str> {
+        let Expr::KeySymbol(s) = self else {
+            return None;
+        };
+
+        Some(s.split_once('/').map_or(s.as_str(), |(_, name)| name))
+    }
+}
+
+/// Encodes the bytes as a lowercase hex string, two digits per byte.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
 }
 
 // #TODO think where this function is used. (it is used for Dict keys, hmm...)
@@ -184,10 +339,33 @@ pub fn format_value(expr: impl AsRef<Expr>) -> String {
     match expr {
         Expr::String(s) => s.to_string(),
         Expr::KeySymbol(s) => s.to_string(),
+        Expr::BigInt(n) => n.to_string(),
+        Expr::Decimal(n) => n.to_string(),
         _ => expr.to_string(),
     }
 }
 
+// #TODO support Expr as Dict keys, remove this function.
+/// Returns the Dict key of the expression. A foreign value is only a valid key
+/// if its type has a registered hash function, values with colliding hashes
+/// are told apart with the registered eq function.
+pub fn dict_key(expr: impl AsRef<Expr>) -> Result<String, Error> {
+    let expr = expr.as_ref();
+    match expr {
+        Expr::Foreign(value) => {
+            let Some((hash, index)) = value.key() else {
+                return Err(Error::invalid_arguments(format!(
+                    "foreign values of type `{}` cannot be used as Dict keys, no hash function is registered",
+                    value.type_name()
+                )));
+            };
+
+            Ok(format!("#<{}:{hash:016x}:{index}>", value.type_name()))
+        }
+        _ => Ok(format_value(expr)),
+    }
+}
+
 // #TODO use `.into()` to convert Expr to Annotated<Expr>.
 
 #[cfg(test)]
@@ -199,4 +377,18 @@ mod tests {
         let expr = Expr::string("hello");
         assert_eq!("\"hello\"", format!("{expr}"));
     }
+
+    #[test]
+    fn key_symbol_accessors() {
+        let expr = Expr::KeySymbol("http/status".to_owned());
+        assert_eq!(expr.namespace(), Some("http"));
+        assert_eq!(expr.name(), Some("status"));
+
+        let expr = Expr::KeySymbol("status".to_owned());
+        assert_eq!(expr.namespace(), None);
+        assert_eq!(expr.name(), Some("status"));
+
+        let expr = Expr::symbol("status");
+        assert_eq!(expr.name(), None);
+    }
 }
@@ -0,0 +1,200 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{ann::Ann, error::Error};
+
+use super::Expr;
+
+// #Insight
+// The conversions between Rust values and expressions, used to expose plain
+// Rust functions to Tan, see `api::register_host_fn`. The type names are the
+// Tan types of the converted values, used to register the functions as
+// methods.
+
+// #TODO support tuples, to/from Arrays.
+// #TODO support BigInt and Decimal.
+
+/// A Rust value that can be extracted from an expression.
+pub trait FromExpr: Sized {
+    /// The Tan type of the expression, e.g. `Int`.
+    const TYPE_NAME: &'static str;
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error>;
+}
+
+/// A Rust value that can be converted to an expression.
+pub trait IntoExpr {
+    /// The Tan type of the expression, e.g. `Int`.
+    const TYPE_NAME: &'static str;
+
+    fn into_expr(self) -> Ann<Expr>;
+}
+
+fn type_error(type_name: &str, expr: &Ann<Expr>) -> Error {
+    Error::invalid_arguments(format!("expected {type_name}, found `{expr}`"))
+}
+
+impl FromExpr for Ann<Expr> {
+    const TYPE_NAME: &'static str = "Any";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        Ok(expr.clone())
+    }
+}
+
+impl IntoExpr for Ann<Expr> {
+    const TYPE_NAME: &'static str = "Any";
+
+    fn into_expr(self) -> Ann<Expr> {
+        self
+    }
+}
+
+impl IntoExpr for () {
+    const TYPE_NAME: &'static str = "Unit";
+
+    fn into_expr(self) -> Ann<Expr> {
+        Expr::One.into()
+    }
+}
+
+impl FromExpr for i64 {
+    const TYPE_NAME: &'static str = "Int";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::Int(n) => Ok(*n),
+            _ => Err(type_error("Int", expr)),
+        }
+    }
+}
+
+impl IntoExpr for i64 {
+    const TYPE_NAME: &'static str = "Int";
+
+    fn into_expr(self) -> Ann<Expr> {
+        Ann::with_type(Expr::Int(self), Expr::symbol("Int"))
+    }
+}
+
+impl FromExpr for f64 {
+    const TYPE_NAME: &'static str = "Float";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::Float(n) => Ok(*n),
+            _ => Err(type_error("Float", expr)),
+        }
+    }
+}
+
+impl IntoExpr for f64 {
+    const TYPE_NAME: &'static str = "Float";
+
+    fn into_expr(self) -> Ann<Expr> {
+        Ann::with_type(Expr::Float(self), Expr::symbol("Float"))
+    }
+}
+
+impl FromExpr for bool {
+    const TYPE_NAME: &'static str = "Bool";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::Bool(b) => Ok(*b),
+            _ => Err(type_error("Bool", expr)),
+        }
+    }
+}
+
+impl IntoExpr for bool {
+    const TYPE_NAME: &'static str = "Bool";
+
+    fn into_expr(self) -> Ann<Expr> {
+        Ann::with_type(Expr::Bool(self), Expr::symbol("Bool"))
+    }
+}
+
+impl FromExpr for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::String(s) => Ok(s.clone()),
+            _ => Err(type_error("String", expr)),
+        }
+    }
+}
+
+impl IntoExpr for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn into_expr(self) -> Ann<Expr> {
+        Ann::with_type(Expr::String(self), Expr::symbol("String"))
+    }
+}
+
+impl IntoExpr for &str {
+    const TYPE_NAME: &'static str = "String";
+
+    fn into_expr(self) -> Ann<Expr> {
+        self.to_owned().into_expr()
+    }
+}
+
+impl<T: FromExpr> FromExpr for Vec<T> {
+    const TYPE_NAME: &'static str = "Array";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::Array(items) => items.iter().map(T::from_expr).collect(),
+            _ => Err(type_error("Array", expr)),
+        }
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Vec<T> {
+    const TYPE_NAME: &'static str = "Array";
+
+    fn into_expr(self) -> Ann<Expr> {
+        let items = self.into_iter().map(IntoExpr::into_expr).collect();
+        Ann::with_type(Expr::Array(items), Expr::symbol("Array"))
+    }
+}
+
+impl<T: FromExpr> FromExpr for HashMap<String, T> {
+    const TYPE_NAME: &'static str = "Dict";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        match expr.as_ref() {
+            Expr::Dict(dict) => dict
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), T::from_expr(value)?)))
+                .collect(),
+            _ => Err(type_error("Dict", expr)),
+        }
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for HashMap<String, T> {
+    const TYPE_NAME: &'static str = "Dict";
+
+    fn into_expr(self) -> Ann<Expr> {
+        let dict: BTreeMap<String, Ann<Expr>> = self
+            .into_iter()
+            .map(|(key, value)| (key, value.into_expr()))
+            .collect();
+        Ann::with_type(Expr::Dict(dict), Expr::symbol("Dict"))
+    }
+}
+
+impl<T: IntoExpr> IntoExpr for Option<T> {
+    const TYPE_NAME: &'static str = "Maybe";
+
+    fn into_expr(self) -> Ann<Expr> {
+        let value = match self {
+            Some(value) => Expr::some(value.into_expr()),
+            None => Expr::none(),
+        };
+        Ann::with_type(value, Expr::symbol("Maybe"))
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use crate::{ann::Ann, error::Error, range::Ranged};
+
+use super::{format_value, Expr};
+
+// #Insight
+// Tan is homoiconic, the Tan syntax is also a data format, e.g. for
+// configuration files. The data expressions are read without evaluation, only
+// literal values are accepted: Unit, Bools, numbers, Strings, KeySymbols,
+// Chars, Arrays, Dicts and Maybe values, e.g.
+//
+// {:name "George" :tags ["a" "b"] :score 1.5 :email None}
+//
+// The written text reads back to an equal value. The Dict keys are written as
+// Strings, like the Dict keys of the reader.
+
+// #TODO support a pretty-printed (multi-line) output.
+// #TODO support Lists of data.
+
+fn not_data(expr: &Ann<Expr>) -> Ranged<Error> {
+    Ranged(
+        Error::invalid_arguments(format!("`{expr}` is not a data expression")),
+        expr.get_range(),
+    )
+}
+
+/// Converts a parsed expression to a data value, without evaluation. Returns
+/// an error if the expression is not a literal data expression.
+pub fn expr_to_data(expr: &Ann<Expr>) -> Result<Ann<Expr>, Ranged<Error>> {
+    let value = match &expr.0 {
+        Expr::One
+        | Expr::Bool(..)
+        | Expr::Int(..)
+        | Expr::Float(..)
+        | Expr::BigInt(..)
+        | Expr::Decimal(..)
+        | Expr::String(..)
+        | Expr::KeySymbol(..)
+        | Expr::Char(..) => expr.0.clone(),
+        Expr::Symbol(sym) if sym == "None" => Expr::Maybe(None),
+        Expr::List(terms) => {
+            let Some((Ann(Expr::Symbol(head), ..), tail)) = terms.split_first() else {
+                return Err(not_data(expr));
+            };
+
+            match (head.as_str(), tail) {
+                ("Array", items) => {
+                    Expr::Array(items.iter().map(expr_to_data).collect::<Result<_, _>>()?)
+                }
+                ("Dict", items) => {
+                    let mut dict = BTreeMap::new();
+                    // The parser ensures that all keys have values.
+                    for pair in items.chunks(2) {
+                        if let [key, value] = pair {
+                            let key = expr_to_data(key)?;
+                            dict.insert(format_value(&key), expr_to_data(value)?);
+                        }
+                    }
+                    Expr::Dict(dict)
+                }
+                ("Some", [value]) => Expr::Maybe(Some(Box::new(expr_to_data(value)?))),
+                ("Char", [Ann(Expr::String(s), ..)]) if s.chars().count() == 1 => {
+                    // The unwrap is safe, the string has one char.
+                    Expr::Char(s.chars().next().unwrap())
+                }
+                _ => return Err(not_data(expr)),
+            }
+        }
+        _ => return Err(not_data(expr)),
+    };
+
+    Ok(value.into())
+}
+
+/// Writes a String literal, with escape sequences.
+fn write_string(s: &str, output: &mut String) {
+    output.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\t' => output.push_str("\\t"),
+            '\r' => output.push_str("\\r"),
+            '\0' => output.push_str("\\0"),
+            // Escaped, to prevent interpolation.
+            '$' => output.push_str("\\$"),
+            _ => output.push(ch),
+        }
+    }
+    output.push('"');
+}
+
+fn write_data(expr: &Expr, output: &mut String) -> Result<(), Error> {
+    match expr {
+        Expr::One => output.push_str("()"),
+        Expr::Float(n) => {
+            if !n.is_finite() {
+                return Err(Error::invalid_arguments(format!(
+                    "the Float `{n}` cannot be written as data"
+                )));
+            }
+            // A Float is always written with a decimal point, to read back
+            // as a Float, e.g. `1.0`, `1.0e20`.
+            let s = format!("{n:?}");
+            match s.split_once('e') {
+                Some((mantissa, exponent)) if !mantissa.contains('.') => {
+                    output.push_str(&format!("{mantissa}.0e{exponent}"));
+                }
+                _ => output.push_str(&s),
+            }
+        }
+        Expr::Bool(..) | Expr::Int(..) | Expr::BigInt(..) | Expr::Decimal(..) => {
+            output.push_str(&expr.to_string())
+        }
+        Expr::KeySymbol(s) => {
+            output.push(':');
+            output.push_str(s);
+        }
+        Expr::String(s) => write_string(s, output),
+        Expr::Char(c) => {
+            output.push_str("(Char ");
+            write_string(&c.to_string(), output);
+            output.push(')');
+        }
+        Expr::Maybe(None) => output.push_str("None"),
+        Expr::Maybe(Some(value)) => {
+            output.push_str("(Some ");
+            write_data(&value.0, output)?;
+            output.push(')');
+        }
+        Expr::Array(items) => {
+            output.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    output.push(' ');
+                }
+                write_data(&item.0, output)?;
+            }
+            output.push(']');
+        }
+        Expr::Dict(dict) => {
+            output.push('{');
+            for (i, (key, value)) in dict.iter().enumerate() {
+                if i > 0 {
+                    output.push(' ');
+                }
+                write_string(key, output);
+                output.push(' ');
+                write_data(&value.0, output)?;
+            }
+            output.push('}');
+        }
+        _ => {
+            return Err(Error::invalid_arguments(format!(
+                "`{expr}` cannot be written as data"
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a data value as Tan text, the text reads back to an equal value,
+/// see `expr_to_data`.
+pub fn data_to_string(expr: &Expr) -> Result<String, Error> {
+    let mut output = String::new();
+    write_data(expr, &mut output)?;
+    Ok(output)
+}
@@ -34,7 +34,7 @@ impl<'a> Iterator for ExprIter<'a> {
 
     // #TODO this does not traverse Array, Dict, etc.
     fn next(&mut self) -> Option<Self::Item> {
-        let expr = self.children.get(0);
+        let expr = self.children.first();
 
         match expr {
             None => match self.parent.take() {
@@ -56,7 +56,7 @@ impl<'a> Iterator for ExprIter<'a> {
                 expr
             }
             _ => {
-                // let x = self.children.get(0);
+                // let x = self.children.first();
                 self.children = &self.children[1..];
                 expr
             }
@@ -21,6 +21,53 @@ impl Ann<Expr> {
             _ => f(self),
         }
     }
+
+    /// Transforms the expression bottom-up, the fallible `f` mapping function
+    /// is applied to the items of the Lists, Arrays and Dicts before the
+    /// collection itself (post-order).
+    pub fn try_transform<F, E>(self, f: &mut F) -> Result<Self, E>
+    where
+        F: FnMut(Self) -> Result<Self, E>,
+    {
+        let expr = self.try_map_items(|item| item.try_transform(f))?;
+        f(expr)
+    }
+
+    /// Transforms the expression top-down, the fallible `f` mapping function
+    /// is applied to a collection before its items (pre-order), the items of
+    /// the mapped collection are transformed.
+    pub fn try_transform_pre<F, E>(self, f: &mut F) -> Result<Self, E>
+    where
+        F: FnMut(Self) -> Result<Self, E>,
+    {
+        f(self)?.try_map_items(|item| item.try_transform_pre(f))
+    }
+
+    /// Maps the direct items of a List, Array or Dict, any other expression is
+    /// returned as-is.
+    fn try_map_items<F, E>(self, mut f: F) -> Result<Self, E>
+    where
+        F: FnMut(Self) -> Result<Self, E>,
+    {
+        match self {
+            Ann(Expr::List(terms), ann) => {
+                let terms = terms.into_iter().map(f).collect::<Result<_, _>>()?;
+                Ok(Ann(Expr::List(terms), ann))
+            }
+            Ann(Expr::Array(items), ann) => {
+                let items = items.into_iter().map(f).collect::<Result<_, _>>()?;
+                Ok(Ann(Expr::Array(items), ann))
+            }
+            Ann(Expr::Dict(dict), ann) => {
+                let dict = dict
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, f(value)?)))
+                    .collect::<Result<_, _>>()?;
+                Ok(Ann(Expr::Dict(dict), ann))
+            }
+            _ => Ok(self),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -43,4 +90,27 @@ mod tests {
 
         assert_eq!(expr_string, expr_transformed.0.to_string());
     }
+
+    #[test]
+    fn try_transform_visits_the_items_in_order() {
+        let expr: Ann<Expr> = Expr::Array(vec![
+            Expr::Int(1).into(),
+            Expr::List(vec![Expr::Int(2).into()]).into(),
+        ])
+        .into();
+
+        let mut visited = Vec::new();
+        let _ = expr.clone().try_transform(&mut |expr: Ann<Expr>| {
+            visited.push(expr.0.to_string());
+            Ok::<_, ()>(expr)
+        });
+        assert_eq!(visited, ["1", "2", "(2)", "[1 (2)]"]);
+
+        let mut visited = Vec::new();
+        let _ = expr.try_transform_pre(&mut |expr: Ann<Expr>| {
+            visited.push(expr.0.to_string());
+            Ok::<_, ()>(expr)
+        });
+        assert_eq!(visited, ["[1 (2)]", "1", "(2)", "2"]);
+    }
 }
@@ -0,0 +1,214 @@
+use std::{any::Any, cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+// #Insight
+// Foreign values are host (Rust) objects, opaque to Tan. The host can register
+// hooks per type-name to control how the values are rendered in script output
+// and error messages, compared, and hashed (e.g. to be used as Dict keys).
+
+// #TODO move the hooks registry to a runtime context.
+// #TODO consider using TypeId as the registry key.
+
+/// A function that renders a foreign value.
+pub type ForeignPrinter = dyn Fn(&ForeignValue) -> String;
+
+/// A function that compares two foreign values of the same type.
+pub type ForeignEq = dyn Fn(&ForeignValue, &ForeignValue) -> bool;
+
+/// A function that hashes a foreign value. Equal values must have equal hashes.
+pub type ForeignHash = dyn Fn(&ForeignValue) -> u64;
+
+/// The host-provided hooks of a foreign type.
+#[derive(Default, Clone)]
+struct ForeignHooks {
+    printer: Option<Rc<ForeignPrinter>>,
+    eq: Option<Rc<ForeignEq>>,
+    hash: Option<Rc<ForeignHash>>,
+}
+
+thread_local! {
+    static HOOKS: RefCell<HashMap<String, ForeignHooks>> = RefCell::new(HashMap::new());
+}
+
+// #Insight
+// The Dict keys are Strings, a hash alone does not identify a foreign key,
+// distinct values may collide. The keyed values are interned per type-name and
+// hash, the key of a value is the index of the first interned value equal to
+// it, so colliding values get distinct keys and equal values get the same key.
+
+// #TODO the interned key values are never released.
+
+/// The interned keyed values, per type-name and hash.
+type KeySlots = HashMap<(Rc<str>, u64), Vec<ForeignValue>>;
+
+thread_local! {
+    static KEYS: RefCell<KeySlots> = RefCell::new(HashMap::new());
+}
+
+fn update_hooks(type_name: impl Into<String>, f: impl FnOnce(&mut ForeignHooks)) {
+    HOOKS.with(|hooks| f(hooks.borrow_mut().entry(type_name.into()).or_default()));
+}
+
+// #Insight
+// The hooks are cloned out of the registry before invocation, to allow hooks
+// that process nested foreign values.
+
+fn get_hooks(type_name: &str) -> ForeignHooks {
+    HOOKS.with(|hooks| hooks.borrow().get(type_name).cloned().unwrap_or_default())
+}
+
+/// Registers a printer for the foreign values of the given type-name, replaces
+/// any previously registered printer.
+pub fn register_printer(
+    type_name: impl Into<String>,
+    printer: impl Fn(&ForeignValue) -> String + 'static,
+) {
+    update_hooks(type_name, |hooks| hooks.printer = Some(Rc::new(printer)));
+}
+
+/// Removes the printer of the given type-name.
+pub fn unregister_printer(type_name: &str) {
+    update_hooks(type_name, |hooks| hooks.printer = None);
+}
+
+/// Registers an equality function for the foreign values of the given type-name.
+/// Without an equality function, foreign values are compared by identity.
+pub fn register_eq(
+    type_name: impl Into<String>,
+    eq: impl Fn(&ForeignValue, &ForeignValue) -> bool + 'static,
+) {
+    update_hooks(type_name, |hooks| hooks.eq = Some(Rc::new(eq)));
+}
+
+/// Registers a hash function for the foreign values of the given type-name.
+/// Without a hash function, foreign values cannot be used as Dict keys.
+pub fn register_hash(type_name: impl Into<String>, hash: impl Fn(&ForeignValue) -> u64 + 'static) {
+    update_hooks(type_name, |hooks| hooks.hash = Some(Rc::new(hash)));
+}
+
+/// A host value, wrapped to be used as a Tan expression.
+#[derive(Clone)]
+pub struct ForeignValue {
+    type_name: Rc<str>,
+    value: Rc<dyn Any>,
+}
+
+impl ForeignValue {
+    pub fn new<T: Any>(type_name: impl Into<Rc<str>>, value: T) -> Self {
+        Self {
+            type_name: type_name.into(),
+            value: Rc::new(value),
+        }
+    }
+
+    /// Returns the Tan type-name of the value.
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    /// Returns a reference to the wrapped host value, if it is of type `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref()
+    }
+
+    /// Returns true if both values wrap the same host object.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+
+    /// Returns the hash of the value, or None if the type has no registered
+    /// hash function.
+    pub fn hash(&self) -> Option<u64> {
+        get_hooks(self.type_name()).hash.map(|hash| hash(self))
+    }
+
+    /// Returns the hash of the value and the index that distinguishes it from
+    /// the unequal values with the same hash, if the type has a registered
+    /// hash function.
+    pub fn key(&self) -> Option<(u64, usize)> {
+        let hash = self.hash()?;
+        let slot = (self.type_name.clone(), hash);
+
+        // The interned values are cloned out, the eq hook may key nested values.
+        let interned = KEYS.with(|keys| keys.borrow().get(&slot).cloned().unwrap_or_default());
+
+        if let Some(index) = interned.iter().position(|value| value == self) {
+            return Some((hash, index));
+        }
+
+        let index = KEYS.with(|keys| {
+            let mut keys = keys.borrow_mut();
+            let values = keys.entry(slot).or_default();
+            values.push(self.clone());
+            values.len() - 1
+        });
+
+        Some((hash, index))
+    }
+}
+
+impl PartialEq for ForeignValue {
+    fn eq(&self, other: &Self) -> bool {
+        if self.type_name != other.type_name {
+            return false;
+        }
+
+        if let Some(eq) = get_hooks(self.type_name()).eq {
+            eq(self, other)
+        } else {
+            self.ptr_eq(other)
+        }
+    }
+}
+
+impl fmt::Display for ForeignValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(printer) = get_hooks(self.type_name()).printer {
+            f.write_str(&printer(self))
+        } else {
+            write!(f, "#<{}>", self.type_name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{register_eq, register_hash, register_printer, ForeignValue};
+
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    #[test]
+    fn foreign_value_uses_registered_printer() {
+        let value = ForeignValue::new("Point", Point { x: 1, y: 2 });
+        assert_eq!(value.to_string(), "#<Point>");
+
+        register_printer("Point", |value| {
+            let point = value.downcast_ref::<Point>().unwrap();
+            format!("(Point {} {})", point.x, point.y)
+        });
+
+        assert_eq!(value.to_string(), "(Point 1 2)");
+    }
+
+    #[test]
+    fn foreign_value_uses_registered_eq_and_hash() {
+        let a = ForeignValue::new("Id", 7_u64);
+        let b = ForeignValue::new("Id", 7_u64);
+
+        // Identity comparison by default.
+        assert!(a == a.clone());
+        assert!(a != b);
+        assert_eq!(a.hash(), None);
+
+        register_eq("Id", |a, b| {
+            a.downcast_ref::<u64>() == b.downcast_ref::<u64>()
+        });
+        register_hash("Id", |value| *value.downcast_ref::<u64>().unwrap());
+
+        assert!(a == b);
+        assert_eq!(a.hash(), Some(7));
+        assert!(a != ForeignValue::new("Other", 7_u64));
+    }
+}
@@ -0,0 +1,86 @@
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use crate::error::Error;
+
+use super::Expr;
+
+// #Insight
+// A type annotation before a number literal sets the type of the literal,
+// e.g. `#U8 255`, `#I32 7`, `#Float 1`. The sized integers are represented as
+// Ints at runtime, the type annotation is kept for the typecheck. A literal
+// out of the range of its type is a parse error.
+
+// #TODO support U64, it does not fit in the Int representation.
+// #TODO support sized Floats, e.g. F32.
+
+/// The sized integer types, with their ranges.
+const SIZED_INT_TYPES: [(&str, i64, i64); 7] = [
+    ("I8", i8::MIN as i64, i8::MAX as i64),
+    ("U8", u8::MIN as i64, u8::MAX as i64),
+    ("I16", i16::MIN as i64, i16::MAX as i64),
+    ("U16", u16::MIN as i64, u16::MAX as i64),
+    ("I32", i32::MIN as i64, i32::MAX as i64),
+    ("U32", u32::MIN as i64, u32::MAX as i64),
+    ("I64", i64::MIN, i64::MAX),
+];
+
+/// Returns the range of a sized integer type, e.g. `(0, 255)` for `U8`.
+pub fn sized_int_range(type_name: &str) -> Option<(i64, i64)> {
+    SIZED_INT_TYPES
+        .iter()
+        .find(|(name, ..)| *name == type_name)
+        .map(|(_, min, max)| (*min, *max))
+}
+
+/// Returns `Int` for a sized integer type, the type of its runtime
+/// representation, any other type is returned as-is.
+pub fn widen_int_type(type_name: &str) -> &str {
+    if sized_int_range(type_name).is_some() {
+        "Int"
+    } else {
+        type_name
+    }
+}
+
+/// Returns true if the type is a number type, a literal can be annotated with
+/// the type.
+pub fn is_number_type(type_name: &str) -> bool {
+    matches!(type_name, "Int" | "Float" | "BigInt" | "Decimal")
+        || sized_int_range(type_name).is_some()
+}
+
+/// Converts a number literal to the number type. Returns an error if the
+/// literal cannot be represented in the type, e.g. `#U8 256`, `#Int 1.5`.
+pub fn convert_number_literal(expr: &Expr, type_name: &str) -> Result<Expr, Error> {
+    let out_of_range = || Error::NumberOutOfRange(expr.to_string(), type_name.to_owned());
+
+    let value = match (expr, type_name) {
+        (Expr::Int(n), "Int") => Expr::Int(*n),
+        (Expr::Int(n), "Float") => Expr::Float(*n as f64),
+        (Expr::Int(n), "BigInt") => Expr::BigInt(BigInt::from(*n)),
+        (Expr::Int(n), "Decimal") => Expr::Decimal(Decimal::from(*n)),
+        (Expr::BigInt(n), "Int") => Expr::Int(i64::try_from(n).map_err(|_| out_of_range())?),
+        (Expr::BigInt(n), "BigInt") => Expr::BigInt(n.clone()),
+        (Expr::Float(n), "Float") => Expr::Float(*n),
+        (Expr::Float(n), "Decimal") => {
+            Expr::Decimal(Decimal::try_from(*n).map_err(|_| out_of_range())?)
+        }
+        (Expr::Decimal(n), "Decimal") => Expr::Decimal(*n),
+        (Expr::Int(..) | Expr::BigInt(..), _) => {
+            let n = match expr {
+                Expr::Int(n) => Some(*n),
+                Expr::BigInt(n) => i64::try_from(n).ok(),
+                _ => None,
+            };
+
+            match (n, sized_int_range(type_name)) {
+                (Some(n), Some((min, max))) if (min..=max).contains(&n) => Expr::Int(n),
+                _ => return Err(out_of_range()),
+            }
+        }
+        _ => return Err(out_of_range()),
+    };
+
+    Ok(value)
+}
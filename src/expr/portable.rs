@@ -0,0 +1,220 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
+use crate::{ann::Ann, eval::env::ScopeRef};
+
+use super::{Expr, FuncClause};
+
+// #Insight
+// Expressions are not thread-safe, they share data through Rc. A Portable is a
+// deep, thread-safe copy of an expression, that can be sent to another thread
+// and converted back to an expression there, e.g. to evaluate a function on a
+// worker thread.
+//
+// Host values (foreign values and functions), macros and iterators are not
+// portable. Functions are portable, but the captured scopes are dropped, the
+// receiver provides the scopes.
+
+// #TODO consider sharing the data between threads through Arc.
+
+/// A thread-safe copy of an annotated expression.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub struct Portable(pub PortableExpr, pub Option<BTreeMap<String, PortableExpr>>);
+
+/// A thread-safe copy of an expression, see `Portable`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "cache", derive(serde::Serialize, serde::Deserialize))]
+pub enum PortableExpr {
+    One,
+    Never,
+    Comment(String),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    BigInt(BigInt),
+    Decimal(Decimal),
+    Symbol(String),
+    KeySymbol(String),
+    Char(char),
+    String(String),
+    List(Vec<Portable>),
+    Array(Vec<Portable>),
+    Dict(BTreeMap<String, Portable>),
+    // The clauses of the function, the parameter patterns and the body.
+    Func(Vec<(Vec<Portable>, Portable)>),
+    Range(i64, i64, i64),
+    Buffer(Vec<u8>),
+    Maybe(Option<Box<Portable>>),
+    Error(Box<Portable>),
+    Do,
+    Let,
+    If(Box<Portable>, Box<Portable>, Option<Box<Portable>>),
+}
+
+fn portable_items(items: &[Ann<Expr>]) -> Option<Vec<Portable>> {
+    items.iter().map(Portable::from_ann).collect()
+}
+
+impl PortableExpr {
+    /// Copies the expression, returns None if the expression is not portable.
+    pub fn from_expr(expr: &Expr) -> Option<Self> {
+        let expr = match expr {
+            Expr::One => PortableExpr::One,
+            Expr::Never => PortableExpr::Never,
+            Expr::Comment(s) => PortableExpr::Comment(s.clone()),
+            Expr::Bool(b) => PortableExpr::Bool(*b),
+            Expr::Int(n) => PortableExpr::Int(*n),
+            Expr::Float(n) => PortableExpr::Float(*n),
+            Expr::BigInt(n) => PortableExpr::BigInt(n.clone()),
+            Expr::Decimal(n) => PortableExpr::Decimal(*n),
+            Expr::Symbol(s) => PortableExpr::Symbol(s.clone()),
+            Expr::KeySymbol(s) => PortableExpr::KeySymbol(s.clone()),
+            Expr::Char(c) => PortableExpr::Char(*c),
+            Expr::String(s) => PortableExpr::String(s.clone()),
+            Expr::List(items) => PortableExpr::List(portable_items(items)?),
+            Expr::Array(items) => PortableExpr::Array(portable_items(items)?),
+            Expr::Dict(dict) => PortableExpr::Dict(
+                dict.iter()
+                    .map(|(key, value)| Some((key.clone(), Portable::from_ann(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            Expr::Func(clauses, _) => PortableExpr::Func(
+                clauses
+                    .iter()
+                    .map(|clause| {
+                        Some((
+                            portable_items(&clause.params)?,
+                            Portable::from_ann(&clause.body)?,
+                        ))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Expr::Range(start, end, step) => PortableExpr::Range(*start, *end, *step),
+            Expr::Buffer(bytes) => PortableExpr::Buffer(bytes.to_vec()),
+            Expr::Maybe(value) => PortableExpr::Maybe(match value {
+                Some(value) => Some(Box::new(Portable::from_ann(value)?)),
+                None => None,
+            }),
+            Expr::Error(value) => PortableExpr::Error(Box::new(Portable::from_ann(value)?)),
+            Expr::Do => PortableExpr::Do,
+            Expr::Let => PortableExpr::Let,
+            Expr::If(predicate, then, otherwise) => PortableExpr::If(
+                Box::new(Portable::from_ann(predicate)?),
+                Box::new(Portable::from_ann(then)?),
+                match otherwise {
+                    Some(otherwise) => Some(Box::new(Portable::from_ann(otherwise)?)),
+                    None => None,
+                },
+            ),
+            Expr::Macro(..)
+            | Expr::ForeignFunc(..)
+            | Expr::Foreign(..)
+            | Expr::Iterator(..)
+            | Expr::Module(..) => {
+                return None;
+            }
+        };
+
+        Some(expr)
+    }
+
+    /// Converts back to an expression, the functions capture the given scopes.
+    pub fn to_expr(&self, scopes: &[ScopeRef]) -> Expr {
+        let items = |items: &[Portable]| -> Vec<Ann<Expr>> {
+            items.iter().map(|item| item.to_ann(scopes)).collect()
+        };
+
+        match self {
+            PortableExpr::One => Expr::One,
+            PortableExpr::Never => Expr::Never,
+            PortableExpr::Comment(s) => Expr::Comment(s.clone()),
+            PortableExpr::Bool(b) => Expr::Bool(*b),
+            PortableExpr::Int(n) => Expr::Int(*n),
+            PortableExpr::Float(n) => Expr::Float(*n),
+            PortableExpr::BigInt(n) => Expr::BigInt(n.clone()),
+            PortableExpr::Decimal(n) => Expr::Decimal(*n),
+            PortableExpr::Symbol(s) => Expr::Symbol(s.clone()),
+            PortableExpr::KeySymbol(s) => Expr::KeySymbol(s.clone()),
+            PortableExpr::Char(c) => Expr::Char(*c),
+            PortableExpr::String(s) => Expr::String(s.clone()),
+            PortableExpr::List(list) => Expr::List(items(list)),
+            PortableExpr::Array(array) => Expr::Array(items(array)),
+            PortableExpr::Dict(dict) => Expr::Dict(
+                dict.iter()
+                    .map(|(key, value)| (key.clone(), value.to_ann(scopes)))
+                    .collect(),
+            ),
+            PortableExpr::Func(clauses) => Expr::Func(
+                clauses
+                    .iter()
+                    .map(|(params, body)| FuncClause {
+                        params: items(params),
+                        body: body.to_ann(scopes),
+                    })
+                    .collect(),
+                Rc::from(scopes),
+            ),
+            PortableExpr::Range(start, end, step) => Expr::Range(*start, *end, *step),
+            PortableExpr::Buffer(bytes) => Expr::Buffer(bytes.as_slice().into()),
+            PortableExpr::Maybe(value) => {
+                Expr::Maybe(value.as_ref().map(|value| Box::new(value.to_ann(scopes))))
+            }
+            PortableExpr::Error(value) => Expr::error(value.to_ann(scopes)),
+            PortableExpr::Do => Expr::Do,
+            PortableExpr::Let => Expr::Let,
+            PortableExpr::If(predicate, then, otherwise) => Expr::If(
+                Box::new(predicate.to_ann(scopes)),
+                Box::new(then.to_ann(scopes)),
+                otherwise
+                    .as_ref()
+                    .map(|otherwise| Box::new(otherwise.to_ann(scopes))),
+            ),
+        }
+    }
+}
+
+impl Portable {
+    /// Copies the annotated expression, returns None if the expression is not
+    /// portable.
+    pub fn from_ann(expr: &Ann<Expr>) -> Option<Self> {
+        let ann = match &expr.1 {
+            Some(ann) => Some(
+                ann.iter()
+                    .map(|(name, value)| Some((name.clone(), PortableExpr::from_expr(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            None => None,
+        };
+
+        Some(Portable(PortableExpr::from_expr(&expr.0)?, ann))
+    }
+
+    /// Returns true if the expression is plain data, i.e. it contains no
+    /// functions.
+    pub fn is_data(&self) -> bool {
+        match &self.0 {
+            PortableExpr::Func(..) => false,
+            PortableExpr::List(items) | PortableExpr::Array(items) => {
+                items.iter().all(Portable::is_data)
+            }
+            PortableExpr::Dict(dict) => dict.values().all(Portable::is_data),
+            PortableExpr::Maybe(Some(value)) | PortableExpr::Error(value) => value.is_data(),
+            _ => true,
+        }
+    }
+
+    /// Converts back to an annotated expression, the functions capture the
+    /// given scopes.
+    pub fn to_ann(&self, scopes: &[ScopeRef]) -> Ann<Expr> {
+        let ann = self.1.as_ref().map(|ann| {
+            ann.iter()
+                .map(|(name, value)| (name.clone(), value.to_expr(scopes)))
+                .collect()
+        });
+
+        Ann(self.0.to_expr(scopes), ann)
+    }
+}
@@ -0,0 +1,46 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The items of a lazy sequence are pulled on demand, with access to the
+// environment, e.g. to invoke the function of a `map`. A Rust iterator cannot
+// access the environment, or report errors.
+
+/// A lazy sequence of expressions.
+pub trait Seq {
+    /// Returns the next item, or None if the sequence is exhausted.
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>>;
+}
+
+/// A shared lazy sequence, the state is shared between clones, i.e. the items
+/// are consumed once.
+pub type SeqRef = Rc<RefCell<dyn Seq>>;
+
+impl Seq for SeqRef {
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        let Ok(mut seq) = self.try_borrow_mut() else {
+            return Err(Error::invalid_arguments("the iterator is already being consumed").into());
+        };
+
+        seq.next(env)
+    }
+}
+
+impl Seq for Box<dyn Seq> {
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        self.as_mut().next(env)
+    }
+}
+
+/// A lazy sequence that wraps a Rust (host) iterator.
+pub struct IterSeq<I>(pub I);
+
+impl<I> Seq for IterSeq<I>
+where
+    I: Iterator<Item = Ann<Expr>>,
+{
+    fn next(&mut self, _env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        Ok(self.0.next())
+    }
+}
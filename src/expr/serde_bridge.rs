@@ -0,0 +1,513 @@
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{
+        self,
+        value::{self, MapAccessDeserializer, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any, ser, Serialize,
+};
+
+use crate::{ann::Ann, error::Error};
+
+use super::{convert::FromExpr, format_value, Expr};
+
+// #Insight
+// The serde bridge converts expressions to Rust values and back, e.g. to
+// accept typed configuration from a script:
+//
+// #[derive(Deserialize)]
+// struct Config { name: String, port: i64, tags: Vec<String> }
+//
+// let config: Config = from_expr(&eval_string(input, &mut env)?)?;
+//
+// The Dicts map to structs and maps, the Arrays to sequences and tuples. The
+// unit enum variants map to KeySymbols (or Strings), the variants with data
+// to single-entry Dicts, e.g. `{:Circle 2.0}`. `None` (or `()`) maps to
+// `Option::None`, any other value to `Some`.
+
+// #TODO support borrowed deserialization, e.g. `&str` fields.
+
+/// Converts an expression to a Rust value, e.g. a struct from a Dict.
+pub fn from_expr<T: DeserializeOwned>(expr: &Ann<Expr>) -> Result<T, Error> {
+    T::deserialize(ExprDeserializer(expr))
+        .map_err(|error| Error::invalid_arguments(error.to_string()))
+}
+
+/// Converts a Rust value to an expression, e.g. a struct to a Dict.
+pub fn to_expr<T: Serialize + ?Sized>(value: &T) -> Result<Ann<Expr>, Error> {
+    value
+        .serialize(ExprSerializer)
+        .map(Ann::new)
+        .map_err(|error| Error::invalid_arguments(error.to_string()))
+}
+
+/// A host function argument converted with serde, e.g. a struct, see
+/// `api::register_host_fn`.
+pub struct Serde<T>(pub T);
+
+impl<T: DeserializeOwned> FromExpr for Serde<T> {
+    const TYPE_NAME: &'static str = "Any";
+
+    fn from_expr(expr: &Ann<Expr>) -> Result<Self, Error> {
+        from_expr(expr).map(Serde)
+    }
+}
+
+// Deserialization
+
+struct ExprDeserializer<'a>(&'a Ann<Expr>);
+
+impl<'de> IntoDeserializer<'de, value::Error> for ExprDeserializer<'_> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn dict_deserializer<'de, 'a>(
+    dict: &'a BTreeMap<String, Ann<Expr>>,
+) -> MapDeserializer<'de, impl Iterator<Item = (&'a str, ExprDeserializer<'a>)>, value::Error> {
+    MapDeserializer::new(
+        dict.iter()
+            .map(|(key, value)| (key.as_str(), ExprDeserializer(value))),
+    )
+}
+
+impl<'de> de::Deserializer<'de> for ExprDeserializer<'_> {
+    type Error = value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0.as_ref() {
+            Expr::One => visitor.visit_unit(),
+            Expr::Maybe(None) => visitor.visit_none(),
+            Expr::Maybe(Some(value)) => visitor.visit_some(ExprDeserializer(value)),
+            Expr::Bool(b) => visitor.visit_bool(*b),
+            Expr::Int(n) => visitor.visit_i64(*n),
+            Expr::Float(n) => visitor.visit_f64(*n),
+            Expr::Char(c) => visitor.visit_char(*c),
+            Expr::String(s) | Expr::KeySymbol(s) => visitor.visit_str(s),
+            Expr::BigInt(..) | Expr::Decimal(..) => visitor.visit_string(format_value(self.0)),
+            Expr::Buffer(bytes) => visitor.visit_bytes(bytes),
+            Expr::Array(items) | Expr::List(items) => {
+                visitor.visit_seq(SeqDeserializer::new(items.iter().map(ExprDeserializer)))
+            }
+            Expr::Dict(dict) => visitor.visit_map(dict_deserializer(dict)),
+            _ => Err(de::Error::custom(format!(
+                "the expression `{}` is not data",
+                self.0
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0.as_ref() {
+            Expr::One | Expr::Maybe(None) => visitor.visit_none(),
+            Expr::Maybe(Some(value)) => visitor.visit_some(ExprDeserializer(value)),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.0.as_ref() {
+            Expr::String(s) | Expr::KeySymbol(s) => {
+                visitor.visit_enum(s.as_str().into_deserializer())
+            }
+            Expr::Dict(dict) if dict.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(dict_deserializer(dict)))
+            }
+            _ => Err(de::Error::custom(format!(
+                "expected an enum variant, found `{}`",
+                self.0
+            ))),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+// Serialization
+
+struct ExprSerializer;
+
+fn int<E: ser::Error>(value: impl TryInto<i64> + Copy + ToString) -> Result<Expr, E> {
+    value.try_into().map(Expr::Int).map_err(|_| {
+        E::custom(format!(
+            "the integer `{}` is out of range",
+            value.to_string()
+        ))
+    })
+}
+
+/// Wraps the value of an enum variant in a single-entry Dict.
+fn variant(name: &str, value: Expr) -> Expr {
+    Expr::Dict(BTreeMap::from([(name.to_owned(), Ann::new(value))]))
+}
+
+impl ser::Serializer for ExprSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = DictSerializer;
+    type SerializeStruct = DictSerializer;
+    type SerializeStructVariant = DictSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Expr, Self::Error> {
+        Ok(Expr::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Expr, Self::Error> {
+        Ok(Expr::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Expr, Self::Error> {
+        int(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Expr, Self::Error> {
+        Ok(Expr::Float(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Expr, Self::Error> {
+        Ok(Expr::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Expr, Self::Error> {
+        Ok(Expr::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Expr, Self::Error> {
+        Ok(Expr::string(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Expr, Self::Error> {
+        Ok(Expr::Buffer(v.into()))
+    }
+
+    fn serialize_none(self) -> Result<Expr, Self::Error> {
+        Ok(Expr::none())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Expr, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Expr, Self::Error> {
+        Ok(Expr::One)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Expr, Self::Error> {
+        Ok(Expr::One)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Expr, Self::Error> {
+        Ok(Expr::KeySymbol(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Expr, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Expr, Self::Error> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, Self::Error> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, Self::Error> {
+        Ok(SeqSerializer {
+            variant: Some(variant),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<DictSerializer, Self::Error> {
+        Ok(DictSerializer {
+            variant: None,
+            dict: BTreeMap::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<DictSerializer, Self::Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<DictSerializer, Self::Error> {
+        Ok(DictSerializer {
+            variant: Some(variant),
+            dict: BTreeMap::new(),
+            key: None,
+        })
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<Ann<Expr>>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), value::Error> {
+        self.items.push(Ann::new(value.serialize(ExprSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Expr, value::Error> {
+        let array = Expr::Array(self.items);
+        Ok(match self.variant {
+            Some(name) => variant(name, array),
+            None => array,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+struct DictSerializer {
+    variant: Option<&'static str>,
+    dict: BTreeMap<String, Ann<Expr>>,
+    key: Option<String>,
+}
+
+impl DictSerializer {
+    fn insert<T: Serialize + ?Sized>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<(), value::Error> {
+        self.dict
+            .insert(key, Ann::new(value.serialize(ExprSerializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Expr, value::Error> {
+        let dict = Expr::Dict(self.dict);
+        Ok(match self.variant {
+            Some(name) => variant(name, dict),
+            None => dict,
+        })
+    }
+}
+
+impl ser::SerializeMap for DictSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+        // The keys of a Dict are Strings, like `dict_key`.
+        let key = match key.serialize(ExprSerializer)? {
+            key @ (Expr::String(..)
+            | Expr::KeySymbol(..)
+            | Expr::Int(..)
+            | Expr::Bool(..)
+            | Expr::Char(..)) => format_value(&key),
+            key => {
+                return Err(ser::Error::custom(format!(
+                    "the key `{key}` is not supported"
+                )));
+            }
+        };
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| ser::Error::custom("the map value is serialized before the key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for DictSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for DictSerializer {
+    type Ok = Expr;
+    type Error = value::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Self::Error> {
+        self.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Expr, Self::Error> {
+        self.finish()
+    }
+}
@@ -0,0 +1,140 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    ser::{self, SerializeMap, SerializeSeq},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::ann::Ann;
+
+use super::{format_value, Expr};
+
+// #Insight
+// Only data expressions are serializable, i.e. the values that can be
+// represented in JSON-like formats. The annotations are not serialized.
+// KeySymbols and Chars are serialized as Strings, Lists as sequences, so the
+// round-trip is lossy for those variants, they are deserialized as Strings and
+// Arrays. BigInts and Decimals are also serialized as Strings, to preserve the
+// precision. `(Some value)` is serialized as the value, `None` as unit.
+
+// #TODO consider a tagged representation for lossless round-trips.
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Expr::One | Expr::Maybe(None) => serializer.serialize_unit(),
+            Expr::Maybe(Some(value)) => value.0.serialize(serializer),
+            Expr::Bool(b) => serializer.serialize_bool(*b),
+            Expr::Int(n) => serializer.serialize_i64(*n),
+            Expr::Float(n) => serializer.serialize_f64(*n),
+            Expr::String(s) => serializer.serialize_str(s),
+            Expr::KeySymbol(..) | Expr::Char(..) | Expr::BigInt(..) | Expr::Decimal(..) => {
+                serializer.serialize_str(&format_value(self))
+            }
+            Expr::Array(items) | Expr::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(&item.0)?;
+                }
+                seq.end()
+            }
+            Expr::Dict(dict) => {
+                let mut map = serializer.serialize_map(Some(dict.len()))?;
+                for (key, value) in dict {
+                    map.serialize_entry(key, &value.0)?;
+                }
+                map.end()
+            }
+            _ => Err(ser::Error::custom(format!(
+                "the expression `{self}` is not serializable"
+            ))),
+        }
+    }
+}
+
+impl Serialize for Ann<Expr> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+struct ExprVisitor;
+
+impl<'de> Visitor<'de> for ExprVisitor {
+    type Value = Expr;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a data expression")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Expr, E> {
+        Ok(Expr::One)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Expr, E> {
+        Ok(Expr::One)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Expr, D::Error> {
+        Expr::deserialize(deserializer)
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Expr, E> {
+        Ok(Expr::Bool(value))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Expr, E> {
+        Ok(Expr::Int(value))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Expr, E> {
+        i64::try_from(value)
+            .map(Expr::Int)
+            .map_err(|_| E::custom(format!("the integer `{value}` is out of range")))
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Expr, E> {
+        Ok(Expr::Float(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Expr, E> {
+        Ok(Expr::string(value))
+    }
+
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Expr, E> {
+        Ok(Expr::String(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Expr, A::Error> {
+        let mut items = Vec::new();
+
+        while let Some(item) = seq.next_element::<Expr>()? {
+            items.push(Ann::new(item));
+        }
+
+        Ok(Expr::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Expr, A::Error> {
+        let mut dict = BTreeMap::new();
+
+        while let Some((key, value)) = map.next_entry::<String, Expr>()? {
+            dict.insert(key, Ann::new(value));
+        }
+
+        Ok(Expr::Dict(dict))
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ExprVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ann<Expr> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Expr::deserialize(deserializer).map(Ann::new)
+    }
+}
@@ -7,13 +7,12 @@ use crate::{
     range::{Range, Ranged},
 };
 
-use self::token::Token;
+use self::token::{StringSegment, Token};
 
 // https://en.wikipedia.org/wiki/Lexical_analysis
 
 // #TODO lex_all, lex_single
 // #TODO introduce SemanticToken, with extra semantic information, _after_ parsing.
-// #TODO use annotations before number literals to set the type?
 // #TODO use (doc_comment ...) for doc-comments.
 // #TODO support `\ ` for escaped space in symbols.
 // #TODO implement PutBackIterator
@@ -120,11 +119,7 @@ impl<'a> Lexer<'a> {
     fn scan_lexeme(&mut self) -> String {
         let mut text = String::new();
 
-        loop {
-            let Some(ch) = self.next_char() else {
-                break;
-            };
-
+        while let Some(ch) = self.next_char() {
             // #TODO maybe whitespace does not need put_back, but need to adjust range.
             if is_whitespace(ch) || is_delimiter(ch) || is_eol(ch) {
                 self.put_back_char(ch);
@@ -141,11 +136,7 @@ impl<'a> Lexer<'a> {
     fn scan_line(&mut self) -> String {
         let mut comment = String::from("");
 
-        loop {
-            let Some(ch) = self.next_char() else {
-                break;
-            };
-
+        while let Some(ch) = self.next_char() {
             if is_eol(ch) {
                 break;
             }
@@ -156,9 +147,99 @@ impl<'a> Lexer<'a> {
         comment
     }
 
+    /// Scans an escape sequence, the leading `\` is already consumed, e.g.
+    /// `\n`, `\"`, `\u{1F600}`.
+    fn scan_escape(&mut self) -> Option<char> {
+        // An unterminated string is reported by the caller.
+        let ch = self.next_char()?;
+
+        let escaped = match ch {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+            'u' => {
+                // Unicode code point, e.g. `\u{1F600}`.
+                let mut seq = String::from("u");
+
+                if self.next_char() != Some('{') {
+                    self.push_error(Error::MalformedStringEscape(seq));
+                    return None;
+                }
+
+                seq.push('{');
+
+                while let Some(ch) = self.next_char() {
+                    seq.push(ch);
+
+                    if ch == '}' || ch == '"' {
+                        break;
+                    }
+                }
+
+                let code = seq
+                    .strip_prefix("u{")
+                    .and_then(|s| s.strip_suffix('}'))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .and_then(char::from_u32);
+
+                let Some(code) = code else {
+                    if seq.ends_with('"') {
+                        // Don't consume the closing quote.
+                        seq.pop();
+                        self.put_back_char('"');
+                    }
+                    self.push_error(Error::MalformedStringEscape(seq));
+                    return None;
+                };
+
+                code
+            }
+            _ => {
+                self.push_error(Error::MalformedStringEscape(ch.to_string()));
+                return None;
+            }
+        };
+
+        Some(escaped)
+    }
+
+    /// Scans the source of an interpolated expression, the leading `${` is
+    /// already consumed.
+    fn scan_interpolation(&mut self) -> Option<String> {
+        let mut source = String::new();
+
+        let mut nesting = 0;
+
+        loop {
+            let Some(ch) = self.next_char() else {
+                self.push_error(Error::UnterminatedString);
+                return None;
+            };
+
+            if ch == '{' {
+                nesting += 1;
+            } else if ch == '}' {
+                if nesting == 0 {
+                    break;
+                }
+                nesting -= 1;
+            }
+
+            source.push(ch);
+        }
+
+        Some(source)
+    }
+
     // #TODO support 'raw' strings, e.g. (write #raw "this is \ cool")
-    /// Scans a string lexeme.
-    fn scan_string(&mut self) -> Option<String> {
+    /// Scans a string lexeme. Escape sequences are processed and `${expr}`
+    /// interpolations produce an interpolated string.
+    fn scan_string(&mut self) -> Option<Token> {
+        let mut segments = Vec::new();
         let mut string = String::new();
 
         loop {
@@ -167,20 +248,53 @@ impl<'a> Lexer<'a> {
                 return None;
             };
 
-            if ch == '"' {
-                break;
+            match ch {
+                '"' => break,
+                '\\' => {
+                    // Continue scanning to detect more errors.
+                    if let Some(ch) = self.scan_escape() {
+                        string.push(ch);
+                    }
+                }
+                '$' => {
+                    let Some(ch1) = self.next_char() else {
+                        self.push_error(Error::UnterminatedString);
+                        return None;
+                    };
+
+                    if ch1 != '{' {
+                        self.put_back_char(ch1);
+                        string.push(ch);
+                        continue;
+                    }
+
+                    let source = self.scan_interpolation()?;
+
+                    if !string.is_empty() {
+                        segments.push(StringSegment::Text(std::mem::take(&mut string)));
+                    }
+                    segments.push(StringSegment::Expr(source));
+                }
+                _ => string.push(ch),
             }
+        }
 
-            string.push(ch);
+        if segments.is_empty() {
+            return Some(Token::String(string));
         }
 
-        Some(string)
+        if !string.is_empty() {
+            segments.push(StringSegment::Text(string));
+        }
+
+        Some(Token::InterpolatedString(segments))
     }
 
     // #TODO needs cleanup.
     // #TODO does not support leading tabs.
     // #TODO find better name, `scan_indented_string`.
     // #TODO support 'raw' strings, e.g. (write #raw "this is \ cool")
+    // #TODO support escape sequences and interpolation.
     /// Scans a multi-string 'layout'.
     fn scan_text(&mut self, indent: u64) -> Option<String> {
         let mut string = String::new();
@@ -235,11 +349,7 @@ impl<'a> Lexer<'a> {
 
         // #TODO only allow one level of nesting?
 
-        loop {
-            let Some(ch) = self.next_char() else {
-                break;
-            };
-
+        while let Some(ch) = self.next_char() {
             if ch == '(' {
                 nesting += 1;
             } else if ch == ')' {
@@ -309,6 +419,12 @@ impl<'a> Lexer<'a> {
                 '\'' => {
                     tokens.push(Ranged(Token::Quote, self.range()));
                 }
+                '`' => {
+                    tokens.push(Ranged(Token::QuasiQuote, self.range()));
+                }
+                '$' => {
+                    tokens.push(Ranged(Token::Unquote, self.range()));
+                }
                 '"' => {
                     let Some(ch1) = self.next_char() else {
                         self.push_error(Error::UnterminatedString);
@@ -352,10 +468,10 @@ impl<'a> Lexer<'a> {
 
                     self.put_back_char(ch1);
 
-                    let Some(string) = self.scan_string() else {
+                    let Some(token) = self.scan_string() else {
                         break;
                     };
-                    tokens.push(Ranged(Token::String(string), self.range()));
+                    tokens.push(Ranged(token, self.range()));
                 }
                 '-' => {
                     let Some(ch1) = self.next_char() else {
@@ -21,6 +21,14 @@ use std::fmt;
 
 // #TODO support #quot annotation?
 
+/// A segment of an interpolated string, either literal text or the source of
+/// an embedded expression, e.g. `"Hello ${name}!"`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringSegment {
+    Text(String),
+    Expr(String),
+}
+
 /// A lexical Token gives semantic meaning to a Lexeme.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
@@ -31,8 +39,11 @@ pub enum Token {
     LeftBrace,
     RightBrace,
     Quote,
+    QuasiQuote,
+    Unquote,
     // Char(char),
     String(String),
+    InterpolatedString(Vec<StringSegment>),
     Symbol(String),
     Number(String),
     Annotation(String),
@@ -52,8 +63,17 @@ impl fmt::Display for Token {
                 Token::LeftBrace => "{".to_owned(),
                 Token::RightBrace => "}".to_owned(),
                 Token::Quote => "'".to_owned(),
+                Token::QuasiQuote => "`".to_owned(),
+                Token::Unquote => "$".to_owned(),
                 // Token::Char(c) => c.to_string(), // #TODO should show the delimiters?
                 Token::String(s) => s.clone(), // #TODO should show the delimiters?
+                Token::InterpolatedString(segments) => segments
+                    .iter()
+                    .map(|segment| match segment {
+                        StringSegment::Text(s) => s.clone(),
+                        StringSegment::Expr(s) => format!("${{{s}}}"),
+                    })
+                    .collect(),
                 Token::Symbol(s) => s.clone(),
                 Token::Number(s) => s.clone(),
                 Token::Annotation(s) => s.clone(),
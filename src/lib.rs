@@ -1,5 +1,7 @@
 pub mod ann;
 pub mod api;
+#[cfg(feature = "emit")]
+pub mod emit;
 pub mod error;
 // pub mod error2;
 pub mod eval;
@@ -11,4 +13,8 @@ pub mod optimize;
 pub mod parser;
 pub mod range;
 pub mod resolver;
+pub mod runtime;
+pub mod style;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod util;
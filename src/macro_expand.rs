@@ -1,184 +1,226 @@
 use crate::{
     ann::Ann,
     error::Error,
-    eval::{env::Env, eval},
+    eval::{check_pattern, env::Env, eval},
     expr::Expr,
     range::Ranged,
-    util::is_reserved_symbol,
 };
 
 // #Insight it mutates the env which is used in eval also!
 
+// #Insight
+// The macro_expand pass runs between parsing and evaluation (resolving). It
+// collects the Macro definitions and expands the macro call-sites.
+
 // #TODO `elision`, `elide` sounds better than `prune`?
 // #TODO rename to `prune_expand`?
 // #TODO split prune and expand into separate passes?
 // #TODO consider renaming the expr parameter to ast?
 // #TODO macro_expand (and all comptime/static passes should return Vec<Ranged<Error>>>)
 // #TODO support multiple errors, like in resolve.
+// #TODO support hygienic macros.
+
+/// The maximum nesting of macro expansions, protects against infinitely
+/// recursive macros.
+pub const MAX_EXPANSION_DEPTH: usize = 128;
 
 /// Expands macro invocations, at compile time.
 pub fn macro_expand(expr: Ann<Expr>, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+    expand(expr, env, 0)
+}
+
+/// Macro-expands the terms of a list, pruned terms are removed.
+fn expand_terms(
+    terms: &[Ann<Expr>],
+    env: &mut Env,
+    depth: usize,
+) -> Result<Vec<Ann<Expr>>, Ranged<Error>> {
+    let mut expanded_terms = Vec::new();
+
+    for term in terms {
+        if let Some(term) = expand(term.clone(), env, depth)? {
+            expanded_terms.push(term);
+        }
+    }
+
+    Ok(expanded_terms)
+}
+
+fn expand(
+    expr: Ann<Expr>,
+    env: &mut Env,
+    depth: usize,
+) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
     match expr {
         Ann(Expr::Comment(..), ..) => {
             // Prune Comment expressions.
             Ok(None)
         }
-        Ann(Expr::List(ref list), ..) => {
-            // if list.is_empty() {
-            //     // This is handled statically, in the parser, but an extra, dynamic
-            //     // check is needed in the evaluator to handle the case where the
-            //     // expression is constructed programmatically (e.g. self-modifying code,
-            //     // dynamically constructed expression, homoiconicity, etc).
-            //     return Ok(None);
-            // }
-
-            let head = list.first().unwrap(); // The unwrap here is safe.
-            let tail = &list[1..];
-
-            // Evaluate the head
-            let Ok(head) = eval(head, env) else {
-                // Don't err if we cannot eval the head.
+        Ann(Expr::List(ref list), ref ann) => {
+            // This is handled statically, in the parser, but an extra, dynamic
+            // check is needed to handle the case where the expression is
+            // constructed programmatically.
+            let Some(head) = list.first() else {
                 return Ok(Some(expr));
             };
 
-            // #TODO can we remove this as_ref?
-            match head.as_ref() {
-                Expr::Macro(params, body) => {
-                    // This is the actual macro-expansion
-
-                    // #Insight
-                    // Macro arguments are lazily evaluated.
-
-                    let args = tail;
-
-                    // #TODO ultra-hack to kill shared ref to `env`.
-                    let params = params.clone();
-                    let body = body.clone();
-
-                    // #TODO what kind of scoping is this?
-
-                    env.push_new_scope();
-
-                    for (param, arg) in params.iter().zip(args) {
-                        let Ann(Expr::Symbol(param), ..) = param else {
-                                return Err(Ranged(Error::invalid_arguments("parameter is not a symbol"), param.get_range()));
-                            };
-
-                        env.insert(param, arg.clone());
-                    }
+            let tail = &list[1..];
 
-                    let result = eval(&body, env)?;
+            // #Insight
+            // The head is not evaluated, macros are looked-up by symbol.
 
-                    env.pop();
+            let Ann(Expr::Symbol(sym), ..) = head else {
+                // Other kind of list with non-symbol head, macro-expand all terms.
+                let terms = expand_terms(list, env, depth)?;
+                return Ok(Some(Ann(Expr::List(terms), ann.clone())));
+            };
 
-                    Ok(Some(result))
-                }
-                Expr::Symbol(sym) => {
-                    // #TODO oof the checks here happen also in resolver and eval, fix!
-                    // #TODO actually we should use `def` for this purpose, instead of `let`.
-                    if sym == "let" {
-                        let mut args = tail.iter();
+            if let Some(feature) = env.context.features.disabled_form(sym) {
+                return Err(Ranged(
+                    Error::FeatureDisabled(sym.clone(), feature.to_string()),
+                    expr.get_range(),
+                ));
+            }
 
-                        // #TODO should be def, no loop.
+            // #TODO oof the checks here happen also in resolver and eval, fix!
+            // #TODO actually we should use `def` for this purpose, instead of `let`.
+            match sym.as_str() {
+                "let" => {
+                    let mut args = tail.iter();
 
-                        let Some(binding_sym) = args.next() else {
-                            return Err(Ranged(Error::invalid_arguments("missing binding symbol"), expr.get_range()));
-                        };
+                    let mut terms = vec![head.clone()];
 
+                    while let Some(binding_sym) = args.next() {
                         let Some(binding_value) = args.next() else {
                             return Err(Ranged(Error::invalid_arguments("missing binding value"), expr.get_range()));
                         };
 
-                        let Ann(Expr::Symbol(s), ..) = binding_sym else {
-                            return Err(Ranged(Error::invalid_arguments(format!("`{sym}` is not a Symbol")), binding_sym.get_range()));
-                        };
+                        let syms = check_pattern(binding_sym)?;
 
-                        if is_reserved_symbol(s) {
-                            return Err(Ranged(
-                                Error::invalid_arguments(format!(
-                                    "let cannot shadow the reserved symbol `{s}`"
-                                )),
-                                binding_sym.get_range(),
-                            ));
-                        }
+                        let binding_value = expand(binding_value.clone(), env, depth)?;
 
-                        let binding_value = macro_expand(binding_value.clone(), env)?;
+                        // #TODO argh, remove the unwrap!
+                        let binding_value = binding_value.unwrap_or_else(|| Expr::One.into());
 
                         // #TODO notify about overrides? use `set`?
                         // #TODO consider if we should allow redefinitions.
 
-                        if let Some(Ann(Expr::Macro(..), ..)) = binding_value {
+                        if let (Ann(Expr::Macro(..), ..), [s]) = (&binding_value, &syms[..]) {
                             // #TODO put all the definitions in one pass.
                             // Only define macros in this pass.
-                            env.insert(s, binding_value.unwrap());
+                            env.insert(*s, binding_value);
 
-                            // #TODO verify with unit-test.
                             // Macro definition is pruned.
-                            return Ok(None);
+                            continue;
                         }
 
-                        Ok(Some(
-                            Expr::List(vec![
-                                Expr::Symbol("let".to_owned()).into(),
-                                binding_sym.clone(),
-                                binding_value.unwrap(), // #TODO argh, remove the unwrap!
-                            ])
-                            .into(),
-                        ))
-                    } else if sym == "quot" {
-                        let [value] = tail else {
-                                return Err(Ranged(Error::invalid_arguments("missing quote target"), expr.get_range()));
-                            };
-
-                        // #TODO super nasty, quotes should be resolved statically (at compile time)
-                        // #TODO hm, that clone, maybe `Rc` can fix this?
-                        Ok(Some(
-                            Expr::List(vec![
-                                Expr::Symbol("quot".to_owned()).into(),
-                                value.0.clone().into(),
-                            ])
-                            .into(),
-                        ))
-                    } else if sym == "Macro" {
-                        let [args, body] = tail else {
-                            return Err(Ranged(Error::invalid_arguments("malformed macro definition"), expr.get_range()));
-                        };
-
-                        let Ann(Expr::List(params), ..) = args else {
-                            return Err(Ranged(Error::invalid_arguments("malformed macro parameters definition"), expr.get_range()));
-                        };
+                        terms.push(binding_sym.clone());
+                        terms.push(binding_value);
+                    }
 
-                        // #TODO optimize!
-                        Ok(Some(
-                            Expr::Macro(params.clone(), Box::new(body.clone())).into(),
-                        ))
-                    } else {
-                        // Other kind of list with symbol head, macro-expand tail.
-
-                        let mut terms = Vec::new();
-                        terms.push(head.clone());
-                        for term in tail {
-                            let term = macro_expand(term.clone(), env)?;
-                            if let Some(term) = term {
-                                terms.push(term);
-                            }
+                    if terms.len() == 1 {
+                        if tail.is_empty() {
+                            return Err(Ranged(
+                                Error::invalid_arguments("missing binding symbol"),
+                                expr.get_range(),
+                            ));
                         }
 
-                        Ok(Some(Expr::List(terms).into()))
+                        // All the bindings are macro definitions, the let is pruned.
+                        return Ok(None);
                     }
+
+                    Ok(Some(Ann(Expr::List(terms), ann.clone())))
+                }
+                "defn" => {
+                    // Named function definition sugar:
+                    // (defn name (params) body) -> (let name (Func (params) body))
+                    let Some((name, func)) = tail.split_first() else {
+                        return Err(Ranged(Error::invalid_arguments("malformed defn definition"), expr.get_range()));
+                    };
+
+                    let mut func_terms = vec![Ann(Expr::symbol("Func"), head.1.clone())];
+                    func_terms.extend_from_slice(func);
+
+                    let let_expr = Ann(
+                        Expr::List(vec![
+                            Ann(Expr::symbol("let"), head.1.clone()),
+                            name.clone(),
+                            Ann(Expr::List(func_terms), ann.clone()),
+                        ]),
+                        ann.clone(),
+                    );
+
+                    expand(let_expr, env, depth)
+                }
+                "quot" => {
+                    // The quoted expression is not expanded.
+                    // #TODO super nasty, quotes should be resolved statically (at compile time)
+                    let [_] = tail else {
+                        return Err(Ranged(Error::invalid_arguments("missing quote target"), expr.get_range()));
+                    };
+
+                    Ok(Some(expr))
+                }
+                "Macro" => {
+                    let [args, body] = tail else {
+                        return Err(Ranged(Error::invalid_arguments("malformed macro definition"), expr.get_range()));
+                    };
+
+                    let Ann(Expr::List(params), ..) = args else {
+                        return Err(Ranged(Error::invalid_arguments("malformed macro parameters definition"), expr.get_range()));
+                    };
+
+                    Ok(Some(Expr::macro_(params.clone(), body.clone()).into()))
                 }
                 _ => {
-                    // Other kind of list with non-symbol head, macro-expand tail.
-                    let mut terms = Vec::new();
-                    terms.push(head.clone());
-                    for term in tail {
-                        let term = macro_expand(term.clone(), env)?;
-                        if let Some(term) = term {
-                            terms.push(term);
-                        }
+                    let Some(Ann(Expr::Macro(params, body), ..)) = env.get(sym) else {
+                        // Other kind of list with symbol head, macro-expand all terms.
+                        let terms = expand_terms(list, env, depth)?;
+                        return Ok(Some(Ann(Expr::List(terms), ann.clone())));
+                    };
+
+                    // This is the actual macro-expansion
+
+                    if depth >= MAX_EXPANSION_DEPTH {
+                        return Err(Ranged(
+                            Error::MacroExpansionLimit(sym.clone()),
+                            expr.get_range(),
+                        ));
+                    }
+
+                    // #Insight
+                    // Macro arguments are lazily evaluated.
+
+                    let args = tail;
+
+                    // #TODO what kind of scoping is this?
+
+                    env.push_new_scope();
+
+                    for (param, arg) in params.iter().zip(args) {
+                        let Ann(Expr::Symbol(param), ..) = param else {
+                            env.pop();
+                            return Err(Ranged(Error::invalid_arguments("parameter is not a symbol"), param.get_range()));
+                        };
+
+                        env.insert(param, arg.clone());
+                    }
+
+                    let result = eval(&body, env);
+
+                    env.pop();
+
+                    let mut result = result?;
+
+                    // Attribute the expansion to the call-site, useful for
+                    // error reporting.
+                    if !result.contains_annotation("range") {
+                        result.set_range(&expr.get_range());
                     }
 
-                    Ok(Some(Expr::List(terms).into()))
+                    // The expansion may contain more macro invocations.
+                    expand(result, env, depth + 1)
                 }
             }
         }
@@ -1,8 +1,87 @@
+use crate::{ann::Ann, error::Error, expr::Expr, range::Ranged};
+
+pub mod agent;
 pub mod arithmetic;
+pub mod array;
+pub mod buffer;
+pub mod cli;
+pub mod dict;
+pub mod diff;
+pub mod encoding;
 pub mod eq;
+pub mod error;
+#[cfg(feature = "glob")]
+pub mod glob;
 pub mod io;
+pub mod isolate;
 pub mod lang;
+pub mod logic;
+pub mod math;
+pub mod maybe;
+pub mod parallel;
+pub mod path;
 pub mod process;
+pub mod progress;
+#[cfg(feature = "prompt")]
+pub mod prompt;
+#[cfg(feature = "regex")]
+pub mod regex;
+pub mod schema;
+pub mod seq;
+#[cfg(feature = "signal")]
+pub mod signal;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod stats;
+#[cfg(feature = "store")]
+pub mod store;
+pub mod string;
+pub mod style;
+pub mod table;
+pub mod template;
+#[cfg(feature = "tensor")]
+pub mod tensor;
+pub mod time;
+#[cfg(feature = "toml")]
+pub mod toml;
+pub mod walk;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "yaml")]
+pub mod yaml;
+
+// #Insight
+// The argument helpers are shared by the ops, for consistent error messages.
+
+/// Returns the String argument, `name` is the name of the argument, e.g. `path`.
+pub(crate) fn string_arg<'a>(arg: &'a Ann<Expr>, name: &str) -> Result<&'a str, Ranged<Error>> {
+    let Ann(Expr::String(s), ..) = arg else {
+        return Err(Error::invalid_arguments(format!(
+            "`{name}` argument should be a String, found `{arg}`"
+        ))
+        .into());
+    };
+
+    Ok(s)
+}
+
+/// Returns the Int argument.
+pub(crate) fn int_arg(arg: &Ann<Expr>) -> Result<i64, Ranged<Error>> {
+    let Ann(Expr::Int(n), ..) = arg else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not an Int")).into());
+    };
+
+    Ok(*n)
+}
+
+/// Returns the argument as a Float, Ints are promoted, e.g. in `(/ 1.0 2)`.
+pub(crate) fn float_arg(arg: &Ann<Expr>) -> Result<f64, Ranged<Error>> {
+    match arg {
+        Ann(Expr::Float(n), ..) => Ok(*n),
+        Ann(Expr::Int(n), ..) => Ok(*n as f64),
+        _ => Err(Error::invalid_arguments(format!("`{arg}` is not a Float")).into()),
+    }
+}
 
 // #TODO helper function or macro for arithmetic operations!
 // #TODO also eval 'if', 'do', 'for' and other keywords here!
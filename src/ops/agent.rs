@@ -0,0 +1,266 @@
+use std::{
+    sync::{mpsc, Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::Instant,
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{
+        cancellation::POLL_INTERVAL,
+        capture::{CapturedFunc, PortableError},
+        context::{Context, WorkerPolicy},
+        env::Env,
+        invoke,
+    },
+    expr::{foreign::ForeignValue, portable::Portable, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// An agent holds a value that is updated asynchronously, e.g.
+//
+// (let counter (agent 0))
+// (send-update counter (Func (n) (+ n 1)))
+// (await counter) ; => 1
+//
+// The updates are applied in order, one at a time, on the dedicated thread of
+// the agent, in its own Env, with the policy of the Context that created the
+// agent, see `WorkerPolicy`. The update functions are captured, see
+// `CapturedFunc`. An update function that cannot be captured, e.g. with
+// side-effects, is applied on the calling thread, after the queued updates.
+//
+// If an update fails, the agent keeps the last value and the error is
+// reported by the following `send-update` and `await` calls.
+
+// #TODO support `(restart-agent agent value)` to recover from a failure.
+// #TODO support update functions with extra arguments.
+
+const AGENT_TYPE: &str = "Agent";
+
+struct AgentState {
+    value: Portable,
+    error: Option<Ranged<PortableError>>,
+    pending: usize,
+}
+
+struct Shared {
+    state: Mutex<AgentState>,
+    idle: Condvar,
+}
+
+impl Shared {
+    fn new(value: Portable) -> Self {
+        Self {
+            state: Mutex::new(AgentState {
+                value,
+                error: None,
+                pending: 0,
+            }),
+            idle: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, AgentState> {
+        // A panic while the state is locked leaves the state consistent.
+        self.state.lock().unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Waits until the queued updates are applied, the evaluation deadline
+    /// and the cancellation of the waiting Context are honored.
+    fn wait_idle(&self, context: &Context) -> Result<MutexGuard<'_, AgentState>, Error> {
+        let mut state = self.lock();
+
+        while state.pending > 0 {
+            context.check_deadline()?;
+            context.check_cancelled()?;
+
+            let mut timeout = context
+                .deadline
+                .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+            // With a cancellation token, wait in slices to observe the
+            // cancellation.
+            if context.cancellation.is_some() {
+                timeout = Some(timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL)));
+            }
+
+            state = match timeout {
+                None => self
+                    .idle
+                    .wait(state)
+                    .unwrap_or_else(|error| error.into_inner()),
+                Some(timeout) => {
+                    self.idle
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|error| error.into_inner())
+                        .0
+                }
+            };
+        }
+
+        Ok(state)
+    }
+}
+
+struct Agent {
+    shared: Arc<Shared>,
+    updates: mpsc::Sender<CapturedFunc>,
+}
+
+/// Applies an update function to the value.
+fn apply(func: &Ann<Expr>, value: &Portable, env: &mut Env) -> Result<Portable, Ranged<Error>> {
+    let value = invoke(func, vec![value.to_ann(&[])], env)?;
+
+    Portable::from_ann(&value)
+        .filter(Portable::is_data)
+        .ok_or_else(|| {
+            Error::invalid_arguments(format!("the value `{value}` cannot be stored in an agent"))
+                .into()
+        })
+}
+
+/// Applies the queued updates, until the agent is dropped.
+fn run_agent(shared: Arc<Shared>, updates: mpsc::Receiver<CapturedFunc>, policy: WorkerPolicy) {
+    let mut env = policy.env();
+
+    for update in updates {
+        let value = {
+            let state = shared.lock();
+            state.error.is_none().then(|| state.value.clone())
+        };
+
+        // After a failure, the queued updates are skipped.
+        let result = value.map(|value| {
+            let func = update.instantiate(&mut env);
+            apply(&func, &value, &mut env).map_err(PortableError::from_error)
+        });
+
+        let mut state = shared.lock();
+
+        match result {
+            Some(Ok(value)) => state.value = value,
+            Some(Err(error)) => state.error = Some(error),
+            None => (),
+        }
+
+        state.pending -= 1;
+        shared.idle.notify_all();
+    }
+}
+
+fn agent_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a Agent, Ranged<Error>> {
+    let Some(agent) = (match args.first().map(AsRef::as_ref) {
+        Some(Expr::Foreign(value)) => value.downcast_ref::<Agent>(),
+        _ => None,
+    }) else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires an Agent argument")).into(),
+        );
+    };
+
+    Ok(agent)
+}
+
+/// Reports the error of a failed update.
+fn check_error(state: &AgentState) -> Result<(), Ranged<Error>> {
+    match &state.error {
+        Some(error) => Err(PortableError::into_error(error.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Creates an agent with the initial value, e.g. `(agent 0)`. The value
+/// should be plain data.
+pub fn agent(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`agent` requires one argument").into());
+    };
+
+    let Some(value) = Portable::from_ann(value).filter(Portable::is_data) else {
+        return Err(
+            Error::invalid_arguments(format!("`{value}` cannot be stored in an agent")).into(),
+        );
+    };
+
+    let shared = Arc::new(Shared::new(value));
+
+    let (updates, receiver) = mpsc::channel();
+
+    {
+        let shared = shared.clone();
+        let policy = env.context.worker_policy();
+        thread::Builder::new()
+            .name("tan-agent".to_owned())
+            .spawn(move || run_agent(shared, receiver, policy))?;
+    }
+
+    Ok(Ann::with_type(
+        Expr::Foreign(ForeignValue::new(AGENT_TYPE, Agent { shared, updates })),
+        Expr::symbol(AGENT_TYPE),
+    ))
+}
+
+/// Queues an update of the agent value, e.g.
+/// `(send-update counter (Func (n) (+ n 1)))`. The function receives the
+/// current value and returns the new value. Returns the agent.
+pub fn send_update(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [_, func] = args else {
+        return Err(
+            Error::invalid_arguments("`send-update` requires `agent`, `func` arguments").into(),
+        );
+    };
+
+    let agent = agent_arg("send-update", args)?;
+
+    if let Some(update) = CapturedFunc::capture(func, env) {
+        let mut state = agent.shared.lock();
+        check_error(&state)?;
+        state.pending += 1;
+        drop(state);
+
+        // The agent thread runs as long as the agent is alive.
+        agent
+            .updates
+            .send(update)
+            .map_err(|_| Error::invalid_arguments("the agent thread is not running"))?;
+    } else {
+        let value = {
+            let state = agent.shared.wait_idle(&env.context)?;
+            check_error(&state)?;
+            state.value.clone()
+        };
+
+        // #Insight
+        // The state is not locked while the function is applied, the function
+        // may refer to the agent, e.g. with `deref`.
+        let value = apply(func, &value, env)?;
+
+        agent.shared.lock().value = value;
+    }
+
+    Ok(args[0].clone())
+}
+
+/// Returns the current value of the agent, the queued updates may not be
+/// applied yet, see `await`.
+pub fn deref(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let agent = agent_arg("deref", args)?;
+
+    let state = agent.shared.lock();
+
+    Ok(state.value.to_ann(&[]))
+}
+
+/// Waits until the queued updates are applied, returns the value of the
+/// agent. Reports the error of a failed update. The evaluation deadline and
+/// the cancellation are honored.
+pub fn await_agent(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let agent = agent_arg("await", args)?;
+
+    let state = agent.shared.wait_idle(&env.context)?;
+    check_error(&state)?;
+
+    Ok(state.value.to_ann(&[]))
+}
@@ -1,4 +1,15 @@
-use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use rust_decimal::Decimal;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::Expr,
+    ops::{float_arg, int_arg},
+    range::Ranged,
+};
 
 // #Insight
 // Named `arithmetic` as those operators can apply to non-numbers, e.g. Time, Date
@@ -7,8 +18,76 @@ use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
 // #TODO use macros to generate specializations for generic versions.
 // #TODO deduct from type if the function can affect the env or have any other side-effects.
 
+// #Insight
+// The generic (unspecialized) operators dispatch dynamically on the types of
+// the arguments. They are used when the argument types are not statically
+// resolved, e.g. when the operator is passed around as a value.
+
+// #Insight
+// The Int operations detect overflow, an overflow is reported as an error
+// instead of silently wrapping around.
+
+// #Insight
+// BigInts and Decimals are 'contagious', Ints are promoted when mixed with
+// them, e.g. `(+ 1n 2)` is `3n`. Mixing Decimals with Floats is an error, as
+// the precision would be silently lost.
+
+// #TODO promote to BigInt on overflow.
+
+/// Returns the argument as a BigInt, Ints are promoted.
+fn big_int_arg(arg: &Ann<Expr>) -> Result<BigInt, Ranged<Error>> {
+    match arg {
+        Ann(Expr::BigInt(n), ..) => Ok(n.clone()),
+        Ann(Expr::Int(n), ..) => Ok(BigInt::from(*n)),
+        _ => Err(Error::invalid_arguments(format!("`{arg}` is not a BigInt")).into()),
+    }
+}
+
+/// Returns the argument as a Decimal, Ints are promoted.
+fn decimal_arg(arg: &Ann<Expr>) -> Result<Decimal, Ranged<Error>> {
+    match arg {
+        Ann(Expr::Decimal(n), ..) => Ok(*n),
+        Ann(Expr::Int(n), ..) => Ok(Decimal::from(*n)),
+        _ => Err(Error::invalid_arguments(format!("`{arg}` is not a Decimal")).into()),
+    }
+}
+
+fn overflow(op: &str) -> Ranged<Error> {
+    Error::IntegerOverflow(op.to_owned()).into()
+}
+
+/// Returns true if any of the arguments is a Float.
+fn has_float_arg(args: &[Ann<Expr>]) -> bool {
+    args.iter()
+        .any(|arg| matches!(arg, Ann(Expr::Float(..), ..)))
+}
+
+/// Returns true if any of the arguments is a BigInt.
+fn has_big_int_arg(args: &[Ann<Expr>]) -> bool {
+    args.iter()
+        .any(|arg| matches!(arg, Ann(Expr::BigInt(..), ..)))
+}
+
+/// Returns true if any of the arguments is a Decimal.
+fn has_decimal_arg(args: &[Ann<Expr>]) -> bool {
+    args.iter()
+        .any(|arg| matches!(arg, Ann(Expr::Decimal(..), ..)))
+}
+
+pub fn add(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        add_float(args, env)
+    } else if has_decimal_arg(args) {
+        add_decimal(args, env)
+    } else if has_big_int_arg(args) {
+        add_big_int(args, env)
+    } else {
+        add_int(args, env)
+    }
+}
+
 // #TODO autogen with a macro!
-pub fn add_int(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn add_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     let mut xs = Vec::new();
 
     for arg in args {
@@ -18,16 +97,16 @@ pub fn add_int(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error
         xs.push(*n);
     }
 
-    let sum = add_int_impl(xs);
+    let sum = add_int_impl(xs).ok_or_else(|| overflow("+"))?;
 
     Ok(Expr::Int(sum).into())
 }
 
-fn add_int_impl(xs: Vec<i64>) -> i64 {
-    xs.iter().sum()
+fn add_int_impl(xs: Vec<i64>) -> Option<i64> {
+    xs.iter().try_fold(0_i64, |sum, x| sum.checked_add(*x))
 }
 
-pub fn add_float(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn add_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     let mut sum = 0.0;
 
     for arg in args {
@@ -40,7 +119,19 @@ pub fn add_float(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Err
     Ok(Expr::Float(sum).into())
 }
 
-pub fn sub(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn sub(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        sub_float(args, env)
+    } else if has_decimal_arg(args) {
+        sub_decimal(args, env)
+    } else if has_big_int_arg(args) {
+        sub_big_int(args, env)
+    } else {
+        sub_int(args, env)
+    }
+}
+
+pub fn sub_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     // #TODO support multiple arguments.
     let [a, b] = args else {
         return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
@@ -54,10 +145,40 @@ pub fn sub(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
         return Err(Error::invalid_arguments(format!("`{b}` is not an Int")).into());
     };
 
-    Ok(Expr::Int(a - b).into())
+    let difference = a.checked_sub(*b).ok_or_else(|| overflow("-"))?;
+
+    Ok(Expr::Int(difference).into())
+}
+
+pub fn sub_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
+    };
+
+    let Ann(Expr::Float(a), ..) = a else {
+        return Err(Error::invalid_arguments(format!("`{a}` is not a Float")).into());
+    };
+
+    let Ann(Expr::Float(b), ..) = b else {
+        return Err(Error::invalid_arguments(format!("`{b}` is not a Float")).into());
+    };
+
+    Ok(Expr::Float(a - b).into())
 }
 
-pub fn mul(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn mul(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        mul_float(args, env)
+    } else if has_decimal_arg(args) {
+        mul_decimal(args, env)
+    } else if has_big_int_arg(args) {
+        mul_big_int(args, env)
+    } else {
+        mul_int(args, env)
+    }
+}
+
+pub fn mul_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     // #TODO optimize!
     let mut prod = 1;
 
@@ -65,8 +186,225 @@ pub fn mul(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
         let Ann(Expr::Int(n), ..) = arg else {
             return Err(Error::invalid_arguments(format!("`{arg}` is not an Int")).into());
         };
-        prod *= n;
+        prod = n.checked_mul(prod).ok_or_else(|| overflow("*"))?;
     }
 
     Ok(Expr::Int(prod).into())
 }
+
+pub fn mul_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut prod = 1.0;
+
+    for arg in args {
+        let Ann(Expr::Float(n), ..) = arg else {
+            return Err(Error::invalid_arguments(format!("`{arg}` is not a Float")).into());
+        };
+        prod *= n;
+    }
+
+    Ok(Expr::Float(prod).into())
+}
+
+// #Insight
+// The Int `/` truncates towards zero, and `%` has the sign of the dividend, as
+// in Rust and C. See `div` and `mod` for the Euclidean versions.
+
+pub fn div(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        div_float(args, env)
+    } else if has_decimal_arg(args) {
+        div_decimal(args, env)
+    } else if has_big_int_arg(args) {
+        div_big_int(args, env)
+    } else {
+        div_int(args, env)
+    }
+}
+
+pub fn div_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`/` requires two arguments").into());
+    };
+
+    let (a, b) = (int_arg(a)?, int_arg(b)?);
+
+    if b == 0 {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    let quotient = a.checked_div(b).ok_or_else(|| overflow("/"))?;
+
+    Ok(Expr::Int(quotient).into())
+}
+
+/// Divides two Floats, the division by zero results to an infinity or NaN.
+pub fn div_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`/` requires two arguments").into());
+    };
+
+    Ok(Expr::Float(float_arg(a)? / float_arg(b)?).into())
+}
+
+pub fn rem(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        rem_float(args, env)
+    } else if has_decimal_arg(args) {
+        rem_decimal(args, env)
+    } else if has_big_int_arg(args) {
+        rem_big_int(args, env)
+    } else {
+        rem_int(args, env)
+    }
+}
+
+pub fn rem_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`%` requires two arguments").into());
+    };
+
+    let (a, b) = (int_arg(a)?, int_arg(b)?);
+
+    if b == 0 {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    let remainder = a.checked_rem(b).ok_or_else(|| overflow("%"))?;
+
+    Ok(Expr::Int(remainder).into())
+}
+
+pub fn rem_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`%` requires two arguments").into());
+    };
+
+    Ok(Expr::Float(float_arg(a)? % float_arg(b)?).into())
+}
+
+pub fn add_big_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut sum = BigInt::zero();
+
+    for arg in args {
+        sum += big_int_arg(arg)?;
+    }
+
+    Ok(Expr::BigInt(sum).into())
+}
+
+pub fn sub_big_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
+    };
+
+    Ok(Expr::BigInt(big_int_arg(a)? - big_int_arg(b)?).into())
+}
+
+pub fn mul_big_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut prod = BigInt::from(1);
+
+    for arg in args {
+        prod *= big_int_arg(arg)?;
+    }
+
+    Ok(Expr::BigInt(prod).into())
+}
+
+pub fn div_big_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`/` requires two arguments").into());
+    };
+
+    let (a, b) = (big_int_arg(a)?, big_int_arg(b)?);
+
+    if b.is_zero() {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    Ok(Expr::BigInt(a / b).into())
+}
+
+pub fn rem_big_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`%` requires two arguments").into());
+    };
+
+    let (a, b) = (big_int_arg(a)?, big_int_arg(b)?);
+
+    if b.is_zero() {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    Ok(Expr::BigInt(a % b).into())
+}
+
+// #Insight
+// The Decimal operations detect overflow, like the Int operations.
+
+pub fn add_decimal(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut sum = Decimal::ZERO;
+
+    for arg in args {
+        sum = sum
+            .checked_add(decimal_arg(arg)?)
+            .ok_or_else(|| overflow("+"))?;
+    }
+
+    Ok(Expr::Decimal(sum).into())
+}
+
+pub fn sub_decimal(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
+    };
+
+    let difference = decimal_arg(a)?
+        .checked_sub(decimal_arg(b)?)
+        .ok_or_else(|| overflow("-"))?;
+
+    Ok(Expr::Decimal(difference).into())
+}
+
+pub fn mul_decimal(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut prod = Decimal::ONE;
+
+    for arg in args {
+        prod = prod
+            .checked_mul(decimal_arg(arg)?)
+            .ok_or_else(|| overflow("*"))?;
+    }
+
+    Ok(Expr::Decimal(prod).into())
+}
+
+pub fn div_decimal(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`/` requires two arguments").into());
+    };
+
+    let (a, b) = (decimal_arg(a)?, decimal_arg(b)?);
+
+    if b.is_zero() {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    let quotient = a.checked_div(b).ok_or_else(|| overflow("/"))?;
+
+    Ok(Expr::Decimal(quotient).into())
+}
+
+pub fn rem_decimal(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`%` requires two arguments").into());
+    };
+
+    let (a, b) = (decimal_arg(a)?, decimal_arg(b)?);
+
+    if b.is_zero() {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    let remainder = a.checked_rem(b).ok_or_else(|| overflow("%"))?;
+
+    Ok(Expr::Decimal(remainder).into())
+}
@@ -0,0 +1,52 @@
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The Array update functions are 'persistent-style', they return a modified
+// copy of the array, the original array is not mutated.
+
+// #TODO optimize with persistent data-structures, avoid the full clone.
+
+/// Returns a copy of the array with the element at index `i` replaced by the value.
+pub fn put(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [array, index, value] = args else {
+        return Err(
+            Error::invalid_arguments("`put` requires `array`, `index`, `value` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::Array(array), ..) = array else {
+        return Err(Error::invalid_arguments(format!("`{array}` is not an Array")).into());
+    };
+
+    let Ann(Expr::Int(index), ..) = index else {
+        return Err(Error::invalid_arguments(format!("`{index}` is not an Int")).into());
+    };
+
+    let mut array = array.clone();
+
+    let Some(elem) = usize::try_from(*index).ok().and_then(|i| array.get_mut(i)) else {
+        return Err(Error::invalid_arguments(format!("index `{index}` is out of bounds")).into());
+    };
+
+    *elem = value.clone();
+
+    Ok(Expr::Array(array).into())
+}
+
+/// Returns a copy of the array with the value appended at the end.
+pub fn append(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [array, value] = args else {
+        return Err(
+            Error::invalid_arguments("`append` requires `array`, `value` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::Array(array), ..) = array else {
+        return Err(Error::invalid_arguments(format!("`{array}` is not an Array")).into());
+    };
+
+    let mut array = array.clone();
+    array.push(value.clone());
+
+    Ok(Expr::Array(array).into())
+}
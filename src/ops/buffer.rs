@@ -0,0 +1,243 @@
+use std::rc::Rc;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{hex, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The Buffer accessors allow scripts to parse simple binary formats, e.g.
+// file headers and fixed-width records. The offsets are in bytes.
+
+// #TODO support writing into buffers, e.g. `buf/put-u16-le`.
+// #TODO support u64, the Int type is signed.
+
+/// The byte-order of multi-byte integers.
+#[derive(Clone, Copy)]
+enum Endian {
+    Little,
+    Big,
+}
+
+fn buffer_arg(arg: &Ann<Expr>) -> Result<&[u8], Ranged<Error>> {
+    let Ann(Expr::Buffer(bytes), ..) = arg else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not a Buffer")).into());
+    };
+
+    Ok(bytes)
+}
+
+fn offset_arg(arg: &Ann<Expr>) -> Result<usize, Ranged<Error>> {
+    let Some(offset) = (match arg.as_ref() {
+        Expr::Int(n) => usize::try_from(*n).ok(),
+        _ => None,
+    }) else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not a valid offset")).into());
+    };
+
+    Ok(offset)
+}
+
+pub(crate) fn buffer(bytes: impl Into<Rc<[u8]>>) -> Ann<Expr> {
+    Ann::with_type(Expr::Buffer(bytes.into()), Expr::symbol("Buffer"))
+}
+
+/// Parses a hex string, two digits per byte.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Creates a buffer from an Array of byte Ints, e.g. `(Buffer [0xca 0xfe])`, or
+/// from a hex String, e.g. `(Buffer "cafe")`.
+pub fn buffer_new(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [bytes] = args else {
+        return Err(Error::invalid_arguments("`Buffer` requires a `bytes` argument").into());
+    };
+
+    match bytes.as_ref() {
+        Expr::String(s) => {
+            let Some(bytes) = parse_hex(s) else {
+                return Err(
+                    Error::invalid_arguments(format!("`{s}` is not a valid hex String")).into(),
+                );
+            };
+
+            Ok(buffer(bytes))
+        }
+        Expr::Array(items) => {
+            let bytes = items
+                .iter()
+                .map(|item| match item.as_ref() {
+                    Expr::Int(n) => u8::try_from(*n)
+                        .map_err(|_| Error::invalid_arguments(format!("`{n}` is not a byte"))),
+                    _ => Err(Error::invalid_arguments(format!("`{item}` is not a byte"))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(buffer(bytes))
+        }
+        _ => Err(Error::invalid_arguments(format!(
+            "`{bytes}` is not an Array of bytes or a hex String"
+        ))
+        .into()),
+    }
+}
+
+/// Returns the length of the buffer, in bytes.
+pub fn buf_len(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [buf] = args else {
+        return Err(Error::invalid_arguments("`buf/len` requires a `buffer` argument").into());
+    };
+
+    let bytes = buffer_arg(buf)?;
+
+    Ok(Ann::with_type(
+        Expr::Int(bytes.len() as i64),
+        Expr::symbol("Int"),
+    ))
+}
+
+/// Returns a new buffer with the bytes from `start` to `end` (exclusive), e.g.
+/// `(buf/slice buf 0 4)`. Without an `end`, slices to the end of the buffer.
+pub fn buf_slice(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (buf, start, end) = match args {
+        [buf, start] => (buf, start, None),
+        [buf, start, end] => (buf, start, Some(end)),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`buf/slice` requires `buffer`, `start` and optional `end` arguments",
+            )
+            .into());
+        }
+    };
+
+    let bytes = buffer_arg(buf)?;
+    let start = offset_arg(start)?;
+    let end = match end {
+        Some(end) => offset_arg(end)?,
+        None => bytes.len(),
+    };
+
+    let Some(slice) = bytes.get(start..end) else {
+        return Err(Error::invalid_arguments(format!(
+            "the slice {start}..{end} is out of bounds, the buffer has {} bytes",
+            bytes.len()
+        ))
+        .into());
+    };
+
+    Ok(buffer(slice))
+}
+
+/// Returns the bytes of the buffer as a hex string, e.g. `"cafe"`.
+pub fn buf_hex(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [buf] = args else {
+        return Err(Error::invalid_arguments("`buf/hex` requires a `buffer` argument").into());
+    };
+
+    Ok(Expr::String(hex(buffer_arg(buf)?)).into())
+}
+
+/// Reads an integer of `N` bytes at the offset, the first argument is the
+/// buffer, the second the offset.
+fn read_int<const N: usize>(
+    name: &str,
+    args: &[Ann<Expr>],
+    endian: Endian,
+    signed: bool,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [buf, offset] = args else {
+        return Err(Error::invalid_arguments(format!(
+            "`{name}` requires `buffer`, `offset` arguments"
+        ))
+        .into());
+    };
+
+    let bytes = buffer_arg(buf)?;
+    let offset = offset_arg(offset)?;
+
+    let Some(bytes) = offset.checked_add(N).and_then(|end| bytes.get(offset..end)) else {
+        return Err(Error::invalid_arguments(format!(
+            "cannot read {N} bytes at offset {offset}, the buffer has {} bytes",
+            bytes.len()
+        ))
+        .into());
+    };
+
+    let mut value: u64 = 0;
+    for i in 0..N {
+        let byte = match endian {
+            Endian::Little => bytes[N - 1 - i],
+            Endian::Big => bytes[i],
+        };
+        value = (value << 8) | u64::from(byte);
+    }
+
+    let value = if signed {
+        // Sign-extend the value.
+        let shift = 64 - 8 * N as u32;
+        ((value << shift) as i64) >> shift
+    } else {
+        value as i64
+    };
+
+    Ok(Ann::with_type(Expr::Int(value), Expr::symbol("Int")))
+}
+
+pub fn buf_u8(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<1>("buf/u8", args, Endian::Little, false)
+}
+
+pub fn buf_i8(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<1>("buf/i8", args, Endian::Little, true)
+}
+
+pub fn buf_u16_le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<2>("buf/u16-le", args, Endian::Little, false)
+}
+
+pub fn buf_u16_be(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<2>("buf/u16-be", args, Endian::Big, false)
+}
+
+pub fn buf_i16_le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<2>("buf/i16-le", args, Endian::Little, true)
+}
+
+pub fn buf_i16_be(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<2>("buf/i16-be", args, Endian::Big, true)
+}
+
+pub fn buf_u32_le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<4>("buf/u32-le", args, Endian::Little, false)
+}
+
+pub fn buf_u32_be(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<4>("buf/u32-be", args, Endian::Big, false)
+}
+
+pub fn buf_i32_le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<4>("buf/i32-le", args, Endian::Little, true)
+}
+
+pub fn buf_i32_be(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<4>("buf/i32-be", args, Endian::Big, true)
+}
+
+pub fn buf_i64_le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<8>("buf/i64-le", args, Endian::Little, true)
+}
+
+pub fn buf_i64_be(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    read_int::<8>("buf/i64-be", args, Endian::Big, true)
+}
@@ -0,0 +1,360 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The command-line interface is described with a declarative spec Dict, e.g.
+//
+// {
+//     :name "greet"
+//     :about "Greets people."
+//     :flags {:loud {:short "l" :help "Shout the greeting"}}
+//     :options {:greeting {:short "g" :help "The greeting" :default "Hello"}}
+//     :positionals [{:name "who" :help "Who to greet" :default "World"}]
+// }
+//
+// Flags are Bools, options and positionals are Strings. The default values are
+// used as-is, an option without a default is `()` when missing, a positional
+// without a default is required. `-h` and `--help` are always supported.
+
+// #TODO support repeated options, e.g. `-v -v` or `--include a --include b`.
+// #TODO support combined short flags, e.g. `-abc`.
+// #TODO support sub-commands.
+
+struct OptionSpec {
+    name: String,
+    short: Option<char>,
+    help: String,
+    default: Option<Ann<Expr>>,
+    is_flag: bool,
+}
+
+struct PositionalSpec {
+    name: String,
+    help: String,
+    default: Option<Ann<Expr>>,
+}
+
+struct CliSpec {
+    name: String,
+    about: Option<String>,
+    options: Vec<OptionSpec>,
+    positionals: Vec<PositionalSpec>,
+}
+
+fn spec_error(message: impl AsRef<str>) -> Error {
+    Error::invalid_arguments(format!("invalid cli spec: {}", message.as_ref()))
+}
+
+fn string_field(dict: &BTreeMap<String, Ann<Expr>>, key: &str) -> Result<Option<String>, Error> {
+    match dict.get(key) {
+        None => Ok(None),
+        Some(Ann(Expr::String(s), ..)) => Ok(Some(s.clone())),
+        Some(value) => Err(spec_error(format!(
+            "`{key}` should be a String, found `{value}`"
+        ))),
+    }
+}
+
+fn parse_option_specs(
+    spec: &BTreeMap<String, Ann<Expr>>,
+    key: &str,
+    is_flag: bool,
+) -> Result<Vec<OptionSpec>, Error> {
+    let options = match spec.get(key) {
+        None => return Ok(Vec::new()),
+        Some(Ann(Expr::Dict(options), ..)) => options,
+        Some(value) => {
+            return Err(spec_error(format!(
+                "`{key}` should be a Dict, found `{value}`"
+            )));
+        }
+    };
+
+    let mut specs = Vec::new();
+
+    for (name, option) in options {
+        let Ann(Expr::Dict(option), ..) = option else {
+            return Err(spec_error(format!(
+                "`{name}` should be a Dict, found `{option}`"
+            )));
+        };
+
+        let short = match string_field(option, "short")? {
+            None => None,
+            Some(short) => {
+                let mut chars = short.chars();
+                let (Some(c), None) = (chars.next(), chars.next()) else {
+                    return Err(spec_error(format!(
+                        "the short name of `{name}` should be a single character"
+                    )));
+                };
+                Some(c)
+            }
+        };
+
+        specs.push(OptionSpec {
+            name: name.clone(),
+            short,
+            help: string_field(option, "help")?.unwrap_or_default(),
+            default: if is_flag {
+                None
+            } else {
+                option.get("default").cloned()
+            },
+            is_flag,
+        });
+    }
+
+    Ok(specs)
+}
+
+fn parse_spec(spec: &Ann<Expr>) -> Result<CliSpec, Error> {
+    let Ann(Expr::Dict(spec), ..) = spec else {
+        return Err(spec_error(format!("`{spec}` is not a Dict")));
+    };
+
+    let mut options = parse_option_specs(spec, "flags", true)?;
+    options.extend(parse_option_specs(spec, "options", false)?);
+
+    let mut positionals = Vec::new();
+
+    match spec.get("positionals") {
+        None => (),
+        Some(Ann(Expr::Array(items), ..)) => {
+            for item in items {
+                let Ann(Expr::Dict(item), ..) = item else {
+                    return Err(spec_error(format!("the positional `{item}` is not a Dict")));
+                };
+
+                let Some(name) = string_field(item, "name")? else {
+                    return Err(spec_error("a positional is missing the `name`"));
+                };
+
+                positionals.push(PositionalSpec {
+                    name,
+                    help: string_field(item, "help")?.unwrap_or_default(),
+                    default: item.get("default").cloned(),
+                });
+            }
+        }
+        Some(value) => {
+            return Err(spec_error(format!(
+                "`positionals` should be an Array, found `{value}`"
+            )));
+        }
+    }
+
+    Ok(CliSpec {
+        name: string_field(spec, "name")?.unwrap_or_else(|| "script".to_owned()),
+        about: string_field(spec, "about")?,
+        options,
+        positionals,
+    })
+}
+
+/// Appends the rows as two aligned columns.
+fn push_rows(text: &mut String, rows: &[(String, String)]) {
+    let width = rows.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+
+    for (left, right) in rows {
+        let line = format!("  {left:width$}  {right}");
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+}
+
+fn help_text(spec: &CliSpec) -> String {
+    let mut text = format!("Usage: {} [OPTIONS]", spec.name);
+
+    for positional in &spec.positionals {
+        if positional.default.is_some() {
+            text.push_str(&format!(" [{}]", positional.name));
+        } else {
+            text.push_str(&format!(" <{}>", positional.name));
+        }
+    }
+
+    text.push('\n');
+
+    if let Some(about) = &spec.about {
+        text.push_str(&format!("\n{about}\n"));
+    }
+
+    if !spec.positionals.is_empty() {
+        text.push_str("\nArguments:\n");
+        let rows: Vec<_> = spec
+            .positionals
+            .iter()
+            .map(|positional| (format!("<{}>", positional.name), positional.help.clone()))
+            .collect();
+        push_rows(&mut text, &rows);
+    }
+
+    text.push_str("\nOptions:\n");
+
+    let mut rows: Vec<_> = spec
+        .options
+        .iter()
+        .map(|option| {
+            let short = match option.short {
+                Some(c) => format!("-{c}, "),
+                None => "    ".to_owned(),
+            };
+            let value = if option.is_flag { "" } else { " <VALUE>" };
+            let mut help = option.help.clone();
+            if let Some(default) = &option.default {
+                help.push_str(&format!(" [default: {}]", format_value(default)));
+            }
+            (format!("{short}--{}{value}", option.name), help)
+        })
+        .collect();
+    rows.push(("-h, --help".to_owned(), "Print help".to_owned()));
+    push_rows(&mut text, &rows);
+
+    text
+}
+
+/// Parses the command-line arguments according to the spec.
+fn parse_args(
+    spec: &CliSpec,
+    args: &[String],
+) -> Result<(BTreeMap<String, Ann<Expr>>, bool), Error> {
+    let mut values = BTreeMap::new();
+    let mut positionals = Vec::new();
+    let mut is_help = false;
+
+    for option in &spec.options {
+        let value = if option.is_flag {
+            Expr::Bool(false).into()
+        } else {
+            option.default.clone().unwrap_or_else(|| Expr::One.into())
+        };
+        values.insert(option.name.clone(), value);
+    }
+
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            positionals.extend(args.by_ref().cloned());
+            break;
+        }
+
+        if arg == "-h" || arg == "--help" {
+            is_help = true;
+            continue;
+        }
+
+        let (option, inline_value) = if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline_value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (long, None),
+            };
+            let option = spec.options.iter().find(|option| option.name == name);
+            (option, inline_value)
+        } else if arg.len() == 2 && arg.starts_with('-') && arg != "-" {
+            let c = arg.chars().nth(1);
+            let option = spec.options.iter().find(|option| option.short == c);
+            (option, None)
+        } else {
+            positionals.push(arg.clone());
+            continue;
+        };
+
+        let Some(option) = option else {
+            return Err(Error::invalid_arguments(format!("unknown option `{arg}`")));
+        };
+
+        let value = if option.is_flag {
+            if inline_value.is_some() {
+                return Err(Error::invalid_arguments(format!(
+                    "the flag `--{}` does not accept a value",
+                    option.name
+                )));
+            }
+            Expr::Bool(true)
+        } else {
+            let Some(value) = inline_value.or_else(|| args.next().cloned()) else {
+                return Err(Error::invalid_arguments(format!(
+                    "the option `--{}` requires a value",
+                    option.name
+                )));
+            };
+            Expr::String(value)
+        };
+
+        values.insert(option.name.clone(), value.into());
+    }
+
+    if positionals.len() > spec.positionals.len() && !is_help {
+        return Err(Error::invalid_arguments(format!(
+            "unexpected argument `{}`",
+            positionals[spec.positionals.len()]
+        )));
+    }
+
+    let mut positionals = positionals.into_iter();
+
+    for positional in &spec.positionals {
+        let value = match (positionals.next(), &positional.default) {
+            (Some(value), _) => Expr::String(value).into(),
+            (None, Some(default)) => default.clone(),
+            (None, None) if is_help => Expr::One.into(),
+            (None, None) => {
+                return Err(Error::invalid_arguments(format!(
+                    "missing required argument `<{}>`",
+                    positional.name
+                )));
+            }
+        };
+        values.insert(positional.name.clone(), value);
+    }
+
+    Ok((values, is_help))
+}
+
+/// Parses the command-line arguments according to a declarative spec, e.g.
+/// `(cli/parse ["-l" "George"] spec)`. Returns a Dict with the parsed
+/// `values`, the generated `help` text, and `help?`, true if help was
+/// requested. The required arguments are not checked when help is requested.
+pub fn cli_parse(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [cli_args, spec] = args else {
+        return Err(
+            Error::invalid_arguments("`cli/parse` requires `args`, `spec` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::Array(cli_args), ..) = cli_args else {
+        return Err(Error::invalid_arguments(format!("`{cli_args}` is not an Array")).into());
+    };
+
+    let cli_args = cli_args
+        .iter()
+        .map(|arg| match arg {
+            Ann(Expr::String(s), ..) => Ok(s.clone()),
+            _ => Err(Error::invalid_arguments(format!(
+                "the argument `{arg}` is not a String"
+            ))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let spec = parse_spec(spec)?;
+    let help = help_text(&spec);
+
+    let (values, is_help) = parse_args(&spec, &cli_args)
+        .map_err(|error| Error::invalid_arguments(format!("{error}\n\n{help}")))?;
+
+    let mut result = BTreeMap::new();
+    result.insert("values".to_owned(), Expr::Dict(values).into());
+    result.insert("help".to_owned(), Expr::String(help).into());
+    result.insert("help?".to_owned(), Expr::Bool(is_help).into());
+
+    Ok(Expr::Dict(result).into())
+}
@@ -0,0 +1,332 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{env::Env, invoke},
+    expr::{dict_key, Expr},
+    range::Ranged,
+};
+
+use super::array;
+
+// #Insight
+// The Dict update functions are 'persistent-style', they return a modified
+// copy of the dict, the original dict is not mutated.
+
+// #TODO optimize with persistent data-structures, avoid the full clone.
+
+/// Returns a copy of the dict with the key associated to the value.
+pub fn assoc(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    assoc_named("assoc", args)
+}
+
+/// Implements `assoc`, the name of the op is used in the error messages.
+fn assoc_named(name: &str, args: &[Ann<Expr>]) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [dict, key, value] = args else {
+        return Err(Error::invalid_arguments(format!(
+            "`{name}` requires `dict`, `key`, `value` arguments"
+        ))
+        .into());
+    };
+
+    let Ann(Expr::Dict(dict), ..) = dict else {
+        return Err(Error::invalid_arguments(format!("`{dict}` is not a Dict")).into());
+    };
+
+    let mut dict = dict.clone();
+    dict.insert(dict_key(key)?, value.clone());
+
+    Ok(Expr::Dict(dict).into())
+}
+
+/// Returns a copy of the dict without the key.
+pub fn dissoc(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    dissoc_named("dissoc", args)
+}
+
+/// Implements `dissoc`, the name of the op is used in the error messages.
+fn dissoc_named(name: &str, args: &[Ann<Expr>]) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [dict, key] = args else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires `dict`, `key` arguments")).into(),
+        );
+    };
+
+    let Ann(Expr::Dict(dict), ..) = dict else {
+        return Err(Error::invalid_arguments(format!("`{dict}` is not a Dict")).into());
+    };
+
+    let mut dict = dict.clone();
+    dict.remove(&dict_key(key)?);
+
+    Ok(Expr::Dict(dict).into())
+}
+
+/// Returns a copy of the dict with the value of the key updated by applying
+/// the function `f`. If the key is missing, `f` is applied to `()`.
+pub fn update(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [dict, key, f] = args else {
+        return Err(
+            Error::invalid_arguments("`update` requires `dict`, `key`, `f` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::Dict(dict), ..) = dict else {
+        return Err(Error::invalid_arguments(format!("`{dict}` is not a Dict")).into());
+    };
+
+    let key = dict_key(key)?;
+    let value = dict.get(&key).cloned().unwrap_or_else(|| Expr::One.into());
+
+    let value = invoke(f, vec![value], env)?;
+
+    let mut dict = dict.clone();
+    dict.insert(key, value);
+
+    Ok(Expr::Dict(dict).into())
+}
+
+// get-in, assoc-in, update-in
+
+// #Insight
+// A path is an Array of Dict keys and Array indices, e.g. `[:servers 0 :port]`.
+
+/// Computes the new nested value from the current one, `None` if missing.
+type UpdateFn<'a> = dyn FnMut(Option<&Ann<Expr>>) -> Result<Ann<Expr>, Ranged<Error>> + 'a;
+
+fn path_arg<'a>(name: &str, path: &'a Ann<Expr>) -> Result<&'a [Ann<Expr>], Ranged<Error>> {
+    let Ann(Expr::Array(path), ..) = path else {
+        return Err(Error::invalid_arguments(format!(
+            "`{name}` requires an Array path, found `{path}`"
+        ))
+        .into());
+    };
+
+    Ok(path)
+}
+
+/// Returns the nested value at the path, `None` if any step is missing.
+fn lookup_in<'a>(
+    data: &'a Ann<Expr>,
+    path: &[Ann<Expr>],
+) -> Result<Option<&'a Ann<Expr>>, Ranged<Error>> {
+    let mut value = data;
+
+    for key in path {
+        let next = match (value.as_ref(), key.as_ref()) {
+            (Expr::Dict(dict), _) => dict.get(&dict_key(key)?),
+            (Expr::Array(array), Expr::Int(index)) => {
+                usize::try_from(*index).ok().and_then(|i| array.get(i))
+            }
+            _ => None,
+        };
+
+        let Some(next) = next else {
+            return Ok(None);
+        };
+
+        value = next;
+    }
+
+    Ok(Some(value))
+}
+
+/// Returns a copy of the value with the nested value at the path replaced by
+/// `f` of the current nested value. The missing Dicts along the path are
+/// created.
+fn update_in_value(
+    value: Option<&Ann<Expr>>,
+    path: &[Ann<Expr>],
+    f: &mut UpdateFn,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((key, path)) = path.split_first() else {
+        return f(value);
+    };
+
+    match value.map(Ann::as_ref) {
+        None => {
+            let mut dict = BTreeMap::new();
+            dict.insert(dict_key(key)?, update_in_value(None, path, f)?);
+            Ok(Expr::Dict(dict).into())
+        }
+        Some(Expr::Dict(dict)) => {
+            let key = dict_key(key)?;
+            let mut dict = dict.clone();
+            let nested = update_in_value(dict.get(&key), path, f)?;
+            dict.insert(key, nested);
+            Ok(Expr::Dict(dict).into())
+        }
+        Some(Expr::Array(array)) => {
+            let Ann(Expr::Int(index), ..) = key else {
+                return Err(
+                    Error::invalid_arguments(format!("`{key}` is not an Int index")).into(),
+                );
+            };
+
+            let mut array = array.clone();
+
+            let Some(elem) = usize::try_from(*index).ok().and_then(|i| array.get_mut(i)) else {
+                return Err(
+                    Error::invalid_arguments(format!("index `{index}` is out of bounds")).into(),
+                );
+            };
+
+            *elem = update_in_value(Some(elem), path, f)?;
+            Ok(Expr::Array(array).into())
+        }
+        Some(_) => Err(Error::invalid_arguments(format!(
+            "`{}` is not a Dict or an Array",
+            value.unwrap()
+        ))
+        .into()),
+    }
+}
+
+/// Returns the nested value at the path, e.g. `(get-in config [:servers 0 :port])`,
+/// `None` if any step of the path is missing.
+pub fn get_in(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [data, path] = args else {
+        return Err(Error::invalid_arguments("`get-in` requires `data`, `path` arguments").into());
+    };
+
+    let value = match lookup_in(data, path_arg("get-in", path)?)? {
+        Some(value) => Expr::some(value.clone()),
+        None => Expr::none(),
+    };
+
+    Ok(Ann::with_type(value, Expr::symbol("Maybe")))
+}
+
+/// Returns a copy of the data with the nested value at the path associated to
+/// the value, e.g. `(assoc-in config [:servers 0 :port] 8080)`. The missing
+/// Dicts along the path are created, the Array indices should be in bounds.
+pub fn assoc_in(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [data, path, value] = args else {
+        return Err(Error::invalid_arguments(
+            "`assoc-in` requires `data`, `path`, `value` arguments",
+        )
+        .into());
+    };
+
+    update_in_value(Some(data), path_arg("assoc-in", path)?, &mut |_| {
+        Ok(value.clone())
+    })
+}
+
+/// Returns a copy of the data with the nested value at the path updated by
+/// applying the function `f`, e.g. `(update-in stats [:hits] inc)`. If the
+/// value is missing, `f` is applied to `()`.
+pub fn update_in(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [data, path, f] = args else {
+        return Err(
+            Error::invalid_arguments("`update-in` requires `data`, `path`, `f` arguments").into(),
+        );
+    };
+
+    update_in_value(Some(data), path_arg("update-in", path)?, &mut |value| {
+        let value = value.cloned().unwrap_or_else(|| Expr::One.into());
+        invoke(f, vec![value], env)
+    })
+}
+
+/// Returns a copy of the collection with the key associated to the value, e.g.
+/// `(put dict :name "George")`. For Arrays, the key is an index. For Dicts,
+/// equivalent to `assoc`.
+pub fn put(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if let Some(Ann(Expr::Array(..), ..)) = args.first() {
+        return array::put(args, env);
+    }
+
+    assoc_named("put", args)
+}
+
+/// Returns a copy of the dict without the key, e.g. `(delete dict :name)`.
+/// Equivalent to `dissoc`.
+pub fn delete(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    dissoc_named("delete", args)
+}
+
+/// Returns the keys of the dict as an Array of Strings, in sorted order.
+pub fn keys(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::Dict(dict), ..)] = args else {
+        return Err(Error::invalid_arguments("`keys` requires a Dict argument").into());
+    };
+
+    let keys = dict
+        .keys()
+        .map(|key| Ann::with_type(Expr::String(key.clone()), Expr::symbol("String")))
+        .collect();
+
+    Ok(Ann::with_type(Expr::Array(keys), Expr::symbol("Array")))
+}
+
+/// Returns the values of the dict as an Array, in the order of the sorted keys.
+pub fn values(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::Dict(dict), ..)] = args else {
+        return Err(Error::invalid_arguments("`values` requires a Dict argument").into());
+    };
+
+    let values = dict.values().cloned().collect();
+
+    Ok(Ann::with_type(Expr::Array(values), Expr::symbol("Array")))
+}
+
+/// Returns true if the dict contains the key.
+pub fn contains_key(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [dict, key] = args else {
+        return Err(
+            Error::invalid_arguments("`contains-key?` requires `dict`, `key` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::Dict(dict), ..) = dict else {
+        return Err(Error::invalid_arguments(format!("`{dict}` is not a Dict")).into());
+    };
+
+    let contains = dict.contains_key(&dict_key(key)?);
+
+    Ok(Ann::with_type(Expr::Bool(contains), Expr::symbol("Bool")))
+}
+
+/// Returns a new dict with the entries of all the dicts, the values of the
+/// later dicts take precedence, e.g. `(merge defaults options)`.
+pub fn merge(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut merged = BTreeMap::new();
+
+    for arg in args {
+        let Ann(Expr::Dict(dict), ..) = arg else {
+            return Err(Error::invalid_arguments(format!("`{arg}` is not a Dict")).into());
+        };
+
+        merged.extend(dict.iter().map(|(key, value)| (key.clone(), value.clone())));
+    }
+
+    Ok(Expr::Dict(merged).into())
+}
+
+/// Returns a new dict from an Array of `[key value]` pairs, later pairs take
+/// precedence, e.g. `(dict-from-pairs [[:a 1] [:b 2]])`.
+pub fn dict_from_pairs(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::Array(pairs) | Expr::List(pairs), ..)] = args else {
+        return Err(
+            Error::invalid_arguments("`dict-from-pairs` requires an Array of pairs").into(),
+        );
+    };
+
+    let mut dict = BTreeMap::new();
+
+    for pair in pairs {
+        let Ann(Expr::Array(items) | Expr::List(items), ..) = pair else {
+            return Err(Error::invalid_arguments(format!("`{pair}` is not a pair")).into());
+        };
+
+        let [key, value] = &items[..] else {
+            return Err(Error::invalid_arguments(format!("`{pair}` is not a pair")).into());
+        };
+
+        dict.insert(dict_key(key)?, value.clone());
+    }
+
+    Ok(Expr::Dict(dict).into())
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    mem,
+};
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The structural diff of two values is an Array of changes, each change is a
+// Dict with the `kind` (`:added`, `:removed` or `:changed`), the `path` of
+// the value (Dict keys and Array indices), and the `old` and/or the `new`
+// value, e.g.
+//
+// (diff {:port 80} {:port 8080 :host "a"})
+// ; [{"kind" :added "new" "a" "path" ["host"]} {"kind" :changed "new" 8080 "old" 80 "path" ["port"]}]
+//
+// The Dicts are compared by key, the Arrays by index, the changes are sorted
+// by path.
+
+// #TODO use the Myers algorithm for the text diff, the LCS is computed in
+// linear space but in quadratic time.
+// #TODO detect moved Array items.
+
+/// Returns true if the values are equal, values of different types are not
+/// equal, e.g. `1` and `1.0`.
+pub fn values_eq(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (Expr::Foreign(a), Expr::Foreign(b)) => a == b,
+        _ => mem::discriminant(a) == mem::discriminant(b) && a.to_string() == b.to_string(),
+    }
+}
+
+fn change(
+    kind: &str,
+    path: &[Ann<Expr>],
+    old: Option<&Ann<Expr>>,
+    new: Option<&Ann<Expr>>,
+) -> Ann<Expr> {
+    let mut dict = BTreeMap::new();
+
+    dict.insert("kind".to_owned(), Expr::KeySymbol(kind.to_owned()).into());
+    dict.insert("path".to_owned(), Expr::Array(path.to_vec()).into());
+
+    if let Some(old) = old {
+        dict.insert("old".to_owned(), old.clone());
+    }
+
+    if let Some(new) = new {
+        dict.insert("new".to_owned(), new.clone());
+    }
+
+    Expr::Dict(dict).into()
+}
+
+fn diff_values(
+    a: &Ann<Expr>,
+    b: &Ann<Expr>,
+    path: &mut Vec<Ann<Expr>>,
+    changes: &mut Vec<Ann<Expr>>,
+) {
+    match (&a.0, &b.0) {
+        (Expr::Dict(a), Expr::Dict(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+
+            for key in keys {
+                path.push(Expr::String(key.clone()).into());
+                match (a.get(key), b.get(key)) {
+                    (Some(old), Some(new)) => diff_values(old, new, path, changes),
+                    (Some(old), None) => changes.push(change("removed", path, Some(old), None)),
+                    (None, Some(new)) => changes.push(change("added", path, None, Some(new))),
+                    (None, None) => (),
+                }
+                path.pop();
+            }
+        }
+        (Expr::Array(a), Expr::Array(b)) => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(Expr::Int(i as i64).into());
+                match (a.get(i), b.get(i)) {
+                    (Some(old), Some(new)) => diff_values(old, new, path, changes),
+                    (Some(old), None) => changes.push(change("removed", path, Some(old), None)),
+                    (None, Some(new)) => changes.push(change("added", path, None, Some(new))),
+                    (None, None) => (),
+                }
+                path.pop();
+            }
+        }
+        _ => {
+            if !values_eq(&a.0, &b.0) {
+                changes.push(change("changed", path, Some(a), Some(b)));
+            }
+        }
+    }
+}
+
+/// Returns the structural diff of two values, e.g. `(diff old-config config)`,
+/// an empty Array if the values are equal.
+pub fn diff(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`diff` requires two arguments").into());
+    };
+
+    let mut changes = Vec::new();
+
+    diff_values(a, b, &mut Vec::new(), &mut changes);
+
+    Ok(Expr::Array(changes).into())
+}
+
+/// A line edit.
+#[derive(Clone, Copy, PartialEq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Returns the LCS lengths of the `old` lines and the prefixes of the `new`
+/// lines, i.e. the last row of the LCS table, in linear space.
+fn lcs_row<'a>(old: impl Iterator<Item = &'a str>, new: &[&str]) -> Vec<usize> {
+    let mut row = vec![0_usize; new.len() + 1];
+
+    for line in old {
+        let mut diagonal = 0;
+        for (j, new_line) in new.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if line == *new_line {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row
+}
+
+/// Pushes the edits that transform the `old` lines to the `new` lines, from
+/// the longest common subsequence. The LCS is computed in linear space, by
+/// splitting the `old` lines in the middle (Hirschberg's algorithm).
+fn push_line_edits(old: &[&str], new: &[&str], edits: &mut Vec<Edit>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix));
+
+    match old {
+        [] => edits.extend(std::iter::repeat_n(Edit::Insert, new.len())),
+        [line] => match new.iter().position(|new_line| new_line == line) {
+            Some(j) => {
+                edits.extend(std::iter::repeat_n(Edit::Insert, j));
+                edits.push(Edit::Equal);
+                edits.extend(std::iter::repeat_n(Edit::Insert, new.len() - j - 1));
+            }
+            None => {
+                edits.push(Edit::Delete);
+                edits.extend(std::iter::repeat_n(Edit::Insert, new.len()));
+            }
+        },
+        _ if new.is_empty() => edits.extend(std::iter::repeat_n(Edit::Delete, old.len())),
+        _ => {
+            let middle = old.len() / 2;
+
+            let left = lcs_row(old[..middle].iter().copied(), new);
+            let reversed: Vec<&str> = new.iter().rev().copied().collect();
+            let right = lcs_row(old[middle..].iter().rev().copied(), &reversed);
+
+            // The split of the `new` lines with the longest LCS.
+            let split = (0..=new.len())
+                .max_by_key(|&k| (left[k] + right[new.len() - k], std::cmp::Reverse(k)))
+                .unwrap_or(0);
+
+            push_line_edits(&old[..middle], &new[..split], edits);
+            push_line_edits(&old[middle..], &new[split..], edits);
+        }
+    }
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+/// Returns the edits that transform the `old` lines to the `new` lines.
+fn line_edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len() + new.len());
+    push_line_edits(old, new, &mut edits);
+    edits
+}
+
+/// Returns the hunk range of a side, e.g. `3,2`, the start is the line
+/// before the hunk for an empty range.
+fn hunk_range(before: usize, len: usize) -> String {
+    let start = if len == 0 { before } else { before + 1 };
+    format!("{start},{len}")
+}
+
+/// Returns the unified diff of two texts, with `context` lines around the
+/// changes. Returns an empty String if the texts are equal.
+pub fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let edits = line_edits(&old, &new);
+
+    let changed: Vec<usize> = (0..edits.len())
+        .filter(|&k| edits[k] != Edit::Equal)
+        .collect();
+
+    let Some(&first) = changed.first() else {
+        return String::new();
+    };
+
+    // The hunks are ranges of edits, the changes with overlapping contexts are
+    // merged.
+    let mut hunks = vec![(first.saturating_sub(context), first + context + 1)];
+
+    for &k in &changed[1..] {
+        let (start, end) = (k.saturating_sub(context), k + context + 1);
+        let last = hunks.last_mut().unwrap();
+        if start <= last.1 {
+            last.1 = end;
+        } else {
+            hunks.push((start, end));
+        }
+    }
+
+    let mut output = vec!["--- a".to_owned(), "+++ b".to_owned()];
+
+    // The positions in the old and the new lines, at the start of each edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    positions.push((i, j));
+    for edit in &edits {
+        match edit {
+            Edit::Equal => (i, j) = (i + 1, j + 1),
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+        positions.push((i, j));
+    }
+
+    for (start, end) in hunks {
+        let end = end.min(edits.len());
+        let (old_start, new_start) = positions[start];
+        let (old_end, new_end) = positions[end];
+
+        output.push(format!(
+            "@@ -{} +{} @@",
+            hunk_range(old_start, old_end - old_start),
+            hunk_range(new_start, new_end - new_start)
+        ));
+
+        for k in start..end {
+            let (i, j) = positions[k];
+            output.push(match edits[k] {
+                Edit::Equal => format!(" {}", old[i]),
+                Edit::Delete => format!("-{}", old[i]),
+                Edit::Insert => format!("+{}", new[j]),
+            });
+        }
+    }
+
+    output.join("\n")
+}
+
+/// Returns the unified diff of two Strings, line by line, e.g.
+/// `(text/diff expected actual)`, an empty String if the Strings are equal.
+pub fn text_diff(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::String(a), ..), Ann(Expr::String(b), ..)] = args else {
+        return Err(Error::invalid_arguments("`text/diff` requires two String arguments").into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::String(unified_diff(a, b, 3)),
+        Expr::symbol("String"),
+    ))
+}
@@ -0,0 +1,102 @@
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, ops::string_arg, range::Ranged};
+
+// #TODO consider `base64/encode`, `base64/decode`.
+
+fn string(s: String) -> Ann<Expr> {
+    Ann::with_type(Expr::String(s), Expr::symbol("String"))
+}
+
+/// Escapes the HTML special characters, the result is safe to use in text
+/// content and quoted attribute values.
+pub fn html_escape(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`html/escape` requires a `string` argument").into());
+    };
+
+    let s = string_arg(s, "string")?;
+
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    Ok(string(escaped))
+}
+
+// #Insight
+// The URL encoding is the percent-encoding of RFC 3986, all the bytes of the
+// UTF-8 representation, except the unreserved characters, are encoded. `+` is
+// not decoded to a space.
+
+/// Percent-encodes a String, e.g. to be used as a URL query value.
+pub fn url_encode(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`url/encode` requires a `string` argument").into());
+    };
+
+    let s = string_arg(s, "string")?;
+
+    let mut encoded = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    Ok(string(encoded))
+}
+
+/// Decodes a percent-encoded String.
+pub fn url_decode(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`url/decode` requires a `string` argument").into());
+    };
+
+    let s = string_arg(s, "string")?;
+
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+
+    while let Some(byte) = input.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let digits = [input.next(), input.next()];
+        let decoded = match digits {
+            [Some(hi), Some(lo)] => std::str::from_utf8(&[hi, lo])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        let Some(decoded) = decoded else {
+            return Err(Error::invalid_arguments(format!(
+                "`{s}` contains a malformed percent-encoding"
+            ))
+            .into());
+        };
+
+        bytes.push(decoded);
+    }
+
+    let Ok(decoded) = String::from_utf8(bytes) else {
+        return Err(
+            Error::invalid_arguments(format!("`{s}` does not decode to valid UTF-8")).into(),
+        );
+    };
+
+    Ok(string(decoded))
+}
@@ -1,56 +1,95 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use rust_decimal::Decimal;
+
 use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
 
-pub fn eq(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+// #TODO support non-numeric types.
+
+/// Compares two numbers, dispatches dynamically on the types of the arguments.
+fn compare(args: &[Ann<Expr>]) -> Result<Option<Ordering>, Ranged<Error>> {
+    // #TODO support multiple arguments.
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("comparison requires two arguments").into());
+    };
+
+    match (a, b) {
+        (Ann(Expr::Int(a), ..), Ann(Expr::Int(b), ..)) => Ok(a.partial_cmp(b)),
+        (Ann(Expr::Float(a), ..), Ann(Expr::Float(b), ..)) => Ok(a.partial_cmp(b)),
+        // Ints are promoted when compared to BigInts or Decimals.
+        (Ann(Expr::BigInt(a), ..), Ann(Expr::BigInt(b), ..)) => Ok(a.partial_cmp(b)),
+        (Ann(Expr::BigInt(a), ..), Ann(Expr::Int(b), ..)) => Ok(a.partial_cmp(&BigInt::from(*b))),
+        (Ann(Expr::Int(a), ..), Ann(Expr::BigInt(b), ..)) => Ok(BigInt::from(*a).partial_cmp(b)),
+        (Ann(Expr::Decimal(a), ..), Ann(Expr::Decimal(b), ..)) => Ok(a.partial_cmp(b)),
+        (Ann(Expr::Decimal(a), ..), Ann(Expr::Int(b), ..)) => Ok(a.partial_cmp(&Decimal::from(*b))),
+        (Ann(Expr::Int(a), ..), Ann(Expr::Decimal(b), ..)) => Ok(Decimal::from(*a).partial_cmp(b)),
+        (Ann(Expr::Int(..), ..), _) => {
+            Err(Error::invalid_arguments(format!("`{b}` is not an Int")).into())
+        }
+        (Ann(Expr::Float(..), ..), _) => {
+            Err(Error::invalid_arguments(format!("`{b}` is not a Float")).into())
+        }
+        (Ann(Expr::BigInt(..), ..), _) => {
+            Err(Error::invalid_arguments(format!("`{b}` is not a BigInt")).into())
+        }
+        (Ann(Expr::Decimal(..), ..), _) => {
+            Err(Error::invalid_arguments(format!("`{b}` is not a Decimal")).into())
+        }
+        _ => Err(Error::invalid_arguments(format!("`{a}` is not an Int")).into()),
+    }
+}
+
+pub fn eq(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     // Use macros to monomorphise functions? or can we leverage Rust's generics? per viariant? maybe with cost generics?
     // #TODO support overloading,
     // #TODO make equality a method of Expr?
-    // #TODO support non-Int types
     // #TODO support multiple arguments.
-    let [a, b] = args else {
-        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
-    };
+    if let [Ann(Expr::Foreign(a), ..), Ann(Expr::Foreign(b), ..)] = args {
+        return Ok(Expr::Bool(a == b).into());
+    }
 
-    let Ann(Expr::Int(a), ..) = a else {
-        return Err(Error::invalid_arguments(format!("`{a}` is not an Int")).into());
-    };
+    if let [Ann(Expr::Buffer(a), ..), Ann(Expr::Buffer(b), ..)] = args {
+        return Ok(Expr::Bool(a == b).into());
+    }
 
-    let Ann(Expr::Int(b), ..) = b else {
-        return Err(Error::invalid_arguments(format!("`{b}` is not an Int")).into());
-    };
+    let ordering = compare(args)?;
 
-    Ok(Expr::Bool(a == b).into())
+    Ok(Expr::Bool(ordering == Some(Ordering::Equal)).into())
 }
 
-pub fn gt(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
-    // #TODO support multiple arguments.
-    let [a, b] = args else {
-        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
+pub fn ne(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Ann(Expr::Bool(equal), ..) = eq(args, env)? else {
+        unreachable!("`=` always returns a Bool");
     };
 
-    let Ann(Expr::Int(a), ..) = a else {
-        return Err(Error::invalid_arguments(format!("`{a}` is not an Int")).into());
-    };
+    Ok(Expr::Bool(!equal).into())
+}
 
-    let Ann(Expr::Int(b), ..) = b else {
-        return Err(Error::invalid_arguments(format!("`{b}` is not an Int")).into());
-    };
+pub fn gt(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let ordering = compare(args)?;
 
-    Ok(Expr::Bool(a > b).into())
+    Ok(Expr::Bool(ordering == Some(Ordering::Greater)).into())
 }
 
-pub fn lt(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
-    // #TODO support multiple arguments.
-    let [a, b] = args else {
-        return Err(Error::invalid_arguments("`-` requires at least two arguments").into());
-    };
+pub fn lt(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let ordering = compare(args)?;
 
-    let Ann(Expr::Int(a), ..) = a else {
-        return Err(Error::invalid_arguments(format!("`{a}` is not an Int")).into());
-    };
+    Ok(Expr::Bool(ordering == Some(Ordering::Less)).into())
+}
 
-    let Ann(Expr::Int(b), ..) = b else {
-        return Err(Error::invalid_arguments(format!("`{b}` is not an Int")).into());
-    };
+pub fn ge(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let ordering = compare(args)?;
+
+    Ok(Expr::Bool(matches!(
+        ordering,
+        Some(Ordering::Greater | Ordering::Equal)
+    ))
+    .into())
+}
+
+pub fn le(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let ordering = compare(args)?;
 
-    Ok(Expr::Bool(a < b).into())
+    Ok(Expr::Bool(matches!(ordering, Some(Ordering::Less | Ordering::Equal))).into())
 }
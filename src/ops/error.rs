@@ -0,0 +1,66 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// Any value can be thrown, e.g. a String message or a Dict with the details of
+// the failure. The `try` special form catches the value as `(Error value)`.
+
+fn error_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a Ann<Expr>, Ranged<Error>> {
+    let Some(Ann(Expr::Error(value), ..)) = args.first() else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires an Error argument")).into(),
+        );
+    };
+
+    Ok(value)
+}
+
+/// Throws a value, e.g. `(throw "not found")`, the evaluation is aborted
+/// unless the error is caught by a `try` expression. Throwing a caught Error
+/// rethrows its value.
+pub fn throw(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`throw` requires one argument").into());
+    };
+
+    let value = match value {
+        Ann(Expr::Error(value), ..) => (**value).clone(),
+        value => value.clone(),
+    };
+
+    Err(Error::Thrown(value).into())
+}
+
+/// Returns the message of an Error, i.e. the formatted value.
+pub fn error_message(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let value = error_arg("error/message", args)?;
+
+    Ok(Ann::with_type(
+        Expr::String(format_value(value)),
+        Expr::symbol("String"),
+    ))
+}
+
+/// Returns the value of an Error, e.g. the thrown value.
+pub fn error_value(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let value = error_arg("error/value", args)?;
+
+    Ok(value.clone())
+}
+
+/// Returns true if the value is an Error, e.g. the result of `validate`.
+pub fn is_error(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`is-error?` requires one argument").into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::Bool(matches!(value, Ann(Expr::Error(..), ..))),
+        Expr::symbol("Bool"),
+    ))
+}
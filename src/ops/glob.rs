@@ -0,0 +1,34 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env},
+    expr::Expr,
+    range::Ranged,
+};
+
+/// Returns the paths that match a glob pattern, e.g. `(glob "src/**/*.tan")`,
+/// as an Array of Strings, in alphabetical order.
+pub fn glob(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "glob")?;
+
+    let [pattern] = args else {
+        return Err(Error::invalid_arguments("`glob` requires a `pattern` argument").into());
+    };
+
+    let Ann(Expr::String(pattern), ..) = pattern else {
+        return Err(Error::invalid_arguments("`pattern` argument should be a String").into());
+    };
+
+    let paths = glob::glob(pattern).map_err(|error| {
+        Error::invalid_arguments(format!("malformed glob pattern `{pattern}`: {error}"))
+    })?;
+
+    let mut exprs = Vec::new();
+
+    for path in paths {
+        let path = path.map_err(|error| Error::Io(error.into()))?;
+        exprs.push(Expr::String(path.to_string_lossy().into_owned()).into());
+    }
+
+    Ok(Expr::Array(exprs).into())
+}
@@ -1,39 +1,36 @@
-use std::fs;
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+};
 
 use crate::{
     ann::Ann,
     error::Error,
-    eval::env::Env,
-    expr::{format_value, Expr},
+    eval::{capabilities::Capability, env::Env},
+    expr::{foreign::ForeignValue, format_value, seq::Seq, Expr},
+    ops::{buffer::buffer, string_arg},
     range::Ranged,
 };
 
 // #TODO do FFI functions really need an env?
 // #TODO differentiate pure functions that do not change the env!
 
-/// Writes one or more expressions to the STDOUT sink/stream.
-pub fn write(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+/// Writes one or more expressions to the stdout stream of the Context.
+pub fn write(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     let output = args.iter().fold(String::new(), |mut str, x| {
         str.push_str(&format_value(x));
         str
     });
 
-    // #TODO shenanigans to handle `\n` in string, how can we do this better?
-    for line in output.split_inclusive("\\n") {
-        if line.ends_with("\\n") {
-            let mut line: String = line.to_owned();
-            line.pop();
-            line.pop();
-            println!("{line}");
-        } else {
-            print!("{line}");
-        }
-    }
+    // #Insight
+    // The escape sequences, e.g. `\n`, are processed by the lexer.
+    env.context.stdout.write_str(&output)?;
 
     Ok(Expr::One.into())
 }
 
-pub fn writeln(args: &[Ann<Expr>], env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn writeln(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     // #TODO nasty implementation!
     write(args, env)?;
     write(&[Expr::string("\n").into()], env)
@@ -41,8 +38,20 @@ pub fn writeln(args: &[Ann<Expr>], env: &Env) -> Result<Ann<Expr>, Ranged<Error>
 
 // #TODO consider mapping `:` to `__` and use #[allow(snake_case)]
 
+// #Insight
+// The file system errors are reported with the path, e.g.
+// "i/o error: `data.txt`: No such file or directory". Like any other error,
+// they are catchable with `try`.
+
+/// Annotates an i/o error with the path.
+fn path_error(path: &str, error: io::Error) -> Error {
+    Error::Io(io::Error::new(error.kind(), format!("`{path}`: {error}")))
+}
+
 /// Reads the contents of a text file as a string.
-pub fn file_read_as_string(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn file_read_as_string(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/read-string")?;
+
     let [path] = args else {
         return Err(Error::invalid_arguments("`read_as_string` requires a `path` argument").into());
     };
@@ -55,3 +64,469 @@ pub fn file_read_as_string(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>,
 
     Ok(Expr::String(contents).into())
 }
+
+/// Reads the contents of a binary file as a Buffer.
+pub fn file_read_bytes(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/read-bytes")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`read_bytes` requires a `path` argument").into());
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let bytes = fs::read(path)?;
+
+    Ok(buffer(bytes))
+}
+
+/// Writes a Buffer to a binary file, replaces the file if it exists.
+pub fn file_write_bytes(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/write-bytes")?;
+
+    let [path, buf] = args else {
+        return Err(
+            Error::invalid_arguments("`write_bytes` requires `path`, `buffer` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let Ann(Expr::Buffer(bytes), ..) = buf else {
+        return Err(Error::invalid_arguments("`buffer` argument should be a Buffer").into());
+    };
+
+    if env.context.audit_effect("io/write-bytes", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::write(path, bytes)?;
+
+    Ok(Expr::One.into())
+}
+
+// #TODO report the read errors that happen while streaming the lines.
+
+/// The lazy sequence of the lines of a text file, a read error, e.g. invalid
+/// UTF-8, is an error of the sequence, it does not end it silently.
+struct LinesSeq(Lines<BufReader<File>>);
+
+impl Seq for LinesSeq {
+    fn next(&mut self, _env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        match self.0.next() {
+            Some(line) => Ok(Some(Expr::String(line?).into())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Returns a lazy sequence of the lines of a text file, the file is read
+/// incrementally, e.g. to process large log files.
+pub fn file_lines(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/lines")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`lines` requires a `path` argument").into());
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let file = File::open(path)?;
+
+    let lines = LinesSeq(BufReader::new(file).lines());
+
+    Ok(Ann::with_type(Expr::seq(lines), Expr::symbol("Iterator")))
+}
+
+/// Writes a String to a text file, replaces the file if it exists.
+pub fn file_write(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/write-string")?;
+
+    let [path, contents] = args else {
+        return Err(
+            Error::invalid_arguments("`write` requires `path`, `contents` arguments").into(),
+        );
+    };
+
+    let path = string_arg(path, "path")?;
+    let contents = string_arg(contents, "contents")?;
+
+    if env.context.audit_effect("io/write-string", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::write(path, contents).map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Appends a String to a text file, creates the file if it does not exist.
+pub fn file_append(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/append-string")?;
+
+    let [path, contents] = args else {
+        return Err(
+            Error::invalid_arguments("`append` requires `path`, `contents` arguments").into(),
+        );
+    };
+
+    let path = string_arg(path, "path")?;
+    let contents = string_arg(contents, "contents")?;
+
+    if env.context.audit_effect("io/append-string", args) {
+        return Ok(Expr::One.into());
+    }
+
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Returns true if the path exists, a file or a directory.
+pub fn file_exists(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/exists?")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`exists?` requires a `path` argument").into());
+    };
+
+    let path = string_arg(path, "path")?;
+
+    let exists = fs::exists(path).map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::Bool(exists).into())
+}
+
+/// Deletes a file.
+pub fn file_delete(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/delete-file")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`delete` requires a `path` argument").into());
+    };
+
+    let path = string_arg(path, "path")?;
+
+    if env.context.audit_effect("io/delete-file", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::remove_file(path).map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Copies a file, replaces the target file if it exists.
+pub fn file_copy(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/copy-file")?;
+
+    let [from, to] = args else {
+        return Err(Error::invalid_arguments("`copy` requires `from`, `to` arguments").into());
+    };
+
+    let from = string_arg(from, "from")?;
+    let to = string_arg(to, "to")?;
+
+    if env.context.audit_effect("io/copy-file", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::copy(from, to).map_err(|error| path_error(from, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Returns the names of the entries of a directory, as an Array of Strings,
+/// in alphabetical order. Use `path/join` to build the paths of the entries.
+pub fn dir_list(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/list-dir")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`list` requires a `path` argument").into());
+    };
+
+    let path = string_arg(path, "path")?;
+
+    let mut names = fs::read_dir(path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .map_err(|error| path_error(path, error))?;
+
+    // read_dir does not guarantee any order.
+    names.sort();
+
+    Ok(Expr::Array(
+        names
+            .into_iter()
+            .map(|name| Expr::String(name).into())
+            .collect(),
+    )
+    .into())
+}
+
+/// Creates a directory, and any missing parent directories.
+pub fn dir_create(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/create-dir")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`create` requires a `path` argument").into());
+    };
+
+    let path = string_arg(path, "path")?;
+
+    if env.context.audit_effect("io/create-dir", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::create_dir_all(path).map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Deletes a directory, with all its contents.
+pub fn dir_delete(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/delete-dir")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`delete` requires a `path` argument").into());
+    };
+
+    let path = string_arg(path, "path")?;
+
+    if env.context.audit_effect("io/delete-dir", args) {
+        return Ok(Expr::One.into());
+    }
+
+    fs::remove_dir_all(path).map_err(|error| path_error(path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+// #Insight
+// A file handle is an opaque (foreign) value, a buffered reader or writer of
+// an open file, to process large files incrementally, e.g.
+//
+// (let log (File:open "app.log"))
+// (read-line log)
+// (close log)
+//
+// The file is closed when the handle is closed, or dropped. A closed handle
+// cannot be used.
+//
+// In audit mode a file is not opened for writing, the handle records the
+// effects as `io/write-string` and `io/append-string` actions, e.g. opening
+// with `:write` records the truncation of the file.
+
+// #TODO support reading/writing bytes.
+// #TODO close the handles automatically at the end of a scope, e.g. `with`.
+
+const FILE_HANDLE_TYPE: &str = "FileHandle";
+
+enum FileStream {
+    Reader(BufReader<File>),
+    Writer(BufWriter<File>),
+    /// A writer in audit mode, the writes are recorded.
+    Audited,
+}
+
+/// An open file, None when closed.
+struct FileHandle {
+    path: String,
+    stream: RefCell<Option<FileStream>>,
+}
+
+fn handle_arg(arg: &Ann<Expr>) -> Result<&FileHandle, Ranged<Error>> {
+    let Some(handle) = (match arg.as_ref() {
+        Expr::Foreign(value) => value.downcast_ref::<FileHandle>(),
+        _ => None,
+    }) else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not a file handle")).into());
+    };
+
+    Ok(handle)
+}
+
+fn closed_handle(handle: &FileHandle) -> Ranged<Error> {
+    Error::invalid_arguments(format!("the file handle of `{}` is closed", handle.path)).into()
+}
+
+/// Opens a file, returns a file handle. The mode is `:read` (default),
+/// `:write` (replaces the file) or `:append`, e.g. `(File:open "out.txt" :write)`.
+pub fn file_open(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "io/open")?;
+
+    let (path, mode) = match args {
+        [path] => (path, "read"),
+        [path, Ann(Expr::KeySymbol(mode), ..)] => (path, mode.as_str()),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`open` requires `path` and optional `mode` arguments",
+            )
+            .into());
+        }
+    };
+
+    let path = string_arg(path, "path")?;
+
+    let mut options = OpenOptions::new();
+
+    // The effect of opening for writing, in audit mode.
+    let effect = match mode {
+        "read" => {
+            options.read(true);
+            None
+        }
+        "write" => {
+            options.write(true).create(true).truncate(true);
+            Some("io/write-string")
+        }
+        "append" => {
+            options.append(true).create(true);
+            Some("io/append-string")
+        }
+        _ => {
+            return Err(Error::invalid_arguments(format!("unknown file mode `:{mode}`")).into());
+        }
+    };
+
+    let effect_args = [Expr::string(path).into(), Expr::string("").into()];
+
+    let stream = if effect.is_some_and(|op| env.context.audit_effect(op, &effect_args)) {
+        FileStream::Audited
+    } else {
+        let file = options
+            .open(path)
+            .map_err(|error| path_error(path, error))?;
+
+        if mode == "read" {
+            FileStream::Reader(BufReader::new(file))
+        } else {
+            FileStream::Writer(BufWriter::new(file))
+        }
+    };
+
+    let handle = FileHandle {
+        path: path.to_owned(),
+        stream: RefCell::new(Some(stream)),
+    };
+
+    Ok(Ann::with_type(
+        Expr::Foreign(ForeignValue::new(FILE_HANDLE_TYPE, handle)),
+        Expr::symbol(FILE_HANDLE_TYPE),
+    ))
+}
+
+/// Reads the next line of a file handle, without the line terminator. Returns
+/// None at the end of the file.
+pub fn read_line(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [handle] = args else {
+        return Err(Error::invalid_arguments("`read-line` requires a `handle` argument").into());
+    };
+
+    let handle = handle_arg(handle)?;
+
+    let mut stream = handle.stream.borrow_mut();
+
+    let Some(FileStream::Reader(reader)) = stream.as_mut() else {
+        return Err(match *stream {
+            None => closed_handle(handle),
+            Some(..) => Error::invalid_arguments(format!(
+                "the file handle of `{}` is not readable",
+                handle.path
+            ))
+            .into(),
+        });
+    };
+
+    let mut line = String::new();
+
+    let count = reader
+        .read_line(&mut line)
+        .map_err(|error| path_error(&handle.path, error))?;
+
+    if count == 0 {
+        return Ok(Expr::none().into());
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+
+    Ok(Expr::some(Expr::String(line)).into())
+}
+
+/// Writes a String and a newline to a file handle.
+pub fn write_line(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [handle, line] = args else {
+        return Err(
+            Error::invalid_arguments("`write-line` requires `handle`, `line` arguments").into(),
+        );
+    };
+
+    let handle = handle_arg(handle)?;
+    let line = string_arg(line, "line")?;
+
+    let mut stream = handle.stream.borrow_mut();
+
+    if let Some(FileStream::Audited) = *stream {
+        let line = Expr::string(format!("{line}\n"));
+        env.context.audit_effect(
+            "io/append-string",
+            &[Expr::string(&handle.path).into(), line.into()],
+        );
+        return Ok(Expr::One.into());
+    }
+
+    let Some(FileStream::Writer(writer)) = stream.as_mut() else {
+        return Err(match *stream {
+            None => closed_handle(handle),
+            Some(..) => Error::invalid_arguments(format!(
+                "the file handle of `{}` is not writable",
+                handle.path
+            ))
+            .into(),
+        });
+    };
+
+    writeln!(writer, "{line}").map_err(|error| path_error(&handle.path, error))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Closes a file handle, the buffered output is flushed.
+pub fn close(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [handle] = args else {
+        return Err(Error::invalid_arguments("`close` requires a `handle` argument").into());
+    };
+
+    let handle = handle_arg(handle)?;
+
+    let Some(stream) = handle.stream.borrow_mut().take() else {
+        return Err(closed_handle(handle));
+    };
+
+    if let FileStream::Writer(mut writer) = stream {
+        writer
+            .flush()
+            .map_err(|error| path_error(&handle.path, error))?;
+    }
+
+    Ok(Expr::One.into())
+}
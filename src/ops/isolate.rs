@@ -0,0 +1,76 @@
+use std::{sync::mpsc::RecvTimeoutError, time::Instant};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{cancellation::POLL_INTERVAL, env::Env},
+    expr::{portable::Portable, Expr},
+    range::Ranged,
+    runtime::Mailbox,
+};
+
+// #Insight
+// The messages between the host and an isolate are plain data, see
+// `Runtime::spawn_isolate`.
+
+fn mailbox<'a>(name: &str, env: &'a Env) -> Result<&'a Mailbox, Ranged<Error>> {
+    env.context.mailbox.as_ref().ok_or_else(|| {
+        Error::invalid_arguments(format!("`{name}` is only available in an isolate")).into()
+    })
+}
+
+/// Sends a message to the host of the isolate, e.g. `(send {:progress 50})`.
+pub fn send(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [message] = args else {
+        return Err(Error::invalid_arguments("`send` requires one argument").into());
+    };
+
+    let Some(portable) = Portable::from_ann(message).filter(Portable::is_data) else {
+        return Err(Error::invalid_arguments(format!("`{message}` is not plain data")).into());
+    };
+
+    mailbox("send", env)?
+        .outbox
+        .send(portable)
+        .map_err(|_| Error::invalid_arguments("the host is disconnected"))?;
+
+    Ok(Expr::One.into())
+}
+
+/// Receives a message from the host, blocks until a message is available.
+/// Returns `None` if the host will not send more messages.
+pub fn recv(_args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let inbox = &mailbox("recv", env)?.inbox;
+
+    let message = loop {
+        env.context.check_cancelled()?;
+
+        let mut timeout = env
+            .context
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+
+        // With a cancellation token, wait in slices to observe the
+        // cancellation.
+        if env.context.cancellation.is_some() {
+            timeout = Some(timeout.map_or(POLL_INTERVAL, |timeout| timeout.min(POLL_INTERVAL)));
+        }
+
+        let Some(timeout) = timeout else {
+            break inbox.recv().ok();
+        };
+
+        match inbox.recv_timeout(timeout) {
+            Ok(message) => break Some(message),
+            Err(RecvTimeoutError::Timeout) => env.context.check_deadline()?,
+            Err(RecvTimeoutError::Disconnected) => break None,
+        }
+    };
+
+    let message = match message {
+        Some(message) => Expr::some(message.to_ann(&[])),
+        None => Expr::none(),
+    };
+
+    Ok(Ann::with_type(message, Expr::symbol("Maybe")))
+}
@@ -1,6 +1,13 @@
-use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+use crate::{
+    ann::Ann,
+    api::read_value,
+    error::Error,
+    eval::env::Env,
+    expr::{data::data_to_string, Expr},
+    range::Ranged,
+};
 
-pub fn ann(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
+pub fn ann(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
     if args.len() != 1 {
         return Err(Error::invalid_arguments("`ann` requires one argument").into());
     }
@@ -13,3 +20,46 @@ pub fn ann(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
 
     Ok(Expr::One.into())
 }
+
+/// Returns true if the argument is the Unit value `()`.
+pub fn is_unit(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`unit?` requires one argument").into());
+    };
+
+    Ok(Expr::Bool(matches!(value.0, Expr::One)).into())
+}
+
+/// Returns true if the argument is the Never value.
+pub fn is_never(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`never?` requires one argument").into());
+    };
+
+    Ok(Expr::Bool(matches!(value.0, Expr::Never)).into())
+}
+
+/// Reads a data value from Tan text, without evaluation, e.g.
+/// `(expr/read "{:port 8080}")`.
+pub fn expr_read(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::String(text), ..)] = args else {
+        return Err(Error::invalid_arguments("`expr/read` requires a String argument").into());
+    };
+
+    read_value(text).map_err(|error| {
+        let Ranged(error, _) = &error[0];
+        Error::invalid_arguments(format!("cannot read data: {error}")).into()
+    })
+}
+
+/// Writes a data value as Tan text, the text reads back with `expr/read`.
+pub fn expr_write(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`expr/write` requires one argument").into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::String(data_to_string(&value.0)?),
+        Expr::symbol("String"),
+    ))
+}
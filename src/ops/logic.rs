@@ -0,0 +1,17 @@
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// `and` and `or` are special forms, they short-circuit, see `eval`.
+
+/// Returns the negation of a Bool.
+pub fn not(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`not` requires one argument").into());
+    };
+
+    let Ann(Expr::Bool(value), ..) = value else {
+        return Err(Error::invalid_arguments(format!("`{value}` is not a Bool")).into());
+    };
+
+    Ok(Ann::with_type(Expr::Bool(!value), Expr::symbol("Bool")))
+}
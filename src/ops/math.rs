@@ -0,0 +1,298 @@
+use std::cmp::Ordering;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{number::convert_number_literal, Expr},
+    ops::{float_arg, int_arg},
+    range::Ranged,
+};
+
+// #Insight
+// Like the arithmetic operators, the generic math functions dispatch
+// dynamically on the types of the arguments, the `$$`-mangled specializations
+// are selected statically by the resolver. The generic functions promote Ints
+// to Floats when any argument is a Float.
+
+// #TODO support BigInt and Decimal.
+
+pub const PI: f64 = std::f64::consts::PI;
+pub const E: f64 = std::f64::consts::E;
+
+/// Returns true if any of the arguments is a Float.
+fn has_float_arg(args: &[Ann<Expr>]) -> bool {
+    args.iter()
+        .any(|arg| matches!(arg, Ann(Expr::Float(..), ..)))
+}
+
+fn int(n: i64) -> Ann<Expr> {
+    Ann::with_type(Expr::Int(n), Expr::symbol("Int"))
+}
+
+fn float(n: f64) -> Ann<Expr> {
+    Ann::with_type(Expr::Float(n), Expr::symbol("Float"))
+}
+
+fn binary_args<'a>(
+    name: &str,
+    args: &'a [Ann<Expr>],
+) -> Result<(&'a Ann<Expr>, &'a Ann<Expr>), Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments(format!("`{name}` requires two arguments")).into());
+    };
+
+    Ok((a, b))
+}
+
+fn unary_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a Ann<Expr>, Ranged<Error>> {
+    let [a] = args else {
+        return Err(Error::invalid_arguments(format!("`{name}` requires one argument")).into());
+    };
+
+    Ok(a)
+}
+
+fn overflow(name: &str) -> Ranged<Error> {
+    Error::IntegerOverflow(name.to_owned()).into()
+}
+
+// div, mod
+
+// #Insight
+// The integer `div` and `mod` are Euclidean, the remainder is never negative,
+// e.g. `(mod -7 2)` is 1.
+
+pub fn div(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        div_float(args, env)
+    } else {
+        div_int(args, env)
+    }
+}
+
+pub fn div_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (a, b) = binary_args("div", args)?;
+    let (a, b) = (int_arg(a)?, int_arg(b)?);
+
+    if b == 0 {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    a.checked_div_euclid(b)
+        .map(int)
+        .ok_or_else(|| overflow("div"))
+}
+
+pub fn div_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (a, b) = binary_args("div", args)?;
+
+    Ok(float(float_arg(a)? / float_arg(b)?))
+}
+
+pub fn modulo(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        mod_float(args, env)
+    } else {
+        mod_int(args, env)
+    }
+}
+
+pub fn mod_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (a, b) = binary_args("mod", args)?;
+    let (a, b) = (int_arg(a)?, int_arg(b)?);
+
+    if b == 0 {
+        return Err(Error::DivisionByZero.into());
+    }
+
+    a.checked_rem_euclid(b)
+        .map(int)
+        .ok_or_else(|| overflow("mod"))
+}
+
+pub fn mod_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (a, b) = binary_args("mod", args)?;
+
+    Ok(float(float_arg(a)?.rem_euclid(float_arg(b)?)))
+}
+
+// pow, sqrt
+
+pub fn pow(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        pow_float(args, env)
+    } else {
+        pow_int(args, env)
+    }
+}
+
+pub fn pow_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (base, exponent) = binary_args("pow", args)?;
+    let base = int_arg(base)?;
+
+    let Ok(exponent) = u32::try_from(int_arg(exponent)?) else {
+        return Err(Error::invalid_arguments(format!(
+            "the Int exponent `{exponent}` should be non-negative"
+        ))
+        .into());
+    };
+
+    base.checked_pow(exponent)
+        .map(int)
+        .ok_or_else(|| overflow("pow"))
+}
+
+pub fn pow_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (base, exponent) = binary_args("pow", args)?;
+
+    Ok(float(float_arg(base)?.powf(float_arg(exponent)?)))
+}
+
+/// Returns the square root as a Float, Ints are promoted.
+pub fn sqrt(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let n = float_arg(unary_arg("sqrt", args)?)?;
+
+    Ok(float(n.sqrt()))
+}
+
+// abs
+
+pub fn abs(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if has_float_arg(args) {
+        abs_float(args, env)
+    } else {
+        abs_int(args, env)
+    }
+}
+
+pub fn abs_int(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let n = int_arg(unary_arg("abs", args)?)?;
+
+    n.checked_abs().map(int).ok_or_else(|| overflow("abs"))
+}
+
+pub fn abs_float(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let n = float_arg(unary_arg("abs", args)?)?;
+
+    Ok(float(n.abs()))
+}
+
+// floor, ceil, round
+
+// #Insight
+// The rounding functions preserve the type of the argument, Ints are returned
+// unchanged.
+
+fn rounding(name: &str, args: &[Ann<Expr>], f: fn(f64) -> f64) -> Result<Ann<Expr>, Ranged<Error>> {
+    match unary_arg(name, args)? {
+        Ann(Expr::Int(n), ..) => Ok(int(*n)),
+        Ann(Expr::Float(n), ..) => Ok(float(f(*n))),
+        arg => Err(Error::invalid_arguments(format!("`{arg}` is not a number")).into()),
+    }
+}
+
+pub fn floor(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    rounding("floor", args, f64::floor)
+}
+
+pub fn ceil(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    rounding("ceil", args, f64::ceil)
+}
+
+/// Rounds half-way cases away from zero.
+pub fn round(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    rounding("round", args, f64::round)
+}
+
+// min, max
+
+fn extremum(name: &str, args: &[Ann<Expr>], target: Ordering) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((first, rest)) = args.split_first() else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires at least one argument")).into(),
+        );
+    };
+
+    if has_float_arg(args) {
+        let mut result = float_arg(first)?;
+        for arg in rest {
+            let n = float_arg(arg)?;
+            if n.partial_cmp(&result) == Some(target) {
+                result = n;
+            }
+        }
+        Ok(float(result))
+    } else {
+        let mut result = int_arg(first)?;
+        for arg in rest {
+            let n = int_arg(arg)?;
+            if n.cmp(&result) == target {
+                result = n;
+            }
+        }
+        Ok(int(result))
+    }
+}
+
+/// Returns the smallest of the arguments, e.g. `(min 3 1 2)`.
+pub fn min(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    extremum("min", args, Ordering::Less)
+}
+
+/// Returns the largest of the arguments, e.g. `(max 3 1 2)`.
+pub fn max(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    extremum("max", args, Ordering::Greater)
+}
+
+// to-i8, to-u8, ...
+
+// #Insight
+// The checked casts convert an Int (or a BigInt) to a sized integer, for
+// interop with binary formats, e.g. `(to-u8 n)`. The sized integers are Ints
+// at runtime, annotated with the sized type. An out of range value is an
+// error, the value is never truncated.
+
+fn to_sized_int(
+    name: &str,
+    type_name: &str,
+    args: &[Ann<Expr>],
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let a = unary_arg(name, args)?;
+
+    match &a.0 {
+        Expr::Int(..) | Expr::BigInt(..) => Ok(Ann::with_type(
+            convert_number_literal(&a.0, type_name)?,
+            Expr::symbol(type_name),
+        )),
+        _ => Err(Error::invalid_arguments(format!("`{a}` is not an Int")).into()),
+    }
+}
+
+pub fn to_i8(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-i8", "I8", args)
+}
+
+pub fn to_u8(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-u8", "U8", args)
+}
+
+pub fn to_i16(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-i16", "I16", args)
+}
+
+pub fn to_u16(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-u16", "U16", args)
+}
+
+pub fn to_i32(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-i32", "I32", args)
+}
+
+pub fn to_u32(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-u32", "U32", args)
+}
+
+pub fn to_i64(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    to_sized_int("to-i64", "I64", args)
+}
@@ -0,0 +1,70 @@
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The lookups, e.g. `(arr 0)` or `(dict :key)`, return a Maybe, a missing
+// value is distinguishable from a stored `()`. See also the `?` special form.
+
+fn maybe_arg<'a>(
+    name: &str,
+    args: &'a [Ann<Expr>],
+) -> Result<Option<&'a Ann<Expr>>, Ranged<Error>> {
+    let Some(Ann(Expr::Maybe(value), ..)) = args.first() else {
+        return Err(Error::invalid_arguments(format!("`{name}` requires a Maybe argument")).into());
+    };
+
+    Ok(value.as_deref())
+}
+
+/// Creates a present optional value, e.g. `(Some 1)`.
+pub fn some(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value] = args else {
+        return Err(Error::invalid_arguments("`Some` requires one argument").into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::some(value.clone()),
+        Expr::symbol("Maybe"),
+    ))
+}
+
+pub fn is_some(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let value = maybe_arg("is-some?", args)?;
+
+    Ok(Ann::with_type(
+        Expr::Bool(value.is_some()),
+        Expr::symbol("Bool"),
+    ))
+}
+
+pub fn is_none(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let value = maybe_arg("is-none?", args)?;
+
+    Ok(Ann::with_type(
+        Expr::Bool(value.is_none()),
+        Expr::symbol("Bool"),
+    ))
+}
+
+/// Returns the value of a `(Some value)`, unwrapping `None` is an error.
+pub fn unwrap(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some(value) = maybe_arg("unwrap", args)? else {
+        return Err(Error::invalid_arguments("cannot unwrap `None`").into());
+    };
+
+    Ok(value.clone())
+}
+
+/// Returns the value of a `(Some value)`, or the default for `None`, e.g.
+/// `(unwrap-or (dict :port) 8080)`. The default is always evaluated, use `?`
+/// to evaluate the default lazily.
+pub fn unwrap_or(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [_, default] = args else {
+        return Err(
+            Error::invalid_arguments("`unwrap-or` requires `maybe`, `default` arguments").into(),
+        );
+    };
+
+    let value = maybe_arg("unwrap-or", args)?;
+
+    Ok(value.unwrap_or(default).clone())
+}
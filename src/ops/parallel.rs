@@ -0,0 +1,159 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{
+        capture::{CapturedFunc, PortableError},
+        context::WorkerPolicy,
+        env::Env,
+        invoke,
+    },
+    expr::{portable::Portable, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// Every worker thread evaluates a captured copy of the function in its own
+// Env, forked from a fresh prelude, see `CapturedFunc`. The worker Envs inherit
+// the policy of the Context, e.g. the capabilities, see `WorkerPolicy`. The
+// parallel evaluation is only used for pure functions that can be captured.
+// In any other case, or if threading is unavailable, the items are processed
+// sequentially, the result is the same.
+//
+// The number of the workers is configured with `Context::workers`, the
+// available parallelism by default.
+
+// #TODO the I/O effects are not tracked yet, e.g. `writeln` output may interleave.
+// #TODO support Lists and lazy sequences.
+// #TODO reuse the worker threads between invocations.
+
+/// A parallel map, the captured function and the items.
+struct Task {
+    func: CapturedFunc,
+    items: Vec<Portable>,
+}
+
+impl Task {
+    fn new(func: &Ann<Expr>, items: &[Ann<Expr>], env: &mut Env) -> Option<Self> {
+        let items = items
+            .iter()
+            .map(|item| Portable::from_ann(item).filter(Portable::is_data))
+            .collect::<Option<Vec<_>>>()?;
+
+        let func = CapturedFunc::capture(func, env)?;
+
+        Some(Task { func, items })
+    }
+
+    /// Processes items until all items are processed or a worker fails.
+    fn work(
+        &self,
+        next: &AtomicUsize,
+        failed: &AtomicBool,
+        policy: &WorkerPolicy,
+    ) -> Vec<(usize, Result<Portable, Ranged<PortableError>>)> {
+        let mut env = policy.env();
+
+        let func = self.func.instantiate(&mut env);
+
+        let mut results = Vec::new();
+
+        while !failed.load(Ordering::Relaxed) {
+            let i = next.fetch_add(1, Ordering::Relaxed);
+
+            let Some(item) = self.items.get(i) else {
+                break;
+            };
+
+            let result = invoke(&func, vec![item.to_ann(&[])], &mut env)
+                .map_err(PortableError::from_error)
+                .and_then(|value| {
+                    Portable::from_ann(&value)
+                        .filter(Portable::is_data)
+                        .ok_or_else(|| {
+                            Ranged::new(PortableError::Other(format!(
+                                "the result `{value}` cannot be returned from a `pmap` worker"
+                            )))
+                        })
+                });
+
+            if result.is_err() {
+                failed.store(true, Ordering::Relaxed);
+            }
+
+            results.push((i, result));
+        }
+
+        results
+    }
+
+    fn run(&self, workers: usize, policy: &WorkerPolicy) -> Result<Ann<Expr>, Ranged<Error>> {
+        let next = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+
+        let mut results: Vec<Option<Result<Portable, Ranged<PortableError>>>> =
+            (0..self.items.len()).map(|_| None).collect();
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers)
+                .map(|_| scope.spawn(|| self.work(&next, &failed, policy)))
+                .collect();
+
+            for handle in handles {
+                let worker_results = handle
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+                for (i, result) in worker_results {
+                    results[i] = Some(result);
+                }
+            }
+        });
+
+        // #Insight
+        // After a failure, some items are not processed, the error of the
+        // first failed item is reported.
+        let mut values = Vec::with_capacity(results.len());
+
+        for result in results.into_iter().flatten() {
+            values.push(result.map_err(PortableError::into_error)?.to_ann(&[]));
+        }
+
+        Ok(Expr::Array(values).into())
+    }
+}
+
+/// Maps the function over the items of an Array, in parallel, e.g.
+/// `(pmap (Func (x) (* x x)) [1 2 3])`. Returns an Array of the results, in
+/// the order of the items.
+pub fn pmap(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [func, items] = args else {
+        return Err(Error::invalid_arguments("`pmap` requires `func`, `items` arguments").into());
+    };
+
+    let Ann(Expr::Array(items), ..) = items else {
+        return Err(Error::invalid_arguments(format!("`{items}` is not an Array")).into());
+    };
+
+    let workers = env
+        .context
+        .workers
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from))
+        .min(items.len());
+
+    if workers > 1 {
+        if let Some(task) = Task::new(func, items, env) {
+            return task.run(workers, &env.context.worker_policy());
+        }
+    }
+
+    let values = items
+        .iter()
+        .map(|item| invoke(func, vec![item.clone()], env))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Expr::Array(values).into())
+}
@@ -0,0 +1,68 @@
+use std::path::{Path, PathBuf};
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The paths are Strings, the path helpers are pure, they don't access the
+// file system.
+
+fn path_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a Path, Ranged<Error>> {
+    let [path] = args else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires a `path` argument")).into(),
+        );
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    Ok(Path::new(path))
+}
+
+fn path_value(path: impl AsRef<Path>) -> Ann<Expr> {
+    Expr::String(path.as_ref().to_string_lossy().into_owned()).into()
+}
+
+/// Joins path segments, e.g. `(path/join "src" "main.tan")`. An absolute
+/// segment replaces the preceding segments.
+pub fn path_join(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if args.is_empty() {
+        return Err(Error::invalid_arguments("`path/join` requires at least one argument").into());
+    }
+
+    let mut path = PathBuf::new();
+
+    for arg in args {
+        let Ann(Expr::String(segment), ..) = arg else {
+            return Err(Error::invalid_arguments(format!("`{arg}` is not a String")).into());
+        };
+        path.push(segment);
+    }
+
+    Ok(path_value(path))
+}
+
+/// Returns the parent directory of a path, e.g. `(Some "src")` for
+/// `"src/main.tan"`, None for a root or empty path.
+pub fn path_parent(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let path = path_arg("path/parent", args)?;
+
+    Ok(match path.parent() {
+        Some(parent) => Expr::some(path_value(parent)),
+        None => Expr::none(),
+    }
+    .into())
+}
+
+/// Returns the extension of a path, without the `.`, e.g. `(Some "tan")` for
+/// `"src/main.tan"`, None if the path has no extension.
+pub fn path_extension(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let path = path_arg("path/extension", args)?;
+
+    Ok(match path.extension() {
+        Some(extension) => Expr::some(Expr::String(extension.to_string_lossy().into_owned())),
+        None => Expr::none(),
+    }
+    .into())
+}
@@ -1,19 +1,29 @@
-use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env},
+    expr::Expr,
+    range::Ranged,
+};
 
 /// Terminates the current process with the specified exit code.
-pub fn exit(args: &[Ann<Expr>], _env: &Env) -> Result<Ann<Expr>, Ranged<Error>> {
-    if let Some(code) = args.first() {
-        let Ann(Expr::Int(code), ..) = code else {
-            return Err(Error::InvalidArguments("expected Int argument".to_owned()).into());
-        };
+pub fn exit(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Process, "exit")?;
 
-        let code = *code as i32;
+    // Exit with code=0 by default.
+    let code = match args.first() {
+        Some(Ann(Expr::Int(code), ..)) => *code as i32,
+        Some(_) => {
+            return Err(Error::InvalidArguments("expected Int argument".to_owned()).into());
+        }
+        None => 0,
+    };
 
-        std::process::exit(code);
-    } else {
-        // Exit with code=0 by default.
-        std::process::exit(0);
+    if env.context.audit_effect("exit", args) {
+        return Ok(Expr::One.into());
     }
+
+    std::process::exit(code);
 }
 
 // #TODO args
@@ -0,0 +1,40 @@
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+/// Reports the progress of a long-running script, e.g.
+/// `(progress/report 42 "Processing files")`. The report is passed to the
+/// host handler of the environment, or written to the stderr stream.
+pub fn progress_report(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [percent, message] = args else {
+        return Err(Error::invalid_arguments(
+            "`progress/report` requires `percent`, `message` arguments",
+        )
+        .into());
+    };
+
+    let percent = match percent {
+        Ann(Expr::Int(n), ..) => *n as f64,
+        Ann(Expr::Float(n), ..) => *n,
+        _ => return Err(Error::invalid_arguments(format!("`{percent}` is not a number")).into()),
+    };
+
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(Error::invalid_arguments(format!(
+            "the percent `{percent}` should be between 0 and 100"
+        ))
+        .into());
+    }
+
+    let Ann(Expr::String(message), ..) = message else {
+        return Err(Error::invalid_arguments(format!("`{message}` is not a String")).into());
+    };
+
+    match &mut env.context.progress_handler {
+        Some(handler) => (handler.0)(percent, message),
+        None => env
+            .context
+            .stderr
+            .write_str(&format!("[{percent:>3.0}%] {message}\n"))?,
+    }
+
+    Ok(Expr::One.into())
+}
@@ -0,0 +1,184 @@
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env},
+    expr::Expr,
+    range::Ranged,
+};
+
+// #Insight
+// The prompts are written to the stderr stream of the Context, so that the
+// output of a script can be piped. When stdin is not a terminal (e.g. the
+// answers are piped in), the prompts are not shown and the answers are read
+// line by line.
+
+// #TODO `(select "msg" choices)`, a menu of choices.
+
+fn is_interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+fn show_prompt(message: &str, env: &Env) -> Result<(), Error> {
+    if is_interactive() {
+        env.context.stderr.write_str(message)?;
+        env.context.stderr.flush()?;
+    }
+
+    Ok(())
+}
+
+/// Reads a line, without the line terminator. Returns None at the end of the
+/// input.
+fn read_answer(input: &mut impl BufRead) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(line.trim_end_matches(['\n', '\r']).to_owned()))
+}
+
+fn no_input(message: &str) -> Ranged<Error> {
+    Error::invalid_arguments(format!(
+        "no answer to the prompt `{message}`, the input is closed"
+    ))
+    .into()
+}
+
+/// Parses a yes/no answer, an empty answer selects the default.
+fn parse_confirmation(answer: &str, default: Option<bool>) -> Option<bool> {
+    match answer.trim().to_lowercase().as_str() {
+        "" => default,
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn message_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a str, Ranged<Error>> {
+    let Some(Ann(Expr::String(message), ..)) = args.first() else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires a `message` String")).into(),
+        );
+    };
+
+    Ok(message)
+}
+
+fn string(s: String) -> Ann<Expr> {
+    Ann::with_type(Expr::String(s), Expr::symbol("String"))
+}
+
+/// Asks for a line of text, e.g. `(prompt "Name: ")` or, with a default value
+/// for an empty answer, `(prompt "Name: " "World")`.
+pub fn prompt(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Process, "prompt")?;
+
+    let message = message_arg("prompt", args)?;
+
+    let default = match args.get(1) {
+        None => None,
+        Some(Ann(Expr::String(default), ..)) => Some(default.clone()),
+        Some(default) => {
+            return Err(Error::invalid_arguments(format!("`{default}` is not a String")).into());
+        }
+    };
+
+    show_prompt(message, env)?;
+
+    let answer = match read_answer(&mut io::stdin().lock())? {
+        Some(answer) if answer.is_empty() => default.unwrap_or(answer),
+        Some(answer) => answer,
+        None => default.ok_or_else(|| no_input(message))?,
+    };
+
+    Ok(string(answer))
+}
+
+/// Asks a yes/no question, e.g. `(confirm "Proceed?")`, returns a Bool. The
+/// optional default is selected by an empty answer, e.g. `(confirm "Proceed?" true)`.
+pub fn confirm(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Process, "confirm")?;
+
+    let message = message_arg("confirm", args)?;
+
+    let default = match args.get(1) {
+        None => None,
+        Some(Ann(Expr::Bool(default), ..)) => Some(*default),
+        Some(default) => {
+            return Err(Error::invalid_arguments(format!("`{default}` is not a Bool")).into());
+        }
+    };
+
+    let hint = match default {
+        None => "[y/n]",
+        Some(true) => "[Y/n]",
+        Some(false) => "[y/N]",
+    };
+
+    let mut stdin = io::stdin().lock();
+
+    loop {
+        show_prompt(&format!("{message} {hint} "), env)?;
+
+        let Some(answer) = read_answer(&mut stdin)? else {
+            return default
+                .map(|default| Ann::with_type(Expr::Bool(default), Expr::symbol("Bool")))
+                .ok_or_else(|| no_input(message));
+        };
+
+        if let Some(confirmed) = parse_confirmation(&answer, default) {
+            return Ok(Ann::with_type(Expr::Bool(confirmed), Expr::symbol("Bool")));
+        }
+
+        // Only ask again when a human is answering.
+        if !is_interactive() {
+            return Err(Error::invalid_arguments(format!(
+                "invalid answer `{answer}` to the prompt `{message}`"
+            ))
+            .into());
+        }
+    }
+}
+
+/// Asks for a secret, e.g. `(prompt/secret "Token: ")`, the answer is not
+/// echoed.
+pub fn prompt_secret(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Process, "prompt/secret")?;
+
+    let message = message_arg("prompt/secret", args)?;
+
+    let answer = if is_interactive() {
+        rpassword::prompt_password(message).map_err(Error::from)?
+    } else {
+        read_answer(&mut io::stdin().lock())?.ok_or_else(|| no_input(message))?
+    };
+
+    Ok(string(answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_confirmation, read_answer};
+
+    #[test]
+    fn parse_confirmation_accepts_yes_no_and_default() {
+        assert_eq!(parse_confirmation("y", None), Some(true));
+        assert_eq!(parse_confirmation(" YES ", Some(false)), Some(true));
+        assert_eq!(parse_confirmation("no", Some(true)), Some(false));
+        assert_eq!(parse_confirmation("", Some(true)), Some(true));
+        assert_eq!(parse_confirmation("", None), None);
+        assert_eq!(parse_confirmation("maybe", Some(true)), None);
+    }
+
+    #[test]
+    fn read_answer_strips_the_line_terminator() {
+        let mut input = "first\r\nsecond\n".as_bytes();
+        assert_eq!(read_answer(&mut input).unwrap().as_deref(), Some("first"));
+        assert_eq!(read_answer(&mut input).unwrap().as_deref(), Some("second"));
+        assert_eq!(read_answer(&mut input).unwrap(), None);
+    }
+}
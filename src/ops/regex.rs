@@ -0,0 +1,124 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use regex::Regex;
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The patterns are Strings with the syntax of the `regex` crate, e.g.
+// `(regex/find-all "\\d+" text)`. The compiled regexes are cached by pattern,
+// repeated use in loops does not recompile.
+
+// #TODO support capture groups, e.g. `regex/captures`.
+// #TODO support regex flags, e.g. case-insensitive matching (`(?i)` works).
+
+/// The maximum number of cached regexes, the cache is cleared when full.
+const MAX_CACHED_REGEXES: usize = 256;
+
+thread_local! {
+    static REGEXES: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Returns the compiled regex of the pattern, from the cache if available.
+fn compile(pattern: &str) -> Result<Regex, Ranged<Error>> {
+    REGEXES.with(|regexes| {
+        let mut regexes = regexes.borrow_mut();
+
+        if let Some(regex) = regexes.get(pattern) {
+            return Ok(regex.clone());
+        }
+
+        let regex = Regex::new(pattern).map_err(|error| {
+            Error::invalid_arguments(format!("malformed regex `{pattern}`: {error}"))
+        })?;
+
+        if regexes.len() >= MAX_CACHED_REGEXES {
+            regexes.clear();
+        }
+        regexes.insert(pattern.to_owned(), regex.clone());
+
+        Ok(regex)
+    })
+}
+
+/// Returns the compiled regex and the text of the `pattern`, `text` arguments.
+fn regex_args<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<(Regex, &'a str), Ranged<Error>> {
+    let [Ann(Expr::String(pattern), ..), Ann(Expr::String(text), ..), ..] = args else {
+        return Err(Error::invalid_arguments(format!(
+            "`{name}` requires String `pattern`, `text` arguments"
+        ))
+        .into());
+    };
+
+    Ok((compile(pattern)?, text))
+}
+
+fn strings<'a>(items: impl Iterator<Item = &'a str>) -> Ann<Expr> {
+    let items = items
+        .map(|s| Ann::with_type(Expr::String(s.to_owned()), Expr::symbol("String")))
+        .collect();
+
+    Ann::with_type(Expr::Array(items), Expr::symbol("Array"))
+}
+
+/// Returns true if the text contains a match of the pattern, e.g.
+/// `(regex/match? "^\\d+$" text)`.
+pub fn regex_is_match(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (regex, text) = regex_args("regex/match?", args)?;
+
+    Ok(Ann::with_type(
+        Expr::Bool(regex.is_match(text)),
+        Expr::symbol("Bool"),
+    ))
+}
+
+/// Returns the first match of the pattern in the text, `None` if there is no
+/// match.
+pub fn regex_find(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (regex, text) = regex_args("regex/find", args)?;
+
+    let value = match regex.find(text) {
+        Some(m) => Expr::some(Ann::with_type(
+            Expr::String(m.as_str().to_owned()),
+            Expr::symbol("String"),
+        )),
+        None => Expr::none(),
+    };
+
+    Ok(Ann::with_type(value, Expr::symbol("Maybe")))
+}
+
+/// Returns all the (non-overlapping) matches of the pattern in the text, as
+/// an Array of Strings.
+pub fn regex_find_all(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (regex, text) = regex_args("regex/find-all", args)?;
+
+    Ok(strings(regex.find_iter(text).map(|m| m.as_str())))
+}
+
+/// Replaces all the matches of the pattern in the text, e.g.
+/// `(regex/replace "(\\w+)@(\\w+)" text "$2 at $1")`. The replacement can
+/// refer to the capture groups, `$1` or `${name}`.
+pub fn regex_replace(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (regex, text) = regex_args("regex/replace", args)?;
+
+    let [_, _, Ann(Expr::String(replacement), ..)] = args else {
+        return Err(Error::invalid_arguments(
+            "`regex/replace` requires String `pattern`, `text`, `replacement` arguments",
+        )
+        .into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::String(regex.replace_all(text, replacement.as_str()).into_owned()),
+        Expr::symbol("String"),
+    ))
+}
+
+/// Splits the text by the matches of the pattern, e.g.
+/// `(regex/split "\\s*,\\s*" "a, b ,c")`.
+pub fn regex_split(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (regex, text) = regex_args("regex/split", args)?;
+
+    Ok(strings(regex.split(text)))
+}
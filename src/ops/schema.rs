@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+use super::diff::values_eq;
+
+// #Insight
+// A schema is a declarative Dict, e.g.
+//
+// {
+//     :type "Dict"
+//     :required ["name" "port"]
+//     :keys {
+//         :name "String"
+//         :port "Int"
+//         :level {:type "String" :enum ["debug" "info"]}
+//         :tags {:type "Array" :items "String"}
+//     }
+// }
+//
+// A type name is a shorthand for `{:type name}`. The supported types are
+// `Int`, `Float`, `Number`, `String`, `Bool`, `Array`, `Dict` and `Any`. The
+// keys of a Dict that are not in the schema are allowed.
+//
+// `validate` returns the value if it is valid, else an Error with the Array
+// of all the violations, each violation is a Dict with the `path` of the value
+// and the `message`.
+
+// #TODO support numeric ranges, string lengths and patterns.
+// #TODO support closed Dicts, i.e. reject the unknown keys.
+// #TODO integrate with the type annotations.
+
+const SCHEMA_TYPES: [&str; 8] = [
+    "Int", "Float", "Number", "String", "Bool", "Array", "Dict", "Any",
+];
+
+/// Returns the type name of a value, as used in the violation messages.
+pub fn value_type(value: &Expr) -> &'static str {
+    match value {
+        Expr::Int(..) => "Int",
+        Expr::Float(..) => "Float",
+        Expr::BigInt(..) => "BigInt",
+        Expr::Decimal(..) => "Decimal",
+        Expr::String(..) => "String",
+        Expr::Bool(..) => "Bool",
+        Expr::Array(..) => "Array",
+        Expr::Dict(..) => "Dict",
+        Expr::KeySymbol(..) => "KeySymbol",
+        Expr::One => "()",
+        _ => "value",
+    }
+}
+
+/// Returns true if the value is of the schema type, e.g. `Number`.
+pub fn is_type(value: &Expr, type_name: &str) -> bool {
+    match type_name {
+        "Any" => true,
+        "Number" => matches!(
+            value,
+            Expr::Int(..) | Expr::Float(..) | Expr::BigInt(..) | Expr::Decimal(..)
+        ),
+        _ => value_type(value) == type_name,
+    }
+}
+
+fn schema_error(message: impl Into<String>) -> Ranged<Error> {
+    Error::invalid_arguments(format!("invalid schema, {}", message.into())).into()
+}
+
+/// Returns the type name of a schema type, a String or a KeySymbol.
+fn type_name(schema: &Ann<Expr>) -> Result<&str, Ranged<Error>> {
+    let (Expr::String(name) | Expr::KeySymbol(name)) = schema.as_ref() else {
+        return Err(schema_error(format!("`{schema}` is not a type name")));
+    };
+
+    if !SCHEMA_TYPES.contains(&name.as_str()) {
+        return Err(schema_error(format!("unknown type `{name}`")));
+    }
+
+    Ok(name)
+}
+
+/// A value validator, collects the violations.
+struct Validator {
+    path: Vec<Ann<Expr>>,
+    violations: Vec<Ann<Expr>>,
+}
+
+impl Validator {
+    fn violation(&mut self, message: String) {
+        let mut dict = BTreeMap::new();
+
+        dict.insert("path".to_owned(), Expr::Array(self.path.clone()).into());
+        dict.insert(
+            "message".to_owned(),
+            Ann::with_type(Expr::String(message), Expr::symbol("String")),
+        );
+
+        self.violations.push(Expr::Dict(dict).into());
+    }
+
+    /// Validates the value in the current path.
+    fn validate(&mut self, value: &Ann<Expr>, schema: &Ann<Expr>) -> Result<(), Ranged<Error>> {
+        let schema = match schema.as_ref() {
+            Expr::Dict(schema) => schema,
+            _ => {
+                let name = type_name(schema)?;
+                if !is_type(value.as_ref(), name) {
+                    self.violation(format!(
+                        "expected {name}, found {}",
+                        value_type(value.as_ref())
+                    ));
+                }
+                return Ok(());
+            }
+        };
+
+        for key in schema.keys() {
+            if !matches!(
+                key.as_str(),
+                "type" | "required" | "keys" | "items" | "enum"
+            ) {
+                return Err(schema_error(format!("unknown key `{key}`")));
+            }
+        }
+
+        if let Some(name) = schema.get("type") {
+            let name = type_name(name)?;
+            if !is_type(value.as_ref(), name) {
+                self.violation(format!(
+                    "expected {name}, found {}",
+                    value_type(value.as_ref())
+                ));
+                // The nested constraints are meaningless for the wrong type.
+                return Ok(());
+            }
+        }
+
+        if let Some(allowed) = schema.get("enum") {
+            let Ann(Expr::Array(allowed), ..) = allowed else {
+                return Err(schema_error("`enum` should be an Array"));
+            };
+
+            if !allowed.iter().any(|item| values_eq(&item.0, &value.0)) {
+                let allowed: Vec<String> = allowed
+                    .iter()
+                    .map(|item| format!("`{}`", format_value(item)))
+                    .collect();
+                self.violation(format!(
+                    "`{}` is not one of {}",
+                    format_value(value),
+                    allowed.join(", ")
+                ));
+            }
+        }
+
+        if let Some(required) = schema.get("required") {
+            let Ann(Expr::Array(required), ..) = required else {
+                return Err(schema_error("`required` should be an Array of keys"));
+            };
+
+            if let Expr::Dict(dict) = value.as_ref() {
+                for key in required {
+                    let key = format_value(key);
+                    if !dict.contains_key(&key) {
+                        self.violation(format!("missing required key `{key}`"));
+                    }
+                }
+            }
+        }
+
+        if let Some(keys) = schema.get("keys") {
+            let Ann(Expr::Dict(keys), ..) = keys else {
+                return Err(schema_error("`keys` should be a Dict of schemas"));
+            };
+
+            if let Expr::Dict(dict) = value.as_ref() {
+                for (key, schema) in keys {
+                    if let Some(value) = dict.get(key) {
+                        self.path.push(Expr::String(key.clone()).into());
+                        self.validate(value, schema)?;
+                        self.path.pop();
+                    }
+                }
+            }
+        }
+
+        if let Some(schema) = schema.get("items") {
+            if let Expr::Array(items) = value.as_ref() {
+                for (i, item) in items.iter().enumerate() {
+                    self.path.push(Expr::Int(i as i64).into());
+                    self.validate(item, schema)?;
+                    self.path.pop();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Validates a value against a schema, e.g. `(validate config config-schema)`.
+/// Returns the value if it is valid, else an Error with the Array of the
+/// violations. An invalid schema is an error.
+pub fn validate(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [value, schema] = args else {
+        return Err(
+            Error::invalid_arguments("`validate` requires `value`, `schema` arguments").into(),
+        );
+    };
+
+    let mut validator = Validator {
+        path: Vec::new(),
+        violations: Vec::new(),
+    };
+
+    validator.validate(value, schema)?;
+
+    if validator.violations.is_empty() {
+        return Ok(value.clone());
+    }
+
+    Ok(Ann::with_type(
+        Expr::error(Expr::Array(validator.violations)),
+        Expr::symbol("Error"),
+    ))
+}
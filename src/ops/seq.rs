@@ -0,0 +1,315 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{env::Env, invoke, seq_iter},
+    expr::{seq::Seq, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The sequence functions are lazy, they return an Iterator that processes the
+// items on demand, intermediate collections are not materialized. As a
+// convenience, `map` and `filter` are eager for Arrays and Lists, they return a
+// collection of the same kind.
+
+// #TODO support Maybe for the result of `next`.
+
+/// Returns a lazy sequence over the items of the argument.
+fn to_seq(seq: &Ann<Expr>) -> Result<Box<dyn Seq>, Ranged<Error>> {
+    seq_iter(seq.clone())
+        .ok_or_else(|| Error::invalid_arguments(format!("`{seq}` is not a Seq")).into())
+}
+
+/// Applies the predicate to the item, the predicate should return a boolean.
+fn test(predicate: &Ann<Expr>, item: Ann<Expr>, env: &mut Env) -> Result<bool, Ranged<Error>> {
+    match invoke(predicate, vec![item], env)? {
+        Ann(Expr::Bool(value), ..) => Ok(value),
+        value => Err(Error::invalid_arguments(format!(
+            "the predicate should return a boolean value, found `{value}`"
+        ))
+        .into()),
+    }
+}
+
+/// Rebuilds a collection of the same kind as `seq`, for Arrays and Lists.
+fn collect_like(seq: &Ann<Expr>, items: Vec<Ann<Expr>>) -> Ann<Expr> {
+    match seq.as_ref() {
+        Expr::List(..) => Expr::List(items).into(),
+        _ => Ann::with_type(Expr::Array(items), Expr::symbol("Array")),
+    }
+}
+
+/// Returns the items of an Array or List, None for other sequences.
+fn collection_items(seq: &Ann<Expr>) -> Option<&[Ann<Expr>]> {
+    match seq.as_ref() {
+        Expr::Array(items) | Expr::List(items) => Some(items),
+        _ => None,
+    }
+}
+
+fn iterator(seq: impl Seq + 'static) -> Ann<Expr> {
+    Ann::with_type(Expr::seq(seq), Expr::symbol("Iterator"))
+}
+
+/// Creates a lazy Range of Ints, e.g. `(range 10)`, `(range 0 10)` or
+/// `(range 10 0 -2)`. The end is exclusive.
+pub fn range(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let ints = args
+        .iter()
+        .map(|arg| match arg.as_ref() {
+            Expr::Int(n) => Ok(*n),
+            _ => Err(Error::invalid_arguments(format!("`{arg}` is not an Int"))),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (start, end, step) = match ints[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`range` requires `end`, or `start`, `end` and optional `step` arguments",
+            )
+            .into());
+        }
+    };
+
+    if step == 0 {
+        return Err(Error::invalid_arguments("the `range` step cannot be zero").into());
+    }
+
+    Ok(Ann::with_type(
+        Expr::Range(start, end, step),
+        Expr::symbol("Range"),
+    ))
+}
+
+/// Returns an Iterator over the items of an Array, Dict, Range, String, etc.
+pub fn iter(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [seq] = args else {
+        return Err(Error::invalid_arguments("`iter` requires a `seq` argument").into());
+    };
+
+    if let Expr::Iterator(..) = seq.as_ref() {
+        return Ok(seq.clone());
+    }
+
+    Ok(iterator(to_seq(seq)?))
+}
+
+/// Returns the next item of an Iterator, or `()` if the Iterator is exhausted.
+pub fn next(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [seq] = args else {
+        return Err(Error::invalid_arguments("`next` requires an `iterator` argument").into());
+    };
+
+    let Ann(Expr::Iterator(seq), ..) = seq else {
+        return Err(Error::invalid_arguments(format!("`{seq}` is not an Iterator")).into());
+    };
+
+    let mut seq = seq.clone();
+
+    Ok(seq.next(env)?.unwrap_or_else(|| Expr::One.into()))
+}
+
+struct TakeSeq {
+    source: Box<dyn Seq>,
+    remaining: usize,
+}
+
+impl Seq for TakeSeq {
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        self.source.next(env)
+    }
+}
+
+/// Returns a lazy sequence of the first `n` items of the sequence, e.g.
+/// `(take 3 (range 100))`.
+pub fn take(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [n, seq] = args else {
+        return Err(Error::invalid_arguments("`take` requires `n`, `seq` arguments").into());
+    };
+
+    let Some(n) = (match n.as_ref() {
+        Expr::Int(n) => usize::try_from(*n).ok(),
+        _ => None,
+    }) else {
+        return Err(Error::invalid_arguments(format!("`{n}` is not a non-negative Int")).into());
+    };
+
+    Ok(iterator(TakeSeq {
+        source: to_seq(seq)?,
+        remaining: n,
+    }))
+}
+
+struct MapSeq {
+    source: Box<dyn Seq>,
+    func: Ann<Expr>,
+}
+
+impl Seq for MapSeq {
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        let Some(item) = self.source.next(env)? else {
+            return Ok(None);
+        };
+
+        invoke(&self.func, vec![item], env).map(Some)
+    }
+}
+
+/// Returns a lazy sequence of the results of applying the function to the
+/// items of the sequence, e.g. `(map (Func (x) (* x 2)) (range 10))`.
+pub fn map(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [func, seq] = args else {
+        return Err(Error::invalid_arguments("`map` requires `func`, `seq` arguments").into());
+    };
+
+    if let Some(items) = collection_items(seq) {
+        let items = items
+            .iter()
+            .map(|item| invoke(func, vec![item.clone()], env))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(collect_like(seq, items));
+    }
+
+    Ok(iterator(MapSeq {
+        source: to_seq(seq)?,
+        func: func.clone(),
+    }))
+}
+
+struct FilterSeq {
+    source: Box<dyn Seq>,
+    predicate: Ann<Expr>,
+}
+
+impl Seq for FilterSeq {
+    fn next(&mut self, env: &mut Env) -> Result<Option<Ann<Expr>>, Ranged<Error>> {
+        while let Some(item) = self.source.next(env)? {
+            if test(&self.predicate, item.clone(), env)? {
+                return Ok(Some(item));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Returns a lazy sequence of the items of the sequence that satisfy the
+/// predicate, e.g. `(filter (Func (x) (> x 2)) (range 10))`.
+pub fn filter(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [predicate, seq] = args else {
+        return Err(
+            Error::invalid_arguments("`filter` requires `predicate`, `seq` arguments").into(),
+        );
+    };
+
+    if let Some(items) = collection_items(seq) {
+        let mut accepted = Vec::new();
+        for item in items {
+            if test(predicate, item.clone(), env)? {
+                accepted.push(item.clone());
+            }
+        }
+        return Ok(collect_like(seq, accepted));
+    }
+
+    Ok(iterator(FilterSeq {
+        source: to_seq(seq)?,
+        predicate: predicate.clone(),
+    }))
+}
+
+/// Folds the items of the sequence with the function, starting with the
+/// initial value, e.g. `(reduce + 0 [1 2 3])`.
+pub fn reduce(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [func, init, seq] = args else {
+        return Err(
+            Error::invalid_arguments("`reduce` requires `func`, `init`, `seq` arguments").into(),
+        );
+    };
+
+    let mut seq = to_seq(seq)?;
+    let mut acc = init.clone();
+
+    while let Some(item) = seq.next(env)? {
+        acc = invoke(func, vec![acc, item], env)?;
+    }
+
+    Ok(acc)
+}
+
+/// Returns true if any item of the sequence satisfies the predicate. Stops at
+/// the first such item.
+pub fn any(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [predicate, seq] = args else {
+        return Err(
+            Error::invalid_arguments("`any?` requires `predicate`, `seq` arguments").into(),
+        );
+    };
+
+    let mut seq = to_seq(seq)?;
+
+    while let Some(item) = seq.next(env)? {
+        if test(predicate, item, env)? {
+            return Ok(Expr::Bool(true).into());
+        }
+    }
+
+    Ok(Expr::Bool(false).into())
+}
+
+/// Returns true if all items of the sequence satisfy the predicate. Stops at
+/// the first item that does not.
+pub fn all(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [predicate, seq] = args else {
+        return Err(
+            Error::invalid_arguments("`all?` requires `predicate`, `seq` arguments").into(),
+        );
+    };
+
+    let mut seq = to_seq(seq)?;
+
+    while let Some(item) = seq.next(env)? {
+        if !test(predicate, item, env)? {
+            return Ok(Expr::Bool(false).into());
+        }
+    }
+
+    Ok(Expr::Bool(true).into())
+}
+
+/// Counts the items of the sequence, e.g. `(count [1 2 3])`, or the items that
+/// satisfy the predicate, e.g. `(count (Func (x) (> x 1)) [1 2 3])`.
+pub fn count(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (predicate, seq) = match args {
+        [seq] => (None, seq),
+        [predicate, seq] => (Some(predicate), seq),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`count` requires a `seq` argument, and an optional `predicate`",
+            )
+            .into());
+        }
+    };
+
+    let mut seq = to_seq(seq)?;
+    let mut count = 0;
+
+    while let Some(item) = seq.next(env)? {
+        if let Some(predicate) = predicate {
+            if !test(predicate, item, env)? {
+                continue;
+            }
+        }
+        count += 1;
+    }
+
+    Ok(Ann::with_type(Expr::Int(count), Expr::symbol("Int")))
+}
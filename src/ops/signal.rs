@@ -0,0 +1,112 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Once,
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env, invoke},
+    expr::Expr,
+    range::Ranged,
+};
+
+// #Insight
+// The OS signal handler runs in a separate thread, it only raises a flag. The
+// flag is checked at every evaluation step, the Tan handler is invoked in the
+// evaluation thread.
+
+// #Insight
+// The OS handler is process-wide and permanent, it replaces the default
+// termination on Ctrl-C of the host process. It is only installed when the
+// host opts in, see `enable_signal_handling` and
+// `Runtime::with_signal_handling`, the scripts cannot install it.
+
+// #TODO differentiate between SIGINT and SIGTERM.
+// #TODO support multiple handlers.
+
+static SIGNALED: AtomicBool = AtomicBool::new(false);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static INSTALL: Once = Once::new();
+
+/// Installs the process-wide handler of the termination signals, SIGINT and
+/// SIGTERM (Ctrl-C on Windows), once. The handler replaces the default
+/// termination of the process and cannot be removed, the termination signals
+/// are only observed by `on-signal` and `signaled?`.
+pub fn enable_signal_handling() -> Result<(), Error> {
+    let mut result = Ok(());
+
+    INSTALL.call_once(|| {
+        result = ctrlc::set_handler(|| SIGNALED.store(true, Ordering::SeqCst))
+            .map_err(|error| Error::invalid_arguments(format!("cannot handle signals: {error}")));
+
+        if result.is_ok() {
+            ENABLED.store(true, Ordering::SeqCst);
+        }
+    });
+
+    result?;
+
+    // A failed installation is not retried.
+    if !is_signal_handling_enabled() {
+        return Err(Error::invalid_arguments(
+            "cannot handle signals, the handler is not installed",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns true if the host enabled the signal handling.
+pub fn is_signal_handling_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Registers a handler for the termination signals, e.g.
+/// `(on-signal (Func () (cleanup)))`. When a signal is received, the handler is
+/// invoked and the evaluation is interrupted.
+pub fn on_signal(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Process, "on-signal")?;
+
+    let [handler] = args else {
+        return Err(Error::invalid_arguments("`on-signal` requires a `handler` argument").into());
+    };
+
+    if !is_signal_handling_enabled() {
+        return Err(Error::invalid_arguments(
+            "signal handling is not enabled by the host, see `Runtime::with_signal_handling`",
+        )
+        .into());
+    }
+
+    env.context.signal_handler = Some(handler.clone());
+
+    Ok(Expr::One.into())
+}
+
+/// Returns true if a termination signal is received, e.g. to exit the main
+/// loop of a daemon-style script, `(while (not (signaled?)) ...)`. Always false
+/// if the host did not enable the signal handling.
+pub fn is_signaled(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    if !args.is_empty() {
+        return Err(Error::invalid_arguments("`signaled?` does not accept arguments").into());
+    }
+
+    Ok(Expr::Bool(SIGNALED.load(Ordering::SeqCst)).into())
+}
+
+/// Invokes the registered handler if a termination signal is received, then
+/// interrupts the evaluation of `expr`.
+pub fn handle_signal(expr: &Ann<Expr>, env: &mut Env) -> Result<(), Ranged<Error>> {
+    if env.context.signal_handler.is_none() || !SIGNALED.swap(false, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    if let Some(handler) = env.context.signal_handler.take() {
+        invoke(&handler, Vec::new(), env)?;
+    }
+
+    Err(Ranged(Error::Interrupted, expr.get_range()))
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+
+use rusqlite::{
+    types::{ToSqlOutput, Value, ValueRef},
+    Connection, ToSql,
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env},
+    expr::{foreign::ForeignValue, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The connection is an opaque foreign value, it is passed explicitly to the
+// `db/*` ops, e.g. `(db/query db "SELECT * FROM users WHERE age > ?" [18])`.
+
+// #TODO support named parameters, with a Dict.
+// #TODO support transactions.
+
+const CONNECTION_TYPE: &str = "Connection";
+
+fn db_error(error: rusqlite::Error) -> Error {
+    Error::invalid_arguments(format!("sqlite error: {error}"))
+}
+
+fn connection_arg(arg: &Ann<Expr>) -> Result<&Connection, Ranged<Error>> {
+    let Some(connection) = (match arg.as_ref() {
+        Expr::Foreign(value) => value.downcast_ref::<Connection>(),
+        _ => None,
+    }) else {
+        return Err(
+            Error::invalid_arguments(format!("`{arg}` is not a database Connection")).into(),
+        );
+    };
+
+    Ok(connection)
+}
+
+/// A query parameter.
+struct Param<'a>(&'a Ann<Expr>);
+
+impl ToSql for Param<'_> {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let value = match self.0.as_ref() {
+            Expr::One => Value::Null,
+            Expr::Bool(b) => Value::Integer(i64::from(*b)),
+            Expr::Int(n) => Value::Integer(*n),
+            Expr::Float(n) => Value::Real(*n),
+            Expr::String(s) => return Ok(ToSqlOutput::Borrowed(ValueRef::Text(s.as_bytes()))),
+            Expr::Buffer(bytes) => return Ok(ToSqlOutput::Borrowed(ValueRef::Blob(bytes))),
+            expr => {
+                return Err(rusqlite::Error::ToSqlConversionFailure(
+                    format!("`{expr}` is not a valid query parameter").into(),
+                ));
+            }
+        };
+
+        Ok(ToSqlOutput::Owned(value))
+    }
+}
+
+/// Splits the `db`, `sql`, optional `params` arguments.
+fn statement_args<'a>(
+    name: &str,
+    args: &'a [Ann<Expr>],
+) -> Result<(&'a Connection, &'a str, Vec<Param<'a>>), Ranged<Error>> {
+    let (db, sql, params) = match args {
+        [db, sql] => (db, sql, None),
+        [db, sql, params] => (db, sql, Some(params)),
+        _ => {
+            return Err(Error::invalid_arguments(format!(
+                "`{name}` requires `db`, `sql` and optional `params` arguments"
+            ))
+            .into());
+        }
+    };
+
+    let connection = connection_arg(db)?;
+
+    let Ann(Expr::String(sql), ..) = sql else {
+        return Err(Error::invalid_arguments("`sql` argument should be a String").into());
+    };
+
+    let params = match params {
+        None => Vec::new(),
+        Some(Ann(Expr::Array(params), ..)) => params.iter().map(Param).collect(),
+        Some(params) => {
+            return Err(Error::invalid_arguments(format!(
+                "`{params}` is not an Array of parameters"
+            ))
+            .into());
+        }
+    };
+
+    Ok((connection, sql, params))
+}
+
+fn column_value(value: ValueRef) -> Ann<Expr> {
+    match value {
+        ValueRef::Null => Expr::One.into(),
+        ValueRef::Integer(n) => Expr::Int(n).into(),
+        ValueRef::Real(n) => Expr::Float(n).into(),
+        ValueRef::Text(s) => Expr::String(String::from_utf8_lossy(s).into_owned()).into(),
+        ValueRef::Blob(bytes) => Expr::Buffer(bytes.into()).into(),
+    }
+}
+
+/// Opens (or creates) the SQLite database at the path, `":memory:"` opens an
+/// in-memory database. Returns the connection.
+pub fn db_open(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`db/open` requires a `path` argument").into());
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let connection = if path == ":memory:" {
+        Connection::open_in_memory()
+    } else {
+        env.context.require(Capability::Fs, "db/open")?;
+        Connection::open(path)
+    }
+    .map_err(db_error)?;
+
+    Ok(Ann::with_type(
+        Expr::Foreign(ForeignValue::new(CONNECTION_TYPE, connection)),
+        Expr::symbol(CONNECTION_TYPE),
+    ))
+}
+
+/// Executes a query, returns the rows as an Array of Dicts, keyed by column
+/// name, e.g. `(db/query db "SELECT name FROM users WHERE id = ?" [1])`.
+pub fn db_query(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (connection, sql, params) = statement_args("db/query", args)?;
+
+    let mut statement = connection.prepare(sql).map_err(db_error)?;
+
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    let mut rows = statement
+        .query(rusqlite::params_from_iter(params))
+        .map_err(db_error)?;
+
+    let mut records = Vec::new();
+
+    while let Some(row) = rows.next().map_err(db_error)? {
+        let mut record = BTreeMap::new();
+
+        for (i, column) in columns.iter().enumerate() {
+            let value = row.get_ref(i).map_err(db_error)?;
+            record.insert(column.clone(), column_value(value));
+        }
+
+        records.push(Expr::Dict(record).into());
+    }
+
+    Ok(Expr::Array(records).into())
+}
+
+/// Executes a statement, e.g. an INSERT or UPDATE, returns the number of
+/// changed rows.
+pub fn db_exec(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (connection, sql, params) = statement_args("db/exec", args)?;
+
+    if env.context.audit_effect("db/exec", args) {
+        return Ok(Ann::with_type(Expr::Int(0), Expr::symbol("Int")));
+    }
+
+    // #Insight
+    // Without parameters, the sql may contain multiple statements, e.g. a schema.
+    let changes = if params.is_empty() {
+        connection.execute_batch(sql).map_err(db_error)?;
+        connection.changes() as i64
+    } else {
+        connection
+            .execute(sql, rusqlite::params_from_iter(params))
+            .map_err(db_error)? as i64
+    };
+
+    Ok(Ann::with_type(Expr::Int(changes), Expr::symbol("Int")))
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// The descriptive statistics of numeric Arrays, the Ints are promoted to
+// Floats. The mean and the variance are computed in one pass (Welford), the
+// median and the percentiles sort a copy of the values.
+//
+// The variance and the standard deviation are the population statistics,
+// like `tensor/std`.
+
+// #TODO support the sample variance.
+// #TODO support the mode.
+
+fn float(n: f64) -> Ann<Expr> {
+    Ann::with_type(Expr::Float(n), Expr::symbol("Float"))
+}
+
+/// Returns the values of a non-empty numeric Array.
+fn values_arg(name: &str, arg: &Ann<Expr>) -> Result<Vec<f64>, Ranged<Error>> {
+    let Ann(Expr::Array(items), ..) = arg else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires an Array of numbers")).into(),
+        );
+    };
+
+    if items.is_empty() {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires a non-empty Array")).into(),
+        );
+    }
+
+    items
+        .iter()
+        .map(|item| match item {
+            Ann(Expr::Float(n), ..) => Ok(*n),
+            Ann(Expr::Int(n), ..) => Ok(*n as f64),
+            _ => Err(Error::invalid_arguments(format!("`{item}` is not a number")).into()),
+        })
+        .collect()
+}
+
+fn unary_values(name: &str, args: &[Ann<Expr>]) -> Result<Vec<f64>, Ranged<Error>> {
+    let [values] = args else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires a `values` argument")).into(),
+        );
+    };
+
+    values_arg(name, values)
+}
+
+/// Returns the mean and the (population) variance, in one pass.
+fn mean_variance(values: &[f64]) -> (f64, f64) {
+    let mut mean = 0.0;
+    let mut m2 = 0.0;
+
+    for (i, x) in values.iter().enumerate() {
+        let delta = x - mean;
+        mean += delta / (i + 1) as f64;
+        m2 += delta * (x - mean);
+    }
+
+    (mean, m2 / values.len() as f64)
+}
+
+/// Returns the percentile `p` (0-100) of the sorted values, with linear
+/// interpolation between the closest ranks.
+fn percentile_of(sorted: &[f64], p: f64) -> f64 {
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+fn sorted(mut values: Vec<f64>) -> Vec<f64> {
+    values.sort_by(f64::total_cmp);
+    values
+}
+
+/// Returns the arithmetic mean of the values, e.g. `(stats/mean [1 2 3])`.
+pub fn stats_mean(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let values = unary_values("stats/mean", args)?;
+
+    Ok(float(mean_variance(&values).0))
+}
+
+/// Returns the median of the values, the mean of the two middle values for an
+/// even count.
+pub fn stats_median(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let values = unary_values("stats/median", args)?;
+
+    Ok(float(percentile_of(&sorted(values), 50.0)))
+}
+
+/// Returns the percentile of the values, e.g. `(stats/percentile latencies 95)`,
+/// the percentile is between 0 and 100.
+pub fn stats_percentile(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [values, p] = args else {
+        return Err(Error::invalid_arguments(
+            "`stats/percentile` requires `values`, `percentile` arguments",
+        )
+        .into());
+    };
+
+    let values = values_arg("stats/percentile", values)?;
+
+    let p = match p {
+        Ann(Expr::Int(n), ..) => *n as f64,
+        Ann(Expr::Float(n), ..) => *n,
+        _ => return Err(Error::invalid_arguments(format!("`{p}` is not a number")).into()),
+    };
+
+    if !(0.0..=100.0).contains(&p) {
+        return Err(Error::invalid_arguments(format!(
+            "the percentile `{p}` is not between 0 and 100"
+        ))
+        .into());
+    }
+
+    Ok(float(percentile_of(&sorted(values), p)))
+}
+
+/// Returns the (population) variance of the values.
+pub fn stats_variance(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let values = unary_values("stats/variance", args)?;
+
+    Ok(float(mean_variance(&values).1))
+}
+
+/// Returns the (population) standard deviation of the values.
+pub fn stats_std(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let values = unary_values("stats/std", args)?;
+
+    Ok(float(mean_variance(&values).1.sqrt()))
+}
+
+/// Counts the values in equal-width bins between the minimum and the maximum
+/// value, e.g. `(stats/histogram values 10)`. Returns a Dict with the `edges`
+/// (one more than the bins) and the `counts` of the bins. The maximum value is
+/// counted in the last bin.
+pub fn stats_histogram(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [values, bins] = args else {
+        return Err(Error::invalid_arguments(
+            "`stats/histogram` requires `values`, `bins` arguments",
+        )
+        .into());
+    };
+
+    let values = values_arg("stats/histogram", values)?;
+
+    let Ann(Expr::Int(bins @ 1..), ..) = bins else {
+        return Err(Error::invalid_arguments(format!("`{bins}` is not a positive Int")).into());
+    };
+
+    let bins = *bins as usize;
+
+    let (min, max) = values
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &x| {
+            (min.min(x), max.max(x))
+        });
+
+    let width = (max - min) / bins as f64;
+
+    let mut counts = vec![0_i64; bins];
+
+    for x in values {
+        let bin = if width > 0.0 {
+            (((x - min) / width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+        counts[bin] += 1;
+    }
+
+    let edges = (0..=bins).map(|i| float(min + width * i as f64)).collect();
+    let counts = counts.into_iter().map(|n| Expr::Int(n).into()).collect();
+
+    let mut dict = BTreeMap::new();
+    dict.insert("edges".to_owned(), Expr::Array(edges).into());
+    dict.insert("counts".to_owned(), Expr::Array(counts).into());
+
+    Ok(Expr::Dict(dict).into())
+}
@@ -0,0 +1,158 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env},
+    expr::{dict_key, foreign::ForeignValue, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The store is a single JSON file, loaded in memory when opened. Every `put`
+// rewrites the file, the new contents are written to a temporary file that
+// replaces the original, so the file is never left half-written.
+
+// #TODO support multiple stores, e.g. `(store/get db k)`.
+// #TODO batch writes, e.g. in a transaction.
+
+const STORE_TYPE: &str = "Store";
+
+/// A durable key-value store, backed by a JSON file.
+struct Store {
+    path: PathBuf,
+    entries: RefCell<BTreeMap<String, Ann<Expr>>>,
+}
+
+impl Store {
+    fn open(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+
+        let entries = if path.exists() {
+            let contents = fs::read_to_string(&path)?;
+            serde_json::from_str(&contents).map_err(|error| {
+                Error::invalid_arguments(format!(
+                    "cannot read the store `{}`: {error}",
+                    path.display()
+                ))
+            })?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            entries: RefCell::new(entries),
+        })
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(&*self.entries.borrow()).map_err(|error| {
+            Error::invalid_arguments(format!("cannot store the value: {error}"))
+        })?;
+
+        let tmp_path = temp_path(&self.path);
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        Ok(())
+    }
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+fn current_store(env: &Env) -> Result<&Store, Ranged<Error>> {
+    let Some(Ann(Expr::Foreign(store), ..)) = &env.context.store else {
+        return Err(Error::invalid_arguments("no store is open, use `store/open`").into());
+    };
+
+    // The unwrap is safe, only Store values are set as the current store.
+    Ok(store.downcast_ref::<Store>().unwrap())
+}
+
+/// Opens the store at the given path, the file is created on the first `put`.
+/// The store becomes the current store of the `store/get`, `store/put` ops.
+pub fn store_open(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "store/open")?;
+
+    let [path] = args else {
+        return Err(Error::invalid_arguments("`store/open` requires a `path` argument").into());
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let store = ForeignValue::new(STORE_TYPE, Store::open(path)?);
+    let store = Ann::with_type(Expr::Foreign(store), Expr::symbol(STORE_TYPE));
+
+    env.context.store = Some(store.clone());
+
+    Ok(store)
+}
+
+/// Returns the value of the key in the current store as a Maybe, `None` if
+/// the key is missing.
+pub fn store_get(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [key] = args else {
+        return Err(Error::invalid_arguments("`store/get` requires a `key` argument").into());
+    };
+
+    let key = dict_key(key)?;
+
+    let store = current_store(env)?;
+
+    let value = match store.entries.borrow().get(&key) {
+        Some(value) => Expr::some(value.clone()),
+        None => Expr::none(),
+    };
+
+    Ok(Ann::with_type(value, Expr::symbol("Maybe")))
+}
+
+/// Stores the value of the key in the current store, the store is persisted
+/// immediately.
+pub fn store_put(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [key, value] = args else {
+        return Err(
+            Error::invalid_arguments("`store/put` requires `key`, `value` arguments").into(),
+        );
+    };
+
+    let key = dict_key(key)?;
+
+    // The store is checked before the action is recorded.
+    current_store(env)?;
+
+    if env.context.audit_effect("store/put", args) {
+        return Ok(Expr::One.into());
+    }
+
+    let store = current_store(env)?;
+
+    let previous = store
+        .entries
+        .borrow_mut()
+        .insert(key.clone(), value.clone());
+
+    if let Err(error) = store.save() {
+        // Keep the memory in sync with the file.
+        let mut entries = store.entries.borrow_mut();
+        match previous {
+            Some(previous) => entries.insert(key, previous),
+            None => entries.remove(&key),
+        };
+        return Err(error.into());
+    }
+
+    Ok(Expr::One.into())
+}
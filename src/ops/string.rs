@@ -0,0 +1,478 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    ops::string_arg,
+    range::Ranged,
+};
+
+// #Insight
+// The string functions operate on chars (Unicode scalar values), not bytes,
+// e.g. the indices of `str-slice` are char indices.
+
+// #TODO consider a `String:` namespace, e.g. `String:len`.
+
+fn string(s: impl Into<String>) -> Ann<Expr> {
+    Ann::with_type(Expr::String(s.into()), Expr::symbol("String"))
+}
+
+/// The maximum width and precision of a directive, larger values are
+/// rejected as malformed instead of allocating huge strings.
+const MAX_DIRECTIVE_SIZE: usize = 1024;
+
+/// Parses the digits of a width or a precision. Returns `Some(None)` without
+/// digits, and `None` for a size over `MAX_DIRECTIVE_SIZE`.
+fn parse_size(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Option<usize>> {
+    let mut n = None;
+
+    while let Some(d) = chars.peek().and_then(|ch| ch.to_digit(10)) {
+        let size = n
+            .unwrap_or(0usize)
+            .checked_mul(10)?
+            .checked_add(d as usize)
+            .filter(|size| *size <= MAX_DIRECTIVE_SIZE)?;
+        n = Some(size);
+        chars.next();
+    }
+
+    Some(n)
+}
+
+/// A parsed `%` directive of a format template, e.g. `%-8s` or `%.2f`.
+#[derive(Default)]
+struct Directive {
+    left_align: bool,
+    center: bool,
+    zero_pad: bool,
+    width: usize,
+    precision: Option<usize>,
+    conversion: char,
+}
+
+/// Parses a directive, the leading `%` is already consumed.
+fn parse_directive(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<Directive> {
+    let mut directive = Directive::default();
+
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            '-' => directive.left_align = true,
+            '0' => directive.zero_pad = true,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    directive.width = parse_size(chars)?.unwrap_or(0);
+
+    if chars.peek() == Some(&'.') {
+        chars.next();
+        directive.precision = Some(parse_size(chars)?.unwrap_or(0));
+    }
+
+    directive.conversion = chars.next()?;
+    // The strings are padded with spaces.
+    directive.zero_pad &= directive.conversion != 's';
+
+    Some(directive)
+}
+
+/// Converts the argument according to the directive, without padding.
+fn convert(directive: &Directive, arg: &Ann<Expr>) -> Result<String, Error> {
+    match (directive.conversion, arg.as_ref()) {
+        ('s', _) => {
+            let s = format_value(arg);
+            Ok(match directive.precision {
+                Some(precision) => s.chars().take(precision).collect(),
+                None => s,
+            })
+        }
+        ('d', Expr::Int(n)) => Ok(n.to_string()),
+        ('x', Expr::Int(n)) => Ok(format!("{n:x}")),
+        ('X', Expr::Int(n)) => Ok(format!("{n:X}")),
+        ('b', Expr::Int(n)) => Ok(format!("{n:b}")),
+        ('f', Expr::Float(n)) => Ok(format!("{n:.*}", directive.precision.unwrap_or(6))),
+        ('f', Expr::Int(n)) => Ok(format!(
+            "{:.*}",
+            directive.precision.unwrap_or(6),
+            *n as f64
+        )),
+        ('d' | 'x' | 'X' | 'b', _) => Err(Error::invalid_arguments(format!(
+            "the `%{}` directive expects an Int, found `{arg}`",
+            directive.conversion
+        ))),
+        ('f', _) => Err(Error::invalid_arguments(format!(
+            "the `%f` directive expects a Float, found `{arg}`"
+        ))),
+        (conversion, _) => Err(Error::invalid_arguments(format!(
+            "unknown format directive `%{conversion}`"
+        ))),
+    }
+}
+
+/// Pads the converted value to the width of the directive.
+fn pad(directive: &Directive, value: String) -> String {
+    let len = value.chars().count();
+
+    if len >= directive.width {
+        return value;
+    }
+
+    let padding = directive.width - len;
+
+    if directive.left_align {
+        format!("{value}{}", " ".repeat(padding))
+    } else if directive.center {
+        let left = padding / 2;
+        format!("{}{value}{}", " ".repeat(left), " ".repeat(padding - left))
+    } else if directive.zero_pad {
+        // The zeros go after the sign.
+        let (sign, digits) = match value.strip_prefix('-') {
+            Some(digits) => ("-", digits),
+            None => ("", value.as_str()),
+        };
+        format!("{sign}{}{digits}", "0".repeat(padding))
+    } else {
+        format!("{}{value}", " ".repeat(padding))
+    }
+}
+
+/// Formats the arguments into a string, printf-style. The first argument is a
+/// template with `%` directives, e.g. `(format "%-8s|%05.1f" name x)`. The
+/// supported conversions are `s`, `d`, `f`, `x`, `X`, `b` and `%%` for a
+/// literal `%`. The arguments that are not consumed by directives are
+/// appended, i.e. without directives the values are concatenated. String
+/// interpolation, e.g. `"Hello ${name}!"`, is desugared into `format`.
+pub fn format(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let mut output = String::new();
+
+    let mut args = args.iter();
+
+    if let Some(Ann(Expr::String(template), ..)) = args.as_slice().first() {
+        args.next();
+
+        let mut chars = template.chars().peekable();
+
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                output.push(ch);
+                continue;
+            }
+
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                output.push('%');
+                continue;
+            }
+
+            let Some(directive) = parse_directive(&mut chars) else {
+                return Err(Error::invalid_arguments(format!(
+                    "malformed format template `{template}`"
+                ))
+                .into());
+            };
+
+            let Some(arg) = args.next() else {
+                return Err(Error::invalid_arguments(format!(
+                    "missing argument for the `%{}` directive",
+                    directive.conversion
+                ))
+                .into());
+            };
+
+            output.push_str(&pad(&directive, convert(&directive, arg)?));
+        }
+    }
+
+    for arg in args {
+        output.push_str(&format_value(arg));
+    }
+
+    Ok(string(output))
+}
+
+/// Parses a `{}` placeholder spec, e.g. `>8`, `08.2` or `.3`, into the
+/// directive of the equivalent `format` conversion. The alignment is `<`
+/// (left), `>` (right) or `^` (center), the default alignment is left for
+/// Strings and right for numbers. The precision is the number of decimals of a
+/// Float, or the maximum number of chars of other values.
+fn parse_placeholder_spec(spec: &str, arg: &Ann<Expr>) -> Option<Directive> {
+    let is_number = matches!(arg.as_ref(), Expr::Int(..) | Expr::Float(..));
+
+    let mut directive = Directive {
+        left_align: !is_number,
+        ..Default::default()
+    };
+
+    let mut chars = spec.chars().peekable();
+
+    let alignment = chars.next_if(|ch| matches!(ch, '<' | '>' | '^'));
+
+    if chars.next_if_eq(&'0').is_some() {
+        // Only the numbers are zero padded, the zero padding implies the
+        // right alignment.
+        directive.zero_pad = is_number;
+        directive.left_align = false;
+    }
+
+    match alignment {
+        Some('<') => directive.left_align = true,
+        Some('>') => directive.left_align = false,
+        Some('^') => {
+            directive.left_align = false;
+            directive.center = true;
+        }
+        _ => (),
+    }
+
+    directive.width = parse_size(&mut chars)?.unwrap_or(0);
+
+    if chars.next_if_eq(&'.').is_some() {
+        directive.precision = Some(parse_size(&mut chars)??);
+    }
+
+    directive.conversion = match (arg.as_ref(), directive.precision) {
+        (Expr::Int(..), _) => 'd',
+        (Expr::Float(..), Some(_)) => 'f',
+        _ => 's',
+    };
+
+    // Trailing chars are malformed.
+    chars.next().is_none().then_some(directive)
+}
+
+/// Formats the arguments into a string, with `{}` placeholders. The first
+/// argument is the template, e.g. `(fmt "{:<8}|{:>6.2}" name x)`. A
+/// placeholder spec has an optional alignment (`<`, `>`, `^`), an optional
+/// `0` flag for zero padding, a width and a precision. Use `{{` and `}}` for
+/// literal braces. The number of the arguments should match the number of
+/// the placeholders.
+pub fn fmt(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((template, args)) = args.split_first() else {
+        return Err(Error::invalid_arguments("`fmt` requires a `template` argument").into());
+    };
+
+    let template = string_arg(template, "template")?;
+
+    let mut output = String::new();
+    let mut args = args.iter();
+    let mut chars = template.chars();
+
+    let malformed =
+        || Error::invalid_arguments(format!("malformed format template `{template}`")).into();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let Some((placeholder, rest)) = chars.as_str().split_once('}') else {
+                    return Err(malformed());
+                };
+                chars = rest.chars();
+
+                let Some(arg) = args.next() else {
+                    return Err(Error::invalid_arguments(format!(
+                        "missing argument for the `{{{placeholder}}}` placeholder"
+                    ))
+                    .into());
+                };
+
+                let directive = match placeholder.strip_prefix(':') {
+                    Some(spec) => parse_placeholder_spec(spec, arg).ok_or_else(malformed)?,
+                    None if placeholder.is_empty() => parse_placeholder_spec("", arg).unwrap(),
+                    None => return Err(malformed()),
+                };
+
+                output.push_str(&pad(&directive, convert(&directive, arg)?));
+            }
+            '}' => return Err(malformed()),
+            _ => output.push(ch),
+        }
+    }
+
+    if args.next().is_some() {
+        return Err(Error::invalid_arguments(format!(
+            "too many arguments for the format template `{template}`"
+        ))
+        .into());
+    }
+
+    Ok(string(output))
+}
+
+/// Returns the length of the string, in chars.
+pub fn str_len(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`str-len` requires a `string` argument").into());
+    };
+
+    let s = string_arg(s, "string")?;
+
+    Ok(Ann::with_type(
+        Expr::Int(s.chars().count() as i64),
+        Expr::symbol("Int"),
+    ))
+}
+
+/// Returns the chars from `start` to `end` (exclusive), e.g.
+/// `(str-slice "hello" 1 3)`. Without an `end`, slices to the end of the string.
+pub fn str_slice(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (s, start, end) = match args {
+        [s, start] => (s, start, None),
+        [s, start, end] => (s, start, Some(end)),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`str-slice` requires `string`, `start` and optional `end` arguments",
+            )
+            .into());
+        }
+    };
+
+    let s = string_arg(s, "string")?;
+    let len = s.chars().count();
+
+    let index = |arg: &Ann<Expr>| match arg.as_ref() {
+        Expr::Int(n) => usize::try_from(*n).ok().filter(|n| *n <= len),
+        _ => None,
+    };
+
+    let Some(start) = index(start) else {
+        return Err(Error::invalid_arguments(format!("`{start}` is not a valid index")).into());
+    };
+
+    let end = match end {
+        Some(end) => {
+            let Some(end) = index(end).filter(|end| *end >= start) else {
+                return Err(
+                    Error::invalid_arguments(format!("`{end}` is not a valid index")).into(),
+                );
+            };
+            end
+        }
+        None => len,
+    };
+
+    Ok(string(
+        s.chars().skip(start).take(end - start).collect::<String>(),
+    ))
+}
+
+/// Splits the string by the separator, e.g. `(str-split "a,b" ",")`, returns an
+/// Array of Strings.
+pub fn str_split(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s, separator] = args else {
+        return Err(Error::invalid_arguments(
+            "`str-split` requires `string`, `separator` arguments",
+        )
+        .into());
+    };
+
+    let s = string_arg(s, "string")?;
+    let separator = string_arg(separator, "separator")?;
+
+    let parts = if separator.is_empty() {
+        s.chars().map(string).collect()
+    } else {
+        s.split(separator).map(string).collect()
+    };
+
+    Ok(Ann::with_type(Expr::Array(parts), Expr::symbol("Array")))
+}
+
+/// Joins the items of an Array into a string, with the separator, e.g.
+/// `(str-join ["a" "b"] ", ")`.
+pub fn str_join(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (items, separator) = match args {
+        [items] => (items, ""),
+        [items, separator] => (items, string_arg(separator, "separator")?),
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`str-join` requires `array` and optional `separator` arguments",
+            )
+            .into());
+        }
+    };
+
+    let (Ann(Expr::Array(items), ..) | Ann(Expr::List(items), ..)) = items else {
+        return Err(Error::invalid_arguments(format!("`{items}` is not an Array")).into());
+    };
+
+    let output = items
+        .iter()
+        .map(format_value)
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    Ok(string(output))
+}
+
+/// Returns true if the string contains the substring.
+pub fn str_contains(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s, sub] = args else {
+        return Err(Error::invalid_arguments(
+            "`str-contains?` requires `string`, `substring` arguments",
+        )
+        .into());
+    };
+
+    let s = string_arg(s, "string")?;
+    let sub = string_arg(sub, "substring")?;
+
+    Ok(Ann::with_type(
+        Expr::Bool(s.contains(sub)),
+        Expr::symbol("Bool"),
+    ))
+}
+
+/// Replaces all occurrences of `from` with `to`, e.g.
+/// `(str-replace "a-b-c" "-" "+")`.
+pub fn str_replace(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s, from, to] = args else {
+        return Err(Error::invalid_arguments(
+            "`str-replace` requires `string`, `from`, `to` arguments",
+        )
+        .into());
+    };
+
+    let s = string_arg(s, "string")?;
+    let from = string_arg(from, "from")?;
+    let to = string_arg(to, "to")?;
+
+    if from.is_empty() {
+        return Err(Error::invalid_arguments("`str-replace` requires a non-empty `from`").into());
+    }
+
+    Ok(string(s.replace(from, to)))
+}
+
+pub fn uppercase(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`uppercase` requires a `string` argument").into());
+    };
+
+    Ok(string(string_arg(s, "string")?.to_uppercase()))
+}
+
+pub fn lowercase(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`lowercase` requires a `string` argument").into());
+    };
+
+    Ok(string(string_arg(s, "string")?.to_lowercase()))
+}
+
+/// Removes the leading and trailing whitespace.
+pub fn trim(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [s] = args else {
+        return Err(Error::invalid_arguments("`trim` requires a `string` argument").into());
+    };
+
+    Ok(string(string_arg(s, "string")?.trim()))
+}
@@ -0,0 +1,75 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+    style::{output_colors_enabled, Color, Style},
+};
+
+// #Insight
+// The styled text falls back to plain text when the stdout stream of the
+// Context is not a terminal, e.g. it is piped or redirected by the host, or
+// `NO_COLOR` is set, so it is safe to write styled text to any output.
+
+fn color_arg(arg: &Ann<Expr>) -> Result<Color, Ranged<Error>> {
+    let name = match arg.as_ref() {
+        Expr::KeySymbol(name) | Expr::String(name) => name,
+        _ => return Err(Error::invalid_arguments(format!("`{arg}` is not a color")).into()),
+    };
+
+    Color::from_name(name)
+        .ok_or_else(|| Error::invalid_arguments(format!("unknown color `{name}`")).into())
+}
+
+fn bool_arg(arg: &Ann<Expr>) -> Result<bool, Ranged<Error>> {
+    let Ann(Expr::Bool(value), ..) = arg else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not a Bool")).into());
+    };
+
+    Ok(*value)
+}
+
+/// Styles the text for terminal output, e.g. `(style "done" :fg :green :bold true)`.
+/// The supported options are `:fg`, `:bg`, `:bold`, `:dim`, `:italic` and
+/// `:underline`.
+pub fn style(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((text, options)) = args.split_first() else {
+        return Err(Error::invalid_arguments("`style` requires a `text` argument").into());
+    };
+
+    let mut style = Style::default();
+
+    for option in options.chunks(2) {
+        let [name, value] = option else {
+            return Err(Error::invalid_arguments(format!(
+                "missing value for the style option `{}`",
+                option[0]
+            ))
+            .into());
+        };
+
+        let Ann(Expr::KeySymbol(name), ..) = name else {
+            return Err(Error::invalid_arguments(format!("`{name}` is not a style option")).into());
+        };
+
+        match name.as_str() {
+            "fg" => style.fg = Some(color_arg(value)?),
+            "bg" => style.bg = Some(color_arg(value)?),
+            "bold" => style.bold = bool_arg(value)?,
+            "dim" => style.dim = bool_arg(value)?,
+            "italic" => style.italic = bool_arg(value)?,
+            "underline" => style.underline = bool_arg(value)?,
+            _ => {
+                return Err(
+                    Error::invalid_arguments(format!("unknown style option `:{name}`")).into(),
+                );
+            }
+        }
+    }
+
+    let colors = output_colors_enabled(&env.context.stdout);
+    let text = style.paint(&format_value(text), colors);
+
+    Ok(Ann::with_type(Expr::String(text), Expr::symbol("String")))
+}
@@ -0,0 +1,304 @@
+use std::collections::BTreeSet;
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// The rows of a table are Dicts or Arrays. For Dict rows the headers select
+// (and order) the columns, by default the columns are all the keys of the
+// rows, in alphabetical order. For Array rows the headers are just labels.
+//
+// In the text format the columns are aligned, the numbers are aligned to the
+// right. The rendered table has no trailing newline, e.g.
+// `(writeln (table/render users :headers ["name" "age"]))`.
+
+// #TODO support column alignment options.
+// #TODO support truncation of long cells.
+
+/// The output format of a table.
+#[derive(Clone, Copy)]
+enum Format {
+    Text,
+    Csv,
+    Markdown,
+}
+
+/// A rendered cell, numbers are aligned to the right.
+struct Cell {
+    text: String,
+    is_number: bool,
+}
+
+impl Cell {
+    fn new(value: Option<&Ann<Expr>>) -> Self {
+        match value {
+            None | Some(Ann(Expr::One, ..)) => Self {
+                text: String::new(),
+                is_number: false,
+            },
+            Some(value) => Self {
+                text: format_value(value),
+                is_number: matches!(
+                    value.as_ref(),
+                    Expr::Int(..) | Expr::Float(..) | Expr::BigInt(..) | Expr::Decimal(..)
+                ),
+            },
+        }
+    }
+}
+
+fn format_arg(arg: &Ann<Expr>) -> Result<Format, Ranged<Error>> {
+    let name = match arg.as_ref() {
+        Expr::KeySymbol(name) | Expr::String(name) => name,
+        _ => return Err(Error::invalid_arguments(format!("`{arg}` is not a table format")).into()),
+    };
+
+    match name.as_str() {
+        "text" => Ok(Format::Text),
+        "csv" => Ok(Format::Csv),
+        "markdown" => Ok(Format::Markdown),
+        _ => Err(Error::invalid_arguments(format!("unknown table format `{name}`")).into()),
+    }
+}
+
+fn headers_arg(arg: &Ann<Expr>) -> Result<Vec<String>, Ranged<Error>> {
+    let Ann(Expr::Array(items), ..) = arg else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not an Array of headers")).into());
+    };
+
+    Ok(items.iter().map(format_value).collect())
+}
+
+/// Returns the columns of the Dict rows, all the keys in alphabetical order.
+fn dict_columns(rows: &[Ann<Expr>]) -> Vec<String> {
+    let mut columns = BTreeSet::new();
+
+    for row in rows {
+        if let Ann(Expr::Dict(dict), ..) = row {
+            columns.extend(dict.keys().cloned());
+        }
+    }
+
+    columns.into_iter().collect()
+}
+
+fn pad(cell: &Cell, width: usize) -> String {
+    let padding = " ".repeat(width - cell.text.chars().count());
+
+    if cell.is_number {
+        format!("{padding}{}", cell.text)
+    } else {
+        format!("{}{padding}", cell.text)
+    }
+}
+
+fn render_text(headers: Option<&[String]>, rows: &[Vec<Cell>]) -> String {
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain(headers.map(<[String]>::len))
+        .max()
+        .unwrap_or_default();
+
+    let mut widths = vec![0; columns];
+
+    for (i, header) in headers.into_iter().flatten().enumerate() {
+        widths[i] = widths[i].max(header.chars().count());
+    }
+
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.text.chars().count());
+        }
+    }
+
+    let mut lines = Vec::new();
+
+    let line = |cells: Vec<String>| cells.join("  ").trim_end().to_owned();
+
+    if let Some(headers) = headers {
+        lines.push(line(
+            (0..columns)
+                .map(|i| {
+                    let header = headers.get(i).map(String::as_str).unwrap_or_default();
+                    format!("{header:width$}", width = widths[i])
+                })
+                .collect(),
+        ));
+        lines.push(line(
+            widths.iter().map(|&width| "-".repeat(width)).collect(),
+        ));
+    }
+
+    for row in rows {
+        lines.push(line(
+            (0..columns)
+                .map(|i| match row.get(i) {
+                    Some(cell) => pad(cell, widths[i]),
+                    None => " ".repeat(widths[i]),
+                })
+                .collect(),
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn render_csv(headers: Option<&[String]>, rows: &[Vec<Cell>]) -> String {
+    let mut lines = Vec::new();
+
+    if let Some(headers) = headers {
+        lines.push(
+            headers
+                .iter()
+                .map(|header| csv_field(header))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+
+    for row in rows {
+        lines.push(
+            row.iter()
+                .map(|cell| csv_field(&cell.text))
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+    }
+
+    lines.join("\n")
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn render_markdown(headers: Option<&[String]>, rows: &[Vec<Cell>]) -> String {
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain(headers.map(<[String]>::len))
+        .max()
+        .unwrap_or_default();
+
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+    // A Markdown table requires a header row.
+    let headers = (0..columns)
+        .map(|i| {
+            headers
+                .and_then(|headers| headers.get(i))
+                .map(|header| markdown_cell(header))
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut lines = vec![line(headers), line(vec!["---".to_owned(); columns])];
+
+    for row in rows {
+        lines.push(line(
+            (0..columns)
+                .map(|i| {
+                    row.get(i)
+                        .map(|cell| markdown_cell(&cell.text))
+                        .unwrap_or_default()
+                })
+                .collect(),
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Renders rows (Dicts or Arrays) as a table, e.g.
+/// `(table/render users :headers ["name" "age"] :format :markdown)`.
+/// The supported options are `:headers` and `:format` (`:text`, `:csv` or
+/// `:markdown`).
+pub fn table_render(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((rows, options)) = args.split_first() else {
+        return Err(Error::invalid_arguments("`table/render` requires a `rows` argument").into());
+    };
+
+    let Ann(Expr::Array(rows), ..) = rows else {
+        return Err(Error::invalid_arguments("`rows` argument should be an Array").into());
+    };
+
+    let mut headers = None;
+    let mut format = Format::Text;
+
+    for option in options.chunks(2) {
+        let [name, value] = option else {
+            return Err(Error::invalid_arguments(format!(
+                "missing value for the table option `{}`",
+                option[0]
+            ))
+            .into());
+        };
+
+        let Ann(Expr::KeySymbol(name), ..) = name else {
+            return Err(Error::invalid_arguments(format!("`{name}` is not a table option")).into());
+        };
+
+        match name.as_str() {
+            "headers" => headers = Some(headers_arg(value)?),
+            "format" => format = format_arg(value)?,
+            _ => {
+                return Err(
+                    Error::invalid_arguments(format!("unknown table option `:{name}`")).into(),
+                );
+            }
+        }
+    }
+
+    let is_dict_table = rows
+        .first()
+        .is_some_and(|row| matches!(row, Ann(Expr::Dict(..), ..)));
+
+    if is_dict_table && headers.is_none() {
+        headers = Some(dict_columns(rows));
+    }
+
+    let mut cells = Vec::new();
+
+    for row in rows {
+        let row = match (row, &headers) {
+            (Ann(Expr::Dict(dict), ..), Some(headers)) if is_dict_table => headers
+                .iter()
+                .map(|header| Cell::new(dict.get(header)))
+                .collect(),
+            (Ann(Expr::Array(items), ..), _) if !is_dict_table => {
+                items.iter().map(|item| Cell::new(Some(item))).collect()
+            }
+            _ => {
+                return Err(Error::invalid_arguments(format!(
+                    "`{row}` is not a valid table row, the rows should be all Dicts or all Arrays"
+                ))
+                .into());
+            }
+        };
+        cells.push(row);
+    }
+
+    let headers = headers.as_deref();
+
+    let output = match format {
+        Format::Text => render_text(headers, &cells),
+        Format::Csv => render_csv(headers, &cells),
+        Format::Markdown => render_markdown(headers, &cells),
+    };
+
+    Ok(Ann::with_type(Expr::String(output), Expr::symbol("String")))
+}
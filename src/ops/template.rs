@@ -0,0 +1,202 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// A minimal, Mustache-like template engine, for generating reports, HTML,
+// config files, etc. The supported tags:
+//
+// - `{{name}}` interpolates the value, dotted paths are supported, e.g. `{{user.name}}`
+// - `{{#name}}..{{/name}}` renders the section for each item of an Array, once
+//   for any other non-empty value, the value becomes the current context
+// - `{{^name}}..{{/name}}` renders the section if the value is missing, false,
+//   `()` or an empty Array
+// - `{{.}}` interpolates the current context, e.g. the current Array item
+// - `{{! comment }}` is ignored
+//
+// Interpolating an undefined variable is an error, a missing section value is
+// considered empty.
+
+// #TODO support partials.
+// #TODO cache the parsed templates.
+
+enum Node {
+    Text(String),
+    Var(String),
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+fn template_error(message: impl AsRef<str>) -> Error {
+    Error::invalid_arguments(format!("malformed template: {}", message.as_ref()))
+}
+
+fn parse_template(template: &str) -> Result<Vec<Node>, Error> {
+    // The stack of open sections, the root is at the bottom.
+    let mut stack: Vec<(String, bool, Vec<Node>)> = vec![(String::new(), false, Vec::new())];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        // The unwrap is safe, the root is never popped.
+        let nodes = &mut stack.last_mut().unwrap().2;
+
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_owned()));
+        }
+
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            return Err(template_error("unterminated tag"));
+        };
+        let tag = after[..end].trim();
+        rest = &after[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            stack.push((name.trim().to_owned(), false, Vec::new()));
+        } else if let Some(name) = tag.strip_prefix('^') {
+            stack.push((name.trim().to_owned(), true, Vec::new()));
+        } else if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim();
+            if stack.len() < 2 || stack.last().unwrap().0 != name {
+                return Err(template_error(format!("unexpected closing tag `{name}`")));
+            }
+            let (name, inverted, children) = stack.pop().unwrap();
+            stack.last_mut().unwrap().2.push(Node::Section {
+                name,
+                inverted,
+                children,
+            });
+        } else if !tag.starts_with('!') {
+            nodes.push(Node::Var(tag.to_owned()));
+        }
+    }
+
+    if stack.len() > 1 {
+        let name = &stack.last().unwrap().0;
+        return Err(template_error(format!("unclosed section `{name}`")));
+    }
+
+    let mut nodes = stack.pop().unwrap().2;
+
+    if !rest.is_empty() {
+        nodes.push(Node::Text(rest.to_owned()));
+    }
+
+    Ok(nodes)
+}
+
+/// Looks up the (dotted) name, starting from the innermost context.
+fn lookup<'a>(name: &str, contexts: &[&'a Expr]) -> Option<&'a Expr> {
+    if name == "." {
+        return contexts.last().copied();
+    }
+
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+
+    let mut value = contexts.iter().rev().find_map(|context| match context {
+        Expr::Dict(dict) => dict.get(first).map(|value| &value.0),
+        _ => None,
+    })?;
+
+    for part in parts {
+        let Expr::Dict(dict) = value else {
+            return None;
+        };
+        value = &dict.get(part)?.0;
+    }
+
+    Some(value)
+}
+
+fn is_empty(value: Option<&Expr>) -> bool {
+    match value {
+        None | Some(Expr::One) | Some(Expr::Bool(false)) => true,
+        Some(Expr::Array(items)) | Some(Expr::List(items)) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn render_nodes(
+    nodes: &[Node],
+    contexts: &mut Vec<&Expr>,
+    output: &mut String,
+) -> Result<(), Error> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => output.push_str(text),
+            Node::Var(name) => {
+                let Some(value) = lookup(name, contexts) else {
+                    return Err(Error::invalid_arguments(format!(
+                        "undefined template variable `{name}`"
+                    )));
+                };
+                output.push_str(&format_value(value));
+            }
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let value = lookup(name, contexts);
+
+                if *inverted {
+                    if is_empty(value) {
+                        render_nodes(children, contexts, output)?;
+                    }
+                    continue;
+                }
+
+                let Some(value) = value.filter(|value| !is_empty(Some(value))) else {
+                    continue;
+                };
+
+                match value {
+                    Expr::Array(items) | Expr::List(items) => {
+                        for item in items {
+                            contexts.push(&item.0);
+                            render_nodes(children, contexts, output)?;
+                            contexts.pop();
+                        }
+                    }
+                    Expr::Bool(true) => render_nodes(children, contexts, output)?,
+                    value => {
+                        contexts.push(value);
+                        render_nodes(children, contexts, output)?;
+                        contexts.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders a template with the given data, e.g.
+/// `(render "Hello {{name}}" {:name "George"})`.
+pub fn render(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [template, data] = args else {
+        return Err(
+            Error::invalid_arguments("`render` requires `template`, `data` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::String(template), ..) = template else {
+        return Err(Error::invalid_arguments("`template` argument should be a String").into());
+    };
+
+    let nodes = parse_template(template)?;
+
+    let mut output = String::new();
+    render_nodes(&nodes, &mut vec![&data.0], &mut output)?;
+
+    Ok(Ann::with_type(Expr::String(output), Expr::symbol("String")))
+}
@@ -0,0 +1,358 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{
+        foreign::{register_eq, register_printer, ForeignValue},
+        Expr,
+    },
+    range::Ranged,
+};
+
+// #Insight
+// A Tensor is a dense vector or matrix of Floats, in a contiguous buffer (row
+// major), the numeric ops avoid the overhead of the Array items. The Tensors
+// are opaque values, converted from/to Arrays with `tensor/from` and
+// `tensor/to-array`, e.g.
+//
+// (tensor/dot (tensor/from [[1 2] [3 4]]) (tensor/from [1 1]))
+//
+// The Tensors are immutable, the ops return new Tensors.
+
+// #TODO support tensors of rank > 2.
+// #TODO support mean/std along an axis.
+// #TODO support Int tensors.
+
+const TENSOR_TYPE: &str = "Tensor";
+
+/// A dense vector (rank 1) or matrix (rank 2) of Floats.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tensor {
+    /// The dimensions, `[len]` for a vector, `[rows cols]` for a matrix.
+    shape: Vec<usize>,
+    data: Vec<f64>,
+}
+
+impl Tensor {
+    fn new(shape: Vec<usize>, data: Vec<f64>) -> Self {
+        debug_assert_eq!(shape.iter().product::<usize>(), data.len());
+        Self { shape, data }
+    }
+
+    fn is_matrix(&self) -> bool {
+        self.shape.len() == 2
+    }
+
+    /// Returns the rows and columns, a vector is a single row.
+    fn dims(&self) -> (usize, usize) {
+        match self.shape[..] {
+            [rows, cols] => (rows, cols),
+            _ => (1, self.data.len()),
+        }
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Self {
+        Self::new(
+            self.shape.clone(),
+            self.data.iter().map(|&x| f(x)).collect(),
+        )
+    }
+
+    fn mean(&self) -> f64 {
+        self.data.iter().sum::<f64>() / self.data.len() as f64
+    }
+
+    fn to_expr(&self) -> Ann<Expr> {
+        let floats =
+            |row: &[f64]| Expr::Array(row.iter().map(|&x| Expr::Float(x).into()).collect()).into();
+
+        if self.is_matrix() {
+            let (_, cols) = self.dims();
+            let rows = if cols == 0 {
+                vec![floats(&[]); self.shape[0]]
+            } else {
+                self.data.chunks(cols).map(floats).collect()
+            };
+            Expr::Array(rows).into()
+        } else {
+            floats(&self.data)
+        }
+    }
+}
+
+/// Registers the printer and the equality of the Tensor values.
+pub fn register_tensor_hooks() {
+    register_printer(TENSOR_TYPE, |value| {
+        let tensor = value.downcast_ref::<Tensor>().unwrap();
+        format!("(Tensor {})", tensor.to_expr())
+    });
+    register_eq(TENSOR_TYPE, |a, b| {
+        a.downcast_ref::<Tensor>() == b.downcast_ref::<Tensor>()
+    });
+}
+
+fn tensor_value(tensor: Tensor) -> Ann<Expr> {
+    Ann::with_type(
+        Expr::Foreign(ForeignValue::new(TENSOR_TYPE, tensor)),
+        Expr::symbol(TENSOR_TYPE),
+    )
+}
+
+fn tensor_arg(arg: &Ann<Expr>) -> Result<&Tensor, Ranged<Error>> {
+    let Some(tensor) = (match arg.as_ref() {
+        Expr::Foreign(value) => value.downcast_ref::<Tensor>(),
+        _ => None,
+    }) else {
+        return Err(Error::invalid_arguments(format!("`{arg}` is not a Tensor")).into());
+    };
+
+    Ok(tensor)
+}
+
+fn float_item(item: &Ann<Expr>) -> Result<f64, Ranged<Error>> {
+    match item.as_ref() {
+        Expr::Float(n) => Ok(*n),
+        Expr::Int(n) => Ok(*n as f64),
+        _ => Err(Error::invalid_arguments(format!("`{item}` is not a number")).into()),
+    }
+}
+
+fn unary_arg<'a>(name: &str, args: &'a [Ann<Expr>]) -> Result<&'a Tensor, Ranged<Error>> {
+    let [a] = args else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires a Tensor argument")).into(),
+        );
+    };
+
+    tensor_arg(a)
+}
+
+fn shape_mismatch(name: &str, a: &Tensor, b: &Tensor) -> Ranged<Error> {
+    Error::invalid_arguments(format!(
+        "`{name}` shape mismatch, {:?} and {:?}",
+        a.shape, b.shape
+    ))
+    .into()
+}
+
+/// Converts an Array of numbers, or an Array of rows, to a Tensor, e.g.
+/// `(tensor/from [1 2 3])`, `(tensor/from [[1 2] [3 4]])`.
+pub fn tensor_from(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::Array(items), ..)] = args else {
+        return Err(Error::invalid_arguments("`tensor/from` requires an Array argument").into());
+    };
+
+    let is_matrix = matches!(items.first(), Some(Ann(Expr::Array(..), ..)));
+
+    if !is_matrix {
+        let data = items.iter().map(float_item).collect::<Result<_, _>>()?;
+        return Ok(tensor_value(Tensor::new(vec![items.len()], data)));
+    }
+
+    let mut data = Vec::new();
+    let mut cols = None;
+
+    for row in items {
+        let Ann(Expr::Array(row), ..) = row else {
+            return Err(Error::invalid_arguments(format!("`{row}` is not an Array row")).into());
+        };
+
+        if *cols.get_or_insert(row.len()) != row.len() {
+            return Err(
+                Error::invalid_arguments("the rows of a Tensor should have equal lengths").into(),
+            );
+        }
+
+        for item in row {
+            data.push(float_item(item)?);
+        }
+    }
+
+    Ok(tensor_value(Tensor::new(
+        vec![items.len(), cols.unwrap_or_default()],
+        data,
+    )))
+}
+
+/// Converts a Tensor to an Array of Floats, or an Array of rows.
+pub fn tensor_to_array(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    Ok(unary_arg("tensor/to-array", args)?.to_expr())
+}
+
+/// Returns the shape of a Tensor, e.g. `[2 3]` for a matrix with 2 rows and
+/// 3 columns.
+pub fn tensor_shape(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let tensor = unary_arg("tensor/shape", args)?;
+
+    Ok(Expr::Array(
+        tensor
+            .shape
+            .iter()
+            .map(|&n| Expr::Int(n as i64).into())
+            .collect(),
+    )
+    .into())
+}
+
+// tensor/add, tensor/sub, tensor/mul, tensor/div
+
+/// Applies an elementwise op, the second argument is a Tensor of the same
+/// shape or a number.
+fn elementwise(
+    name: &str,
+    args: &[Ann<Expr>],
+    op: impl Fn(f64, f64) -> f64,
+) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments(format!("`{name}` requires two arguments")).into());
+    };
+
+    let a = tensor_arg(a)?;
+
+    let tensor = match b.as_ref() {
+        Expr::Int(..) | Expr::Float(..) => {
+            let b = float_item(b)?;
+            a.map(|x| op(x, b))
+        }
+        _ => {
+            let b = tensor_arg(b)?;
+            if a.shape != b.shape {
+                return Err(shape_mismatch(name, a, b));
+            }
+            Tensor::new(
+                a.shape.clone(),
+                a.data
+                    .iter()
+                    .zip(&b.data)
+                    .map(|(&x, &y)| op(x, y))
+                    .collect(),
+            )
+        }
+    };
+
+    Ok(tensor_value(tensor))
+}
+
+pub fn tensor_add(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    elementwise("tensor/add", args, |x, y| x + y)
+}
+
+pub fn tensor_sub(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    elementwise("tensor/sub", args, |x, y| x - y)
+}
+
+pub fn tensor_mul(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    elementwise("tensor/mul", args, |x, y| x * y)
+}
+
+pub fn tensor_div(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    elementwise("tensor/div", args, |x, y| x / y)
+}
+
+/// Returns the dot product of two vectors as a Float, or the matrix product
+/// of a matrix and a matrix or a vector as a Tensor.
+pub fn tensor_dot(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [a, b] = args else {
+        return Err(Error::invalid_arguments("`tensor/dot` requires two arguments").into());
+    };
+
+    let (a, b) = (tensor_arg(a)?, tensor_arg(b)?);
+
+    if !a.is_matrix() && !b.is_matrix() {
+        if a.shape != b.shape {
+            return Err(shape_mismatch("tensor/dot", a, b));
+        }
+        let dot = a.data.iter().zip(&b.data).map(|(x, y)| x * y).sum();
+        return Ok(Ann::with_type(Expr::Float(dot), Expr::symbol("Float")));
+    }
+
+    let (rows, n) = a.dims();
+
+    // A vector on the right is a column.
+    let (m, cols) = if b.is_matrix() {
+        b.dims()
+    } else {
+        (b.data.len(), 1)
+    };
+
+    if !a.is_matrix() || n != m {
+        return Err(shape_mismatch("tensor/dot", a, b));
+    }
+
+    let mut data = vec![0.0; rows * cols];
+
+    for i in 0..rows {
+        for k in 0..n {
+            let x = a.data[i * n + k];
+            for j in 0..cols {
+                data[i * cols + j] += x * b.data[k * cols + j];
+            }
+        }
+    }
+
+    let shape = if b.is_matrix() {
+        vec![rows, cols]
+    } else {
+        vec![rows]
+    };
+
+    Ok(tensor_value(Tensor::new(shape, data)))
+}
+
+/// Returns the transpose of a matrix, a vector is returned as-is.
+pub fn tensor_transpose(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let tensor = unary_arg("tensor/transpose", args)?;
+
+    if !tensor.is_matrix() {
+        return Ok(tensor_value(tensor.clone()));
+    }
+
+    let (rows, cols) = tensor.dims();
+
+    let mut data = Vec::with_capacity(tensor.data.len());
+
+    for j in 0..cols {
+        for i in 0..rows {
+            data.push(tensor.data[i * cols + j]);
+        }
+    }
+
+    Ok(tensor_value(Tensor::new(vec![cols, rows], data)))
+}
+
+// tensor/sum, tensor/mean, tensor/std
+
+pub fn tensor_sum(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let tensor = unary_arg("tensor/sum", args)?;
+
+    Ok(Ann::with_type(
+        Expr::Float(tensor.data.iter().sum()),
+        Expr::symbol("Float"),
+    ))
+}
+
+/// Returns the mean of all the elements of a Tensor, NaN if the Tensor is
+/// empty.
+pub fn tensor_mean(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let tensor = unary_arg("tensor/mean", args)?;
+
+    Ok(Ann::with_type(
+        Expr::Float(tensor.mean()),
+        Expr::symbol("Float"),
+    ))
+}
+
+/// Returns the (population) standard deviation of all the elements of a
+/// Tensor.
+pub fn tensor_std(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let tensor = unary_arg("tensor/std", args)?;
+
+    let mean = tensor.mean();
+    let variance =
+        tensor.data.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / tensor.data.len() as f64;
+
+    Ok(Ann::with_type(
+        Expr::Float(variance.sqrt()),
+        Expr::symbol("Float"),
+    ))
+}
@@ -0,0 +1,474 @@
+use std::{
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{cancellation::POLL_INTERVAL, env::Env},
+    expr::{foreign::ForeignValue, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// A time is an Int, the milliseconds since the Unix epoch (UTC), e.g. the
+// result of `(time/now)`. The times are formatted/parsed as RFC 3339 strings,
+// e.g. `2024-03-01T12:30:00Z`, or with a pattern, e.g. `"%Y-%m-%d"`. The
+// supported pattern fields are `%Y`, `%m`, `%d`, `%H`, `%M`, `%S`, `%f` (the
+// milliseconds) and `%%`.
+//
+// An Instant is an opaque value from a monotonic clock, for measuring the
+// elapsed time, e.g. for benchmarking:
+//
+// (let start (time/instant))
+// (run)
+// (writeln (time/elapsed start))
+
+// #TODO support time zones, the times are formatted in UTC.
+// #TODO support adding months and years, with day clamping.
+// #TODO support leap seconds.
+
+const INSTANT_TYPE: &str = "Instant";
+
+const MILLIS_PER_SECOND: i64 = 1_000;
+const MILLIS_PER_MINUTE: i64 = 60 * MILLIS_PER_SECOND;
+const MILLIS_PER_HOUR: i64 = 60 * MILLIS_PER_MINUTE;
+const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+
+/// A calendar date and time, in UTC.
+#[derive(Debug, Default, PartialEq)]
+struct DateTime {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    millis: i64,
+}
+
+// The civil date conversions, from http://howardhinnant.github.io/date_algorithms.html
+
+/// Returns the days since the epoch of a date in the proleptic Gregorian
+/// calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Returns the date of the days since the epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl DateTime {
+    fn from_millis(time: i64) -> Self {
+        let (year, month, day) = civil_from_days(time.div_euclid(MILLIS_PER_DAY));
+        let millis = time.rem_euclid(MILLIS_PER_DAY);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: millis / MILLIS_PER_HOUR,
+            minute: millis % MILLIS_PER_HOUR / MILLIS_PER_MINUTE,
+            second: millis % MILLIS_PER_MINUTE / MILLIS_PER_SECOND,
+            millis: millis % MILLIS_PER_SECOND,
+        }
+    }
+
+    fn to_millis(&self) -> i64 {
+        days_from_civil(self.year, self.month, self.day) * MILLIS_PER_DAY
+            + self.hour * MILLIS_PER_HOUR
+            + self.minute * MILLIS_PER_MINUTE
+            + self.second * MILLIS_PER_SECOND
+            + self.millis
+    }
+
+    fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && (0..24).contains(&self.hour)
+            && (0..60).contains(&self.minute)
+            && (0..60).contains(&self.second)
+    }
+
+    /// Formats as RFC 3339, the milliseconds are omitted if zero.
+    fn to_rfc3339(&self) -> String {
+        let mut text = self.format("%Y-%m-%dT%H:%M:%S");
+        if self.millis != 0 {
+            text.push_str(&format!(".{:03}", self.millis));
+        }
+        text.push('Z');
+        text
+    }
+
+    fn format(&self, pattern: &str) -> String {
+        let mut text = String::new();
+        let mut chars = pattern.chars();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                text.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => text.push_str(&format!("{:04}", self.year)),
+                Some('m') => text.push_str(&format!("{:02}", self.month)),
+                Some('d') => text.push_str(&format!("{:02}", self.day)),
+                Some('H') => text.push_str(&format!("{:02}", self.hour)),
+                Some('M') => text.push_str(&format!("{:02}", self.minute)),
+                Some('S') => text.push_str(&format!("{:02}", self.second)),
+                Some('f') => text.push_str(&format!("{:03}", self.millis)),
+                Some('%') => text.push('%'),
+                Some(c) => {
+                    text.push('%');
+                    text.push(c);
+                }
+                None => text.push('%'),
+            }
+        }
+
+        text
+    }
+}
+
+/// A cursor over the parsed text.
+struct Scanner<'a> {
+    text: &'a str,
+}
+
+impl Scanner<'_> {
+    /// Consumes a number of exactly `width` digits.
+    fn digits(&mut self, width: usize) -> Option<i64> {
+        let digits = self.text.get(..width)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        self.text = &self.text[width..];
+        digits.parse().ok()
+    }
+
+    fn char(&mut self, expected: impl Fn(char) -> bool) -> Option<char> {
+        let c = self.text.chars().next().filter(|&c| expected(c))?;
+        self.text = &self.text[c.len_utf8()..];
+        Some(c)
+    }
+
+    /// Consumes an optional fraction of a second, returns the milliseconds.
+    fn fraction(&mut self) -> Option<i64> {
+        if self.char(|c| c == '.').is_none() {
+            return Some(0);
+        }
+        let len = self.text.bytes().take_while(u8::is_ascii_digit).count();
+        if len == 0 {
+            return None;
+        }
+        let digits = &self.text[..len];
+        self.text = &self.text[len..];
+        // Only the milliseconds are kept, the rest of the digits are truncated.
+        format!("{:0<3}", &digits[..len.min(3)]).parse().ok()
+    }
+}
+
+/// Parses an RFC 3339 time, e.g. `2024-03-01T12:30:00.250+02:00`, returns the
+/// milliseconds since the epoch.
+fn parse_rfc3339(text: &str) -> Option<i64> {
+    let mut s = Scanner { text };
+
+    let mut time = DateTime {
+        year: s.digits(4)?,
+        ..Default::default()
+    };
+    s.char(|c| c == '-')?;
+    time.month = s.digits(2)?;
+    s.char(|c| c == '-')?;
+    time.day = s.digits(2)?;
+    s.char(|c| matches!(c, 'T' | 't' | ' '))?;
+    time.hour = s.digits(2)?;
+    s.char(|c| c == ':')?;
+    time.minute = s.digits(2)?;
+    s.char(|c| c == ':')?;
+    time.second = s.digits(2)?;
+    time.millis = s.fraction()?;
+
+    let offset = match s.char(|c| matches!(c, 'Z' | 'z' | '+' | '-'))? {
+        'Z' | 'z' => 0,
+        sign => {
+            let hours = s.digits(2)?;
+            s.char(|c| c == ':')?;
+            let minutes = s.digits(2)?;
+            let offset = hours * MILLIS_PER_HOUR + minutes * MILLIS_PER_MINUTE;
+            if sign == '-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+    };
+
+    if !s.text.is_empty() || !time.is_valid() {
+        return None;
+    }
+
+    Some(time.to_millis() - offset)
+}
+
+/// Parses a time with a pattern, e.g. `"%Y-%m-%d"`, the missing fields are
+/// the start of their range, e.g. midnight. Returns the milliseconds since the
+/// epoch.
+fn parse_pattern(text: &str, pattern: &str) -> Option<i64> {
+    let mut s = Scanner { text };
+
+    let mut time = DateTime {
+        year: 1970,
+        month: 1,
+        day: 1,
+        ..Default::default()
+    };
+
+    let mut chars = pattern.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            s.char(|x| x == c)?;
+            continue;
+        }
+        match chars.next()? {
+            'Y' => time.year = s.digits(4)?,
+            'm' => time.month = s.digits(2)?,
+            'd' => time.day = s.digits(2)?,
+            'H' => time.hour = s.digits(2)?,
+            'M' => time.minute = s.digits(2)?,
+            'S' => time.second = s.digits(2)?,
+            'f' => time.millis = s.digits(3)?,
+            '%' => {
+                s.char(|x| x == '%')?;
+            }
+            _ => return None,
+        }
+    }
+
+    if !s.text.is_empty() || !time.is_valid() {
+        return None;
+    }
+
+    Some(time.to_millis())
+}
+
+fn time_value(time: i64) -> Ann<Expr> {
+    Ann::with_type(Expr::Int(time), Expr::symbol("Int"))
+}
+
+fn string_value(s: String) -> Ann<Expr> {
+    Ann::with_type(Expr::String(s), Expr::symbol("String"))
+}
+
+fn now_millis() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as i64,
+        Err(error) => -(error.duration().as_millis() as i64),
+    }
+}
+
+/// Returns the current time, as milliseconds since the epoch, or as an RFC
+/// 3339 string, e.g. `(time/now :rfc3339)`.
+pub fn time_now(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let now = now_millis();
+
+    match args {
+        [] => Ok(time_value(now)),
+        [Ann(Expr::KeySymbol(format), ..)] if format == "millis" => Ok(time_value(now)),
+        [Ann(Expr::KeySymbol(format), ..)] if format == "rfc3339" => {
+            Ok(string_value(DateTime::from_millis(now).to_rfc3339()))
+        }
+        _ => Err(Error::invalid_arguments(
+            "`time/now` accepts an optional `:millis` or `:rfc3339` argument",
+        )
+        .into()),
+    }
+}
+
+/// Parses a time, an RFC 3339 string or a string with a pattern, e.g.
+/// `(time/parse "01/03/2024" "%d/%m/%Y")`. Returns the milliseconds since the
+/// epoch.
+pub fn time_parse(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (text, time) = match args {
+        [Ann(Expr::String(text), ..)] => (text, parse_rfc3339(text)),
+        [Ann(Expr::String(text), ..), Ann(Expr::String(pattern), ..)] => {
+            (text, parse_pattern(text, pattern))
+        }
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`time/parse` requires a String argument and an optional pattern",
+            )
+            .into());
+        }
+    };
+
+    let Some(time) = time else {
+        return Err(Error::invalid_arguments(format!("invalid time `{text}`")).into());
+    };
+
+    Ok(time_value(time))
+}
+
+/// Formats a time, as RFC 3339 or with a pattern, e.g.
+/// `(time/format (time/now) "%Y-%m-%d")`.
+pub fn time_format(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let text = match args {
+        [Ann(Expr::Int(time), ..)] => DateTime::from_millis(*time).to_rfc3339(),
+        [Ann(Expr::Int(time), ..), Ann(Expr::String(pattern), ..)] => {
+            DateTime::from_millis(*time).format(pattern)
+        }
+        _ => {
+            return Err(Error::invalid_arguments(
+                "`time/format` requires an Int time argument and an optional pattern",
+            )
+            .into());
+        }
+    };
+
+    Ok(string_value(text))
+}
+
+/// Adds durations to a time, e.g. `(time/add t :days 1 :hours -2)`. The
+/// supported units are `:millis`, `:seconds`, `:minutes`, `:hours`, `:days`
+/// and `:weeks`.
+pub fn time_add(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let Some((Ann(Expr::Int(time), ..), durations)) = args.split_first() else {
+        return Err(Error::invalid_arguments("`time/add` requires an Int time argument").into());
+    };
+
+    let mut time = *time;
+
+    for duration in durations.chunks(2) {
+        let [unit, amount] = duration else {
+            return Err(Error::invalid_arguments(format!(
+                "missing amount for the time unit `{}`",
+                duration[0]
+            ))
+            .into());
+        };
+
+        let Ann(Expr::KeySymbol(unit), ..) = unit else {
+            return Err(Error::invalid_arguments(format!("`{unit}` is not a time unit")).into());
+        };
+
+        let scale = match unit.as_str() {
+            "millis" => 1,
+            "seconds" => MILLIS_PER_SECOND,
+            "minutes" => MILLIS_PER_MINUTE,
+            "hours" => MILLIS_PER_HOUR,
+            "days" => MILLIS_PER_DAY,
+            "weeks" => 7 * MILLIS_PER_DAY,
+            _ => {
+                return Err(Error::invalid_arguments(format!("unknown time unit `:{unit}`")).into())
+            }
+        };
+
+        let Ann(Expr::Int(amount), ..) = amount else {
+            return Err(Error::invalid_arguments(format!("`{amount}` is not an Int")).into());
+        };
+
+        time = amount
+            .checked_mul(scale)
+            .and_then(|delta| time.checked_add(delta))
+            .ok_or_else(|| Error::invalid_arguments("the time is out of range"))?;
+    }
+
+    Ok(time_value(time))
+}
+
+/// Returns an Instant of the monotonic clock, see `time/elapsed`.
+pub fn time_instant(_args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    Ok(Ann::with_type(
+        Expr::Foreign(ForeignValue::new(INSTANT_TYPE, Instant::now())),
+        Expr::symbol(INSTANT_TYPE),
+    ))
+}
+
+/// Returns the milliseconds elapsed since an Instant, as a Float.
+pub fn time_elapsed(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let instant = match args {
+        [Ann(Expr::Foreign(value), ..)] => value.downcast_ref::<Instant>(),
+        _ => None,
+    };
+
+    let Some(instant) = instant else {
+        return Err(Error::invalid_arguments("`time/elapsed` requires an Instant argument").into());
+    };
+
+    Ok(Ann::with_type(
+        Expr::Float(instant.elapsed().as_secs_f64() * 1000.0),
+        Expr::symbol("Float"),
+    ))
+}
+
+/// Blocks the evaluation for a number of milliseconds, e.g. `(sleep 500)`.
+/// The evaluation deadline and the cancellation are honored.
+pub fn sleep(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [Ann(Expr::Int(millis @ 0..), ..)] = args else {
+        return Err(
+            Error::invalid_arguments("`sleep` requires a non-negative Int argument").into(),
+        );
+    };
+
+    let duration = Duration::from_millis(*millis as u64);
+
+    let mut wake_at = Instant::now() + duration;
+
+    let is_timed_out = matches!(env.context.deadline, Some(deadline) if wake_at >= deadline);
+
+    if let Some(deadline) = env.context.deadline {
+        wake_at = wake_at.min(deadline);
+    }
+
+    // With a cancellation token, sleep in slices to observe the cancellation.
+    loop {
+        env.context.check_cancelled()?;
+
+        let remaining = wake_at.saturating_duration_since(Instant::now());
+
+        if remaining.is_zero() {
+            break;
+        }
+
+        match env.context.cancellation {
+            Some(..) => thread::sleep(remaining.min(POLL_INTERVAL)),
+            None => thread::sleep(remaining),
+        }
+    }
+
+    if is_timed_out {
+        return Err(Error::TimedOut.into());
+    }
+
+    Ok(Expr::One.into())
+}
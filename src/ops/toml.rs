@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use toml::Value;
+
+use crate::{ann::Ann, error::Error, eval::env::Env, expr::Expr, range::Ranged};
+
+// #Insight
+// A TOML document is converted to a Dict, the TOML tables to Dicts and the
+// TOML arrays to Arrays. The dates and times are converted to Strings, e.g.
+// `"1979-05-27T07:32:00Z"`.
+
+// #TODO support `toml/write`.
+
+fn value_to_expr(value: Value) -> Ann<Expr> {
+    let expr = match value {
+        Value::String(s) => Expr::String(s),
+        Value::Integer(n) => Expr::Int(n),
+        Value::Float(n) => Expr::Float(n),
+        Value::Boolean(b) => Expr::Bool(b),
+        Value::Datetime(datetime) => Expr::String(datetime.to_string()),
+        Value::Array(items) => Expr::Array(items.into_iter().map(value_to_expr).collect()),
+        Value::Table(table) => Expr::Dict(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, value_to_expr(value)))
+                .collect::<BTreeMap<_, _>>(),
+        ),
+    };
+
+    expr.into()
+}
+
+/// Parses a TOML document to a Dict, e.g. `(toml/parse "port = 8080")`.
+pub fn toml_parse(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [source] = args else {
+        return Err(Error::invalid_arguments("`toml/parse` requires a `source` argument").into());
+    };
+
+    let Ann(Expr::String(source), ..) = source else {
+        return Err(Error::invalid_arguments("`source` argument should be a String").into());
+    };
+
+    let table = toml::from_str::<toml::Table>(source).map_err(|error| {
+        Error::invalid_arguments(format!("malformed TOML: {}", error.message()))
+    })?;
+
+    Ok(value_to_expr(Value::Table(table)))
+}
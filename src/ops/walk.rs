@@ -0,0 +1,49 @@
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{env::Env, invoke},
+    expr::Expr,
+    range::Ranged,
+};
+
+// #Insight
+// The walks apply a function to every node of a nested value, i.e. the Dicts,
+// the Arrays and their items, e.g. to scrub the secrets of a JSON document:
+//
+// (postwalk (Func (x) (if (= x "secret") "***" x)) data)
+//
+// `walk` is top-down, `f` is applied to a collection before its items, the
+// items of the returned collection are walked. `postwalk` is bottom-up, `f`
+// is applied to a collection with already transformed items. The walks are
+// not recursive Tan functions, deeply nested values are supported.
+
+fn walk_args<'a>(
+    name: &str,
+    args: &'a [Ann<Expr>],
+) -> Result<(&'a Ann<Expr>, &'a Ann<Expr>), Ranged<Error>> {
+    let [f, data] = args else {
+        return Err(
+            Error::invalid_arguments(format!("`{name}` requires `f`, `data` arguments")).into(),
+        );
+    };
+
+    Ok((f, data))
+}
+
+/// Applies the function to every node of the value, top-down, e.g.
+/// `(walk f data)`.
+pub fn walk(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (f, data) = walk_args("walk", args)?;
+
+    data.clone()
+        .try_transform_pre(&mut |node| invoke(f, vec![node], env))
+}
+
+/// Applies the function to every node of the value, bottom-up, e.g.
+/// `(postwalk f data)`.
+pub fn postwalk(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let (f, data) = walk_args("postwalk", args)?;
+
+    data.clone()
+        .try_transform(&mut |node| invoke(f, vec![node], env))
+}
@@ -0,0 +1,85 @@
+use std::{
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use notify::{RecursiveMode, Watcher};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::{capabilities::Capability, env::Env, flow::Flow, invoke},
+    expr::Expr,
+    range::Ranged,
+};
+
+// #Insight
+// The watch loop polls the events with a timeout, to respect the evaluation
+// deadline and the cancellation.
+
+// #TODO debounce the events.
+// #TODO pass the kind of the change to the handler.
+
+/// The interval between deadline checks, while waiting for events.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+fn watch_error(error: notify::Error) -> Error {
+    match error.kind {
+        notify::ErrorKind::Io(error) => Error::Io(error),
+        _ => Error::invalid_arguments(format!("cannot watch: {error}")),
+    }
+}
+
+/// Watches a path, recursively, for file-system changes, and invokes the
+/// handler with the changed path, e.g. `(watch "src" (Func (path) (rebuild path)))`.
+/// The watch loop is exited with `(break value)` in the handler.
+pub fn watch(args: &[Ann<Expr>], env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    env.context.require(Capability::Fs, "watch")?;
+
+    let [path, handler] = args else {
+        return Err(
+            Error::invalid_arguments("`watch` requires `path`, `handler` arguments").into(),
+        );
+    };
+
+    let Ann(Expr::String(path), ..) = path else {
+        return Err(Error::invalid_arguments("`path` argument should be a String").into());
+    };
+
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    watcher
+        .watch(Path::new(path), RecursiveMode::Recursive)
+        .map_err(watch_error)?;
+
+    loop {
+        let event = match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(event) => event.map_err(watch_error)?,
+            Err(RecvTimeoutError::Timeout) => {
+                env.context.check_deadline()?;
+                env.context.check_cancelled()?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        if event.kind.is_access() {
+            continue;
+        }
+
+        for path in event.paths {
+            let path = Expr::String(path.to_string_lossy().into_owned());
+
+            match invoke(handler, vec![path.into()], env) {
+                // The signals of the handler body escape the handler function.
+                Ok(_) | Err(Ranged(Error::EscapedFlow(Flow::Continue), ..)) => (),
+                Err(Ranged(Error::EscapedFlow(Flow::Break(value)), ..)) => return Ok(value),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    Ok(Expr::One.into())
+}
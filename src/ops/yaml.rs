@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use yaml_rust2::{Yaml, YamlLoader};
+
+use crate::{
+    ann::Ann,
+    error::Error,
+    eval::env::Env,
+    expr::{format_value, Expr},
+    range::Ranged,
+};
+
+// #Insight
+// A YAML document is converted to Dicts and Arrays, the YAML nulls to `()`.
+// The Dict keys are Strings, non-String keys are converted to Strings, e.g.
+// `1: one` to `{"1" "one"}`. Only the first document of a multi-document
+// stream is converted.
+
+// #TODO support `yaml/write`.
+// #TODO support aliases.
+
+fn yaml_to_expr(yaml: Yaml) -> Result<Ann<Expr>, Error> {
+    let expr = match yaml {
+        Yaml::Real(ref s) => match yaml.as_f64() {
+            Some(n) => Expr::Float(n),
+            None => {
+                return Err(Error::invalid_arguments(format!(
+                    "malformed YAML real `{s}`"
+                )))
+            }
+        },
+        Yaml::Integer(n) => Expr::Int(n),
+        Yaml::String(s) => Expr::String(s),
+        Yaml::Boolean(b) => Expr::Bool(b),
+        Yaml::Null => Expr::One,
+        Yaml::Array(items) => Expr::Array(
+            items
+                .into_iter()
+                .map(yaml_to_expr)
+                .collect::<Result<_, _>>()?,
+        ),
+        Yaml::Hash(hash) => {
+            let mut dict = BTreeMap::new();
+            for (key, value) in hash {
+                let key = match yaml_to_expr(key)? {
+                    Ann(Expr::String(key), ..) => key,
+                    key => format_value(key),
+                };
+                dict.insert(key, yaml_to_expr(value)?);
+            }
+            Expr::Dict(dict)
+        }
+        Yaml::Alias(..) | Yaml::BadValue => {
+            return Err(Error::invalid_arguments("unsupported YAML value"));
+        }
+    };
+
+    Ok(expr.into())
+}
+
+/// Parses a YAML document, e.g. `(yaml/parse "port: 8080")`.
+pub fn yaml_parse(args: &[Ann<Expr>], _env: &mut Env) -> Result<Ann<Expr>, Ranged<Error>> {
+    let [source] = args else {
+        return Err(Error::invalid_arguments("`yaml/parse` requires a `source` argument").into());
+    };
+
+    let Ann(Expr::String(source), ..) = source else {
+        return Err(Error::invalid_arguments("`source` argument should be a String").into());
+    };
+
+    let documents = YamlLoader::load_from_str(source)
+        .map_err(|error| Error::invalid_arguments(format!("malformed YAML: {error}")))?;
+
+    match documents.into_iter().next() {
+        Some(document) => Ok(yaml_to_expr(document)?),
+        None => Ok(Expr::One.into()),
+    }
+}
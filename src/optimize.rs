@@ -1,6 +1,6 @@
 // #TODO combine a vec of expressions into one `do` expression?, in this pass?
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use crate::{
     ann::Ann,
@@ -16,15 +16,15 @@ pub fn optimize_fn(expr: Ann<Expr>) -> Ann<Expr> {
             if !terms.is_empty() {
                 if let Ann(Expr::Symbol(s), ..) = &terms[0] {
                     if s == "Array" {
-                        let items = terms[1..].iter().map(|ax| ax.0.clone()).collect();
+                        let items = terms[1..].to_vec();
                         return Ann(Expr::Array(items), expr.1);
                     } else if s == "Dict" {
-                        let items: Vec<Expr> = terms[1..].iter().map(|ax| ax.0.clone()).collect();
-                        let mut dict = HashMap::new();
-                        for pair in items.chunks(2) {
-                            let k = pair[0].clone();
-                            let v = pair[1].clone();
-                            dict.insert(format_value(k), v);
+                        let mut dict = BTreeMap::new();
+                        for pair in terms[1..].chunks(2) {
+                            // The parser ensures that all keys have values.
+                            if let [k, v] = pair {
+                                dict.insert(format_value(k), v.clone());
+                            }
                         }
                         return Ann(Expr::Dict(dict), expr.1);
                     }
@@ -67,6 +67,6 @@ mod tests {
 
         let s = format!("{expr_optimized:?}");
 
-        assert!(s.contains(r#"Dict({"name": String("George"), "age": Int(25)})"#));
+        assert!(s.contains(r#"Dict({"age": Int(25), "name": String("George")})"#));
     }
 }
@@ -1,10 +1,20 @@
+use num_bigint::BigInt;
+use num_traits::Num;
+use rust_decimal::Decimal;
+
 use crate::{
     ann::Ann,
     error::Error,
-    expr::Expr,
-    lexer::{token::Token, Lexer},
+    expr::{
+        number::{convert_number_literal, is_number_type},
+        Expr,
+    },
+    lexer::{
+        token::{StringSegment, Token},
+        Lexer,
+    },
     range::{Range, Ranged},
-    util::Break,
+    util::{Break, FORMAT_SYMBOL},
 };
 
 // #TODO no need to keep iterator as state in parser!
@@ -15,6 +25,21 @@ use crate::{
 // #Insight
 // The syntax of the language is explicitly designed to _not_ require a lookahead buffer.
 
+// #Insight
+// A KeySymbol is a name with an optional namespace, e.g. `:status` or
+// `:http/status`. The namespace and the name are non-empty and don't contain
+// `:` or `/`.
+
+/// Returns true if `key` (without the leading `:`) is a well-formed KeySymbol.
+fn is_valid_key_symbol(key: &str) -> bool {
+    let is_valid_part = |part: &str| !part.is_empty() && !part.contains([':', '/']);
+
+    match key.split_once('/') {
+        Some((namespace, name)) => is_valid_part(namespace) && is_valid_part(name),
+        None => is_valid_part(key),
+    }
+}
+
 // #Insight
 // We move the tokens into the parser to simplify the code. The tokens are useless outside the parser.
 
@@ -160,6 +185,8 @@ where
 
         let start = range.start;
 
+        let is_number = matches!(t, Token::Number(..));
+
         let expr = match t {
             Token::Comment(s) => {
                 // Preserve the comments as expressions, may be useful for analysis passes (e.g. formatting)
@@ -168,20 +195,50 @@ where
             }
             // Token::Char(c) => Some(Expr::Char(c)),
             Token::String(s) => Some(Expr::String(s)),
+            Token::InterpolatedString(segments) => {
+                // "Hello ${name}!" -> (#format "Hello " name "!")
+                let mut terms = vec![Expr::symbol(FORMAT_SYMBOL).into()];
+
+                // #Insight
+                // The first argument of `format` is a template, the template is
+                // always a literal string, with any `%` escaped, so that the
+                // interpolated values are never interpreted as directives.
+                if let Some(StringSegment::Expr(..)) = segments.first() {
+                    terms.push(Expr::string("").into());
+                }
+
+                for (i, segment) in segments.into_iter().enumerate() {
+                    match segment {
+                        StringSegment::Text(s) if i == 0 => {
+                            terms.push(Expr::String(s.replace('%', "%%")).into())
+                        }
+                        StringSegment::Text(s) => terms.push(Expr::String(s).into()),
+                        StringSegment::Expr(source) => {
+                            if let Some(expr) = self.parse_interpolation(&source, &range) {
+                                terms.push(expr);
+                            }
+                        }
+                    }
+                }
+
+                Some(Expr::List(terms))
+            }
             Token::Symbol(s) => {
-                if s.starts_with(':') {
-                    let s = s.strip_prefix(':').unwrap();
-                    Some(Expr::KeySymbol(s.to_string()))
+                if let Some(key) = s.strip_prefix(':') {
+                    if is_valid_key_symbol(key) {
+                        Some(Expr::KeySymbol(key.to_string()))
+                    } else {
+                        self.push_error(Error::MalformedKeySymbol(s), &range);
+                        None
+                    }
                 } else if s == "true" {
-                    // #TODO consider using (True) for true 'literal'.
-                    // #TODO e.g. (let flag (True))
+                    // #Insight
+                    // `true` and `false` are canonicalized to Bool values, they
+                    // cannot be shadowed. `(True)` and `(False)` are equivalent
+                    // constructors.
                     // #TODO Bool = True + False = True | False = ~False | False
                     Some(Expr::Bool(true))
                 } else if s == "false" {
-                    // #TODO consider using False for false 'literal'.
-                    // #TODO consider having only true (and use something like nil for false)
-                    // #TODO consider using nil for false and everything else for true
-                    // #TODO consider using nothing/never for false and everything else for true.
                     Some(Expr::Bool(false))
                 } else {
                     Some(Expr::Symbol(s))
@@ -191,7 +248,19 @@ where
                 // #TODO more detailed Number error!
                 // #TODO error handling not enough, we need to add context, check error_stack
 
-                if s.contains('.') {
+                // #Insight
+                // The `n` suffix denotes a BigInt, the `d` suffix a Decimal, e.g.
+                // `123n`, `1.5d`. In hex literals `d` is a digit, not a suffix.
+
+                if let Some(digits) = s.strip_suffix('d').filter(|_| !s.starts_with("0x")) {
+                    match digits.parse::<Decimal>().map_err(Error::MalformedDecimal) {
+                        Ok(n) => Some(Expr::Decimal(n)),
+                        Err(error) => {
+                            self.push_error(error, &range);
+                            None
+                        }
+                    }
+                } else if s.contains('.') {
                     // #TODO support radix for non-integers?
                     // #TODO find a better name for 'non-integer'.
                     match s.parse::<f64>().map_err(Error::MalformedFloat) {
@@ -217,11 +286,22 @@ where
                         radix = 8
                     }
 
-                    match i64::from_str_radix(&s, radix).map_err(Error::MalformedInt) {
-                        Ok(n) => Some(Expr::Int(n)),
-                        Err(error) => {
-                            self.push_error(error, &range);
-                            None
+                    if let Some(digits) = s.strip_suffix('n') {
+                        match BigInt::from_str_radix(digits, radix).map_err(Error::MalformedBigInt)
+                        {
+                            Ok(n) => Some(Expr::BigInt(n)),
+                            Err(error) => {
+                                self.push_error(error, &range);
+                                None
+                            }
+                        }
+                    } else {
+                        match i64::from_str_radix(&s, radix).map_err(Error::MalformedInt) {
+                            Ok(n) => Some(Expr::Int(n)),
+                            Err(error) => {
+                                self.push_error(error, &range);
+                                None
+                            }
                         }
                     }
                 }
@@ -240,7 +320,7 @@ where
 
                 None
             }
-            Token::Quote => {
+            Token::Quote | Token::QuasiQuote | Token::Unquote => {
                 // #Insight we should allow consecutive quotes, emit a linter warning instead!
 
                 let Ok(quot_expr) = self.parse_expr() else {
@@ -257,9 +337,15 @@ where
                 };
 
                 // #TODO the actual quoting should be handled here?
-                // #TODO what about interpolation?
 
-                Some(Expr::List(vec![Expr::symbol("quot").into(), target]))
+                // 'x -> (quot x), `x -> (qquot x), $x -> (unquot x)
+                let op = match t {
+                    Token::Quote => "quot",
+                    Token::QuasiQuote => "qquot",
+                    _ => "unquot",
+                };
+
+                Some(Expr::List(vec![Expr::symbol(op).into(), target]))
             }
             Token::LeftParen => {
                 let terms = self.parse_many(Token::RightParen, start)?;
@@ -323,11 +409,18 @@ where
                 // Don't optimize to `Expr::Dict` here, leave the parser expr
                 // 'normalized as it is beneficial for some kinds of analysis.
 
-                // #TODO add error checking!
                 // #TODO optimize.
 
                 let exprs = self.parse_many(Token::RightBrace, start)?;
 
+                if exprs.len() % 2 != 0 {
+                    // The error refers to the key with the missing value.
+                    let key = exprs.last().unwrap();
+                    self.push_error(Error::MissingDictValue(key.to_string()), &key.get_range());
+                    // Parsing can continue.
+                    return Ok(None);
+                }
+
                 let mut items = vec![Ann::with_range(Expr::symbol("Dict"), range)];
 
                 for expr in exprs {
@@ -347,12 +440,59 @@ where
         match expr {
             Some(expr) => {
                 let range = start..self.index;
-                Ok(Some(self.attach_annotations(expr, range)))
+                let expr = self.attach_annotations(expr, range);
+                if is_number {
+                    Ok(Some(self.apply_number_type(expr)))
+                } else {
+                    Ok(Some(expr))
+                }
             }
             _ => Ok(None),
         }
     }
 
+    /// Converts a number literal to the type of its type annotation, e.g.
+    /// `#Float 1`, `#U8 255`, see `convert_number_literal`.
+    fn apply_number_type(&mut self, mut expr: Ann<Expr>) -> Ann<Expr> {
+        let Some(Expr::Symbol(type_name)) = expr.get_annotation("type").cloned() else {
+            return expr;
+        };
+
+        if !is_number_type(&type_name) {
+            return expr;
+        }
+
+        match convert_number_literal(&expr.0, &type_name) {
+            Ok(value) => expr.0 = value,
+            Err(error) => self.push_error(error, &expr.get_range()),
+        }
+
+        expr
+    }
+
+    // #TODO the ranges of the interpolated expression are relative to the source of the expression.
+    /// Parses the source of an expression interpolated in a string, the errors
+    /// are attributed to the range of the string.
+    fn parse_interpolation(&mut self, source: &str, range: &Range) -> Option<Ann<Expr>> {
+        let exprs = Lexer::new(source)
+            .lex()
+            .and_then(|tokens| Parser::new(tokens).parse());
+
+        match exprs {
+            Ok(mut exprs) if exprs.len() == 1 => exprs.pop(),
+            Ok(..) => {
+                self.push_error(Error::MalformedInterpolation(source.to_owned()), range);
+                None
+            }
+            Err(errors) => {
+                for Ranged(error, _) in errors {
+                    self.push_error(error, range);
+                }
+                None
+            }
+        }
+    }
+
     // #TODO rename to `parse_until`?
     pub fn parse_many(&mut self, delimiter: Token, start: usize) -> Result<Vec<Ann<Expr>>, Break> {
         let mut exprs = Vec::new();
@@ -397,12 +537,11 @@ where
                 break;
             };
 
+            // #Insight
+            // Parsing continues after recoverable errors, to report all the
+            // errors in the input at once.
             if let Some(expr) = expr {
-                if self.errors.is_empty() {
-                    exprs.push(expr);
-                } else {
-                    break;
-                }
+                exprs.push(expr);
             }
         }
 
@@ -4,6 +4,10 @@ use std::fmt;
 // of line,col for error messages and/or LSP, and to allow to index the buffer by line
 // (if we keep the buffer as array of lines)
 
+// #Insight
+// The ranges are char offsets (not byte offsets) in the source, the positions
+// are computed on demand from the source.
+
 // #TODO use Annotated instead of Ranged.
 // #TODO add support for Set operations for ranges.
 // #TODO if/when we convert this to a range of positions, we should consider renaming to Span.
@@ -35,13 +39,27 @@ impl<T> Ranged<T> {
     }
 }
 
+impl<T> Ranged<T> {
+    /// Returns the position of the start of the range, in the `input` source.
+    pub fn start_position(&self, input: &str) -> Position {
+        Position::from(self.1.start, input)
+    }
+
+    /// Returns the position of the end of the range, in the `input` source.
+    pub fn end_position(&self, input: &str) -> Position {
+        Position::from(self.1.end, input)
+    }
+}
+
 impl<T> AsRef<T> for Ranged<T> {
     fn as_ref(&self) -> &T {
         &self.0
     }
 }
 
-/// A position within a text document.
+/// A position within a text document. The line and the column are zero-based,
+/// like in LSP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     pub line: usize,
     pub col: usize,
@@ -49,7 +67,7 @@ pub struct Position {
 
 impl Position {
     // #TODO seems this conversion is needed too often, maybe should keep line,col info in range?
-    // #TODO add unit test.
+    /// Computes the position of the char `index` in the `input` source.
     pub fn from(index: usize, input: &str) -> Self {
         let chars = input.chars();
 
@@ -75,3 +93,31 @@ impl Position {
         Self { line, col }
     }
 }
+
+impl fmt::Display for Position {
+    /// Formats the position as one-based `line:col`, the convention of
+    /// compiler diagnostics.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line + 1, self.col + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range::{Position, Ranged};
+
+    #[test]
+    fn position_from_computes_line_and_col() {
+        let input = "(let a 1)\n(let b\n  (+ a 2))";
+
+        assert_eq!(Position::from(0, input), Position { line: 0, col: 0 });
+        assert_eq!(Position::from(5, input), Position { line: 0, col: 5 });
+        assert_eq!(Position::from(9, input), Position { line: 0, col: 9 });
+        assert_eq!(Position::from(10, input), Position { line: 1, col: 0 });
+        assert_eq!(Position::from(19, input), Position { line: 2, col: 2 });
+
+        let ranged = Ranged((), 19..26);
+        assert_eq!(ranged.start_position(input).to_string(), "3:3");
+        assert_eq!(ranged.end_position(input).to_string(), "3:10");
+    }
+}
@@ -1,12 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::{
     ann::Ann,
-    error::Error,
-    eval::{env::Env, eval},
-    expr::Expr,
+    error::{Error, Warning},
+    eval::{bind_pattern, check_pattern, env::Env, eval},
+    expr::{
+        number::{sized_int_range, widen_int_type},
+        Expr,
+    },
     range::Ranged,
-    util::is_reserved_symbol,
+    util::{is_mutating_symbol, is_reserved_symbol},
 };
 
 // #TODO rename file to `sema`?
@@ -16,31 +19,139 @@ use crate::{
 
 // #Insight resolve_type and resolve_invocable should be combined, cannot be separate passes.
 
+/// Returns true if the type is the bottom type Never.
+fn is_never_type(type_expr: &Expr) -> bool {
+    matches!(type_expr, Expr::Symbol(s) if s == "Never")
+}
+
+// #TODO implement a proper type join (least upper bound).
+
+/// Computes the type of a resolved `if` expression. Never is the bottom type,
+/// a diverging branch does not contribute to the type. An `if` without an else
+/// clause has the Unit type.
+fn if_type(list: &Ann<Expr>) -> Expr {
+    let Ann(Expr::List(terms), ..) = list else {
+        return Expr::One;
+    };
+
+    let (true_type, false_type) = match &terms[..] {
+        [_, _, true_clause] => (true_clause.get_type(), &Expr::symbol("Unit")),
+        [_, _, true_clause, false_clause] => (true_clause.get_type(), false_clause.get_type()),
+        _ => return Expr::One,
+    };
+
+    if is_never_type(true_type) {
+        false_type.clone()
+    } else if is_never_type(false_type) || true_type.to_string() == false_type.to_string() {
+        true_type.clone()
+    } else {
+        // #TODO introduce union types.
+        Expr::One
+    }
+}
+
+// #Insight
+// The types of the top-level bindings are recorded in a type environment. The
+// type environment can be kept across resolutions, e.g. in a REPL, so that an
+// input is resolved against the types of the previous inputs, see `Runtime`.
+
+// #TODO the type environment does not track the nested (lexical) scopes.
+
+/// The types of the bindings, by name.
+pub type TypeEnv = HashMap<String, Expr>;
+
 pub struct Resolver {
     errors: Vec<Ranged<Error>>,
+    warnings: Vec<Ranged<Warning>>,
+    types: TypeEnv,
 }
 
 impl Resolver {
     pub fn new() -> Self {
-        Self { errors: Vec::new() }
+        Self::with_types(TypeEnv::new())
+    }
+
+    /// Creates a resolver that continues from the given type environment.
+    pub fn with_types(types: TypeEnv) -> Self {
+        Self {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            types,
+        }
+    }
+
+    /// Returns the type environment, the types of the resolved bindings.
+    pub fn types(&self) -> &TypeEnv {
+        &self.types
+    }
+
+    pub fn into_types(self) -> TypeEnv {
+        self.types
     }
 
     fn push_error(&mut self, error: Ranged<Error>) {
         self.errors.push(error);
     }
 
+    /// Returns the warnings reported so far, and clears them.
+    pub fn take_warnings(&mut self) -> Vec<Ranged<Warning>> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Reports the expressions of a `do` block that follow a diverging
+    /// expression, one warning for the unreachable expressions. The ranges
+    /// come from the unresolved terms, a resolved list has the range of its
+    /// head.
+    fn check_unreachable(&mut self, terms: &[Ann<Expr>], resolved_terms: &[Ann<Expr>]) {
+        let Some(i) = resolved_terms
+            .iter()
+            .position(|term| is_never_type(term.get_type()))
+        else {
+            return;
+        };
+
+        if let (Some(first), Some(last)) = (terms.get(i + 1), terms.last()) {
+            let range = first.get_range().start..last.get_range().end;
+            self.warnings.push(Ranged(Warning::UnreachableCode, range));
+        }
+    }
+
     pub fn resolve_expr(&mut self, mut expr: Ann<Expr>, env: &mut Env) -> Ann<Expr> {
         // #TODO update the original annotations!
         // #TODO need to handle _all_ Expr variants.
         match expr {
+            Ann(Expr::One, _) => {
+                expr.set_type(Expr::symbol("Unit"));
+                expr
+            }
+            Ann(Expr::Never, _) => {
+                expr.set_type(Expr::symbol("Never"));
+                expr
+            }
+            Ann(Expr::Bool(_), _) => {
+                expr.set_type(Expr::symbol("Bool"));
+                expr
+            }
             Ann(Expr::Int(_), _) => {
-                expr.set_type(Expr::symbol("Int"));
+                // A sized integer literal keeps its type, e.g. `#U8 255`.
+                if !matches!(expr.get_type(), Expr::Symbol(type_name) if sized_int_range(type_name).is_some())
+                {
+                    expr.set_type(Expr::symbol("Int"));
+                }
                 expr
             }
             Ann(Expr::Float(_), _) => {
                 expr.set_type(Expr::symbol("Float"));
                 expr
             }
+            Ann(Expr::BigInt(_), _) => {
+                expr.set_type(Expr::symbol("BigInt"));
+                expr
+            }
+            Ann(Expr::Decimal(_), _) => {
+                expr.set_type(Expr::symbol("Decimal"));
+                expr
+            }
             Ann(Expr::String(_), _) => {
                 expr.set_type(Expr::symbol("String"));
                 expr
@@ -49,11 +160,54 @@ impl Resolver {
                 expr.set_type(Expr::symbol("KeySymbol"));
                 expr
             }
+            Ann(Expr::Foreign(ref value), _) => {
+                let type_name = value.type_name().to_owned();
+                expr.set_type(Expr::Symbol(type_name));
+                expr
+            }
+            Ann(Expr::Range(..), _) => {
+                expr.set_type(Expr::symbol("Range"));
+                expr
+            }
+            Ann(Expr::Buffer(..), _) => {
+                expr.set_type(Expr::symbol("Buffer"));
+                expr
+            }
+            Ann(Expr::Maybe(..), _) => {
+                expr.set_type(Expr::symbol("Maybe"));
+                expr
+            }
+            Ann(Expr::Error(..), _) => {
+                expr.set_type(Expr::symbol("Error"));
+                expr
+            }
+            Ann(Expr::Module(..), _) => {
+                expr.set_type(Expr::symbol("Module"));
+                expr
+            }
+            Ann(Expr::Iterator(..), _) => {
+                expr.set_type(Expr::symbol("Iterator"));
+                expr
+            }
             // #TODO hmm... ultra-hack.
-            Ann(Expr::Array(..), _) => {
+            Ann(Expr::Array(items), ann) => {
+                let items = items
+                    .into_iter()
+                    .map(|item| self.resolve_expr(item, env))
+                    .collect();
+                let mut expr = Ann(Expr::Array(items), ann);
                 expr.set_type(Expr::symbol("Array"));
                 expr
             }
+            Ann(Expr::Dict(dict), ann) => {
+                let dict = dict
+                    .into_iter()
+                    .map(|(key, value)| (key, self.resolve_expr(value, env)))
+                    .collect();
+                let mut expr = Ann(Expr::Dict(dict), ann);
+                expr.set_type(Expr::symbol("Dict"));
+                expr
+            }
             Ann(Expr::Symbol(ref sym), _) => {
                 if is_reserved_symbol(sym) {
                     expr.set_type(Expr::symbol("Symbol"));
@@ -66,14 +220,18 @@ impl Resolver {
                 // #TODO please note that multiple-dispatch is supposed to be dynamic!
 
                 let result = if let Some(Expr::Symbol(method)) = expr.get_annotation("method") {
-                    env.get(method)
+                    // #TODO ultra-hack, if the method is not found, fall-back to the function symbol, like eval.
+                    env.get(method).or_else(|| env.get(sym))
                 } else {
                     // #TODO ultra-hack just fall-back to 'function' name if method does not exist.
                     env.get(sym)
                 };
 
                 let Some(value) = result else {
-                    expr.set_type(Expr::symbol("Symbol"));
+                    // The binding may not be evaluated, e.g. its value is
+                    // computed with side-effects, use the recorded type.
+                    let type_expr = self.types.get(sym).cloned();
+                    expr.set_type(type_expr.unwrap_or_else(|| Expr::symbol("Symbol")));
                     return expr;
                 };
 
@@ -100,6 +258,12 @@ impl Resolver {
                 // #TODO handle non-symbol cases!
                 // #TODO signature should be the type, e.g. +::(Func Int Int Int) instead of +$$Int$$Int
                 if let Ann(Expr::Symbol(ref sym), _) = head {
+                    // The arguments of a host form are not resolved, the form
+                    // receives the unevaluated expressions.
+                    if env.context.host_forms.contains_key(sym) {
+                        return expr;
+                    }
+
                     // #TODO special handling of def
                     if sym == "let" {
                         // #TODO also report some of these errors statically, maybe in a sema phase?
@@ -108,29 +272,16 @@ impl Resolver {
                         let mut resolved_let_list = vec![Ann::new(Expr::symbol("let"))];
                         let mut ann = None;
 
-                        loop {
-                            let Some(sym) = args.next() else {
-                                break;
-                            };
-
+                        while let Some(sym) = args.next() {
                             let Some(value) = args.next() else {
                                 // #TODO error?
                                 break;
                             };
 
-                            let Ann(Expr::Symbol(s), ..) = sym else {
-                                self.push_error(Ranged(Error::invalid_arguments(format!("`{sym}` is not a Symbol")), sym.get_range()));
-                                // Continue to detect more errors.
-                                continue;
-                            };
-
-                            if is_reserved_symbol(s) {
-                                self.push_error(Ranged(
-                                    Error::invalid_arguments(format!(
-                                        "let cannot shadow the reserved symbol `{s}`"
-                                    )),
-                                    sym.get_range(),
-                                ));
+                            // The binding is either a symbol, or a list of symbols
+                            // that destructures multiple values.
+                            if let Err(error) = check_pattern(sym) {
+                                self.push_error(error);
                                 // Continue to detect more errors.
                                 continue;
                             }
@@ -140,6 +291,13 @@ impl Resolver {
                             map.insert("type".to_owned(), value.get_type().clone());
                             ann = Some(map);
 
+                            if let Ann(Expr::Symbol(name), ..) = sym {
+                                // The unknown type `()` is not recorded.
+                                if !matches!(value.get_type(), Expr::One) {
+                                    self.types.insert(name.clone(), value.get_type().clone());
+                                }
+                            }
+
                             resolved_let_list.push(sym.clone());
                             resolved_let_list.push(value.clone());
 
@@ -161,7 +319,9 @@ impl Resolver {
                             };
 
                             // #TODO notify about overrides? use `set`?
-                            env.insert(s, value);
+                            if let Err(error) = bind_pattern(sym, value, env) {
+                                self.push_error(error);
+                            }
                         }
 
                         Ann(Expr::List(resolved_let_list), ann)
@@ -183,12 +343,25 @@ impl Resolver {
                                     signature.push(term.to_type_string())
                                 }
 
-                                let signature = signature.join("$$");
+                                let mut method = format!("{sym}$${}", signature.join("$$"));
+
+                                // The sized integers are Ints at runtime, they
+                                // fall back to the Int specializations, e.g.
+                                // `(+ #U8 1 #U8 2)` to `+$$Int$$Int`.
+                                if env.get(&method).is_none() {
+                                    let widened: Vec<&str> = signature
+                                        .iter()
+                                        .map(|type_name| widen_int_type(type_name))
+                                        .collect();
+                                    let widened = format!("{sym}$${}", widened.join("$$"));
+                                    if env.get(&widened).is_some() {
+                                        method = widened;
+                                    }
+                                }
 
-                                ann_sym.get_or_insert(HashMap::new()).insert(
-                                    "method".to_owned(),
-                                    Expr::Symbol(format!("{sym}$${signature}")),
-                                );
+                                ann_sym
+                                    .get_or_insert(BTreeMap::new())
+                                    .insert("method".to_owned(), Expr::Symbol(method));
                             };
 
                             Ann(Expr::Symbol(sym.clone()), ann_sym)
@@ -199,10 +372,54 @@ impl Resolver {
                         // #Insight head should get resolved after the tail.
                         let head = self.resolve_expr(head, env);
 
+                        let mut diverges = false;
+
+                        if sym == "do" {
+                            diverges = resolved_tail
+                                .iter()
+                                .any(|term| is_never_type(term.get_type()));
+                            self.check_unreachable(tail, &resolved_tail);
+                        }
+
                         let mut list = vec![head.clone()];
                         list.extend(resolved_tail);
 
-                        Ann(Expr::List(list), head.1)
+                        let mut list = Ann(Expr::List(list), head.1);
+
+                        // Preserve the annotations of the list expression, e.g. `#curry`.
+                        if let Some(ref ann) = expr.1 {
+                            for (name, value) in ann {
+                                if !list.contains_annotation(name) {
+                                    list.set_annotation(name, value.clone());
+                                }
+                            }
+                        }
+
+                        if sym == "if" {
+                            list.set_type(if_type(&list));
+                        }
+
+                        if sym == "and" || sym == "or" {
+                            list.set_type(Expr::symbol("Bool"));
+                        }
+
+                        // The control-flow forms diverge, like `exit`.
+                        if sym == "break" || sym == "continue" {
+                            list.set_type(Expr::symbol("Never"));
+                        }
+
+                        // A block that contains a diverging expression
+                        // diverges.
+                        if diverges {
+                            list.set_type(Expr::symbol("Never"));
+                        }
+
+                        // #TODO encode effects in the type-system.
+                        if is_mutating_symbol(sym) {
+                            list.set_annotation("effect", Expr::symbol("Mutation"));
+                        }
+
+                        list
                     }
                 } else {
                     // #TODO handle map lookup case.
@@ -238,7 +455,12 @@ impl Default for Resolver {
 
 #[cfg(test)]
 mod tests {
-    use crate::{api::parse_string, eval::env::Env, resolver::Resolver};
+    use crate::{
+        api::parse_string,
+        eval::env::Env,
+        expr::Expr,
+        resolver::{Resolver, TypeEnv},
+    };
 
     #[test]
     fn resolve_specializes_functions() {
@@ -252,4 +474,105 @@ mod tests {
         let expr = resolver.resolve(expr, &mut env).unwrap();
         dbg!(&expr);
     }
+
+    #[test]
+    fn resolve_annotates_mutation_effect() {
+        let expr = parse_string("(push! arr 4)").unwrap();
+        let mut resolver = Resolver::new();
+        let mut env = Env::prelude();
+        let expr = resolver.resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_annotation("effect"), Some(Expr::Symbol(s)) if s == "Mutation"));
+    }
+
+    #[test]
+    fn resolve_annotates_bool_type() {
+        let expr = parse_string("true").unwrap();
+        let mut resolver = Resolver::new();
+        let mut env = Env::prelude();
+        let expr = resolver.resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Bool"));
+    }
+
+    #[test]
+    fn resolve_specializes_big_int_and_decimal_operators() {
+        let mut env = Env::prelude();
+
+        let expr = parse_string("(+ 1n (* 2n 3n))").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "BigInt"));
+
+        let expr = parse_string("(- 1.5d (/ 1d 4d))").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Decimal"));
+    }
+
+    #[test]
+    fn resolve_treats_never_as_the_bottom_type() {
+        let mut env = Env::prelude();
+
+        let expr = parse_string("(exit 1)").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Never"));
+
+        let expr = parse_string("(if true (exit 1) 2)").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Int"));
+
+        let expr = parse_string("(if true 1)").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::One));
+
+        let expr = parse_string("(if true () (exit 1))").unwrap();
+        let expr = Resolver::new().resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Unit"));
+    }
+
+    #[test]
+    fn resolve_uses_the_recorded_types() {
+        let mut env = Env::prelude();
+
+        let mut types = TypeEnv::new();
+        types.insert("visits".to_owned(), Expr::symbol("Int"));
+
+        let expr = parse_string("visits").unwrap();
+        let expr = Resolver::with_types(types).resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Int"));
+
+        let expr = parse_string("(let name \"George\")").unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve(expr, &mut env).unwrap();
+        assert!(matches!(resolver.types().get("name"), Some(Expr::Symbol(s)) if s == "String"));
+    }
+
+    #[test]
+    fn resolve_warns_about_unreachable_code() {
+        let mut env = Env::prelude();
+
+        let input = "(do (writeln 1) (exit 1) (writeln 2) 3)";
+        let expr = parse_string(input).unwrap();
+        let mut resolver = Resolver::new();
+        let expr = resolver.resolve(expr, &mut env).unwrap();
+        assert!(matches!(expr.get_type(), Expr::Symbol(s) if s == "Never"));
+
+        let warnings = resolver.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(&input[warnings[0].1.clone()], "(writeln 2) 3");
+
+        // The diverging expressions propagate through nested blocks.
+        for input in [
+            "(do (do (throw 1)) 2)",
+            "(while true (do (break) 1))",
+            "(Func (x) (do (continue) x))",
+        ] {
+            let expr = parse_string(input).unwrap();
+            let mut resolver = Resolver::new();
+            resolver.resolve(expr, &mut env).unwrap();
+            assert_eq!(resolver.take_warnings().len(), 1, "`{input}`");
+        }
+
+        let expr = parse_string("(do (writeln 1) (exit 1))").unwrap();
+        let mut resolver = Resolver::new();
+        resolver.resolve(expr, &mut env).unwrap();
+        assert!(resolver.take_warnings().is_empty());
+    }
 }
@@ -0,0 +1,452 @@
+use std::{
+    fmt,
+    io::{self, Write},
+    rc::Rc,
+    sync::mpsc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ann::Ann,
+    api::{
+        eval_string,
+        foreign::{register_fn, register_form, register_host_fn, FnSignature, HostFn},
+        resolve_string_with_types,
+        signature::{signature_help_with, SignatureHelp},
+    },
+    error::{Error, PipelineError},
+    eval::{
+        cancellation::CancellationToken,
+        capabilities::Capabilities,
+        context::Context,
+        env::Env,
+        eval,
+        features::{Features, LanguageVersion},
+        limits::Limits,
+        module_cache::AstCache,
+        output::Output,
+        prelude::PreludeOptions,
+    },
+    expr::{portable::Portable, Expr},
+    range::Ranged,
+    resolver::TypeEnv,
+};
+
+// #Insight
+// An isolate is a script evaluated on its own thread, in its own Env, with its
+// own limits. The isolates share nothing, the host and the isolate communicate
+// with messages, copies of plain data values, see `Portable`. In the script,
+// the messages are exchanged with `(send value)` and `(recv)`.
+
+// #TODO support memory limits per isolate.
+// #TODO support messages between isolates.
+
+/// The message channels of an isolate, available to the script through the
+/// Env.
+#[derive(Debug)]
+pub struct Mailbox {
+    pub inbox: mpsc::Receiver<Portable>,
+    pub outbox: mpsc::Sender<Portable>,
+}
+
+/// An error of an isolate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IsolateError {
+    /// The evaluation of the script failed, the error messages.
+    Eval(Vec<String>),
+    /// The isolate thread panicked.
+    Panicked,
+    /// The isolate is finished, it does not receive messages.
+    Disconnected,
+    /// The message is not plain data, e.g. a function.
+    NotPortable(String),
+}
+
+impl std::error::Error for IsolateError {}
+
+impl fmt::Display for IsolateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsolateError::Eval(errors) => write!(f, "isolate failed: {}", errors.join(", ")),
+            IsolateError::Panicked => write!(f, "isolate panicked"),
+            IsolateError::Disconnected => write!(f, "isolate disconnected"),
+            IsolateError::NotPortable(message) => {
+                write!(f, "the message `{message}` is not plain data")
+            }
+        }
+    }
+}
+
+/// A handle to a running isolate, used by the host to exchange messages with
+/// the isolate and to supervise it.
+pub struct Isolate {
+    outbox: mpsc::Sender<Portable>,
+    inbox: mpsc::Receiver<Portable>,
+    cancellation: CancellationToken,
+    handle: JoinHandle<Result<Portable, IsolateError>>,
+}
+
+impl Isolate {
+    /// Sends a message to the isolate.
+    pub fn send(&self, message: &Ann<Expr>) -> Result<(), IsolateError> {
+        let Some(message) = Portable::from_ann(message).filter(Portable::is_data) else {
+            return Err(IsolateError::NotPortable(message.to_string()));
+        };
+
+        self.outbox
+            .send(message)
+            .map_err(|_| IsolateError::Disconnected)
+    }
+
+    /// Receives a message from the isolate, blocks until a message is
+    /// available. Returns None if the isolate is finished.
+    pub fn recv(&self) -> Option<Ann<Expr>> {
+        self.inbox.recv().ok().map(|message| message.to_ann(&[]))
+    }
+
+    /// Receives a message from the isolate, waits up to `timeout`.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Ann<Expr>> {
+        self.inbox
+            .recv_timeout(timeout)
+            .ok()
+            .map(|message| message.to_ann(&[]))
+    }
+
+    /// Cancels the evaluation of the script, the isolate fails with
+    /// `Error::Interrupted`.
+    pub fn cancel(&self) {
+        self.cancellation.cancel();
+    }
+
+    /// Returns true if the evaluation of the script is finished.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the isolate to finish, returns the value of the script.
+    pub fn join(self) -> Result<Ann<Expr>, IsolateError> {
+        // Close the channel, an isolate waiting for messages is unblocked.
+        drop(self.outbox);
+
+        match self.handle.join() {
+            Ok(result) => result.map(|value| value.to_ann(&[])),
+            Err(..) => Err(IsolateError::Panicked),
+        }
+    }
+}
+
+// #Insight
+// The runtime keeps a main Env and its type environment across evaluations,
+// the inputs of an interactive session (REPL) are resolved against the
+// bindings and the types of the previous inputs.
+
+/// The Tan runtime, evaluates incremental inputs in a persistent Env and
+/// spawns isolates, with the configured limits.
+#[derive(Debug)]
+pub struct Runtime {
+    limits: Limits,
+    prelude_options: PreludeOptions,
+    env: Env,
+    types: TypeEnv,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Runtime {
+    pub fn new() -> Self {
+        Self {
+            limits: Limits::default(),
+            prelude_options: PreludeOptions::default(),
+            env: Env::prelude(),
+            types: TypeEnv::new(),
+        }
+    }
+
+    /// Configures the prelude of the main Env, replaces the main Env, i.e.
+    /// should be called before any other configuration.
+    pub fn with_prelude_options(mut self, options: &PreludeOptions) -> Self {
+        self.env = Env::prelude_with(options);
+        self.prelude_options = options.clone();
+        self
+    }
+
+    /// Limits the evaluation time of each input and each isolate.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.limits.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits the evaluation of each input and each isolate, e.g. the fuel
+    /// and the call depth. Replaces the configured timeout.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Restricts the language of the inputs and the isolates to the features
+    /// of the version.
+    pub fn with_language_version(self, version: LanguageVersion) -> Self {
+        self.with_features(version.features())
+    }
+
+    /// Restricts the language of the inputs and the isolates to the enabled
+    /// features, e.g. `Features::default().without(Feature::Macros)`.
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.env.context.features = features;
+        self
+    }
+
+    /// Caches the resolved modules in the host cache, e.g. a
+    /// `MemoryAstCache` shared between runtimes.
+    pub fn with_ast_cache(mut self, cache: Rc<dyn AstCache>) -> Self {
+        self.env.context.ast_cache = Some(cache);
+        self
+    }
+
+    /// Redirects the output of `write`/`writeln` of the inputs, e.g. to a
+    /// `SharedBuffer`.
+    pub fn with_stdout(mut self, writer: impl Write + 'static) -> Self {
+        self.env.context.stdout = Output::new(writer);
+        self
+    }
+
+    /// Redirects the diagnostic output of the inputs, e.g. the progress
+    /// reports.
+    pub fn with_stderr(mut self, writer: impl Write + 'static) -> Self {
+        self.env.context.stderr = Output::new(writer);
+        self
+    }
+
+    /// Cancels the evaluation of the inputs with the token, e.g. from another
+    /// thread. The isolates are cancelled individually, see `Isolate::cancel`.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.env.context.cancellation = Some(token);
+        self
+    }
+
+    /// Restricts the ops of the inputs and the isolates to the capabilities,
+    /// e.g. `Capabilities::none()` for a sandbox.
+    pub fn with_capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.env.context.capabilities = capabilities;
+        if !capabilities.env {
+            self.env.context.module_paths.clear();
+        }
+        self
+    }
+
+    /// Enables the `on-signal` handlers of the scripts. Installs the
+    /// process-wide handler of SIGINT and SIGTERM (Ctrl-C on Windows), which
+    /// replaces the default termination of the host process and cannot be
+    /// removed. Fails if another handler is already installed.
+    #[cfg(feature = "signal")]
+    pub fn with_signal_handling(self) -> Result<Self, Error> {
+        crate::ops::signal::enable_signal_handling()?;
+        Ok(self)
+    }
+
+    /// Returns the main Env of the runtime.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Env {
+        &mut self.env
+    }
+
+    /// Returns the runtime context of the main Env, e.g. to register the
+    /// handlers.
+    pub fn context(&self) -> &Context {
+        &self.env.context
+    }
+
+    pub fn context_mut(&mut self) -> &mut Context {
+        &mut self.env.context
+    }
+
+    /// Registers a foreign function in the main Env, e.g.
+    /// `runtime.register_fn("area", FnSignature::typed(&["Float", "Float"], "Float"), area)`.
+    /// See `api::register_fn`.
+    pub fn register_fn<F>(&mut self, name: &str, signature: FnSignature, f: F) -> &mut Self
+    where
+        F: Fn(&[Ann<Expr>], &mut Env) -> Result<Ann<Expr>, Ranged<Error>> + 'static,
+    {
+        register_fn(&mut self.env, name, signature, f);
+        self
+    }
+
+    /// Registers a host special form in the main Env, the form receives the
+    /// unevaluated arguments. See `api::register_form`.
+    pub fn register_form<F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: Fn(&[Ann<Expr>], &mut Env) -> Result<Ann<Expr>, Ranged<Error>> + 'static,
+    {
+        register_form(&mut self.env, name, f);
+        self
+    }
+
+    /// Registers a Rust function as a typed foreign function in the main Env,
+    /// e.g. `runtime.register_host_fn("add", |a: i64, b: i64| a + b)`. See
+    /// `api::register_host_fn`.
+    pub fn register_host_fn<Args, F>(&mut self, name: &str, f: F) -> &mut Self
+    where
+        F: HostFn<Args> + 'static,
+    {
+        register_host_fn(&mut self.env, name, f);
+        self
+    }
+
+    /// Evaluates the input in the main Env. The definitions and their types
+    /// are kept for the following inputs.
+    ///
+    /// A closure captures the scopes of its definition site, a closure bound
+    /// in a scope it captures is a reference cycle. The cycles of the main
+    /// scope are broken when the Runtime is dropped, the cycles of the local
+    /// closures, e.g. a recursive closure defined in a function, are leaked.
+    pub fn eval(&mut self, input: impl AsRef<str>) -> Result<Ann<Expr>, PipelineError> {
+        let previous_deadline = self.env.context.deadline;
+
+        // The fuel is reset for each input.
+        self.env.context.limits = self.limits.clone();
+        self.env.context.fuel_used = 0;
+
+        if let Some(timeout) = self.limits.timeout {
+            self.env.context.deadline = Some(Instant::now() + timeout);
+        }
+
+        let result = self.eval_input(input.as_ref());
+
+        self.env.context.deadline = previous_deadline;
+
+        result
+    }
+
+    fn eval_input(&mut self, input: &str) -> Result<Ann<Expr>, PipelineError> {
+        // The input is resolved against a copy of the types, the type of a
+        // definition is kept only if the definition is evaluated, a failed
+        // definition should not leave a stale type.
+        let mut types = self.types.clone();
+        let exprs = resolve_string_with_types(input, &mut self.env, &mut types)?;
+
+        let mut last_value = Expr::One.into();
+
+        for expr in exprs {
+            last_value =
+                eval(&expr, &mut self.env).map_err(|error| PipelineError::Eval(vec![error]))?;
+            self.record_types(&expr);
+        }
+
+        Ok(last_value)
+    }
+
+    /// Records the types of the names defined by an evaluated top-level `let`.
+    fn record_types(&mut self, expr: &Ann<Expr>) {
+        let Ann(Expr::List(list), ..) = expr else {
+            return;
+        };
+
+        let Some((Ann(Expr::Symbol(head), ..), tail)) = list.split_first() else {
+            return;
+        };
+
+        if head != "let" {
+            return;
+        }
+
+        for pair in tail.chunks(2) {
+            if let [Ann(Expr::Symbol(name), ..), value] = pair {
+                // The unknown type `()` is not recorded, like in the resolver.
+                if !matches!(value.get_type(), Expr::One) {
+                    self.types.insert(name.clone(), value.get_type().clone());
+                }
+            }
+        }
+    }
+
+    /// Returns the type of a binding defined in the main Env, e.g. `Int`.
+    /// Returns None if the binding is not defined or its type is unknown.
+    pub fn type_of(&self, name: &str) -> Option<Expr> {
+        self.types.get(name).cloned()
+    }
+
+    /// Returns the signature help of the call form at the cursor `offset` of
+    /// the source, against the definitions and the types of the main Env.
+    pub fn signature_help(&self, source: impl AsRef<str>, offset: usize) -> Option<SignatureHelp> {
+        signature_help_with(source.as_ref(), offset, &self.env, &self.types)
+    }
+
+    /// Evaluates the source in a new isolate. Returns a handle to exchange
+    /// messages with the isolate.
+    ///
+    /// The isolate inherits the prelude options, the limits, the features,
+    /// the capabilities and the module paths of the runtime. The output of an
+    /// isolate is discarded if the output of the runtime is redirected, the
+    /// host writers are not shared between threads. The AST cache, the host
+    /// functions and forms, the cancellation token and the bindings of the
+    /// main Env are not inherited.
+    pub fn spawn_isolate(&self, source: impl Into<String>) -> Isolate {
+        let source = source.into();
+        let limits = self.limits.clone();
+        let prelude_options = self.prelude_options.clone();
+        let cancellation = CancellationToken::new();
+        let isolate_cancellation = cancellation.clone();
+        let features = self.env.context.features.clone();
+        let capabilities = self.env.context.capabilities;
+        let module_paths = self.env.context.module_paths.clone();
+        let is_stdout_redirected = self.env.context.stdout.is_redirected();
+        let is_stderr_redirected = self.env.context.stderr.is_redirected();
+
+        let (outbox, isolate_inbox) = mpsc::channel();
+        let (isolate_outbox, inbox) = mpsc::channel();
+
+        let handle = thread::spawn(move || {
+            let mut env = Env::prelude_with(&prelude_options);
+            env.context.features = features;
+            env.context.capabilities = capabilities;
+            env.context.module_paths = module_paths;
+            if is_stdout_redirected {
+                env.context.stdout = Output::new(io::sink());
+            }
+            if is_stderr_redirected {
+                env.context.stderr = Output::new(io::sink());
+            }
+            env.context.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+            env.context.limits = limits;
+            env.context.cancellation = Some(isolate_cancellation);
+            env.context.mailbox = Some(Mailbox {
+                inbox: isolate_inbox,
+                outbox: isolate_outbox,
+            });
+
+            match eval_string(source, &mut env) {
+                Ok(value) => Portable::from_ann(&value)
+                    .filter(Portable::is_data)
+                    .ok_or_else(|| IsolateError::NotPortable(value.to_string())),
+                Err(errors) => Err(IsolateError::Eval(
+                    errors.iter().map(|error| error.to_string()).collect(),
+                )),
+            }
+        });
+
+        Isolate {
+            outbox,
+            inbox,
+            cancellation,
+            handle,
+        }
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // Clear the scopes, the closures bound in the scopes they capture are
+        // released, see `Runtime::eval`.
+        for scope in self.env.local.drain(..) {
+            scope.borrow_mut().clear();
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::io::IsTerminal;
+
+use crate::eval::output::Output;
+
+// #Insight
+// The styling layer is shared by the `style` op and the diagnostics renderer,
+// see `format_error_styled`. The styles are rendered with ANSI escape codes.
+
+// #TODO support 256 colors and RGB.
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    pub fn from_name(name: &str) -> Option<Self> {
+        let color = match name {
+            "black" => Color::Black,
+            "red" => Color::Red,
+            "green" => Color::Green,
+            "yellow" => Color::Yellow,
+            "blue" => Color::Blue,
+            "magenta" => Color::Magenta,
+            "cyan" => Color::Cyan,
+            "white" => Color::White,
+            _ => return None,
+        };
+
+        Some(color)
+    }
+
+    fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+/// A text style, the default style renders the text unchanged.
+#[derive(Debug, Default, Clone)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Self {
+        Self {
+            fg: Some(color),
+            ..Default::default()
+        }
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Wraps the text in the ANSI escape codes of the style, the text is
+    /// returned unchanged if `enabled` is false.
+    pub fn paint(&self, text: &str, enabled: bool) -> String {
+        let mut codes = Vec::new();
+
+        if self.bold {
+            codes.push("1".to_owned());
+        }
+        if self.dim {
+            codes.push("2".to_owned());
+        }
+        if self.italic {
+            codes.push("3".to_owned());
+        }
+        if self.underline {
+            codes.push("4".to_owned());
+        }
+        if let Some(fg) = self.fg {
+            codes.push((30 + fg.code()).to_string());
+        }
+        if let Some(bg) = self.bg {
+            codes.push((40 + bg.code()).to_string());
+        }
+
+        if !enabled || codes.is_empty() {
+            return text.to_owned();
+        }
+
+        format!("\x1b[{}m{text}\x1b[0m", codes.join(";"))
+    }
+}
+
+/// Returns true if the output should be colored, i.e. the output is a
+/// terminal and the `NO_COLOR` environment variable is not set, see
+/// https://no-color.org.
+pub fn colors_enabled(is_terminal: bool) -> bool {
+    is_terminal && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+}
+
+/// Returns true if the output to the stream should be colored, e.g. to the
+/// stdout stream of the Context.
+pub fn output_colors_enabled(output: &Output) -> bool {
+    colors_enabled(output.is_terminal())
+}
+
+/// Returns true if the output to stderr should be colored, e.g. for
+/// diagnostics.
+pub fn stderr_colors_enabled() -> bool {
+    colors_enabled(std::io::stderr().is_terminal())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Color, Style};
+
+    #[test]
+    fn paint_wraps_the_text_in_ansi_codes() {
+        let style = Style {
+            bg: Some(Color::White),
+            underline: true,
+            ..Style::fg(Color::Red).bold()
+        };
+
+        assert_eq!(style.paint("hi", true), "\x1b[1;4;31;47mhi\x1b[0m");
+        assert_eq!(style.paint("hi", false), "hi");
+        assert_eq!(Style::default().paint("hi", true), "hi");
+    }
+}
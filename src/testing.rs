@@ -0,0 +1,101 @@
+//! Testing support for Tan programs, e.g. golden (value) tests. Useful for
+//! Tan library authors.
+//!
+//! Enable the `testing` feature to use this module.
+
+use std::path::{Path, PathBuf};
+
+use crate::{api::eval_string, eval::env::Env, ops::diff::unified_diff};
+
+// #TODO support comparing the errors (e.g. `.error.tan` fixtures).
+// #TODO support custom environments.
+
+/// Evaluates the `source`, in a prelude environment, and formats the value.
+/// Panics if the evaluation fails.
+pub fn eval_to_string(source: impl AsRef<str>) -> String {
+    let source = source.as_ref();
+
+    let mut env = Env::prelude();
+
+    match eval_string(source, &mut env) {
+        Ok(value) => format!("{value}"),
+        Err(errors) => {
+            let errors = errors
+                .iter()
+                .map(|error| format!("{} at {:?}", error.0, error.1))
+                .collect::<Vec<String>>()
+                .join("\n");
+            panic!("failed to evaluate `{source}`:\n{errors}");
+        }
+    }
+}
+
+/// Asserts that the value of the `source` is formatted as the
+/// `expected_source`. Leading and trailing whitespace is ignored. On mismatch,
+/// panics with the diff of the expected and the actual value.
+pub fn assert_eval_eq(source: impl AsRef<str>, expected_source: impl AsRef<str>) {
+    let value = eval_to_string(source);
+
+    let (actual, expected) = (value.trim(), expected_source.as_ref().trim());
+
+    if actual != expected {
+        panic!(
+            "the value differs from the expected value:\n{}",
+            unified_diff(expected, actual, 3)
+        );
+    }
+}
+
+/// Returns the path of the value fixture of the `path` fixture, e.g.
+/// `sum.tan` -> `sum.value.tan`.
+pub fn value_fixture_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().with_extension("value.tan")
+}
+
+/// Reads a fixture file. Panics if the file cannot be read.
+pub fn read_fixture(path: impl AsRef<Path>) -> String {
+    let path = path.as_ref();
+
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|error| panic!("failed to read `{}`: {error}", path.display()))
+}
+
+/// Asserts that the value of the `path` fixture, e.g. `sum.tan`, is formatted
+/// as the contents of the corresponding value fixture, e.g. `sum.value.tan`.
+pub fn assert_fixture_eq(path: impl AsRef<Path>) {
+    let path = path.as_ref();
+
+    let source = read_fixture(path);
+    let expected_source = read_fixture(value_fixture_path(path));
+
+    assert_eval_eq(source, expected_source);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{assert_eval_eq, assert_fixture_eq, value_fixture_path};
+
+    #[test]
+    fn assert_eval_eq_compares_formatted_values() {
+        assert_eval_eq("(+ 1 2)", "3\n");
+        assert_eval_eq(r#"(List 1 "two" :three)"#, r#"(1 "two" :three)"#);
+    }
+
+    #[test]
+    #[should_panic(expected = "-4\n+3")]
+    fn assert_eval_eq_panics_on_mismatch() {
+        assert_eval_eq("(+ 1 2)", "4");
+    }
+
+    #[test]
+    fn value_fixture_path_derives_the_value_fixture() {
+        let path = value_fixture_path("tests/fixtures/sum.tan");
+        assert_eq!(path.to_str(), Some("tests/fixtures/sum.value.tan"));
+    }
+
+    #[test]
+    fn assert_fixture_eq_compares_with_value_fixtures() {
+        assert_fixture_eq("tests/fixtures/factorial.tan");
+        assert_fixture_eq("tests/fixtures/quasi_quote_macro.tan");
+    }
+}
@@ -5,6 +5,11 @@
 
 use std::fmt;
 
+/// The head of the desugared string interpolation, e.g. `"Hi ${name}"` ->
+/// `(#format "Hi " name)`. The symbol cannot be written in the source, `#`
+/// starts an annotation, so it cannot be shadowed by a binding.
+pub const FORMAT_SYMBOL: &str = "#format";
+
 /// Returns true if `sym` is reserved.
 pub fn is_reserved_symbol(sym: &str) -> bool {
     // #TODO think about `Func`.
@@ -13,13 +18,34 @@ pub fn is_reserved_symbol(sym: &str) -> bool {
         "do" | "ann"
             | "let"
             | "if"
+            | "cond"
+            | "and"
+            | "or"
+            | "?"
+            | "try"
+            | "case"
+            | "match"
             | "for"
+            | "while"
+            | "break"
+            | "continue"
             | "for_each"
             | "eval"
             | "quot"
+            | "qquot"
+            | "unquot"
             | "use" // #TODO consider `using`
+            | "export"
+            | "push!"
+            | "set!"
             | "Char"
+            | "Never"
+            | "True"
+            | "False"
+            | "true"
+            | "false"
             | "Func"
+            | "defn"
             | "Macro"
             | "List"
             | "Array"
@@ -27,6 +53,21 @@ pub fn is_reserved_symbol(sym: &str) -> bool {
     )
 }
 
+/// Returns true if `sym` is a special form that mutates a binding in-place,
+/// i.e. has the `Mutation` effect.
+pub fn is_mutating_symbol(sym: &str) -> bool {
+    matches!(sym, "push!" | "set!")
+}
+
+/// Truncates the text to `len` chars, an ellipsis marks the truncation.
+pub fn truncate_chars(text: String, len: usize) -> String {
+    if text.chars().count() <= len {
+        text
+    } else {
+        format!("{}...", text.chars().take(len).collect::<String>())
+    }
+}
+
 /// The`Break` is thrown when a pass processor cannot synchronize
 /// to continue processing to detect more errors. Processing is stopped immediately.
 /// Typically signals non-recoverable errors or end of input.
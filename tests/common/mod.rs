@@ -3,7 +3,7 @@
 use tan::{
     ann::Ann,
     api::{eval_string, parse_string},
-    error::Error,
+    error::{Error, PipelineError},
     eval::env::Env,
     expr::Expr,
     lexer::{token::Token, Lexer},
@@ -26,13 +26,13 @@ pub fn lex_file(filename: &str) -> Result<Vec<Ranged<Token>>, Vec<Ranged<Error>>
 }
 
 #[allow(dead_code)]
-pub fn parse_file(filename: &str) -> Result<Ann<Expr>, Vec<Ranged<Error>>> {
+pub fn parse_file(filename: &str) -> Result<Ann<Expr>, PipelineError> {
     let input = &read_file(filename);
     parse_string(input)
 }
 
 #[allow(dead_code)]
-pub fn eval_file(filename: &str) -> Result<Ann<Expr>, Vec<Ranged<Error>>> {
+pub fn eval_file(filename: &str) -> Result<Ann<Expr>, PipelineError> {
     let input = &read_file(filename);
     let mut env = Env::prelude();
     eval_string(input, &mut env)
@@ -0,0 +1,109 @@
+#![cfg(feature = "emit")]
+
+use tan::{
+    emit::{emit_string, js::JsEmitter},
+    error::{Error, PipelineError},
+    range::Ranged,
+};
+
+#[test]
+fn emit_js_literals() {
+    let js = emit_string(
+        r#"
+        -- The literals.
+        (let a 1 b -2.5 c "say \"hi\"\n" d true e :key)
+        (let f [1 2 3] g {:x 1 "y" [true false]})
+        "#,
+        &JsEmitter,
+    )
+    .unwrap();
+
+    assert_eq!(
+        js,
+        r#"// The literals.
+const a = 1; const b = -2.5; const c = "say \"hi\"\n"; const d = true; const e = "key";
+const f = [1, 2, 3]; const g = ({"x": 1, "y": [true, false]});
+"#
+    );
+}
+
+#[test]
+fn emit_js_functions_and_calls() {
+    let js = emit_string(
+        r#"
+        (let count-down (Func (n) (if (<= n 0) 0 (count-down (- n 1)))))
+        (let area (Func (w h) (do (let s (* w h)) (+ s 1))))
+        (let even? (Func (n) (= (% n 2) 0)))
+        (writeln (area 2 3) (even? 4) ((Func (x) (- x)) 5))
+        "#,
+        &JsEmitter,
+    )
+    .unwrap();
+
+    assert_eq!(
+        js,
+        r#"const count_down = ((n) => ((n <= 0) ? 0 : count_down((n - 1))));
+const area = ((w, h) => { const s = (w * h); return (s + 1); });
+const even$p = ((n) => ((n % 2) === 0));
+writeln(area(2, 3), even$p(4), ((x) => (-x))(5));
+"#
+    );
+}
+
+#[test]
+fn emit_js_blocks_in_expression_position() {
+    let js = emit_string(
+        "(let x (if (> 1 0) (do (let y 2) y) (let z 3)))",
+        &JsEmitter,
+    )
+    .unwrap();
+
+    assert_eq!(
+        js,
+        "const x = ((1 > 0) ? (() => { const y = 2; return y; })() : (() => { const z = 3; return z; })());\n"
+    );
+}
+
+#[test]
+fn emit_js_reports_unsupported_forms() {
+    let input = "(let x 1)\n(while true (writeln x))";
+    let result = emit_string(input, &JsEmitter);
+
+    let Err(PipelineError::Emit(errors)) = result else {
+        panic!("expected an emit error");
+    };
+
+    let Ranged(error, range) = &errors[0];
+    assert!(matches!(error, Error::Unsupported(form, _) if form == "while"));
+    assert_eq!(&input[range.clone()], "(while true (writeln x))");
+    assert_eq!(
+        error.to_string(),
+        "`while` is not supported by the JavaScript emitter"
+    );
+
+    let result = emit_string("(let (a b) [1 2])", &JsEmitter);
+    assert!(matches!(result, Err(PipelineError::Emit(..))));
+
+    let result = emit_string("(let f (Func ((0) 1) ((n) n)))", &JsEmitter);
+    let Err(PipelineError::Emit(errors)) = result else {
+        panic!("expected an emit error");
+    };
+    assert_eq!(
+        errors[0].0.to_string(),
+        "`multi-clause Func` is not supported by the JavaScript emitter"
+    );
+}
+
+#[test]
+fn emit_js_edge_cases() {
+    let js = emit_string(
+        r#"(let a (- -2) b (- (- 1)) f (Func () 1) t (and) u (or) s "n: ${a}")"#,
+        &JsEmitter,
+    )
+    .unwrap();
+
+    assert_eq!(
+        js,
+        "const a = (- -2); const b = (-(-1)); const f = (() => 1); const t = true; const u = false; const s = format(\"n: \", a);\n"
+    );
+}
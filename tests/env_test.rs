@@ -10,7 +10,7 @@ fn env_binds_names_to_values() {
     // dbg!(&expr);
 
     assert!(matches!(env.get("a"), Some(Ann(Expr::Symbol(sym), ..)) if sym == "hello"));
-    assert!(matches!(env.get("b"), None));
+    assert!(env.get("b").is_none());
 }
 
 #[test]
@@ -1,11 +1,30 @@
 mod common;
 
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
 use tan::{
     ann::Ann,
-    api::eval_string,
-    error::Error,
-    eval::{env::Env, eval},
-    expr::{format_value, Expr},
+    api::{
+        eval_string, eval_string_with_diagnostics, eval_string_with_stats, eval_with_limits,
+        eval_with_timeout, run_conformance, ConformanceOutcome,
+    },
+    error::{Error, PipelineError},
+    eval::{
+        audit::AuditReport,
+        cancellation::CancellationToken,
+        env::Env,
+        eval,
+        limits::{Limit, Limits},
+        output::{Output, SharedBuffer},
+        prelude::PreludeOptions,
+        replay::ReplayLog,
+        telemetry::OpEvent,
+    },
+    expr::{
+        foreign::{register_eq, register_hash, register_printer, ForeignValue},
+        format_value, Expr,
+    },
+    ops::diff::unified_diff,
     range::Ranged,
 };
 
@@ -269,3 +288,3306 @@ fn eval_processes_macros() {
 
     assert_eq!(value, expected_value);
 }
+
+#[test]
+fn let_destructures_multiple_values() {
+    let mut env = Env::prelude();
+    let result = eval_string(
+        "
+    (do
+        (let square_pair (Func (x) (List x (* x x))))
+        (let (a b) (square_pair 3))
+        (+ a b)
+    )",
+        &mut env,
+    );
+    assert!(result.is_ok());
+
+    let value = format!("{}", result.unwrap());
+    assert_eq!(value, "12");
+
+    let mut env = Env::prelude();
+    let value = eval_string("(let (a b) [1 2]) b", &mut env).unwrap();
+    assert!(matches!(value, Ann(Expr::Int(2), ..)));
+
+    let mut env = Env::prelude();
+    let result = eval_string("(let (a b c) [1 2]) a", &mut env);
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let err = &err[0];
+
+    assert!(
+        matches!(err, Ranged(Error::InvalidArguments(x), ..) if x == "expected 3 values, found 2")
+    );
+}
+
+#[test]
+fn let_returns_the_last_bound_value() {
+    let mut env = Env::prelude();
+    let value = eval_string("(let a 1 b (+ a 1))", &mut env).unwrap();
+    assert!(matches!(value, Ann(Expr::Int(2), ..)));
+}
+
+#[test]
+fn let_destructures_arrays_and_dicts() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let [a b] [1 2])
+        (let {:name name :score score} {:name "George" :score 98 :role "admin"})
+        (List a b name score)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), r#"(1 2 "George" 98)"#);
+
+    let result = eval_string(r#"(let {:age age} {:name "George"})"#, &mut env);
+    let err = result.unwrap_err();
+    assert!(
+        matches!(&err[0], Ranged(Error::InvalidArguments(x), ..) if x == "missing Dict key `age`")
+    );
+
+    let result = eval_string("(let {:name name} [1 2])", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_maybe_values() {
+    let mut env = Env::prelude();
+    eval_string(r#"(let user {:name "George" :nickname ()})"#, &mut env).unwrap();
+    eval_string("(let scores [98 100])", &mut env).unwrap();
+
+    let input = r#"
+    (List
+        (user :name)
+        (user :nickname)
+        (user :email)
+        (scores 1)
+        (scores 2)
+        (scores -1)
+        (is-some? (user :name))
+        (is-none? (user :email))
+        (unwrap (scores 0))
+        (unwrap-or (user :email) "none")
+        (? (user :name) else "anonymous")
+        (? (user :email) else "anonymous")
+        (Some 1)
+        None
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"((Some "George") (Some ()) None (Some 100) None None true true 98 "none" "George" "anonymous" (Some 1) None)"#
+    );
+
+    // The default of `?` is only evaluated for `None`.
+    let value = eval_string(r#"(? (user :name) else (exit 1))"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "George");
+
+    for input in [
+        "(unwrap (user :email))",
+        "(unwrap 1)",
+        "(? 1 else 2)",
+        "(? (user :email) 2)",
+        "(is-some? ())",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_catches_errors() {
+    let mut env = Env::prelude();
+
+    let input = r#"
+    (List
+        (try (/ 1 0) (catch err err))
+        (try (throw {:code 404}) (catch err (error/value err)))
+        (try (throw "not found") (catch err (error/message err)))
+        (try (+ 1 2) (catch err 0))
+        (try (try (throw 1) (catch err (throw err))) (catch err (error/value err)))
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"((Error "division by zero") {"code" 404} "not found" 3 1)"#
+    );
+
+    // The scopes of the failed evaluation are discarded.
+    let input = r#"
+    (do
+        (let x 1)
+        (try (do (let x 2) (throw x)) (catch err x))
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "1");
+
+    // The cleanup is always evaluated, the error is propagated without a
+    // `catch` clause.
+    let input = r#"
+    (do
+        (let #mut log [])
+        (let value (try (+ 1 2) (finally (push! log "first"))))
+        (try (try (throw "boom") (finally (push! log "second"))) (catch err ()))
+        (try (/ 1 0) (catch err (push! log "caught")) (finally (push! log "third")))
+        (while true (try (break 1) (finally (push! log "fourth"))))
+        [value log]
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"[3 ["first" "second" "caught" "third" "fourth"]]"#
+    );
+
+    let result = eval_string(r#"(try 1 (finally (throw "cleanup")))"#, &mut env);
+    assert!(result.is_err());
+
+    // The cleanup is skipped when the evaluation is aborted.
+    eval_string("(let #mut cleanups [])", &mut env).unwrap();
+    let input = "(try (while true 1) (finally (push! cleanups :cleanup)))";
+    let result = eval_with_limits(input, &mut env, &Limits::new().with_fuel(1000));
+    let Err(errors) = result else {
+        panic!("expected a limit error");
+    };
+    assert!(matches!(
+        errors[0].0,
+        Error::LimitExceeded(Limit::Fuel(1000))
+    ));
+    assert!(errors[0].1.start < input.find("(finally").unwrap());
+    let value = eval_string("cleanups", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[]");
+
+    // An uncaught thrown value is reported as the error message.
+    let result = eval_string(r#"(throw "boom")"#, &mut env);
+    let Err(errors) = result else {
+        panic!("expected an error");
+    };
+    assert_eq!(errors[0].0.to_string(), "boom");
+
+    // The control-flow signals are not caught.
+    let value = eval_string("(while true (try (break 1) (catch err 0)))", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "1");
+
+    for input in [
+        "(try 1)",
+        "(try 1 (handle err 2))",
+        "(try 1 (finally 2) (catch err 3))",
+        "(error/message 1)",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_maps_in_parallel() {
+    let mut env = Env::prelude();
+    eval_string("(let factor 3)", &mut env).unwrap();
+    eval_string("(let scale (Func (x) (* x factor)))", &mut env).unwrap();
+    eval_string(
+        "(let fib (Func (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))",
+        &mut env,
+    )
+    .unwrap();
+
+    let value = eval_string("(pmap (Func (x) (* x x)) [1 2 3 4 5])", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[1 4 9 16 25]");
+
+    // The functions and values the function refers to are captured.
+    let value = eval_string("(pmap (Func (x) (scale (+ x 1))) [1 2 3])", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[6 9 12]");
+
+    let value = eval_string("(pmap fib [10 15 20])", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[55 610 6765]");
+
+    let value = eval_string("(pmap (Func (x) x) [])", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[]");
+
+    // Functions with side-effects are evaluated sequentially.
+    let input = r#"
+    (do
+        (let #mut total 0)
+        (let values (pmap (Func (x) (do (set! total (+ total x)) total)) [1 2 3]))
+        [values total]
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[[1 3 6] 6]");
+
+    // The errors are reported, the error of the first failed item is caught.
+    let result = eval_string("(pmap (Func (x) (/ 1 x)) [1 0 2])", &mut env);
+    let Err(errors) = result else {
+        panic!("expected an error");
+    };
+    assert_eq!(errors[0].0.to_string(), "division by zero");
+
+    let value = eval_string(
+        "(try (pmap (Func (x) (throw x)) [1 2 3]) (catch err (error/value err)))",
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format!("{value}"), "1");
+
+    assert!(eval_string("(pmap (Func (x) x) 1)", &mut env).is_err());
+}
+
+#[test]
+fn eval_updates_agents() {
+    let mut env = Env::prelude();
+    eval_string("(let counter (agent 0))", &mut env).unwrap();
+    eval_string("(let step 2)", &mut env).unwrap();
+
+    let input = r#"
+    (do
+        (for i in (range 100) (send-update counter (Func (n) (+ n step))))
+        (await counter)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "200");
+
+    // An update with side-effects is applied on the calling thread, in order.
+    let input = r#"
+    (do
+        (let #mut calls 0)
+        (send-update counter (Func (n) (+ n 1)))
+        (send-update counter (Func (n) (do (set! calls (+ calls 1)) (* n 2))))
+        [(deref counter) calls]
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[402 1]");
+
+    // A failed update is reported, the agent keeps the last value.
+    eval_string(
+        "(send-update counter (Func (n) (throw \"boom\")))",
+        &mut env,
+    )
+    .unwrap();
+    let value = eval_string(
+        "(try (await counter) (catch err (error/value err)))",
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format!("{value}"), r#""boom""#);
+    assert_eq!(
+        format!("{}", eval_string("(deref counter)", &mut env).unwrap()),
+        "402"
+    );
+    assert!(eval_string("(send-update counter (Func (n) n))", &mut env).is_err());
+
+    for input in ["(agent (Func (x) x))", "(deref 1)", "(await [])"] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_await_honors_the_deadline() {
+    let mut env = Env::prelude();
+
+    // The agent is created without a deadline, the update is stuck.
+    eval_string(
+        "(let slow (agent 0)) (send-update slow (Func (n) (do (sleep 5000) n)))",
+        &mut env,
+    )
+    .unwrap();
+
+    let start = std::time::Instant::now();
+    let result = eval_with_timeout("(await slow)", &mut env, Duration::from_millis(50));
+
+    let Err(errors) = result else {
+        panic!("expected a timeout error");
+    };
+    assert!(matches!(errors[0].0, Error::TimedOut));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn eval_await_is_cancelled() {
+    let mut env = Env::prelude();
+
+    // The agent is created without a cancellation token, the update is stuck.
+    eval_string(
+        "(let slow (agent 0)) (send-update slow (Func (n) (do (sleep 5000) n)))",
+        &mut env,
+    )
+    .unwrap();
+
+    let token = CancellationToken::new();
+    env.context.cancellation = Some(token.clone());
+
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        token.cancel();
+    });
+
+    let start = std::time::Instant::now();
+    let result = eval_string("(await slow)", &mut env);
+    canceller.join().unwrap();
+
+    let Err(errors) = result else {
+        panic!("expected an interrupted error");
+    };
+    assert!(matches!(errors[0].0, Error::Interrupted));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+#[test]
+fn eval_uses_modules() {
+    let dir = std::env::temp_dir().join(format!("tan-modules-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("math")).unwrap();
+    std::fs::write(
+        dir.join("math/arithmetic.tan"),
+        r#"
+        (let factor 2)
+        (let square (Func (x) (* x x)))
+        (let double (Func (x) (* x factor)))
+        (export square double)
+        "#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("greet.tan"), r#"(let greeting "Hello")"#).unwrap();
+    std::fs::write(
+        dir.join("a.tan"),
+        format!(r#"(use "{}")"#, dir.join("b").display()),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("b.tan"),
+        format!(r#"(use "{}")"#, dir.join("a").display()),
+    )
+    .unwrap();
+
+    let mut env = Env::prelude();
+    let path = dir.display();
+
+    eval_string(format!(r#"(use "{path}/math")"#), &mut env).unwrap();
+    let value = eval_string("[(math/square 3) (math/double 4)]", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[9 8]");
+    assert_eq!(
+        format!("{}", eval_string("math", &mut env).unwrap()),
+        "#<module math>"
+    );
+
+    // The private definitions are not exported.
+    assert!(eval_string("math/factor", &mut env).is_err());
+    assert!(eval_string("factor", &mut env).is_err());
+
+    eval_string(format!(r#"(use "{path}/math" (square))"#), &mut env).unwrap();
+    let value = eval_string("(square 5)", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "25");
+    assert!(eval_string("double", &mut env).is_err());
+
+    // Without `export` declarations, all the definitions are exported.
+    eval_string(format!(r#"(use "{path}/greet" :as g)"#), &mut env).unwrap();
+    let value = eval_string("g/greeting", &mut env).unwrap();
+    assert_eq!(format_value(&value), "Hello");
+
+    let result = eval_string(format!(r#"(use "{path}/a")"#), &mut env);
+    let Err(errors) = result else {
+        panic!("expected an error");
+    };
+    assert!(errors[0].0.to_string().contains("circular dependency"));
+
+    for input in [
+        format!(r#"(use "{path}/missing")"#),
+        format!(r#"(use "{path}/math" (factor))"#),
+        format!(r#"(use "{path}/math" :as)"#),
+        "(export square)".to_owned(),
+    ] {
+        assert!(
+            eval_string(&input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn eval_resolves_modules_from_search_paths() {
+    let dir = std::env::temp_dir().join(format!("tan-module-paths-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    std::fs::write(dir.join("lib/math.tan"), "(let square (Func (x) (* x x)))").unwrap();
+    std::fs::write(
+        dir.join("lib/geometry.tan"),
+        "(use ./math) (let area (Func (side) (math/square side)))",
+    )
+    .unwrap();
+
+    let mut env = Env::prelude();
+    env.context.add_module_path(&dir);
+
+    eval_string("(use lib//math/)", &mut env).unwrap();
+    let value = eval_string("(math/square 3)", &mut env).unwrap();
+    assert_eq!(format_value(&value), "9");
+
+    // The modules are relative to the using module.
+    eval_string("(use lib/geometry)", &mut env).unwrap();
+    let value = eval_string("(geometry/area 4)", &mut env).unwrap();
+    assert_eq!(format_value(&value), "16");
+
+    // A module is loaded once.
+    let first = eval_string("(use lib/math)", &mut env).unwrap();
+    let second = eval_string("(use \"lib/math.tan\")", &mut env).unwrap();
+    let (Expr::Module(first), Expr::Module(second)) = (first.0, second.0) else {
+        panic!("expected modules");
+    };
+    assert!(std::rc::Rc::ptr_eq(&first, &second));
+    assert_eq!(env.context.modules.borrow().len(), 2);
+
+    assert!(eval_string("(use lib/missing)", &mut env).is_err());
+    assert!(eval_string("(use \"\")", &mut env).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn eval_reports_inlay_hints() {
+    use tan::api::{inlay_hints, InlayHintKind};
+
+    let input = r#"(let a 1 b "hi")
+(do (let c (+ a 2)) c)
+(let greet (Func (name) "hello"))
+(let (p q) [1 2])"#;
+
+    let mut env = Env::prelude();
+    let hints = inlay_hints(input, &mut env).unwrap();
+
+    let hints: Vec<_> = hints
+        .iter()
+        .map(|hint| {
+            (
+                &input[hint.range.clone()],
+                hint.type_name.as_str(),
+                hint.kind,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        hints,
+        vec![
+            ("a", "Int", InlayHintKind::Binding),
+            ("b", "String", InlayHintKind::Binding),
+            ("c", "Int", InlayHintKind::Binding),
+            ("(name)", "String", InlayHintKind::Return),
+        ]
+    );
+
+    // The definitions are applied to the Env.
+    assert_eq!(format_value(eval_string("a", &mut env).unwrap()), "1");
+
+    assert!(inlay_hints("(let a", &mut env).is_err());
+
+    // The effects of the source are not performed.
+    let path = std::env::temp_dir().join(format!("tan-hints-{}.txt", std::process::id()));
+    let input = format!(
+        r#"(let n 1) (let x (io/write-string "{}" "side effect")) (let m 2)"#,
+        path.display()
+    );
+    let hints = inlay_hints(&input, &mut env).unwrap();
+    assert_eq!(hints.len(), 2);
+    assert!(!path.exists());
+    assert!(env.context.audit.is_none());
+    assert!(env.context.capabilities.fs);
+}
+
+#[test]
+fn eval_checks_for_unreachable_code() {
+    use tan::{api::check_string, error::Warning};
+
+    let input = r#"
+    (let report (Func (x)
+        (do
+            (throw (Error "failed"))
+            (writeln x)
+        )
+    ))
+    (do (exit 0) (writeln "unreachable"))
+    "#;
+
+    let mut env = Env::prelude();
+    let warnings = check_string(input, &mut env).unwrap();
+
+    let warnings: Vec<_> = warnings
+        .iter()
+        .map(|warning| (&warning.0, &input[warning.1.clone()]))
+        .collect();
+
+    assert_eq!(
+        warnings,
+        vec![
+            (&Warning::UnreachableCode, "(writeln x)"),
+            (&Warning::UnreachableCode, r#"(writeln "unreachable")"#),
+        ]
+    );
+}
+
+#[test]
+fn eval_reads_and_writes_data() {
+    use tan::api::{read_value, write_value};
+
+    let input = r#"{:name "George \"G\" \${x}" :tags ["a" "b"] :score 1.0 :id 12n :price 1.5d :email None :manager (Some :alice) :initial (Char "G")}"#;
+
+    let value = read_value(input).unwrap();
+    let text = write_value(&value).unwrap();
+    assert_eq!(
+        text,
+        r#"{"email" None "id" 12n "initial" (Char "G") "manager" (Some :alice) "name" "George \"G\" \${x}" "price" 1.5d "score" 1.0 "tags" ["a" "b"]}"#
+    );
+    assert_eq!(read_value(&text).unwrap().to_string(), value.to_string());
+
+    // The data is not evaluated.
+    for input in ["(+ 1 2)", "x", "[1 (f)]", "1 2", ""] {
+        assert!(
+            read_value(input).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+
+    assert!(write_value(Expr::Float(f64::NAN)).is_err());
+
+    let mut env = Env::prelude();
+    let value = eval_string(
+        r#"(let config (expr/read (expr/write {:port 8080 :hosts ["a" "b"]}))) (config "hosts")"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format_value(value), r#"(Some ["a" "b"])"#);
+    assert!(eval_string(r#"(expr/read "(exit 1)")"#, &mut env).is_err());
+    assert!(eval_string("(expr/write (Func (x) x))", &mut env).is_err());
+}
+
+#[test]
+fn eval_processes_dict_updates() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let user {:name "George" :score 98})
+        (let updated (update (assoc user :role "admin") :score (Func (x) (+ x 1))))
+        (List (updated :role) (updated :score) (user :role) ((dissoc user :name) :name))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(
+        format!("{value}"),
+        r#"((Some "admin") (Some 99) None None)"#
+    );
+}
+
+#[test]
+fn eval_processes_array_updates() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let arr [1 2 3])
+        (List (put arr 1 9) (append arr 4) arr)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "([1 9 3] [1 2 3 4] [1 2 3])");
+
+    let result = eval_string("(put [1 2 3] 5 0)", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_nested_updates() {
+    let mut env = Env::prelude();
+    eval_string(
+        r#"(let config {:servers [{:host "a" :port 80} {:host "b" :port 81}]})"#,
+        &mut env,
+    )
+    .unwrap();
+
+    let cases = [
+        ("(get-in config [:servers 1 :port])", "(Some 81)"),
+        ("(get-in config [:servers 2 :port])", "None"),
+        ("(get-in config [:servers 0 :host :x])", "None"),
+        (
+            "(get-in config [])",
+            r#"(Some {"servers" [{"host" "a" "port" 80} {"host" "b" "port" 81}]})"#,
+        ),
+        (
+            "(assoc-in config [:servers 0 :port] 8080)",
+            r#"{"servers" [{"host" "a" "port" 8080} {"host" "b" "port" 81}]}"#,
+        ),
+        ("(assoc-in {} [:a :b] 1)", r#"{"a" {"b" 1}}"#),
+        (
+            "(update-in config [:servers 1 :port] (Func (x) (+ x 1)))",
+            r#"{"servers" [{"host" "a" "port" 80} {"host" "b" "port" 82}]}"#,
+        ),
+        (
+            "(update-in {} [:hits] (Func (x) (if (unit? x) 1 x)))",
+            r#"{"hits" 1}"#,
+        ),
+        // The original value is not mutated.
+        ("(get-in config [:servers 0 :port])", "(Some 80)"),
+    ];
+
+    for (input, expected) in cases {
+        let value = eval_string(input, &mut env).unwrap();
+        assert_eq!(format_value(value), expected, "for `{input}`");
+    }
+
+    for input in [
+        "(assoc-in config [:servers 5 :port] 1)",
+        "(assoc-in config [:servers 0 :port :x] 1)",
+        "(get-in config :servers)",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_walks_nested_values() {
+    let mut env = Env::prelude();
+
+    let cases = [
+        (
+            r#"(postwalk (Func (("secret") "***") ((x) x)) {:a ["secret" "b"] :c {:d "secret"}})"#,
+            r#"{"a" ["***" "b"] "c" {"d" "***"}}"#,
+        ),
+        // Bottom-up, the pairs are summed before the outer pair.
+        (
+            "(postwalk (Func (([a b]) (+ a b)) ((x) x)) [[1 2] [3 4]])",
+            "10",
+        ),
+        // Top-down, the items of the replaced Dict are walked.
+        (
+            r#"(walk (Func (({:password _}) {:password "***" :n 1}) ((1) 2) ((x) x)) [{:password "p"} 1])"#,
+            r#"[{"n" 2 "password" "***"} 2]"#,
+        ),
+        ("(walk (Func (x) x) 1)", "1"),
+    ];
+
+    for (input, expected) in cases {
+        let value = eval_string(input, &mut env).unwrap();
+        assert_eq!(format_value(value), expected, "for `{input}`");
+    }
+
+    assert!(eval_string("(walk (Func (x) (throw x)) [1])", &mut env).is_err());
+    assert!(eval_string("(postwalk [1])", &mut env).is_err());
+}
+
+#[test]
+fn func_captures_lexical_scope() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let make_adder (Func (x) (Func (y) (+ x y))))
+        (let add2 (make_adder 2))
+        (let x 100)
+        (add2 3)
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "5");
+}
+
+#[test]
+fn func_does_not_see_caller_bindings() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let get_z (Func (x) z))
+        (let call_with_z (Func (z) (get_z 1)))
+        (call_with_z 1)
+    )";
+    let result = eval_string(input, &mut env);
+
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let err = &err[0];
+
+    assert!(matches!(err, Ranged(Error::UndefinedSymbol(s), ..) if s == "z"));
+}
+
+#[test]
+fn func_recurses_through_captured_helpers() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let is_even (Func (n) (if (= n 0) true (is_odd (- n 1)))))
+        (let is_odd (Func (n) (if (= n 0) false (is_even (- n 1)))))
+        (is_even 10)
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert!(matches!(value, Ann(Expr::Bool(true), ..)));
+}
+
+#[test]
+fn eval_processes_in_place_mutation() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut arr [1 2 3])
+        (push! arr 4)
+        (set! arr 0 9)
+        (let #mut dict {:name "George"})
+        (set! dict :score 98)
+        (List arr (dict :score))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "([9 2 3 4] (Some 98))");
+}
+
+#[test]
+fn eval_reports_mutation_of_immutable_bindings() {
+    let mut env = Env::prelude();
+    let result = eval_string("(do (let arr [1 2 3]) (push! arr 4))", &mut env);
+
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let err = &err[0];
+
+    assert!(
+        matches!(err, Ranged(Error::InvalidArguments(x), ..) if x == "`arr` is not mutable, bind with `(let #mut arr ...)`")
+    );
+}
+
+#[test]
+fn eval_processes_variable_rebinding() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut count 0)
+        (let inc (Func () (set! count (+ count 1))))
+        (inc)
+        (inc)
+        (set! count (+ count 10))
+        count
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert!(matches!(value, Ann(Expr::Int(12), ..)));
+
+    let result = eval_string("(set! undefined-var 1)", &mut env);
+    let err = result.unwrap_err();
+    assert!(matches!(&err[0], Ranged(Error::UndefinedSymbol(s), ..) if s == "undefined-var"));
+
+    let result = eval_string("(do (let x 1) (set! x 2))", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_quasi_quoted_macros() {
+    let result = eval_file("quasi_quote_macro.tan");
+
+    assert!(result.is_ok());
+
+    let value = format!("{}", result.unwrap());
+    let expected_value = read_file("quasi_quote_macro.value.tan");
+
+    assert_eq!(value, expected_value);
+}
+
+#[test]
+fn macro_expand_reports_infinite_expansion() {
+    let mut env = Env::prelude();
+    let result = eval_string(
+        "(let forever (Macro (x) `(forever $x))) (forever 1)",
+        &mut env,
+    );
+
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let err = &err[0];
+
+    assert!(matches!(err, Ranged(Error::MacroExpansionLimit(s), ..) if s == "forever"));
+}
+
+#[test]
+fn eval_processes_pattern_clause_functions() {
+    let result = eval_file("factorial_clauses.tan");
+
+    assert!(result.is_ok());
+
+    let value = format!("{}", result.unwrap());
+    let expected_value = read_file("factorial_clauses.value.tan");
+
+    assert_eq!(value, expected_value);
+
+    let mut env = Env::prelude();
+    let result = eval_string("(let f (Func ((0) 1) ((1) 2))) (f 3)", &mut env);
+
+    assert!(result.is_err());
+
+    let err = result.unwrap_err();
+    let err = &err[0];
+
+    assert!(
+        matches!(err, Ranged(Error::InvalidArguments(x), ..) if x == "no function clause matches the arguments")
+    );
+}
+
+#[test]
+fn func_can_refer_to_itself() {
+    let mut env = Env::prelude();
+    let input = "((Func ((0) 1) ((n) (* n (self (- n 1))))) 5)";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "120");
+
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (defn sum_to ((0) 0) ((n) (+ n (sum_to (- n 1)))))
+        (defn double (x) (* x 2))
+        (double (sum_to 4))
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "20");
+}
+
+#[test]
+fn operators_are_first_class_values() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (defn apply2 (f a b) (f a b))
+        (let plus +)
+        (List (apply2 + 1 2) (apply2 + 1.5 2.0) (apply2 < 1 2) (apply2 * 1.5 2.0) (plus 3 4))
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "(3 3.5 true 3 7)");
+
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (defn scale (x factor) (* x factor))
+        (List (scale 2 3) (scale 0.5 3.0))
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "(6 1.5)");
+}
+
+#[test]
+fn curried_func_supports_partial_application() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let add3 #curry (Func (a b c) (+ a b c)))
+        (let add1 (add3 1))
+        (let add3_to_4 ((add3 1) 2))
+        (List (add1 2 3) (add3_to_4 5) (((add3 1) 1) 1) (add3 1 2 3))
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "(6 8 3 6)");
+
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        #curry (defn scale (factor x) (* factor x))
+        (let double (scale 2))
+        (double 21)
+    )";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "42");
+
+    let mut env = Env::prelude();
+    let result = eval_string("(let add2 (Func (a b) (+ a b))) (add2 1)", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_with_timeout_aborts_long_running_evaluation() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let fib (Func (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))))
+        (fib 25)
+    )";
+    let result = eval_with_timeout(input, &mut env, Duration::from_millis(10));
+
+    let Err(errors) = result else {
+        panic!("expected a timeout error");
+    };
+
+    assert!(matches!(errors[0].0, Error::TimedOut));
+    assert!(env.context.deadline.is_none());
+
+    let result = eval_with_timeout("(+ 1 2)", &mut env, Duration::from_secs(1));
+    assert_eq!(format!("{}", result.unwrap()), "3");
+}
+
+#[test]
+fn eval_with_limits_aborts_runaway_evaluation() {
+    let mut env = Env::prelude();
+
+    let limits = Limits::new().with_fuel(1000);
+    let result = eval_with_limits("(while true (+ 1 2))", &mut env, &limits);
+
+    let Err(errors) = result else {
+        panic!("expected a limit error");
+    };
+
+    let Ranged(error, range) = &errors[0];
+    assert!(matches!(error, Error::LimitExceeded(Limit::Fuel(1000))));
+    assert!(!range.is_empty());
+    assert_eq!(error.to_string(), "the fuel limit (1000 steps) is exceeded");
+
+    let limits = Limits::new().with_max_call_depth(10);
+    let input = "
+    (do
+        (let count-down (Func (n) (if (= n 0) 0 (count-down (- n 1)))))
+        (count-down 20)
+    )";
+    let result = eval_with_limits(input, &mut env, &limits);
+
+    let Err(errors) = result else {
+        panic!("expected a limit error");
+    };
+
+    // The error is attributed to the call-site.
+    let Ranged(error, range) = &errors[0];
+    assert!(matches!(error, Error::LimitExceeded(Limit::CallDepth(10))));
+    let call_site = input.find("(count-down (- n 1))").unwrap();
+    assert_eq!(range.start, call_site + 1);
+    assert_eq!(&input[range.clone()], "count-down");
+
+    // The loops with an atom body also consume the fuel.
+    for input in [
+        "(while true 1)",
+        "(for true 1)",
+        "(for_each (range 1000000) x 1)",
+    ] {
+        let result = eval_with_limits(input, &mut env, &Limits::new().with_fuel(1000));
+
+        let Err(errors) = result else {
+            panic!("expected a limit error for `{input}`");
+        };
+        assert!(matches!(
+            errors[0].0,
+            Error::LimitExceeded(Limit::Fuel(1000))
+        ));
+    }
+
+    // The exceeded limits are not catchable.
+    let limits = Limits::new().with_fuel(100);
+    let input = "(try (while true (+ 1 2)) (catch err 0))";
+    let result = eval_with_limits(input, &mut env, &limits);
+    assert!(result.is_err());
+
+    // The limits are restored, the fuel is reset.
+    assert_eq!(env.context.limits, Limits::default());
+    let result = eval_with_limits("(+ 1 2)", &mut env, &Limits::new().with_fuel(100));
+    assert_eq!(format!("{}", result.unwrap()), "3");
+}
+
+#[test]
+fn eval_is_cancelled_from_another_thread() {
+    let mut env = Env::prelude();
+
+    let token = CancellationToken::new();
+    env.context.cancellation = Some(token.clone());
+
+    // The interrupt is not catchable, the blocking ops are also cancelled.
+    for input in [
+        "(while true (+ 1 2))",
+        "(try (while true (+ 1 2)) (catch err 0))",
+        "(sleep 60000)",
+    ] {
+        token.reset();
+
+        let canceller = {
+            let token = token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                token.cancel();
+            })
+        };
+
+        let result = eval_string(input, &mut env);
+        canceller.join().unwrap();
+
+        let Err(errors) = result else {
+            panic!("expected an interrupted error");
+        };
+
+        assert!(matches!(errors[0].0, Error::Interrupted));
+    }
+
+    // The token stays cancelled until reset.
+    let result = eval_string("(+ 1 2)", &mut env);
+    assert!(result.is_err());
+
+    token.reset();
+    let result = eval_string("(+ 1 2)", &mut env);
+    assert_eq!(format!("{}", result.unwrap()), "3");
+}
+
+#[test]
+fn eval_string_with_stats_reports_resource_usage() {
+    let mut env = Env::prelude();
+    let input = "
+    (do
+        (let fact (Func (n) (if (= n 0) 1 (* n (fact (- n 1))))))
+        (fact 5)
+    )";
+    let (result, stats) = eval_string_with_stats(input, &mut env);
+
+    assert_eq!(format!("{}", result.unwrap()), "120");
+    assert!(stats.steps > 5);
+    assert!(stats.max_call_depth > 5);
+    assert!(stats.allocations_estimate > 5);
+    assert!(env.context.stats.is_none());
+
+    let (_, shallow_stats) = eval_string_with_stats("(+ 1 2)", &mut env);
+    assert!(shallow_stats.steps < stats.steps);
+}
+
+#[test]
+fn eval_string_with_diagnostics_dumps_the_failure_state() {
+    let mut env = Env::prelude();
+    let input =
+        "(let scale (Func (x factor) (/ x factor)))\n(let run (Func (n) (scale n 0)))\n(run 10)";
+
+    let error = eval_string_with_diagnostics(input, &mut env).unwrap_err();
+    assert!(matches!(error.error, PipelineError::Eval(..)));
+    assert!(env.context.diagnostics.is_none());
+
+    let diagnostics = error.diagnostics.clone().unwrap();
+    let names: Vec<_> = diagnostics
+        .call_stack
+        .iter()
+        .map(|frame| frame.name.as_str())
+        .collect();
+    assert_eq!(names, ["/", "scale", "run"]);
+    assert_eq!(diagnostics.call_stack[2].position.to_string(), "3:2");
+    assert_eq!(
+        diagnostics.bindings,
+        [
+            ("factor".to_owned(), "0".to_owned()),
+            ("x".to_owned(), "10".to_owned())
+        ]
+    );
+    assert_eq!(
+        error.to_string(),
+        "error: division by zero\n --> 1:30\n  |\n1 | (let scale (Func (x factor) (/ x factor)))\n  |                              ^\ncall stack:\n  0: / at 1:30\n  1: scale at 2:21\n  2: run at 3:2\nbindings:\n  factor = 0\n  x = 10"
+    );
+
+    // A caught error is not reported, a top-level failure has no call stack.
+    let input = "(let n (try (run 1) (catch err 0)))\n(+ n unknown)";
+    let diagnostics = eval_string_with_diagnostics(input, &mut env)
+        .unwrap_err()
+        .diagnostics
+        .unwrap();
+    assert!(diagnostics.call_stack.is_empty());
+    assert!(diagnostics
+        .bindings
+        .contains(&("n".to_owned(), "0".to_owned())));
+    assert!(diagnostics
+        .excerpt
+        .starts_with("error: `unknown` is undefined"));
+
+    let value = eval_string_with_diagnostics("(run2 1)", &mut env);
+    assert!(value
+        .unwrap_err()
+        .diagnostics
+        .unwrap()
+        .call_stack
+        .is_empty());
+}
+
+#[test]
+fn eval_records_the_replay_log() {
+    let mut env = Env::prelude();
+    env.context.replay = Some(ReplayLog::new(3));
+
+    let input = "(let f (Func (x) (/ 10 x)))\n(f 5)\n(f (- 1 1))";
+    assert!(eval_string(input, &mut env).is_err());
+
+    let replay = env.context.replay.take().unwrap();
+    assert_eq!(replay.steps().count(), 3);
+
+    let dump = replay.dump(2);
+    let lines: Vec<_> = dump.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("!! division by zero"));
+    assert!(lines[1].ends_with("!! division by zero"));
+
+    let last = replay.steps().last().unwrap();
+    assert!(!last.ok);
+    assert_eq!(&input[last.range.clone()], "f");
+
+    let path = std::env::temp_dir().join(format!("tan-replay-{}.log", std::process::id()));
+    env.context.replay = Some(ReplayLog::with_file(0, &path).unwrap());
+    eval_string("(+ 1 (* 2 3))", &mut env).unwrap();
+    env.context.replay.as_mut().unwrap().flush().unwrap();
+    assert_eq!(env.context.replay.as_ref().unwrap().steps().count(), 0);
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let lines: Vec<_> = log.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("#1 ") && lines[0].ends_with("=> 6"));
+    assert!(lines[1].starts_with("#0 ") && lines[1].ends_with("=> 7"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn eval_reports_the_foreign_function_invocations() {
+    let mut env = Env::prelude();
+
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let recorded = events.clone();
+    let log = events.clone();
+    env.context
+        .set_telemetry_handler(move |event: &OpEvent| log.borrow_mut().push(event.clone()));
+
+    let input = r#"(let f (Func (s) (str-len s)))"#;
+    eval_string(input, &mut env).unwrap();
+
+    let input = r#"(f "hello")"#;
+    eval_string(input, &mut env).unwrap();
+
+    let input = r#"(str-slice "abc" 2 1)"#;
+    assert!(eval_string(input, &mut env).is_err());
+
+    let events = events.borrow();
+    assert_eq!(events.len(), 2);
+
+    let event = &events[0];
+    assert_eq!(event.name, "str-len");
+    assert_eq!(event.args, vec![r#""hello""#.to_owned()]);
+    assert!(event.ok);
+    assert_eq!(event.summary, "5");
+
+    let event = &events[1];
+    assert_eq!(event.name, "str-slice");
+    assert_eq!(event.args.len(), 3);
+    assert!(!event.ok);
+    assert_eq!(&input[event.range.clone()], "str-slice");
+    assert!(event.to_string().starts_with(r#"(str-slice "abc" 2 1) "#));
+    drop(events);
+
+    // The foreign functions invoked as values are reported.
+    let input = r#"(map str-len ["a" "bc"])"#;
+    eval_string(input, &mut env).unwrap();
+
+    let events = recorded.borrow();
+    let names: Vec<_> = events[2..]
+        .iter()
+        .map(|event| event.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["str-len", "str-len", "map"]);
+    assert_eq!(&input[events[2].range.clone()], "str-len");
+}
+
+#[test]
+fn eval_processes_string_interpolation() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let name "George")
+        (let n 2)
+        "Hello ${name}, you have ${(+ n 1)} new\tmessages"
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert!(
+        matches!(value, Ann(Expr::String(s), ..) if s == "Hello George, you have 3 new\tmessages")
+    );
+
+    // The interpolation is not affected by a `format` binding.
+    let value = eval_string(r#"(let format 3) (let n 1) "n = ${n}""#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "n = 1");
+}
+
+#[test]
+fn eval_iterates_dicts_deterministically() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let dict {:zeta 1 :alpha 2 :mu 3})
+        (let #mut keys [])
+        (for_each dict entry (push! keys entry))
+        (List dict keys)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(
+        format!("{value}"),
+        r#"({"alpha" 2 "mu" 3 "zeta" 1} [["alpha" 2] ["mu" 3] ["zeta" 1]])"#
+    );
+}
+
+#[test]
+fn run_conformance_reports_structured_results() {
+    let results = run_conformance("tests/fixtures/conformance").unwrap();
+
+    assert_eq!(results.len(), 3);
+
+    assert!(results[0].path.ends_with("error.tan"));
+    assert!(matches!(results[0].outcome, ConformanceOutcome::Error(..)));
+
+    assert!(results[1].path.ends_with("fail.tan"));
+    assert!(
+        matches!(&results[1].outcome, ConformanceOutcome::Failed { expected, actual } if expected == "5" && actual == "6")
+    );
+
+    assert!(results[2].path.ends_with("pass.tan"));
+    assert!(results[2].passed());
+
+    let results = run_conformance("tests/fixtures").unwrap();
+
+    assert!(results.iter().all(|result| result.passed()));
+}
+
+#[test]
+fn eval_evaluates_collection_elements() {
+    let mut env = Env::prelude();
+    let input = "(do (let x 1) (List [x (+ x 2)] {:a x :b (+ x 1)}))";
+    let value = eval_string(input, &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), r#"([1 3] {"a" 1 "b" 2})"#);
+}
+
+#[test]
+fn eval_reports_errors_at_collection_elements() {
+    let mut env = Env::prelude();
+    let input = "[1 2 undefined_sym 4]";
+    let errors = eval_string(input, &mut env).unwrap_err();
+
+    assert!(matches!(&errors[0].0, Error::UndefinedSymbol(s) if s == "undefined_sym"));
+    assert_eq!(errors[0].1, 5..18);
+
+    let input = "{:a 1 :b (+ 1 undefined_sym)}";
+    let errors = eval_string(input, &mut env).unwrap_err();
+
+    assert!(matches!(&errors[0].0, Error::UndefinedSymbol(s) if s == "undefined_sym"));
+    assert_eq!(errors[0].1, 14..27);
+}
+
+#[test]
+fn pipeline_errors_keep_the_failed_stage() {
+    let mut env = Env::prelude();
+
+    let error = eval_string("(let s \"unterminated", &mut env).unwrap_err();
+    assert!(matches!(error, PipelineError::Lex(..)));
+
+    let error = eval_string("(+ 1 2", &mut env).unwrap_err();
+    assert!(matches!(error, PipelineError::Parse(..)));
+    assert!(matches!(error[0].0, Error::UnterminatedList));
+
+    let error = eval_string("(+ 1 undefined_sym)", &mut env).unwrap_err();
+    assert!(matches!(error, PipelineError::Eval(..)));
+    assert_eq!(error.stage(), "eval");
+
+    fn run(input: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let mut env = Env::prelude();
+        let value = eval_string(input, &mut env)?;
+        Ok(value.to_string())
+    }
+
+    assert_eq!(run("(+ 1 2)").unwrap(), "3");
+    assert!(run("(+ 1 2").is_err());
+}
+
+#[test]
+fn eval_string_reports_all_static_errors() {
+    let mut env = Env::prelude();
+    let input = "(let a 12fdsf356)\n(let b 1)\n(let c 34ad)";
+    let errors = eval_string(input, &mut env).unwrap_err();
+
+    assert!(matches!(errors, PipelineError::Parse(..)));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].1, 7..16);
+    assert_eq!(errors[1].1, 35..39);
+
+    let input = "(let)\n(+ 1 2)\n(let x)";
+    let errors = eval_string(input, &mut env).unwrap_err();
+
+    assert!(matches!(errors, PipelineError::Resolve(..)));
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn eval_processes_bool_constructors() {
+    let mut env = Env::prelude();
+    let value = eval_string("(List (True) (False) true (if (True) 1 2))", &mut env).unwrap();
+
+    assert_eq!(format!("{value}"), "(true false true 1)");
+
+    let result = eval_string("(let True 1)", &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string("(let true 1)", &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string("(True 1)", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_unit_and_never() {
+    let mut env = Env::prelude();
+    let value = eval_string(
+        "(List (unit? ()) (unit? 1) (never? (Never)) (never? ()) (unit? (if false 1)))",
+        &mut env,
+    )
+    .unwrap();
+
+    assert_eq!(format!("{value}"), "(true false true false true)");
+
+    let result = eval_string("(let Never 1)", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_renders_foreign_values_with_registered_printers() {
+    struct Point(i64, i64);
+
+    register_printer("Point", |value| {
+        let Point(x, y) = value.downcast_ref::<Point>().unwrap();
+        format!("(Point {x} {y})")
+    });
+
+    let mut env = Env::prelude();
+    env.insert("p", Expr::Foreign(ForeignValue::new("Point", Point(1, 2))));
+
+    let value = eval_string(r#"(format "p = " p)"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "p = (Point 1 2)");
+
+    let value = eval_string("[p]", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "[(Point 1 2)]");
+    assert_eq!(format!("{:?}", value.0), "Array([Foreign((Point 1 2))])");
+
+    env.insert("q", Expr::Foreign(ForeignValue::new("Unknown", ())));
+    let value = eval_string("q", &mut env).unwrap();
+    assert_eq!(format_value(&value), "#<Unknown>");
+}
+
+#[test]
+fn eval_compares_and_hashes_foreign_values() {
+    register_eq("UserId", |a, b| {
+        a.downcast_ref::<u64>() == b.downcast_ref::<u64>()
+    });
+    register_hash("UserId", |value| *value.downcast_ref::<u64>().unwrap());
+
+    let mut env = Env::prelude();
+    env.insert("a", Expr::Foreign(ForeignValue::new("UserId", 7_u64)));
+    env.insert("b", Expr::Foreign(ForeignValue::new("UserId", 7_u64)));
+    env.insert("c", Expr::Foreign(ForeignValue::new("UserId", 8_u64)));
+
+    let value = eval_string(
+        r#"(do (let d (assoc {} a "George")) (List (= a b) (= a c) (d b) (d c)))"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format!("{value}"), r#"(true false (Some "George") None)"#);
+
+    // Unequal values with colliding hashes are distinct keys.
+    register_eq("Bucket", |a, b| {
+        a.downcast_ref::<u64>() == b.downcast_ref::<u64>()
+    });
+    register_hash("Bucket", |_| 1);
+    env.insert("x", Expr::Foreign(ForeignValue::new("Bucket", 1_u64)));
+    env.insert("y", Expr::Foreign(ForeignValue::new("Bucket", 2_u64)));
+    env.insert("z", Expr::Foreign(ForeignValue::new("Bucket", 1_u64)));
+
+    let value = eval_string(
+        r#"(do (let d (assoc (assoc {} x "x") y "y")) (List (d x) (d y) (d z)))"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format!("{value}"), r#"((Some "x") (Some "y") (Some "x"))"#);
+
+    // Foreign values without hooks are compared by identity, and are not hashable.
+    let handle = ForeignValue::new("Handle", ());
+    env.insert("h1", Expr::Foreign(handle.clone()));
+    env.insert("h2", Expr::Foreign(handle));
+    env.insert("h3", Expr::Foreign(ForeignValue::new("Handle", ())));
+
+    let value = eval_string("(List (= h1 h2) (= h1 h3))", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "(true false)");
+
+    let result = eval_string(r#"(assoc {} h1 "file")"#, &mut env);
+    let err = result.unwrap_err();
+    assert!(
+        matches!(&err[0], Ranged(Error::InvalidArguments(x), ..) if x.contains("`Handle` cannot be used as Dict keys"))
+    );
+}
+
+#[test]
+fn eval_processes_cond() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let sign (Func (x) (cond (> x 0) "positive" (< x 0) "negative" else "zero")))
+        (List (sign 5) (sign -3) (sign 0) (cond false 1))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), r#"("positive" "negative" "zero" ())"#);
+
+    let result = eval_string("(cond 1 2)", &mut env);
+    let err = result.unwrap_err();
+    assert_eq!(err[0].1, 6..7);
+
+    let result = eval_string("(cond true)", &mut env);
+    let err = result.unwrap_err();
+    assert!(
+        matches!(&err[0], Ranged(Error::InvalidArguments(x), ..) if x == "malformed cond, missing clause")
+    );
+}
+
+#[test]
+fn eval_processes_case() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let name (Func (x) (case x 1 "one" 2 "two" :three "three" _ "many")))
+        (List (name 1) (name 2) (name :three) (name 4) (case 5 1 "one"))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), r#"("one" "two" "three" "many" ())"#);
+
+    let result = eval_string("(case 1 x 2)", &mut env);
+    let err = result.unwrap_err();
+    assert_eq!(err[0].1, 8..9);
+}
+
+#[test]
+fn eval_iterates_host_iterators() {
+    let mut env = Env::prelude();
+    env.insert("numbers", Expr::iterator((1..=3).map(Expr::Int)));
+    env.insert(
+        "lines",
+        Expr::iterator("first\nsecond".lines().map(Expr::string)),
+    );
+
+    let input = r#"
+    (do
+        (let #mut items [])
+        (for_each numbers x (push! items (* x 10)))
+        (for_each lines line (push! items line))
+        (for_each numbers x (push! items x))
+        items
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+
+    // The iterators are consumed once.
+    assert_eq!(format!("{value}"), r#"[10 20 30 "first" "second"]"#);
+}
+
+#[test]
+fn eval_processes_match() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let describe (Func (x)
+            (match x
+                ([[a b] c] (+ a (+ b c)))
+                ([a b] :when (> a b) (List "desc" a b))
+                ([a b] (+ a b))
+                ((a b) (List "list" a b))
+                ({:name name :age 30} name)
+                ({:name name} (List "unknown age" name))
+                (0 "zero")
+                (_ "other"))))
+        (List
+            (describe [3 1])
+            (describe [1 2])
+            (describe [[1 2] 3])
+            (describe (List 1 2))
+            (describe {:name "George" :age 30})
+            (describe {:name "Ada" :age 36})
+            (describe 0)
+            (describe "hello")
+            (match 1 (2 "two")))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"(("desc" 3 1) 3 6 ("list" 1 2) "George" ("unknown age" "Ada") "zero" "other" ())"#
+    );
+
+    let result = eval_string("(match 1 (1 :when 2 3))", &mut env);
+    let err = result.unwrap_err();
+    assert!(
+        matches!(&err[0], Ranged(Error::InvalidArguments(x), ..) if x == "the match guard is not a boolean value")
+    );
+
+    let result = eval_string("(match 1 1)", &mut env);
+    let err = result.unwrap_err();
+    assert_eq!(err[0].1, 9..10);
+}
+
+#[test]
+fn eval_streams_file_lines() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut errors [])
+        (for_each (File:lines "tests/fixtures/lines.txt") line
+            (match line
+                ("ERROR disk full" (push! errors line))
+                ("ERROR timeout" (push! errors line))
+                (_ ())))
+        errors
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), r#"["ERROR disk full" "ERROR timeout"]"#);
+
+    let result = eval_string(r#"(File:lines "tests/fixtures/missing.txt")"#, &mut env);
+    assert!(result.is_err());
+
+    // A read error is reported, the lines are not silently truncated.
+    let path = std::env::temp_dir().join(format!("tan-lines-{}.txt", std::process::id()));
+    std::fs::write(&path, b"first\n\xff\xfe\nlast\n").unwrap();
+    env.insert("path", Expr::string(path.to_string_lossy().as_ref()));
+
+    let result = eval_string(
+        "(do (let #mut lines []) (for_each (File:lines path) line (push! lines line)) lines)",
+        &mut env,
+    );
+    std::fs::remove_file(&path).unwrap();
+    let err = result.unwrap_err();
+    assert!(matches!(&err[0], Ranged(Error::Io(..), ..)));
+}
+
+#[test]
+fn eval_processes_the_file_system() {
+    let dir = std::env::temp_dir().join(format!("tan-fs-{}", std::process::id()));
+    let dir = dir.to_string_lossy();
+
+    let mut env = Env::prelude();
+    env.insert("dir", Expr::string(dir.as_ref()));
+
+    let input = r#"
+    (do
+        (Dir:create (path/join dir "logs" "old"))
+        (File:write (path/join dir "logs" "app.log") "started;")
+        (File:append (path/join dir "logs" "app.log") "stopped")
+        (File:append (path/join dir "new.log") "created")
+        (File:copy (path/join dir "logs" "app.log") (path/join dir "logs" "copy.log"))
+        (Dir:list (path/join dir "logs"))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), r#"["app.log" "copy.log" "old"]"#);
+
+    let input = r#"
+    (do
+        (let path (path/join dir "logs" "app.log"))
+        (File:delete path)
+        (List
+            (Dir:list (path/join dir "logs"))
+            (File:read_as_string (path/join dir "logs" "copy.log"))
+            (File:read_as_string (path/join dir "new.log"))
+            (File:exists? path)
+            (File:exists? (path/join dir "logs" "old"))
+            (path/extension path)
+            (path/extension "README")
+            (path/parent "logs/app.log")
+            (path/parent ""))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"(["copy.log" "old"] "started;stopped" "created" false true (Some "log") None (Some "logs") None)"#
+    );
+
+    let value = eval_string("(do (Dir:delete dir) (File:exists? dir))", &mut env).unwrap();
+    assert_eq!(format_value(value), "false");
+
+    // The errors are catchable, and report the path.
+    let value = eval_string(
+        r#"(try (File:delete (path/join dir "missing.txt")) (catch err (error/message err)))"#,
+        &mut env,
+    )
+    .unwrap();
+    let message = format_value(value);
+    assert!(message.starts_with("i/o error: `"), "{message}");
+    assert!(message.contains("missing.txt`"), "{message}");
+}
+
+#[test]
+fn eval_records_the_effects_in_audit_mode() {
+    let dir = std::env::temp_dir().join(format!("tan-audit-{}", std::process::id()));
+    let dir = dir.to_string_lossy();
+
+    let mut env = Env::prelude();
+    env.insert("dir", Expr::string(dir.as_ref()));
+    env.context.audit = Some(AuditReport::new());
+
+    let input = r#"
+    (do
+        (Dir:create dir)
+        (File:write (path/join dir "plan.txt") "applied")
+        (File:delete (path/join dir "obsolete.txt"))
+        (File:exists? dir)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "false");
+
+    // The arguments are validated.
+    let result = eval_string("(File:write dir 1)", &mut env);
+    assert!(result.is_err());
+
+    let mut report = env.context.audit.take().unwrap();
+    assert_eq!(report.actions().len(), 3);
+    assert_eq!(
+        report.actions()[1].to_string(),
+        format!(r#"(io/write-string "{dir}/plan.txt" "applied")"#)
+    );
+
+    report.retain(|action| action.op != "io/delete-file");
+    report.apply(&mut env).unwrap();
+
+    let value = eval_string(
+        r#"(File:read_as_string (path/join dir "plan.txt"))"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format_value(value), "applied");
+
+    // The writes of the file handles and the exit are recorded.
+    env.context.audit = Some(AuditReport::new());
+
+    let input = r#"
+    (do
+        ((Func (out) (do (write-line out "line") (close out)))
+            (File:open (path/join dir "plan.txt") :write))
+        (exit 3)
+        :continued
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "continued");
+
+    let report = env.context.audit.take().unwrap();
+    let actions: Vec<_> = report
+        .actions()
+        .iter()
+        .map(|action| {
+            let args: Vec<_> = action.args.iter().map(format_value).collect();
+            (action.op.as_str(), args)
+        })
+        .collect();
+    let path = format!("{dir}/plan.txt");
+    assert_eq!(
+        actions,
+        vec![
+            ("io/write-string", vec![path.clone(), "".to_owned()]),
+            ("io/append-string", vec![path, "line\n".to_owned()]),
+            ("exit", vec!["3".to_owned()]),
+        ]
+    );
+
+    let value = eval_string(
+        r#"(File:read_as_string (path/join dir "plan.txt"))"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format_value(value), "applied");
+
+    eval_string("(Dir:delete dir)", &mut env).unwrap();
+}
+
+#[test]
+fn eval_denies_the_ops_without_the_capabilities() {
+    let mut env = Env::prelude_sandboxed();
+
+    for (input, message) in [
+        (
+            r#"(File:read_as_string "Cargo.toml")"#,
+            "`io/read-string` requires the `fs` capability",
+        ),
+        (
+            r#"(io/write-string "out.txt" "x")"#,
+            "`io/write-string` requires the `fs` capability",
+        ),
+        (
+            r#"(use "./some/module")"#,
+            "`use` requires the `fs` capability",
+        ),
+        ("(exit 1)", "`exit` requires the `process` capability"),
+    ] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected an error for `{input}`");
+        };
+        assert_eq!(errors[0].0.to_string(), message);
+    }
+
+    // The denied ops are catchable, the pure ops are allowed.
+    let input = r#"
+    (List
+        (try (File:exists? "Cargo.toml") (catch err :denied))
+        (str/len "hello")
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "(:denied 5)");
+
+    env.context.capabilities.fs = true;
+    let value = eval_string(r#"(File:exists? "Cargo.toml")"#, &mut env).unwrap();
+    assert_eq!(format_value(value), "true");
+}
+
+#[test]
+fn eval_sandbox_applies_to_the_worker_threads() {
+    let mut env = Env::prelude_sandboxed();
+    // Force the threaded path of `pmap`, independent of the CPU count.
+    env.context.workers = Some(2);
+
+    let path = std::env::temp_dir().join(format!("tan-escaped-{}.txt", std::process::id()));
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    let agent_input = format!(
+        r#"
+        (do
+            (let a (agent 0))
+            (send-update a (Func (v) (do (io/write-string "{path}" "pwned") (+ v 1))))
+            (await a)
+        )"#
+    );
+    let pmap_input =
+        format!(r#"(pmap (Func (p) (io/write-string p "pwned")) ["{path}" "{path}"])"#);
+
+    for input in [agent_input, pmap_input] {
+        let Err(errors) = eval_string(&input, &mut env) else {
+            panic!("expected a capability error for `{input}`");
+        };
+        assert_eq!(
+            errors[0].0.to_string(),
+            "`io/write-string` requires the `fs` capability"
+        );
+        assert!(!std::path::Path::new(&path).exists());
+    }
+}
+
+#[test]
+fn eval_applies_the_effectful_functions_in_the_host_thread() {
+    let stdout = SharedBuffer::default();
+
+    let mut env = Env::prelude();
+    env.context.stdout = Output::new(stdout.clone());
+    // Force the threaded path of `pmap`, independent of the CPU count.
+    env.context.workers = Some(2);
+
+    let input = r#"
+    (let a (agent 0))
+    (send-update a (Func (v) (do (io/write "agent ") (+ v 1))))
+    (await a)
+    (pmap (Func (x) (writeln x)) [1 2])
+    "#;
+    eval_string(input, &mut env).unwrap();
+
+    // The output is not discarded by the worker threads.
+    assert_eq!(stdout.contents(), "agent 1\n2\n");
+}
+
+#[test]
+fn eval_preserves_the_error_kinds_of_the_worker_threads() {
+    let mut env = Env::prelude();
+    // Force the threaded path of `pmap`, independent of the CPU count.
+    env.context.workers = Some(2);
+
+    let input = "(pmap (Func (x) (/ x 0)) [1 2 3])";
+    let Err(errors) = eval_string(input, &mut env) else {
+        panic!("expected an error");
+    };
+    assert!(matches!(errors[0].0, Error::DivisionByZero));
+
+    let input = "(pmap (Func (x) (throw {:code x})) [1])";
+    let Err(errors) = eval_string(input, &mut env) else {
+        panic!("expected an error");
+    };
+    let Error::Thrown(value) = &errors[0].0 else {
+        panic!("expected a thrown value, found `{}`", errors[0].0);
+    };
+    assert_eq!(format_value(value), r#"{"code" 1}"#);
+
+    let input = "(do (let a (agent 1)) (send-update a (Func (v) (/ v 0))) (await a))";
+    let Err(errors) = eval_string(input, &mut env) else {
+        panic!("expected an error");
+    };
+    assert!(matches!(errors[0].0, Error::DivisionByZero));
+}
+
+#[test]
+fn eval_processes_the_prelude_modules() {
+    let mut env = Env::prelude();
+
+    let input = r#"
+    (List
+        (str/len "hello")
+        (str-len "hello")
+        (seq/map (Func (x) (* x 2)) [1 2])
+        (math/sqrt 16.0)
+        (math/max 1 3)
+        (core/+ 1 2)
+        (io/exists? "Cargo.toml"))
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "(5 5 [2 4] 4 3 3 true)");
+
+    let mut env = Env::prelude_with(&PreludeOptions {
+        flat_aliases: false,
+    });
+
+    let input = r#"
+    (do
+        (let name "world")
+        (List (str/len "hello") (+ 1 2) "hello ${name}" (seq/count [1 2 3])))
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), r#"(5 3 "hello world" 3)"#);
+
+    for input in [
+        r#"(str-len "hello")"#,
+        "(count [1 2])",
+        r#"(File:exists? "a")"#,
+    ] {
+        assert!(eval_string(input, &mut env).is_err(), "{input}");
+    }
+
+    // The qualified names are exported by the modules, also without the flat
+    // names.
+    let value = eval_string(r#"(path/extension "a.tan")"#, &mut env).unwrap();
+    assert_eq!(format_value(value), r#"(Some "tan")"#);
+
+    // The variables do not shadow the modules in the qualified names.
+    let input = r#"
+    (do
+        (let str "abc" path "x" time 1)
+        (List (str/len str) (str/len path) (> (time/now) time)))
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "(3 1 true)");
+}
+
+#[test]
+fn eval_processes_statistics() {
+    let mut env = Env::prelude();
+
+    let input = r#"
+    (do
+        (let values [2 4 4 4 5 5 7 9])
+        (List
+            (stats/mean values)
+            (stats/median values)
+            (stats/median [3 1.5 2])
+            (stats/percentile values 25)
+            (stats/percentile values 100)
+            (stats/variance values)
+            (stats/std values)
+            (stats/histogram [1 2 3 4] 2)
+            (stats/histogram [3 3] 2))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"(5 4.5 2 4 9 4 2 {"counts" [2 2] "edges" [1 2.5 4]} {"counts" [2 0] "edges" [3 3 3]})"#
+    );
+
+    for input in [
+        "(stats/mean [])",
+        r#"(stats/median [1 "a"])"#,
+        "(stats/percentile [1 2] 101)",
+        "(stats/histogram [1 2] 0)",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_renders_tables() {
+    let mut env = Env::prelude();
+
+    eval_string(
+        r#"(let users [{:name "George" :score 98 :role "admin"} {:name "Ada" :score 100.5} {:name "Kostas, Jr." :score 7 :role "a|b"}])"#,
+        &mut env,
+    )
+    .unwrap();
+
+    let value = eval_string(
+        r#"(table/render users :headers ["name" "score"])"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        "name         score\n-----------  -----\nGeorge          98\nAda          100.5\nKostas, Jr.      7"
+    );
+
+    // By default, the columns are all the keys.
+    let value = eval_string("(table/render users)", &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        "name         role   score\n-----------  -----  -----\nGeorge       admin     98\nAda                 100.5\nKostas, Jr.  a|b        7"
+    );
+
+    let value = eval_string(
+        r#"(table/render users :headers ["name" "role"] :format :csv)"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        "name,role\nGeorge,admin\nAda,\n\"Kostas, Jr.\",a|b"
+    );
+
+    let value = eval_string(
+        r#"(table/render users :headers ["name" "role"] :format :markdown)"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        "| name | role |\n| --- | --- |\n| George | admin |\n| Ada |  |\n| Kostas, Jr. | a\\|b |"
+    );
+
+    // Array rows, without headers.
+    let value = eval_string(r#"(table/render [["a" 1] ["bcd" 22]])"#, &mut env).unwrap();
+    assert_eq!(format_value(value), "a     1\nbcd  22");
+
+    for input in [
+        "(table/render [1 2])",
+        "(table/render users :format :html)",
+        "(table/render users :headers)",
+        r#"(table/render [{:a 1} ["b"]])"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_processes_file_handles() {
+    let path = std::env::temp_dir().join(format!("tan-handle-{}.txt", std::process::id()));
+
+    let mut env = Env::prelude();
+    env.insert("path", Expr::string(path.to_string_lossy().as_ref()));
+
+    let input = r#"
+    (do
+        (let out (File:open path :write))
+        (write-line out "one")
+        (write-line out "two")
+        (close out))
+    (let out (File:open path :append))
+    (write-line out "three")
+    (close out)
+    (let in (File:open path))
+    (let #mut lines [])
+    (let #mut line (read-line in))
+    (while (is-some? line)
+        (do
+            (push! lines (unwrap line))
+            (set! line (read-line in))))
+    (close in)
+    lines
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), r#"["one" "two" "three"]"#);
+
+    for input in [
+        "(read-line in)",
+        r#"(write-line (File:open path) "four")"#,
+        "(read-line (File:open path :append))",
+        "(File:open path :execute)",
+        r#"(File:open "tests/fixtures/missing.txt")"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn eval_diffs_values_and_text() {
+    let mut env = Env::prelude();
+
+    let input = r#"(diff {:port 80 :hosts ["a" "b"] :debug true} {:port 8080 :hosts ["a"] :log "info" :debug true})"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"[{"kind" :removed "old" "b" "path" ["hosts" 1]} {"kind" :added "new" "info" "path" ["log"]} {"kind" :changed "new" 8080 "old" 80 "path" ["port"]}]"#
+    );
+
+    let value = eval_string("(diff [1 {:a 2}] [1 {:a 2}])", &mut env).unwrap();
+    assert_eq!(format_value(value), "[]");
+
+    let value = eval_string("(diff 1 1.0)", &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"[{"kind" :changed "new" 1 "old" 1 "path" []}]"#
+    );
+
+    let input = r#"(text/diff "a\nb\nc\nd\ne\nf\ng\nh\ni" "a\nb\nc\nd\nE\nf\ng\nh\ni\nj")"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        "--- a\n+++ b\n@@ -2,8 +2,9 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n i\n+j"
+    );
+
+    let value = eval_string(r#"(text/diff "a\nb" "a\nb")"#, &mut env).unwrap();
+    assert_eq!(format_value(value), "");
+
+    let input = r#"(text/diff "a\nb\nc\nd\ne" "x\na\nc\nd\ny\ne\nz")"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        "--- a\n+++ b\n@@ -1,5 +1,7 @@\n+x\n a\n-b\n c\n d\n+y\n e\n+z"
+    );
+
+    // The large texts are diffed in linear space.
+    let old: Vec<String> = (0..50_000).map(|i| format!("line {i}")).collect();
+    let mut new = old.clone();
+    new[25_000] = "changed".to_owned();
+    let diff = unified_diff(&old.join("\n"), &new.join("\n"), 1);
+    assert_eq!(
+        diff,
+        "--- a\n+++ b\n@@ -25000,3 +25000,3 @@\n line 24999\n-line 25000\n+changed\n line 25001"
+    );
+
+    assert!(eval_string("(diff 1)", &mut env).is_err());
+    assert!(eval_string(r#"(text/diff "a" 1)"#, &mut env).is_err());
+}
+
+#[test]
+fn eval_validates_data_with_schemas() {
+    let mut env = Env::prelude();
+
+    let input = r#"
+    (let schema {
+        :type "Dict"
+        :required ["name" "port"]
+        :keys {
+            :name "String"
+            :port "Int"
+            :level {:type "String" :enum ["debug" "info"]}
+            :tags {:type "Array" :items "String"}
+        }
+    })
+    "#;
+    eval_string(input, &mut env).unwrap();
+
+    let input = r#"(validate {:name "api" :port 80 :level "info" :tags ["a"]} schema)"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"{"level" "info" "name" "api" "port" 80 "tags" ["a"]}"#
+    );
+
+    let input = r#"(validate {:port "80" :level "trace" :tags ["a" 1]} schema)"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"(Error [{"message" "missing required key `name`" "path" []} {"message" "`trace` is not one of `debug`, `info`" "path" ["level"]} {"message" "expected Int, found String" "path" ["port"]} {"message" "expected String, found Int" "path" ["tags" 1]}])"#
+    );
+
+    let value = eval_string("(is-error? (validate [1 2] schema))", &mut env).unwrap();
+    assert_eq!(format_value(value), "true");
+
+    let value = eval_string("(is-error? (validate 1.5 :Number))", &mut env).unwrap();
+    assert_eq!(format_value(value), "false");
+
+    for input in [
+        "(validate 1 :Integer)",
+        "(validate 1 {:type :Int :min 0})",
+        "(validate {} {:required :name})",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_processes_times() {
+    let mut env = Env::prelude();
+
+    let cases = [
+        (r#"(time/parse "2024-02-29T12:30:05Z")"#, "1709209805000"),
+        (
+            r#"(time/parse "2024-02-29T14:30:05.250+02:00")"#,
+            "1709209805250",
+        ),
+        (r#"(time/parse "1969-12-31T23:59:59Z")"#, "-1000"),
+        (r#"(time/parse "01/03/2024" "%d/%m/%Y")"#, "1709251200000"),
+        ("(time/format 1709209805000)", "2024-02-29T12:30:05Z"),
+        ("(time/format 1709209805250)", "2024-02-29T12:30:05.250Z"),
+        ("(time/format -1000)", "1969-12-31T23:59:59Z"),
+        (
+            r#"(time/format 1709209805000 "%Y-%m-%d %H:%M (100%%)")"#,
+            "2024-02-29 12:30 (100%)",
+        ),
+        (
+            "(time/format (time/add 1709209805000 :days 1 :hours -12))",
+            "2024-03-01T00:30:05Z",
+        ),
+        (
+            r#"(time/format (time/parse (time/format 0)))"#,
+            "1970-01-01T00:00:00Z",
+        ),
+    ];
+
+    for (input, expected) in cases {
+        let value = eval_string(input, &mut env).unwrap();
+        assert_eq!(format_value(value), expected, "for `{input}`");
+    }
+
+    let value = eval_string("(time/now)", &mut env).unwrap();
+    assert!(matches!(value.as_ref(), Expr::Int(millis) if *millis > 1_700_000_000_000));
+
+    let value = eval_string("(time/now :rfc3339)", &mut env).unwrap();
+    assert!(format_value(value).ends_with('Z'));
+
+    eval_string("(let start (time/instant))", &mut env).unwrap();
+    eval_string("(sleep 5)", &mut env).unwrap();
+    let value = eval_string("(time/elapsed start)", &mut env).unwrap();
+    assert!(matches!(value.as_ref(), Expr::Float(millis) if *millis >= 5.0));
+
+    for input in [
+        r#"(time/parse "2023-02-29T00:00:00Z")"#,
+        r#"(time/parse "2024-01-01")"#,
+        r#"(time/parse "2024-01-01" "%Y/%m/%d")"#,
+        "(time/add 0 :months 1)",
+        "(time/elapsed 1)",
+        "(sleep -1)",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_processes_while_break_continue() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut i 0)
+        (let #mut odds [])
+        (let found
+            (while true
+                (do
+                    (set! i (+ i 1))
+                    (if (> i 9) (break i))
+                    (if (= i 4) (continue))
+                    (if (= i 7) (continue))
+                    (push! odds i))))
+        (let #mut items [])
+        (for_each [1 2 3 4 5] x
+            (do
+                (if (= x 2) (continue))
+                (if (= x 4) (break))
+                (push! items x)))
+        (set! i 0)
+        (List found odds items (while (< i 3) (set! i (+ i 1))))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "(10 [1 2 3 5 6 8 9] [1 3] ())");
+
+    // The signals do not leave the scopes of the loop body behind.
+    let mut env = Env::prelude();
+    let depth = env.local.len();
+    let input = r#"
+    (let #mut n 0)
+    (while (< n 5) (do (set! n (+ n 1)) (continue)))
+    (for x in [1 2 3] (do (let y x) (continue)))
+    (for_each [1 2 3] x (do (let y x) (if (= x 2) (break) 0)))
+    n
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(&value), "5");
+    assert_eq!(env.local.len(), depth);
+
+    let result = eval_string("(do 1 (break 2))", &mut env);
+    let err = result.unwrap_err();
+    assert_eq!(err[0].0.to_string(), "`break` outside of a loop");
+    assert_eq!(err[0].1, 7..12);
+
+    // The signals do not escape functions, into the loops of the caller.
+    for (input, message) in [
+        (
+            "(let f (Func () (break 1))) (while true (f))",
+            "`break` outside of a loop",
+        ),
+        (
+            "(let g (Func () (continue))) (for_each [1 2] x (g))",
+            "`continue` outside of a loop",
+        ),
+        (
+            "(while true (map (Func (x) (break x)) [1 2]))",
+            "`break` outside of a loop",
+        ),
+    ] {
+        let err = eval_string(input, &mut env).unwrap_err();
+        assert_eq!(err[0].0.to_string(), message, "{input}");
+    }
+
+    // A loop in a function body is not affected.
+    let value = eval_string("((Func () (while true (break 3))))", &mut env).unwrap();
+    assert_eq!(format_value(&value), "3");
+}
+
+#[test]
+fn eval_processes_range_for_loops() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut items [])
+        (for x in (range 0 5) (push! items x))
+        (for x in (range 10 0 -3) (push! items x))
+        (for_each (range 3) x (push! items (* x 100)))
+        (for [k v] in {:a 1} (push! items k))
+        (List items (range 2 4))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"([0 1 2 3 4 10 7 4 1 0 100 200 "a"] (range 2 4 1))"#
+    );
+
+    let result = eval_string("(range 0 10 0)", &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "glob")]
+#[test]
+fn eval_processes_glob() {
+    let mut env = Env::prelude();
+    let value = eval_string(
+        r#"(glob "tests/fixtures/conformance/*.value.tan")"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"["tests/fixtures/conformance/error.value.tan" "tests/fixtures/conformance/fail.value.tan" "tests/fixtures/conformance/pass.value.tan"]"#
+    );
+
+    let result = eval_string(r#"(glob "tests/[")"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_lazy_sequences() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let #mut items [])
+        (let large (filter (Func (x) (> x 2)) (range 1000000000)))
+        (for_each (take 3 (map (Func (x) (* x 10)) large)) x (push! items x))
+        (for_each (map (Func (c) (List c)) (take 2 "hello")) x (push! items x))
+        (let it (iter [1 2]))
+        (List items (next it) (next it) (next it))
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"([30 40 50 ((Char "h")) ((Char "e"))] 1 2 ())"#
+    );
+
+    let result = eval_string("(for_each (map (Func (x) (+ x y)) [1]) x x)", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_higher_order_sequence_functions() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let double (Func (x) (* x 2)))
+        (List
+            (map double [1 2 3])
+            (map double (List 1 2))
+            (filter (Func (x) (> x 1)) [1 2 3])
+            (reduce + 0 [1 2 3 4])
+            (reduce (Func (acc x) (+ acc (double x))) 0 (range 4))
+            (any? (Func (x) (> x 2)) [1 2 3])
+            (all? (Func (x) (> x 2)) [1 2 3])
+            (all? (Func (x) (> x 2)) [])
+            (count [1 2 3])
+            (count (Func (x) (> x 1)) (range 5))
+        )
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        "([2 4 6] (2 4) [2 3] 10 12 true false true 3 3)"
+    );
+
+    let result = eval_string("(any? (Func (x) x) [1])", &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_binary_buffers() {
+    let path = std::env::temp_dir().join(format!("tan-buffer-{}.bin", std::process::id()));
+    let path = path.display().to_string();
+
+    let mut env = Env::prelude();
+    let input = format!(
+        r#"(File:write_bytes "{path}" (Buffer [0x54 0x41 0x4e 0x01 0x34 0x12 0xff 0xff 0xff 0xff]))"#
+    );
+    eval_string(input, &mut env).unwrap();
+
+    let input = format!(
+        r#"
+    (do
+        (let buf (File:read_bytes "{path}"))
+        (let magic (buf/slice buf 0 3))
+        (List
+            (buf/len buf)
+            magic
+            (buf/hex (buf/slice buf 3))
+            (buf/u8 buf 3)
+            (buf/u16-le buf 4)
+            (buf/u16-be buf 4)
+            (buf/u32-le buf 6)
+            (buf/i32-le buf 6)
+            (= magic (Buffer "54414e"))
+        )
+    )"#
+    );
+    let value = eval_string(input, &mut env);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        format!("{}", value.unwrap()),
+        r#"(10 (Buffer "54414e") "013412ffffffff" 1 4660 13330 4294967295 -1 true)"#
+    );
+
+    let result = eval_string(r#"(buf/u32-le (Buffer "0102") 0)"#, &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(buf/slice (Buffer "0102") 1 3)"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_string_functions() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let s "  Hello, Wörld  ")
+        (List
+            (str-len (trim s))
+            (str-slice (trim s) 7)
+            (str-slice "hello" 1 3)
+            (str-split "a,b,,c" ",")
+            (str-join ["a" 1 :b] "-")
+            (str-contains? s "Wör")
+            (str-replace "a-b-c" "-" "+")
+            (uppercase "straße")
+            (lowercase "ABC")
+        )
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"(12 "Wörld" "el" ["a" "b" "" "c"] "a-1-b" true "a+b+c" "STRASSE" "abc")"#
+    );
+
+    let value = eval_string(
+        r#"(format "%-6s|%5d|%05.1f|%x|%.2s|100%%" "ab" 42 -3.14159 255 "xyz" "!")"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format_value(&value), "ab    |   42|-03.1|ff|xy|100%!");
+
+    let value = eval_string(r#"(let n 5) "${n}% done, 100%d""#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "5% done, 100%d");
+
+    let result = eval_string(r#"(format "%d" "x")"#, &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(format "%s and %s" 1)"#, &mut env);
+    assert!(result.is_err());
+
+    // Huge widths and precisions are malformed, they should not overflow or
+    // abort on allocation.
+    for input in [
+        r#"(format "%99999999999999999999999d" 1)"#,
+        r#"(format "%1000000000s" 1)"#,
+        r#"(format "%.1000000000f" 1.0)"#,
+    ] {
+        let result = eval_string(input, &mut env);
+        let Err(errors) = result else {
+            panic!("expected an error for {input}");
+        };
+        assert!(errors[0].to_string().contains("malformed format template"));
+    }
+
+    let result = eval_string(r#"(str-slice "abc" 2 1)"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[test]
+fn eval_processes_fmt() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(r#"(fmt "{}: {} ({})" "name" [1 "a"] 1.5)"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), r#"name: [1 "a"] (1.5)"#);
+
+    let value = eval_string(
+        r#"(fmt "[{:<6}|{:>6}|{:^7}|{:6}|{:06.2}|{:.3}|{:.2}] {{}}" "ab" "cd" "ef" 42 -3.14159 2.0 "xyz")"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(&value),
+        "[ab    |    cd|  ef   |    42|-03.14|2.000|xy] {}"
+    );
+
+    let result = eval_string(r#"(fmt "{} and {}" 1)"#, &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(fmt "{}" 1 2)"#, &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(fmt "{:x}" 1)"#, &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(fmt "{" 1)"#, &mut env);
+    assert!(result.is_err());
+
+    let value = eval_string(r#"(fmt "{:06}|{:05}|{:.1}" 1.5 "ab" 7)"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "0001.5|   ab|7");
+
+    // Huge widths and precisions are malformed, they should not overflow or
+    // abort on allocation.
+    for input in [
+        r#"(fmt "{:99999999999999999999999}" 1)"#,
+        r#"(fmt "{:1000000000}" 1)"#,
+        r#"(fmt "{:.1000000000}" 1.0)"#,
+    ] {
+        let result = eval_string(input, &mut env);
+        let Err(errors) = result else {
+            panic!("expected an error for {input}");
+        };
+        assert!(errors[0].to_string().contains("malformed format template"));
+    }
+}
+
+#[test]
+fn eval_processes_comparison_and_boolean_operators() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (List
+        (!= 1 2) (!= 1 1) (>= 2 1) (>= 1 1) (>= 0 1) (<= 1 2) (<= 2 2) (<= 3 2)
+        (not true) (not false)
+        (and) (and true true) (and true false)
+        (or) (or false true) (or false false)
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        "(true false true true false true true false false true true true false false true false)"
+    );
+
+    // `and` and `or` short-circuit, the remaining operands are not evaluated.
+    let value = eval_string("(and false (exit 1))", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "false");
+    let value = eval_string("(or true (exit 1))", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "true");
+
+    for input in ["(and true 1)", "(or false \"true\")", "(not 0)"] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected a non-Bool operand error for `{input}`");
+        };
+        assert!(matches!(errors[0].0, Error::InvalidArguments(..)));
+    }
+
+    // A non-Bool operand after the short-circuit is not checked.
+    let value = eval_string("(and false 1)", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "false");
+}
+
+#[test]
+fn eval_processes_int_division_and_overflow() {
+    let mut env = Env::prelude();
+    let input = "(List (/ 7 2) (/ -7 2) (% 7 3) (% -7 2) (/ 7.0 2.0) (% 7.5 2.0))";
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format!("{value}"), "(3 -3 1 -1 3.5 1.5)");
+
+    // Mixed with a Float, the Ints are promoted.
+    let value = eval_string("(let div / rem %) (List (div 7.0 2) (rem 7 2.0))", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "(3.5 1)");
+
+    for input in ["(/ 1 0)", "(% 1 0)", "(let z 0) (/ 5 z)"] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected a division by zero error for `{input}`");
+        };
+        assert!(matches!(errors[0].0, Error::DivisionByZero));
+        assert_eq!(errors[0].0.to_string(), "division by zero");
+    }
+
+    for input in [
+        "(+ 9223372036854775807 1)",
+        "(- -9223372036854775807 2)",
+        "(* 9223372036854775807 2)",
+        "(/ (- -9223372036854775807 1) -1)",
+    ] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected an overflow error for `{input}`");
+        };
+        assert!(matches!(errors[0].0, Error::IntegerOverflow(..)));
+    }
+
+    let Err(errors) = eval_string("(+ 9223372036854775807 1)", &mut env) else {
+        panic!("expected an overflow error");
+    };
+    assert_eq!(errors[0].0.to_string(), "integer overflow in `+`");
+}
+
+#[test]
+fn eval_parses_command_line_arguments() {
+    let mut env = Env::prelude();
+    eval_string(
+        r#"
+        (let spec {
+            :name "greet"
+            :about "Greets people."
+            :flags {:loud {:short "l" :help "Shout the greeting"}}
+            :options {:greeting {:short "g" :help "The greeting" :default "Hello"} :times {:help "Repeat"}}
+            :positionals [{:name "who" :help "Who to greet"} {:name "punctuation" :default "!"}]
+        })
+        "#,
+        &mut env,
+    )
+    .unwrap();
+
+    let value = eval_string(
+        r#"(cli/parse ["-l" "--greeting=Hi" "George"] spec)"#,
+        &mut env,
+    )
+    .unwrap();
+    let Expr::Dict(result) = value.as_ref() else {
+        panic!("expected a Dict");
+    };
+    assert_eq!(
+        format!("{}", result["values"]),
+        r#"{"greeting" "Hi" "loud" true "punctuation" "!" "times" () "who" "George"}"#
+    );
+    assert_eq!(format!("{}", result["help?"]), "false");
+    assert_eq!(
+        format_value(&result["help"]),
+        "Usage: greet [OPTIONS] <who> [punctuation]\n\
+        \n\
+        Greets people.\n\
+        \n\
+        Arguments:\n  \
+        <who>          Who to greet\n  \
+        <punctuation>\n\
+        \n\
+        Options:\n  \
+        -l, --loud              Shout the greeting\n  \
+        -g, --greeting <VALUE>  The greeting [default: Hello]\n  \
+        \x20   --times <VALUE>     Repeat\n  \
+        -h, --help              Print help\n"
+    );
+
+    let value = eval_string(
+        r#"(cli/parse ["-g" "Yo" "--times" "2" "--" "-x" "?"] spec)"#,
+        &mut env,
+    )
+    .unwrap();
+    let Expr::Dict(result) = value.as_ref() else {
+        panic!("expected a Dict");
+    };
+    assert_eq!(
+        format!("{}", result["values"]),
+        r#"{"greeting" "Yo" "loud" false "punctuation" "?" "times" "2" "who" "-x"}"#
+    );
+
+    // The required arguments are not checked when help is requested.
+    let value = eval_string(r#"(cli/parse ["--help"] spec)"#, &mut env).unwrap();
+    let Expr::Dict(result) = value.as_ref() else {
+        panic!("expected a Dict");
+    };
+    assert_eq!(format!("{}", result["help?"]), "true");
+
+    for (input, message) in [
+        (
+            r#"(cli/parse [] spec)"#,
+            "missing required argument `<who>`",
+        ),
+        (
+            r#"(cli/parse ["--name" "x"] spec)"#,
+            "unknown option `--name`",
+        ),
+        (
+            r#"(cli/parse ["x" "--times"] spec)"#,
+            "the option `--times` requires a value",
+        ),
+        (
+            r#"(cli/parse ["--loud=yes" "x"] spec)"#,
+            "the flag `--loud` does not accept a value",
+        ),
+        (
+            r#"(cli/parse ["a" "b" "c"] spec)"#,
+            "unexpected argument `c`",
+        ),
+    ] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected an error for `{input}`");
+        };
+        let error = errors[0].0.to_string();
+        assert!(error.starts_with(message), "unexpected error `{error}`");
+        assert!(error.contains("Usage: greet"));
+    }
+}
+
+#[test]
+fn eval_processes_dict_functions() {
+    let mut env = Env::prelude();
+    eval_string(r#"(let user {:name "George" :age 42})"#, &mut env).unwrap();
+
+    let input = r#"
+    (List
+        (put user :email "g@example.com")
+        (put user :age 43)
+        (delete user :age)
+        (keys user)
+        (values user)
+        (contains-key? user :name)
+        (contains-key? user :email)
+        (merge {:a 1 :b 2} {:b 3} {:c 4})
+        (dict-from-pairs [[:a 1] ["b" 2] [:a 3]])
+        (put [1 2 3] 1 20)
+        user
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"({"age" 42 "email" "g@example.com" "name" "George"} {"age" 43 "name" "George"} {"name" "George"} ["age" "name"] [42 "George"] true false {"a" 1 "b" 3 "c" 4} {"a" 3 "b" 2} [1 20 3] {"age" 42 "name" "George"})"#
+    );
+
+    for input in [
+        "(put 1 :a 2)",
+        "(delete [1] :a)",
+        "(keys [1 2])",
+        "(merge {:a 1} [1])",
+        "(dict-from-pairs [[:a]])",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+
+    // The errors name the invoked op.
+    for (input, message) in [
+        (
+            "(delete {:a 1})",
+            "`delete` requires `dict`, `key` arguments",
+        ),
+        (
+            "(put {:a 1} :b)",
+            "`put` requires `dict`, `key`, `value` arguments",
+        ),
+    ] {
+        let err = eval_string(input, &mut env).unwrap_err();
+        assert_eq!(err[0].0.to_string(), message, "{input}");
+    }
+}
+
+#[test]
+fn eval_styles_text() {
+    let mut env = Env::prelude();
+
+    // The text is only styled when stdout is a terminal.
+    let value = eval_string(
+        r#"(style "done" :fg :green :bg "black" :bold true :underline false)"#,
+        &mut env,
+    )
+    .unwrap();
+    let text = format_value(&value);
+    assert!(
+        text == "done" || text == "\x1b[1;32;40mdone\x1b[0m",
+        "unexpected `{text:?}`"
+    );
+
+    // The text is not styled when the stdout stream is redirected.
+    env.context.stdout = Output::new(SharedBuffer::default());
+    let value = eval_string(r#"(style "done" :fg :green)"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "done");
+
+    for input in [
+        r#"(style "x" :fg :purple)"#,
+        r#"(style "x" :blink true)"#,
+        r#"(style "x" :bold 1)"#,
+        r#"(style "x" :bold)"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_routes_progress_reports_to_the_host() {
+    let reports = Rc::new(RefCell::new(Vec::new()));
+
+    let mut env = Env::prelude();
+    let host_reports = reports.clone();
+    env.context.set_progress_handler(move |percent, message| {
+        host_reports
+            .borrow_mut()
+            .push(format!("{percent}: {message}"));
+    });
+
+    let input = r#"
+    (for i in (range 1 4)
+        (progress/report (* i 25) "step ${i}")
+    )
+    (progress/report 100.0 "done")
+    "#;
+    eval_string(input, &mut env).unwrap();
+
+    assert_eq!(
+        *reports.borrow(),
+        ["25: step 1", "50: step 2", "75: step 3", "100: done"]
+    );
+
+    for input in [
+        r#"(progress/report 101 "x")"#,
+        r#"(progress/report -1 "x")"#,
+        r#"(progress/report "50" "x")"#,
+        r#"(progress/report 50 :x)"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_routes_the_output_to_the_context_streams() {
+    let stdout = SharedBuffer::default();
+    let stderr = SharedBuffer::default();
+
+    let mut env = Env::prelude();
+    env.context.stdout = Output::new(stdout.clone());
+    env.context.stderr = Output::new(stderr.clone());
+
+    let input = r#"
+    (let name "World")
+    (write "Hello, " name "!")
+    (writeln)
+    (writeln [1 2] :done)
+    (progress/report 50 "half")
+    "#;
+    eval_string(input, &mut env).unwrap();
+
+    assert_eq!(stdout.contents(), "Hello, World!\n[1 2]done\n");
+    assert_eq!(stderr.contents(), "[ 50%] half\n");
+
+    stdout.clear();
+    eval_string(r#"(writeln "again")"#, &mut env).unwrap();
+    assert_eq!(stdout.contents(), "again\n");
+}
+
+#[test]
+fn eval_processes_encoding_functions() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(
+        r#"(html/escape "<a href=\"x\">Tom & Jerry's</a>")"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(&value),
+        "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+    );
+
+    let value = eval_string(r#"(url/encode "a b&c=d/é~")"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "a%20b%26c%3Dd%2F%C3%A9~");
+
+    let value = eval_string(r#"(url/decode (url/encode "a b&c=d/é~"))"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "a b&c=d/é~");
+
+    for input in [
+        r#"(url/decode "100%")"#,
+        r#"(url/decode "%zz")"#,
+        r#"(url/decode "%FF")"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_renders_templates() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(r#"(render "Hello {{name}}!" {:name "George"})"#, &mut env).unwrap();
+    assert_eq!(format_value(&value), "Hello George!");
+
+    let input = r#"
+    (render
+        "{{! a report }}# {{report.title}}\n{{#items}}- {{name}}: {{qty}}\n{{/items}}{{^items}}no items\n{{/items}}{{#done}}done{{/done}}"
+        {
+            :report {:title "Stock"}
+            :items [{:name "apples" :qty 3} {:name "pears" :qty 0}]
+            :done true
+        }
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format_value(&value),
+        "# Stock\n- apples: 3\n- pears: 0\ndone"
+    );
+
+    let value = eval_string(
+        r#"(render "{{#tags}}[{{.}}]{{/tags}}{{^done}}pending{{/done}}" {:tags ["a" "b"] :done false})"#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(format_value(&value), "[a][b]pending");
+
+    for input in [
+        r#"(render "{{missing}}" {})"#,
+        r#"(render "{{#items}}" {})"#,
+        r#"(render "{{/items}}" {})"#,
+        r#"(render "{{name" {})"#,
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[test]
+fn eval_processes_big_int_and_decimal_numbers() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (List
+        (* 9223372036854775807n 10n)
+        (+ 0xffn 1)
+        (/ -7n 2n)
+        (% -7n 2)
+        (+ 0.1d 0.2d)
+        (- 1d 0.01d)
+        (* 19.99d 3)
+        (/ 1d 4)
+        (% 7.5d 2d)
+        (= (+ 0.1d 0.2d) 0.3d)
+        (> 100000000000000000000n 1)
+        (< 1.5d 2)
+    )
+    "#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        "(92233720368547758070n 256n -3n -1n 0.3d 0.99d 59.97d 0.25d 1.5d true true true)"
+    );
+
+    // Hex literals are not Decimals.
+    let value = eval_string("0xd", &mut env).unwrap();
+    assert_eq!(format!("{value}"), "13");
+
+    let value = eval_string(r#""total: ${(+ 1.10d 2.20d)}""#, &mut env).unwrap();
+    assert_eq!(format!("{value}"), r#""total: 3.30""#);
+
+    for input in ["(/ 1n 0n)", "(% 1d 0)"] {
+        let Err(errors) = eval_string(input, &mut env) else {
+            panic!("expected a division by zero error for `{input}`");
+        };
+        assert!(matches!(errors[0].0, Error::DivisionByZero));
+    }
+
+    let Err(errors) = eval_string("(+ 1.5d 1.5)", &mut env) else {
+        panic!("expected an error when mixing Decimals and Floats");
+    };
+    assert!(matches!(errors[0].0, Error::InvalidArguments(..)));
+
+    let Err(errors) = eval_string("12xn", &mut env) else {
+        panic!("expected a malformed BigInt error");
+    };
+    assert!(matches!(errors[0].0, Error::MalformedBigInt(..)));
+}
+
+#[test]
+fn eval_processes_math_functions() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (List
+        (div 7 2) (div -7 2) (div 7.0 2)
+        (mod 7 3) (mod -7 2) (mod 7.5 2.0)
+        (pow 2 10) (pow 2.0 0.5)
+        (sqrt 16)
+        (abs -3) (abs -2.5)
+        (floor 2.7) (ceil 2.2) (round -2.5) (round 3)
+        (min 3 1 2) (max 3 1 2) (max 1 2.5)
+        (> pi 3.14) (< e 2.72)
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        "(3 -4 3.5 1 1 1.5 1024 1.4142135623730951 4 3 2.5 2 3 -3 3 1 3 2.5 true true)"
+    );
+
+    let Err(errors) = eval_string("(mod 1 0)", &mut env) else {
+        panic!("expected a division by zero error");
+    };
+    assert!(matches!(errors[0].0, Error::DivisionByZero));
+
+    let result = eval_string("(pow 2 64)", &mut env);
+    assert!(result.is_err());
+
+    let result = eval_string("(pow 2 -1)", &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn eval_processes_sqlite_queries() {
+    let mut env = Env::prelude();
+    let input = r#"
+    (do
+        (let db (db/open ":memory:"))
+        (db/exec db "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, score REAL, avatar BLOB)")
+        (List
+            (db/exec db "INSERT INTO users (name, score) VALUES (?, ?)" ["George" 1.5])
+            (db/exec db "INSERT INTO users (name, avatar) VALUES (?, ?)" ["Kostas" (Buffer "cafe")])
+            (db/query db "SELECT * FROM users WHERE id >= ? ORDER BY id" [1])
+        )
+    )"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(
+        format!("{value}"),
+        r#"(1 1 [{"avatar" () "id" 1 "name" "George" "score" 1.5} {"avatar" (Buffer "cafe") "id" 2 "name" "Kostas" "score" ()}])"#
+    );
+
+    let result = eval_string(
+        r#"(db/query (db/open ":memory:") "SELECT * FROM missing")"#,
+        &mut env,
+    );
+    assert!(result.is_err());
+
+    let result = eval_string(r#"(db/query "not a db" "SELECT 1")"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "store")]
+#[test]
+fn eval_persists_store_values() {
+    let path = std::env::temp_dir().join(format!("tan-store-{}.json", std::process::id()));
+    let path = path.display().to_string();
+    let _ = std::fs::remove_file(&path);
+
+    let mut env = Env::prelude();
+    let input = format!(
+        r#"
+    (do
+        (store/open "{path}")
+        (store/put :count 1)
+        (store/put "user" {{:name "George" :tags ["a" "b"] :score 1.5}})
+        (store/put :count (+ (unwrap (store/get :count)) 1))
+    )"#
+    );
+    eval_string(input, &mut env).unwrap();
+
+    // A fresh environment reads the persisted values.
+    let mut env = Env::prelude();
+    let input = format!(
+        r#"(do (store/open "{path}") (List (store/get :count) (store/get "user") (store/get :missing)))"#
+    );
+    let value = eval_string(input, &mut env);
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(
+        format!("{}", value.unwrap()),
+        r#"((Some 2) (Some {"name" "George" "score" 1.5 "tags" ["a" "b"]}) None)"#
+    );
+
+    let result = eval_string("(store/put :f (Func (x) x))", &mut env);
+    assert!(result.is_err());
+
+    let mut env = Env::prelude();
+    let result = eval_string("(store/get :count)", &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn eval_uses_the_module_cache() {
+    use tan::{
+        api::parse_string_all,
+        eval::{
+            features::Features,
+            module_cache::{cache_key, AstCache, FileAstCache},
+        },
+    };
+
+    let dir = std::env::temp_dir().join(format!("tan-module-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("cached.tan");
+    std::fs::write(&file_path, "(let a 1)").unwrap();
+    let _ = std::fs::remove_file(dir.join("cached.tanc"));
+
+    let input = format!(r#"(use "{}") cached/a"#, file_path.display());
+
+    let value = eval_string(&input, &mut Env::prelude()).unwrap();
+    assert_eq!(format_value(&value), "1");
+    assert!(dir.join("cached.tanc").is_file());
+
+    // A cache with the key of the contents is used instead of the module file.
+    let key = cache_key(None, "(let a 1)", &Features::default());
+    FileAstCache.write(&file_path, key, &parse_string_all("(let a 2)").unwrap());
+    let value = eval_string(&input, &mut Env::prelude()).unwrap();
+    assert_eq!(format_value(&value), "2");
+
+    // A cache with a different key is ignored and rewritten.
+    std::fs::write(&file_path, "(let a 3)").unwrap();
+    let value = eval_string(&input, &mut Env::prelude()).unwrap();
+    assert_eq!(format_value(&value), "3");
+    let value = eval_string(&input, &mut Env::prelude()).unwrap();
+    assert_eq!(format_value(&value), "3");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "tensor")]
+#[test]
+fn eval_processes_tensors() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(
+        r#"
+        (let m (tensor/from [[1 2] [3 4]])
+             v (tensor/from [1 1]))
+        (List
+            (tensor/shape m)
+            (tensor/dot m v)
+            (tensor/dot m (tensor/transpose m))
+            (tensor/dot v (tensor/from [2 3.5]))
+            (tensor/to-array (tensor/add m 1))
+            (tensor/mul m m)
+            (tensor/sub (tensor/div m 2) m)
+            (= (tensor/from [1 2]) (tensor/from [1.0 2.0]))
+            (tensor/sum m)
+            (tensor/mean m)
+            (tensor/std (tensor/from [2 4 4 4 5 5 7 9])))
+        "#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        "([2 2] (Tensor [3 7]) (Tensor [[5 11] [11 25]]) 5.5 [[2 3] [4 5]] (Tensor [[1 4] [9 16]]) (Tensor [[-0.5 -1] [-1.5 -2]]) true 10 2.5 2)"
+    );
+
+    for input in [
+        "(tensor/from [[1 2] [3]])",
+        r#"(tensor/from [1 "a"])"#,
+        "(tensor/add (tensor/from [1 2]) (tensor/from [1 2 3]))",
+        "(tensor/dot (tensor/from [[1 2] [3 4]]) (tensor/from [1 2 3]))",
+        "(tensor/shape [1 2])",
+    ] {
+        assert!(
+            eval_string(input, &mut env).is_err(),
+            "expected an error for `{input}`"
+        );
+    }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn eval_parses_toml() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(
+        r#"
+        (let config (toml/parse "
+title = \"demo\"
+ports = [8080, 8081]
+
+[server]
+host = \"localhost\"
+debug = true
+ratio = 0.5
+started = 1979-05-27T07:32:00Z
+"))
+        (List (config "title") (config "ports") (config "server"))
+        "#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"((Some "demo") (Some [8080 8081]) (Some {"debug" true "host" "localhost" "ratio" 0.5 "started" "1979-05-27T07:32:00Z"}))"#
+    );
+
+    let result = eval_string(r#"(toml/parse "port = ")"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "yaml")]
+#[test]
+fn eval_parses_yaml() {
+    let mut env = Env::prelude();
+
+    let value = eval_string(
+        r#"
+        (yaml/parse "
+name: demo
+ports:
+  - 8080
+  - 8081
+server:
+  host: localhost
+  debug: true
+  ratio: 0.5
+  proxy: ~
+1: one
+")
+        "#,
+        &mut env,
+    )
+    .unwrap();
+    assert_eq!(
+        format_value(value),
+        r#"{"1" "one" "name" "demo" "ports" [8080 8081] "server" {"debug" true "host" "localhost" "proxy" () "ratio" 0.5}}"#
+    );
+
+    let value = eval_string(r#"(yaml/parse "")"#, &mut env).unwrap();
+    assert!(matches!(value, Ann(Expr::One, ..)));
+
+    let result = eval_string(r#"(yaml/parse "a: [1, 2")"#, &mut env);
+    assert!(result.is_err());
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn eval_processes_regexes() {
+    let mut env = Env::prelude();
+
+    let cases = [
+        (r#"(regex/match? "^\\d+$" "2024")"#, "true"),
+        (r#"(regex/match? "^\\d+$" "v2")"#, "false"),
+        (r#"(regex/find "\\d+" "v12.3")"#, r#"(Some "12")"#),
+        (r#"(regex/find "\\d+" "none")"#, "None"),
+        (
+            r#"(regex/find-all "\\d+" "v12.3.45")"#,
+            r#"["12" "3" "45"]"#,
+        ),
+        (
+            r#"(regex/replace "(\\w+)@(\\w+)" "me@home, you@work" "$2:$1")"#,
+            "home:me, work:you",
+        ),
+        (r#"(regex/split "\\s*,\\s*" "a, b ,c")"#, r#"["a" "b" "c"]"#),
+    ];
+
+    for (input, expected) in cases {
+        let value = eval_string(input, &mut env).unwrap();
+        assert_eq!(format_value(value), expected, "for `{input}`");
+    }
+
+    // The compiled regexes are reused in loops.
+    let input = r#"(count (filter (Func (s) (regex/match? "^a" s)) ["ab" "ba" "ac"]))"#;
+    let value = eval_string(input, &mut env).unwrap();
+    assert_eq!(format_value(value), "2");
+
+    assert!(eval_string(r#"(regex/find "(" "a")"#, &mut env).is_err());
+    assert!(eval_string(r#"(regex/replace "a" "a")"#, &mut env).is_err());
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn eval_processes_watch() {
+    let dir = std::env::temp_dir().join(format!("tan-watch-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let file = dir.join("changed.tan");
+    let writer_file = file.clone();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(300));
+        std::fs::write(writer_file, "(+ 1 2)").unwrap();
+    });
+
+    let mut env = Env::prelude();
+    let input = format!(r#"(watch "{}" (Func (path) (break path)))"#, dir.display());
+    let value = eval_with_timeout(&input, &mut env, Duration::from_secs(10)).unwrap();
+
+    writer.join().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(format_value(&value), file.display().to_string());
+}
+
+#[cfg(all(feature = "signal", unix))]
+#[test]
+fn eval_invokes_signal_handlers() {
+    use tan::ops::signal::enable_signal_handling;
+
+    let mut env = Env::prelude();
+
+    // The handlers require the host to enable the signal handling.
+    let input = "(on-signal (Func () 1))";
+    let err = eval_string(input, &mut env).unwrap_err();
+    let message = err[0].0.to_string();
+    assert!(message.contains("signal handling is not enabled"));
+
+    enable_signal_handling().unwrap();
+
+    for signal in ["-INT", "-TERM"] {
+        let mut done = Ann::new(Expr::Bool(false));
+        done.set_annotation("mut", Expr::Bool(true));
+        env.insert("done", done);
+
+        let pid = std::process::id();
+        let signaler = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            std::process::Command::new("kill")
+                .args([signal, &pid.to_string()])
+                .status()
+                .unwrap();
+        });
+
+        let input = "(do (on-signal (Func () (set! done true))) (while true (+ 1 1)))";
+        let result = eval_with_timeout(input, &mut env, Duration::from_secs(10));
+
+        signaler.join().unwrap();
+
+        let err = result.unwrap_err();
+        assert!(matches!(err[0], Ranged(Error::Interrupted, ..)), "{signal}");
+        assert!(matches!(env.get("done"), Some(Ann(Expr::Bool(true), ..))));
+    }
+}
@@ -2,7 +2,10 @@ mod common;
 
 use tan::{
     error::Error,
-    lexer::{token::Token, Lexer},
+    lexer::{
+        token::{StringSegment, Token},
+        Lexer,
+    },
 };
 
 use crate::common::read_file;
@@ -183,3 +186,50 @@ fn lex_reports_unterminated_annotations() {
 
     assert_eq!(err.1.start, 21);
 }
+
+#[test]
+fn lex_handles_string_escapes() {
+    let input = r#"(write "a\tb\n\"quoted\" \\ \u{3bb} \$x")"#;
+    let tokens = Lexer::new(input).lex().unwrap();
+
+    assert!(
+        matches!(tokens[2].as_ref(), Token::String(s) if s == "a\tb\n\"quoted\" \\ \u{3bb} $x")
+    );
+}
+
+#[test]
+fn lex_reports_malformed_string_escapes() {
+    let input = r#"(write "a\qb" "\u{zz}")"#;
+    let result = Lexer::new(input).lex();
+
+    let Err(errors) = result else {
+        panic!("expected errors");
+    };
+
+    assert_eq!(errors.len(), 2);
+    assert!(matches!(&errors[0].0, Error::MalformedStringEscape(s) if s == "q"));
+    assert!(matches!(&errors[1].0, Error::MalformedStringEscape(s) if s == "u{zz}"));
+}
+
+#[test]
+fn lex_handles_string_interpolation() {
+    let input = r#""Hello ${(name :first)}, ${n}!""#;
+    let tokens = Lexer::new(input).lex().unwrap();
+
+    assert_eq!(tokens.len(), 1);
+
+    let Token::InterpolatedString(segments) = tokens[0].as_ref() else {
+        panic!("expected an interpolated string");
+    };
+
+    assert_eq!(
+        segments,
+        &vec![
+            StringSegment::Text("Hello ".to_owned()),
+            StringSegment::Expr("(name :first)".to_owned()),
+            StringSegment::Text(", ".to_owned()),
+            StringSegment::Expr("n".to_owned()),
+            StringSegment::Text("!".to_owned()),
+        ]
+    );
+}
@@ -4,8 +4,8 @@ use std::num::IntErrorKind;
 
 use tan::{
     ann::Ann,
-    api::{parse_string, parse_string_all},
-    error::Error,
+    api::{parse_string, parse_string_all, ReplInput, ReplSession},
+    error::{Error, PipelineError},
     expr::Expr,
     lexer::{token::Token, Lexer},
     parser::Parser,
@@ -348,3 +348,108 @@ fn parse_keeps_comments() {
     let expr = &exprs[0];
     assert!(matches!(expr, Ann(Expr::Comment(x), ..) if x == "-- This is a comment"));
 }
+
+#[test]
+fn parse_handles_quasi_quote_and_unquote() {
+    let input = "`(if $predicate () $body)";
+    let expr = parse_string(input).unwrap();
+
+    assert_eq!(
+        format!("{expr}"),
+        "(qquot (if (unquot predicate) () (unquot body)))"
+    );
+}
+
+#[test]
+fn parse_desugars_string_interpolation() {
+    let input = r#""Hello ${(name :first)}!""#;
+    let expr = parse_string(input).unwrap();
+
+    assert_eq!(format!("{expr}"), r#"(#format "Hello " (name :first) "!")"#);
+
+    // The template is always a literal string, with `%` escaped.
+    let expr = parse_string(r#""${n}% of 100%""#).unwrap();
+    assert_eq!(format!("{expr}"), r#"(#format "" n "% of 100%")"#);
+
+    let expr = parse_string(r#""100% ${n}""#).unwrap();
+    assert_eq!(format!("{expr}"), r#"(#format "100%% " n)"#);
+
+    let result = parse_string(r#""${(+ 1 2}""#);
+
+    let Err(errors) = result else {
+        panic!("expected errors");
+    };
+
+    assert!(matches!(errors[0].0, Error::UnterminatedList));
+    assert_eq!(errors[0].1, 0..11);
+}
+
+#[test]
+fn parse_reports_errors_at_collection_elements() {
+    let result = parse_string("[1 2 0xzz 4]");
+
+    let Err(errors) = result else {
+        panic!("expected errors");
+    };
+
+    assert!(matches!(errors[0].0, Error::MalformedInt(..)));
+    assert_eq!(errors[0].1, 5..9);
+
+    let result = parse_string("{:a 1 :b}");
+
+    let Err(errors) = result else {
+        panic!("expected errors");
+    };
+
+    assert!(matches!(&errors[0].0, Error::MissingDictValue(key) if key == ":b"));
+    assert_eq!(errors[0].1, 6..8);
+}
+
+#[test]
+fn parse_validates_key_symbols() {
+    let expr = parse_string(":http/status").unwrap();
+    assert!(matches!(&expr, Ann(Expr::KeySymbol(s), ..) if s == "http/status"));
+    assert_eq!(expr.0.namespace(), Some("http"));
+    assert_eq!(expr.0.name(), Some("status"));
+
+    for input in ["::status", ":", ":http/", ":/status", ":a/b/c", ":a:b"] {
+        let result = parse_string(input);
+
+        let Err(errors) = result else {
+            panic!("expected errors for `{input}`");
+        };
+
+        assert!(matches!(&errors[0].0, Error::MalformedKeySymbol(s) if s == input));
+    }
+}
+
+#[test]
+fn repl_session_reports_incomplete_input() {
+    let mut session = ReplSession::new();
+
+    assert_eq!(
+        session.push_line("(let a (+ 1").unwrap(),
+        ReplInput::Incomplete
+    );
+    assert_eq!(
+        session.push_line("  \"multi").unwrap(),
+        ReplInput::Incomplete
+    );
+    assert!(!session.is_empty());
+
+    let input = session.push_line("line\"))").unwrap();
+    assert_eq!(
+        input,
+        ReplInput::Complete("(let a (+ 1\n  \"multi\nline\"))".to_owned())
+    );
+    assert!(session.is_empty());
+
+    assert_eq!(
+        session.push_line("(+ 1 2)").unwrap(),
+        ReplInput::Complete("(+ 1 2)".to_owned())
+    );
+
+    let result = session.push_line("(+ 1 2))");
+    assert!(matches!(result, Err(PipelineError::Parse(..))));
+    assert!(session.is_empty());
+}
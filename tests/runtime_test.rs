@@ -0,0 +1,717 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+    time::Duration,
+};
+
+use tan::{
+    ann::Ann,
+    api::{eval_string, parse_string_all, signature_help, FnSignature},
+    error::{Error, PipelineError},
+    eval::{
+        env::Env,
+        eval,
+        features::{Feature, Features, LanguageVersion},
+        limits::Limits,
+        module_cache::{cache_key, AstCache, MemoryAstCache},
+        prelude::PreludeOptions,
+    },
+    expr::{format_value, Expr},
+    runtime::{IsolateError, Runtime},
+};
+
+#[test]
+fn runtime_isolates_exchange_messages_with_the_host() {
+    let runtime = Runtime::new();
+
+    let isolate = runtime.spawn_isolate(
+        r#"
+        (let step
+            (Func (message total)
+                (if (is-some? message)
+                    (do
+                        (send (+ total (unwrap message)))
+                        (step (recv) (+ total (unwrap message)))
+                    )
+                    total
+                )
+            )
+        )
+        (step (recv) 0)
+        "#,
+    );
+
+    for (n, total) in [(1, "1"), (2, "3"), (3, "6")] {
+        isolate.send(&Expr::Int(n).into()).unwrap();
+        let reply = isolate.recv().unwrap();
+        assert_eq!(format_value(&reply), total);
+    }
+
+    // Joining closes the channel, the isolate stops waiting for messages.
+    let value = isolate.join().unwrap();
+    assert_eq!(format_value(&value), "6");
+}
+
+#[test]
+fn runtime_isolates_run_concurrently() {
+    let runtime = Runtime::new();
+
+    let isolates: Vec<_> = (1..=4)
+        .map(|n| runtime.spawn_isolate(format!("(send (* {n} 10)) {n}")))
+        .collect();
+
+    for (i, isolate) in isolates.into_iter().enumerate() {
+        let n = i as i64 + 1;
+        let message = isolate.recv().unwrap();
+        assert_eq!(format_value(&message), (n * 10).to_string());
+        assert_eq!(format_value(isolate.join().unwrap()), n.to_string());
+    }
+}
+
+#[test]
+fn runtime_isolates_report_errors_and_limits() {
+    let runtime = Runtime::new().with_timeout(Duration::from_millis(100));
+
+    let isolate = runtime.spawn_isolate("(/ 1 0)");
+    assert_eq!(
+        isolate.join().err(),
+        Some(IsolateError::Eval(vec!["division by zero".to_owned()]))
+    );
+
+    // Waiting for a message or sleeping also respects the limit.
+    for source in ["(while true (+ 1 1))", "(recv)", "(sleep 60000)"] {
+        let isolate = runtime.spawn_isolate(source);
+        assert!(isolate.recv_timeout(Duration::from_secs(5)).is_none());
+        assert_eq!(
+            isolate.join().err(),
+            Some(IsolateError::Eval(vec!["evaluation timed out".to_owned()]))
+        );
+    }
+
+    let isolate = runtime.spawn_isolate("(Func (x) x)");
+    assert!(matches!(isolate.join(), Err(IsolateError::NotPortable(_))));
+
+    let isolate = runtime.spawn_isolate("1");
+    let func = eval_string("(Func (x) x)", &mut Env::prelude()).unwrap();
+    assert!(matches!(
+        isolate.send(&func),
+        Err(IsolateError::NotPortable(_))
+    ));
+}
+
+#[test]
+fn runtime_isolates_inherit_the_prelude_options() {
+    let runtime = Runtime::new().with_prelude_options(&PreludeOptions {
+        flat_aliases: false,
+    });
+
+    let isolate = runtime.spawn_isolate(r#"(str/len "tan")"#);
+    assert_eq!(isolate.join().unwrap().to_string(), "3");
+
+    let isolate = runtime.spawn_isolate(r#"(str-len "tan")"#);
+    assert!(matches!(isolate.join(), Err(IsolateError::Eval(_))));
+}
+
+#[test]
+fn runtime_isolates_are_cancelled() {
+    let runtime = Runtime::new();
+
+    for source in ["(while true (+ 1 1))", "(recv)", "(sleep 60000)"] {
+        let isolate = runtime.spawn_isolate(source);
+        std::thread::sleep(Duration::from_millis(20));
+        isolate.cancel();
+
+        // Joining closes the channels, wait for the cancellation first.
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !isolate.is_finished() && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(isolate.is_finished(), "the isolate is not cancelled");
+
+        assert_eq!(
+            isolate.join().err(),
+            Some(IsolateError::Eval(
+                vec!["evaluation interrupted".to_owned()]
+            ))
+        );
+    }
+}
+
+#[test]
+fn runtime_limits_each_input() {
+    let mut runtime = Runtime::new().with_limits(Limits::new().with_fuel(500));
+
+    let result = runtime.eval("(while true (+ 1 1))");
+    let Err(PipelineError::Eval(errors)) = result else {
+        panic!("expected a limit error");
+    };
+    assert_eq!(
+        errors[0].to_string(),
+        "the fuel limit (500 steps) is exceeded"
+    );
+
+    // The fuel is reset for each input.
+    let value = runtime.eval("(+ 1 2)").unwrap();
+    assert_eq!(format_value(&value), "3");
+
+    let isolate = runtime.spawn_isolate("(while true (+ 1 1))");
+    assert_eq!(
+        isolate.join().err(),
+        Some(IsolateError::Eval(vec![
+            "the fuel limit (500 steps) is exceeded".to_owned()
+        ]))
+    );
+}
+
+#[test]
+fn runtime_keeps_the_types_across_evaluations() {
+    let mut runtime = Runtime::new();
+
+    runtime.eval("(let a 1)").unwrap();
+    runtime.eval("(let b (+ a 2)) (let c 1.5)").unwrap();
+    let value = runtime.eval("(let d (+ c 1.0)) (+ a b)").unwrap();
+    assert_eq!(format_value(&value), "4");
+
+    for (name, type_name) in [("a", "Int"), ("b", "Int"), ("c", "Float"), ("d", "Float")] {
+        assert_eq!(
+            runtime.type_of(name).map(|t| t.to_string()),
+            Some(type_name.to_owned())
+        );
+    }
+    assert!(runtime.type_of("missing").is_none());
+
+    // A failed input does not affect the previous definitions.
+    assert!(runtime.eval("(+ a").is_err());
+    assert_eq!(format_value(runtime.eval("a").unwrap()), "1");
+
+    // A failed definition does not leave a stale type.
+    assert!(runtime.eval("(let x (/ 1 0))").is_err());
+    assert!(runtime.type_of("x").is_none());
+    assert!(runtime.eval(r#"(let a "one") (let b (/ 1 0))"#).is_err());
+    assert_eq!(
+        runtime.type_of("a").map(|t| t.to_string()),
+        Some("String".to_owned())
+    );
+    assert_eq!(
+        runtime.type_of("b").map(|t| t.to_string()),
+        Some("Int".to_owned())
+    );
+}
+
+#[test]
+fn runtime_reports_signature_help() {
+    let help = signature_help("(writeln (+ 1 2) ", 17).unwrap();
+    assert_eq!(help.name, "writeln");
+    assert_eq!(help.active_parameter, 1);
+
+    // The overload that matches the argument types is active.
+    let help = signature_help("(+ 1.5 ", 7).unwrap();
+    let labels: Vec<_> = help.signatures.iter().map(|s| s.label.as_str()).collect();
+    assert!(labels.contains(&"(+ Int Int)"));
+    assert_eq!(
+        help.signatures[help.active_signature.unwrap()].label,
+        "(+ Float Float)"
+    );
+    assert_eq!(help.active_parameter, 1);
+
+    // The cursor at the end of an argument is still editing the argument.
+    let help = signature_help("(+ 1.5", 6).unwrap();
+    assert_eq!(help.active_parameter, 0);
+
+    assert!(signature_help("[1 2]", 3).is_none());
+    assert!(signature_help("(undefined 1 ", 13).is_none());
+
+    // The definitions and types of the runtime are considered.
+    let mut runtime = Runtime::new();
+    runtime
+        .eval("(let add (Func (x y) (+ x y))) (let n 2)")
+        .unwrap();
+    let help = runtime.signature_help("(add n ", 7).unwrap();
+    assert_eq!(help.signatures[0].label, "(add x y)");
+    assert_eq!(help.active_signature, Some(0));
+    assert_eq!(help.active_parameter, 1);
+
+    let help = runtime.signature_help("(+ n ", 5).unwrap();
+    assert_eq!(
+        help.signatures[help.active_signature.unwrap()].label,
+        "(+ Int Int)"
+    );
+}
+
+#[test]
+fn runtime_messages_are_only_available_in_isolates() {
+    let mut env = Env::prelude();
+    assert!(eval_string("(send 1)", &mut env).is_err());
+    assert!(eval_string("(recv)", &mut env).is_err());
+}
+
+#[test]
+fn runtime_honors_the_number_literal_types() {
+    let mut runtime = Runtime::new();
+
+    let value = runtime
+        .eval("(let a #U8 255 b #I32 -7 c #Float 1 d #Decimal 2 e #BigInt 3) [a b c d e]")
+        .unwrap();
+    assert_eq!(format_value(&value), "[255 -7 1 2d 3n]");
+
+    for (name, type_name) in [
+        ("a", "U8"),
+        ("b", "I32"),
+        ("c", "Float"),
+        ("d", "Decimal"),
+        ("e", "BigInt"),
+    ] {
+        assert_eq!(
+            runtime.type_of(name).map(|t| t.to_string()),
+            Some(type_name.to_owned())
+        );
+    }
+
+    // The sized integers are Ints at runtime.
+    assert_eq!(format_value(runtime.eval("(+ a b)").unwrap()), "248");
+
+    for (input, message) in [
+        ("#U8 256", "the number `256` cannot be represented as `U8`"),
+        (
+            "#I8 -129",
+            "the number `-129` cannot be represented as `I8`",
+        ),
+        (
+            "#Int 1.5",
+            "the number `1.5` cannot be represented as `Int`",
+        ),
+        (
+            "#I32 0x100000000",
+            "the number `4294967296` cannot be represented as `I32`",
+        ),
+    ] {
+        let Err(PipelineError::Parse(errors)) = runtime.eval(input) else {
+            panic!("expected a parse error for `{input}`");
+        };
+        assert_eq!(errors[0].0.to_string(), message);
+    }
+}
+
+#[test]
+fn runtime_checks_the_sized_integer_casts() {
+    let mut runtime = Runtime::new();
+
+    let value = runtime
+        .eval("(let a (to-u8 200) b (to-i32 -5) c (+ a 100) d (to-i16 5n)) [a b c d]")
+        .unwrap();
+    assert_eq!(format_value(&value), "[200 -5 300 5]");
+
+    // The arithmetic of sized integers falls back to the Int operations.
+    for (name, type_name) in [("a", "U8"), ("b", "I32"), ("c", "Int"), ("d", "I16")] {
+        assert_eq!(
+            runtime.type_of(name).map(|t| t.to_string()),
+            Some(type_name.to_owned())
+        );
+    }
+
+    for (input, message) in [
+        (
+            "(to-u8 c)",
+            "the number `300` cannot be represented as `U8`",
+        ),
+        (
+            "(to-u32 b)",
+            "the number `-5` cannot be represented as `U32`",
+        ),
+        (
+            "(to-i64 12345678901234567890n)",
+            "the number `12345678901234567890n` cannot be represented as `I64`",
+        ),
+        ("(to-i8 1.5)", "`1.5` is not an Int"),
+    ] {
+        let Err(PipelineError::Eval(errors)) = runtime.eval(input) else {
+            panic!("expected an eval error for `{input}`");
+        };
+        assert_eq!(errors[0].0.to_string(), message);
+    }
+}
+
+#[test]
+fn runtime_registers_foreign_functions() {
+    let mut runtime = Runtime::new();
+
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let log = calls.clone();
+
+    runtime
+        .register_fn(
+            "area",
+            FnSignature::typed(&["Int", "Int"], "Int"),
+            |args, _env| {
+                let [Ann(Expr::Int(w), ..), Ann(Expr::Int(h), ..)] = args else {
+                    unreachable!();
+                };
+                Ok(Expr::Int(w * h).into())
+            },
+        )
+        .register_fn(
+            "area",
+            FnSignature::typed(&["Float", "Float"], "Float"),
+            |args, _env| {
+                let [Ann(Expr::Float(w), ..), Ann(Expr::Float(h), ..)] = args else {
+                    unreachable!();
+                };
+                Ok(Expr::Float(w * h).into())
+            },
+        )
+        .register_fn("log", FnSignature::Arity(1), move |args, _env| {
+            log.borrow_mut().push(format_value(&args[0]));
+            Ok(Expr::One.into())
+        });
+
+    let value = runtime.eval("(area 2 3)").unwrap();
+    assert_eq!(format_value(&value), "6");
+
+    let value = runtime.eval("(area 1.5 2.0)").unwrap();
+    assert_eq!(format_value(&value), "3");
+
+    // The methods are also dispatched dynamically.
+    let value = runtime.eval("(map (Func (x) (area x x)) [2 3])").unwrap();
+    assert_eq!(format_value(&value), "[4 9]");
+
+    // The result type is resolved statically.
+    runtime.eval("(let a (area 2 3))").unwrap();
+    assert_eq!(
+        runtime.type_of("a").map(|t| t.to_string()),
+        Some("Int".to_owned())
+    );
+
+    runtime.eval(r#"(log "hello") (log 1)"#).unwrap();
+    assert_eq!(*calls.borrow(), vec!["hello".to_owned(), "1".to_owned()]);
+
+    let result = runtime.eval(r#"(area "a" 1)"#);
+    assert!(result.is_err());
+
+    let result = runtime.eval(r#"(log 1 2)"#);
+    assert!(result.is_err());
+
+    let help = runtime.signature_help("(area 1 ", 8).unwrap();
+    assert_eq!(help.signatures.len(), 2);
+}
+
+#[test]
+fn runtime_registers_host_functions() {
+    let mut runtime = Runtime::new();
+
+    let counter = Rc::new(Cell::new(0));
+    let count = counter.clone();
+
+    runtime
+        .register_host_fn("gcd", |a: i64, b: i64| {
+            let (mut a, mut b) = (a.abs(), b.abs());
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        })
+        .register_host_fn("safe-sqrt", |x: f64| {
+            if x < 0.0 {
+                Err(Error::invalid_arguments("negative number"))
+            } else {
+                Ok(x.sqrt())
+            }
+        })
+        .register_host_fn("shout", |words: Vec<String>| {
+            words
+                .iter()
+                .map(|word| word.to_uppercase())
+                .collect::<Vec<_>>()
+        })
+        .register_host_fn("total", |items: HashMap<String, i64>| {
+            items.values().sum::<i64>()
+        })
+        .register_host_fn("tick", move || {
+            count.set(count.get() + 1);
+            count.get()
+        });
+
+    let value = runtime.eval("(gcd 12 18)").unwrap();
+    assert_eq!(format_value(&value), "6");
+
+    let value = runtime.eval("(safe-sqrt 16.0)").unwrap();
+    assert_eq!(format_value(&value), "4");
+
+    let result = runtime.eval("(safe-sqrt -1.0)");
+    assert!(result.is_err());
+
+    let value = runtime.eval(r#"(shout ["a" "b"])"#).unwrap();
+    assert_eq!(format_value(&value), r#"["A" "B"]"#);
+
+    let value = runtime.eval(r#"(total {:a 1 :b 2})"#).unwrap();
+    assert_eq!(format_value(&value), "3");
+
+    runtime.eval("(tick) (tick)").unwrap();
+    assert_eq!(counter.get(), 2);
+
+    // The signature is derived from the Rust types.
+    runtime.eval("(let g (gcd 4 6))").unwrap();
+    assert_eq!(
+        runtime.type_of("g").map(|t| t.to_string()),
+        Some("Int".to_owned())
+    );
+
+    let result = runtime.eval(r#"(gcd 1 "a")"#);
+    assert!(result.is_err());
+
+    let result = runtime.eval(r#"(shout ["a" 1])"#);
+    assert!(result.is_err());
+}
+
+#[test]
+fn runtime_registers_host_forms() {
+    let conditions = Rc::new(RefCell::new(Vec::new()));
+
+    let mut runtime = Runtime::new();
+
+    let captured = conditions.clone();
+    runtime.register_form("where", move |args, _env| {
+        let [condition] = args else {
+            return Err(Error::invalid_arguments("`where` requires one condition").into());
+        };
+        captured.borrow_mut().push(format_value(condition));
+        Ok(Expr::One.into())
+    });
+
+    // A control-flow form, the body is evaluated only if the condition fails.
+    runtime.register_form("unless", |args, env| {
+        let [condition, body] = args else {
+            return Err(Error::invalid_arguments("`unless` requires two arguments").into());
+        };
+        match eval(condition, env)?.as_ref() {
+            Expr::Bool(false) => eval(body, env),
+            _ => Ok(Expr::One.into()),
+        }
+    });
+
+    let value = runtime
+        .eval(
+            r#"
+            (let age 12)
+            (where (> age 18))
+            (where (and (= name "x") (undefined-fn)))
+            (unless (> age 18) (+ age 1))
+            "#,
+        )
+        .unwrap();
+    assert_eq!(format_value(&value), "13");
+    assert_eq!(
+        *conditions.borrow(),
+        ["(> age 18)", r#"(and (= name "x") (undefined-fn))"#]
+    );
+
+    // The errors of a form are attributed to the call-site.
+    let Err(PipelineError::Eval(errors)) = runtime.eval("(where)") else {
+        panic!("expected an eval error");
+    };
+    assert_eq!(errors[0].0.to_string(), "`where` requires one condition");
+    assert_eq!(errors[0].1, 0..7);
+}
+
+#[test]
+fn runtime_checks_the_language_features() {
+    let mut runtime = Runtime::new().with_language_version(LanguageVersion::V1);
+
+    let value = runtime.eval("(let a 2) (if (> a 1) (* a 10) 0)").unwrap();
+    assert_eq!(format_value(&value), "20");
+
+    for (input, message) in [
+        (
+            "(match a (2 :two) (_ :other))",
+            "feature `match` requires enabling `match`",
+        ),
+        ("`(a $a)", "feature `qquot` requires enabling `quasi-quote`"),
+        (
+            "(let m (Macro (x) x))",
+            "feature `Macro` requires enabling `macros`",
+        ),
+    ] {
+        let Err(PipelineError::Resolve(errors)) = runtime.eval(input) else {
+            panic!("expected a resolve error for `{input}`");
+        };
+        assert_eq!(errors[0].0.to_string(), message);
+    }
+
+    // A quoted form is data, it is not checked.
+    let value = runtime.eval("'(match a)").unwrap();
+    assert_eq!(format_value(&value), "(match a)");
+
+    // The constructed forms are checked when evaluated.
+    for (input, message) in [
+        (
+            "(eval '(match 1 (1 :one) (_ :two)))",
+            "feature `match` requires enabling `match`",
+        ),
+        (
+            "(eval '(let m (Macro (x) x)))",
+            "feature `Macro` requires enabling `macros`",
+        ),
+    ] {
+        let Err(PipelineError::Eval(errors)) = runtime.eval(input) else {
+            panic!("expected an eval error for `{input}`");
+        };
+        assert_eq!(errors[0].0.to_string(), message);
+    }
+
+    let mut runtime = Runtime::new().with_features(Features::default().without(Feature::Modules));
+    let value = runtime.eval("(match 1 (1 :one) (_ :other))").unwrap();
+    assert_eq!(format_value(&value), "one");
+    let Err(PipelineError::Resolve(errors)) = runtime.eval(r#"(use "./some/module")"#) else {
+        panic!("expected a resolve error");
+    };
+    assert_eq!(
+        errors[0].0.to_string(),
+        "feature `use` requires enabling `modules`"
+    );
+
+    let isolate = runtime.spawn_isolate("(let m (Macro (x) x)) (m 1)");
+    assert_eq!(isolate.join().unwrap().to_string(), "1");
+}
+
+#[test]
+fn runtime_caches_the_resolved_modules() {
+    let dir = std::env::temp_dir().join(format!("tan-ast-cache-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("shapes.tan"), "(let area (Func (w h) (* w h)))").unwrap();
+    std::fs::write(dir.join("macros.tan"), "(let twice (Macro (x) `(* 2 $x)))").unwrap();
+
+    let cache = Rc::new(MemoryAstCache::new());
+    let input = format!(r#"(use "{}/shapes") (shapes/area 2 3)"#, dir.display());
+
+    let mut runtime = Runtime::new().with_ast_cache(cache.clone());
+    let value = runtime.eval(&input).unwrap();
+    assert_eq!(format_value(&value), "6");
+    assert_eq!(cache.len(), 1);
+
+    // The cached expressions are used instead of the module file.
+    let key = cache_key(
+        None,
+        "(let area (Func (w h) (* w h)))",
+        &Features::default(),
+    );
+    cache.write(
+        &dir.join("shapes.tan"),
+        key,
+        &parse_string_all("(let area (Func (w h) (+ w h)))").unwrap(),
+    );
+    let mut runtime = Runtime::new().with_ast_cache(cache.clone());
+    let value = runtime.eval(&input).unwrap();
+    assert_eq!(format_value(&value), "5");
+
+    // The modules that define macros are not cached.
+    let mut runtime = Runtime::new().with_ast_cache(cache.clone());
+    runtime
+        .eval(format!(r#"(use "{}/macros")"#, dir.display()))
+        .unwrap();
+    assert_eq!(cache.len(), 1);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn runtime_converts_values_to_rust_structs() {
+    use serde::{Deserialize, Serialize};
+    use tan::expr::serde_bridge::{from_expr, to_expr, Serde};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle(f64),
+        Rect { w: i64, h: i64 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        name: String,
+        port: u16,
+        level: Level,
+        tags: Vec<String>,
+        limits: HashMap<String, i64>,
+        shapes: Vec<Shape>,
+        proxy: Option<String>,
+        timeout: Option<f64>,
+    }
+
+    let mut runtime = Runtime::new();
+
+    let value = runtime
+        .eval(
+            r#"
+            {
+                :name "api"
+                :port 8080
+                :level :Info
+                :tags ["a" "b"]
+                :limits {:cpu 2}
+                :shapes [{:Circle 1.5} {:Rect {:w 2 :h 3}}]
+                :timeout (Some 2.5)
+            }
+            "#,
+        )
+        .unwrap();
+
+    let config: Config = from_expr(&value).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: "api".to_owned(),
+            port: 8080,
+            level: Level::Info,
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            limits: HashMap::from([("cpu".to_owned(), 2)]),
+            shapes: vec![Shape::Circle(1.5), Shape::Rect { w: 2, h: 3 }],
+            proxy: None,
+            timeout: Some(2.5),
+        }
+    );
+
+    // And back.
+    let value = to_expr(&config).unwrap();
+    let round_trip: Config = from_expr(&value).unwrap();
+    assert_eq!(round_trip, config);
+
+    runtime.env_mut().insert("config", value);
+    let value = runtime
+        .eval("[(config :level) (config :shapes) (config :proxy)]")
+        .unwrap();
+    assert_eq!(
+        format_value(&value),
+        r#"[(Some :Info) (Some [{"Circle" 1.5} {"Rect" {"h" 3 "w" 2}}]) (Some None)]"#
+    );
+
+    let result = from_expr::<Config>(&runtime.eval(r#"{:name 1}"#).unwrap());
+    assert!(result.is_err());
+
+    // A struct argument of a host function.
+    runtime.register_host_fn("port-of", |config: Serde<Config>| config.0.port as i64);
+    let value = runtime
+        .eval(r#"(port-of {:name "x" :port 1 :level :Debug :tags [] :limits {} :shapes []})"#)
+        .unwrap();
+    assert_eq!(format_value(&value), "1");
+}
+
+#[test]
+fn runtime_releases_the_closures_of_the_main_scope_on_drop() {
+    let mut runtime = Runtime::new();
+
+    runtime
+        .eval("(let count (Func (n) (if (> n 0) (count (- n 1)) n)))")
+        .unwrap();
+
+    let scope = Rc::downgrade(&runtime.env().local[0]);
+
+    drop(runtime);
+
+    assert!(scope.upgrade().is_none());
+}